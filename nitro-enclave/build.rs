@@ -0,0 +1,13 @@
+// Compiles `proto/outbox.proto` into the generated `pass_wallet.outbox` Rust module that
+// `outbox_codec` wraps. Running codegen here rather than checking in the generated file means a
+// field added to the `.proto` is available to `outbox_codec` the next time the crate builds,
+// with no separate "regenerate and commit" step to forget.
+//
+// `prost-build` (and `prost`/`prost-types` for the generated code itself) aren't added to a
+// Cargo.toml because this tree has none to extend - see `wallet_client`'s module comment for the
+// same caveat.
+
+fn main() {
+    prost_build::compile_protos(&["proto/outbox.proto"], &["proto/"])
+        .expect("failed to compile proto/outbox.proto");
+}
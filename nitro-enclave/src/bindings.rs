@@ -0,0 +1,265 @@
+// JSON FFI surface over `PassWalletManager`, for host languages other than Rust (mobile, Node,
+// Python) to drive the enclave's wallet logic without linking against its Rust types directly.
+// Modeled on the IOTA SDK's bindings-core crate: a single `handle_message` entry point takes a
+// JSON-encoded tagged request, dispatches to the matching `PassWalletManager` method, and returns
+// a JSON-encoded `{"ok": ...}` / `{"error": ...}` envelope - the same shape regardless of which
+// request was sent, so a thin C ABI (`extern "C"` taking/returning `*const c_char`, plus an
+// explicit free function so the host can release the returned buffer) or a `pyo3`/`neon` module
+// only has to marshal strings, never Rust enums or structs, across the boundary.
+//
+// This is deliberately narrower than `server_logic::Command` (which also covers escrow, recovery,
+// batching, secure-channel setup, and more): it exposes the core wallet operations a mobile/Node/
+// Python host actually drives directly. `server_logic` stays the interface for the enclave's own
+// socket protocol; extend `BindingRequest` as more operations need a binding.
+//
+// The `wasm` and `python` submodules below are thin `#[wasm_bindgen]`/`pyo3` passthroughs onto
+// `handle_message`, gated behind the crate's `wasm` and `python` features respectively so the core
+// build stays free of either dependency. A browser extension or Python test harness links one of
+// those instead of this JSON contract directly.
+//
+// Declared via `pub mod bindings;` in `src/lib.rs`, alongside `pub mod server_logic;`.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::pass_logic::{Amount, Asset, Deposit, PassWalletManager, Subaccount};
+
+/// One binding request, tagged by `"type"` so a host language can build it from a plain dict
+/// without knowing Rust's enum representation.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum BindingRequest {
+    CreateWallet { name: String, owner: String },
+    AddAsset { wallet_address: String, asset_id: String, asset: Asset },
+    AddSubaccount { wallet_address: String, subaccount: Subaccount },
+    InboxDeposit { wallet_address: String, deposit: Deposit },
+    ClaimInbox { wallet_address: String, deposit_id: String, subaccount_id: String },
+    InternalTransfer {
+        wallet_address: String,
+        asset_id: String,
+        amount: Amount,
+        from_subaccount: String,
+        to_subaccount: String,
+        memo: Option<String>,
+    },
+    Withdraw {
+        wallet_address: String,
+        asset_id: String,
+        amount: Amount,
+        subaccount_id: String,
+        destination: String,
+        memo: Option<String>,
+    },
+    WithdrawExternal {
+        wallet_address: String,
+        subaccount_id: String,
+        asset_id: String,
+        amount: Amount,
+        destination: String,
+        gas_price: Option<u64>,
+        gas_limit: Option<u64>,
+        chain_id: u64,
+        memo: Option<String>,
+    },
+    ProcessOutbox { wallet_address: String },
+    GetBalance { wallet_address: String, subaccount_id: String, asset_id: String },
+    GetMemos { wallet_address: String, subaccount_id: String },
+    GetWalletState { wallet_address: String },
+    SignMessage { wallet_address: String, domain: String, message: String },
+    GetProvenanceByAsset { wallet_address: String, asset_id: String },
+    GetProvenanceBySubaccount { wallet_address: String, subaccount_id: String },
+}
+
+/// Uniform reply envelope: exactly one of `ok`/`error` is populated, so a host language can branch
+/// on which key is present instead of parsing an HTTP-style status code.
+#[derive(Serialize)]
+struct BindingResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ok: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BindingResponse {
+    fn ok(value: serde_json::Value) -> String {
+        serde_json::to_string(&BindingResponse { ok: Some(value), error: None })
+            .unwrap_or_else(|_| r#"{"error":"Failed to serialize response"}"#.to_string())
+    }
+
+    fn err(message: impl Into<String>) -> String {
+        serde_json::to_string(&BindingResponse { ok: None, error: Some(message.into()) })
+            .unwrap_or_else(|_| r#"{"error":"Failed to serialize error response"}"#.to_string())
+    }
+}
+
+impl PassWalletManager {
+    /// Single entry point every binding should call: parse `request_json` into a `BindingRequest`,
+    /// invoke the matching method, and return a `{"ok": ...}` / `{"error": ...}` JSON string.
+    /// Never panics across the FFI boundary - a handler panic (e.g. an internal `.unwrap()` hit by
+    /// a malformed host request) is caught here and reported as an ordinary error response instead
+    /// of unwinding into a C caller, which would be undefined behavior.
+    pub fn handle_message(&self, request_json: &str) -> String {
+        let request: BindingRequest = match serde_json::from_str(request_json) {
+            Ok(request) => request,
+            Err(e) => return BindingResponse::err(format!("Failed to parse request: {}", e)),
+        };
+
+        match panic::catch_unwind(AssertUnwindSafe(|| self.dispatch_binding_request(request))) {
+            Ok(Ok(value)) => BindingResponse::ok(value),
+            Ok(Err(e)) => BindingResponse::err(e.to_string()),
+            Err(_) => BindingResponse::err("Internal error: handler panicked"),
+        }
+    }
+
+    fn dispatch_binding_request(&self, request: BindingRequest) -> Result<serde_json::Value> {
+        use BindingRequest::*;
+        Ok(match request {
+            CreateWallet { name, owner } => {
+                serde_json::json!({ "wallet_address": self.create_wallet(name, owner)? })
+            }
+            AddAsset { wallet_address, asset_id, asset } => {
+                self.add_asset(&wallet_address, asset_id, asset)?;
+                serde_json::Value::Null
+            }
+            AddSubaccount { wallet_address, subaccount } => {
+                self.add_subaccount(&wallet_address, subaccount)?;
+                serde_json::Value::Null
+            }
+            InboxDeposit { wallet_address, deposit } => {
+                self.inbox_deposit(&wallet_address, deposit)?;
+                serde_json::Value::Null
+            }
+            ClaimInbox { wallet_address, deposit_id, subaccount_id } => {
+                self.claim_inbox(&wallet_address, &deposit_id, &subaccount_id)?;
+                serde_json::Value::Null
+            }
+            InternalTransfer { wallet_address, asset_id, amount, from_subaccount, to_subaccount, memo } => {
+                self.internal_transfer(&wallet_address, &asset_id, amount, &from_subaccount, &to_subaccount, memo)?;
+                serde_json::Value::Null
+            }
+            Withdraw { wallet_address, asset_id, amount, subaccount_id, destination, memo } => {
+                self.withdraw(&wallet_address, &asset_id, amount, &subaccount_id, &destination, memo)?;
+                serde_json::Value::Null
+            }
+            WithdrawExternal { wallet_address, subaccount_id, asset_id, amount, destination, gas_price, gas_limit, chain_id, memo } => {
+                let (raw_transaction, tx_nonce, gas_price, gas_limit, max_fee_per_gas, max_priority_fee_per_gas) = self.withdraw_to_external(
+                    &wallet_address, &subaccount_id, &asset_id, amount, &destination, gas_price, gas_limit, chain_id, memo,
+                    None, None, Vec::new(),
+                )?;
+                serde_json::json!({
+                    "raw_transaction": raw_transaction,
+                    "tx_nonce": tx_nonce,
+                    "gas_price": gas_price,
+                    "gas_limit": gas_limit,
+                    "max_fee_per_gas": max_fee_per_gas,
+                    "max_priority_fee_per_gas": max_priority_fee_per_gas,
+                })
+            }
+            ProcessOutbox { wallet_address } => {
+                serde_json::to_value(self.process_outbox(&wallet_address)?)?
+            }
+            GetBalance { wallet_address, subaccount_id, asset_id } => {
+                serde_json::json!({ "balance": self.get_balance(&wallet_address, &subaccount_id, &asset_id)? })
+            }
+            GetMemos { wallet_address, subaccount_id } => {
+                serde_json::json!({ "memos": self.get_memos(&wallet_address, &subaccount_id)? })
+            }
+            GetWalletState { wallet_address } => self.get_wallet_state(&wallet_address)?,
+            SignMessage { wallet_address, domain, message } => {
+                serde_json::json!({ "signature": self.sign_message(&wallet_address, &domain, &message)? })
+            }
+            GetProvenanceByAsset { wallet_address, asset_id } => {
+                self.get_provenance_by_asset(&wallet_address, &asset_id)?
+            }
+            GetProvenanceBySubaccount { wallet_address, subaccount_id } => {
+                self.get_provenance_by_subaccount(&wallet_address, &subaccount_id)?
+            }
+        })
+    }
+}
+
+/// Browser-embeddable wrapper: `handle_message` already does all the marshaling this needs, so
+/// the wasm surface is a single `#[wasm_bindgen]` passthrough rather than a parallel API. A
+/// `PassWalletManager` is boxed behind `WasmWalletManager` because `wasm_bindgen` requires its
+/// exported types be `'static` and own their data, not borrow across the JS/Rust boundary.
+#[cfg(feature = "wasm")]
+mod wasm {
+    use wasm_bindgen::prelude::*;
+
+    use super::PassWalletManager;
+    use crate::key_manager::EnclaveKMS;
+    use std::sync::{Arc, Mutex};
+
+    #[wasm_bindgen]
+    pub struct WasmWalletManager {
+        inner: PassWalletManager,
+    }
+
+    #[wasm_bindgen]
+    impl WasmWalletManager {
+        /// `master_secret` seeds the in-memory `EnclaveKMS` this manager signs with - callers
+        /// embedding this in a browser extension are expected to supply one derived from the
+        /// extension's own secure storage, not a hardcoded value.
+        #[wasm_bindgen(constructor)]
+        pub fn new(master_secret: &str) -> Result<WasmWalletManager, JsValue> {
+            let kms = EnclaveKMS::new(master_secret).map_err(|e| JsValue::from_str(&e.to_string()))?;
+            Ok(WasmWalletManager {
+                inner: PassWalletManager::new(Arc::new(Mutex::new(kms))),
+            })
+        }
+
+        /// Same request/response JSON envelope as `PassWalletManager::handle_message` - see
+        /// `BindingRequest` for the tagged request shapes this accepts.
+        #[wasm_bindgen(js_name = handleMessage)]
+        pub fn handle_message(&self, request_json: &str) -> String {
+            self.inner.handle_message(request_json)
+        }
+    }
+}
+
+/// Python-embeddable wrapper over the same JSON surface, for driving the wallet from Python test
+/// harnesses. Mirrors `wasm`'s shape (one opaque manager type, one passthrough method) rather than
+/// exposing a method per `BindingRequest` variant, so this module doesn't need to be kept in sync
+/// with every new operation `BindingRequest` grows - only `handle_message`'s JSON contract does.
+#[cfg(feature = "python")]
+mod python {
+    use pyo3::exceptions::PyRuntimeError;
+    use pyo3::prelude::*;
+
+    use super::PassWalletManager;
+    use crate::key_manager::EnclaveKMS;
+    use std::sync::{Arc, Mutex};
+
+    #[pyclass(name = "WalletManager")]
+    pub struct PyWalletManager {
+        inner: PassWalletManager,
+    }
+
+    #[pymethods]
+    impl PyWalletManager {
+        #[new]
+        fn new(master_secret: &str) -> PyResult<Self> {
+            let kms = EnclaveKMS::new(master_secret).map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+            Ok(PyWalletManager {
+                inner: PassWalletManager::new(Arc::new(Mutex::new(kms))),
+            })
+        }
+
+        /// Same request/response JSON envelope as `PassWalletManager::handle_message`. Raises
+        /// `RuntimeError` only if the manager itself panics handling the request; ordinary
+        /// application errors come back in the `{"error": ...}` envelope, same as every other
+        /// binding, so Python callers branch on the parsed dict rather than catching exceptions
+        /// for expected failures like insufficient balance.
+        fn handle_message(&self, request_json: &str) -> String {
+            self.inner.handle_message(request_json)
+        }
+    }
+
+    #[pymodule]
+    fn pass_wallet(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+        m.add_class::<PyWalletManager>()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+// Length-prefixed, pipelined command loop for one accepted vsock connection.
+//
+// The enclave side of the protocol used to read raw bytes into a growing buffer and retry
+// `serde_json::from_slice::<Command>` until it happened to succeed - a different protocol than the
+// length-prefixed `send_u64`/`send_loop` framing every client (`enclave_transport`'s
+// `VsockTransport`, `wallet_client`'s enclave dial) actually speaks, and one that can only ever
+// handle a single command before the connection must be torn down. `handle_framed_connection`
+// below speaks the same framing as the client: read one `u64` length prefix via `recv_u64_or_eof`,
+// read exactly that many bytes, decode and dispatch one `Command` through `server_logic::
+// parse_command`, write back a length-prefixed `Response`, and loop - so a client can pipeline
+// multiple commands over one connection instead of reconnecting for each.
+//
+// Meant to be called from the still-to-be-written `server()`/`handle_connection()` accept loop
+// (see `connection_pool::ConnectionPool::dispatch`'s doc comment) as the body of each per-
+// connection job: `pool.dispatch(move || { set_read_timeout(fd, idle_timeout)?;
+// connection_handler::handle_framed_connection(fd) })`. Declared via `pub mod connection_handler;`
+// in `src/lib.rs`, next to `connection_pool`.
+
+use std::os::unix::io::RawFd;
+
+use crate::protocol_helpers::{recv_loop, recv_u64_or_eof, send_loop, send_u64};
+use crate::server_logic::parse_command;
+
+/// Sanity ceiling on a single incoming command frame, matching `enclave_transport`'s
+/// `MAX_RESPONSE_LEN` on the response side: `recv_u64_or_eof` reads a length prefix straight off
+/// the wire, so an unbounded value must be rejected before we try to allocate a buffer for it.
+const MAX_COMMAND_LEN: u64 = 64 * 1024 * 1024;
+
+/// Serve one connection until the peer closes it. Each iteration reads exactly one
+/// length-prefixed `Command`, dispatches it, and writes back exactly one length-prefixed
+/// `Response` - a malformed or failing command produces an error `Response`, not a dropped
+/// connection, so the caller can keep pipelining further commands on the same socket. Returns
+/// `Ok(())` once the peer closes the connection cleanly between commands; any other I/O failure
+/// (including a connection closed mid-frame) is returned as `Err`.
+pub fn handle_framed_connection(fd: RawFd) -> Result<(), String> {
+    loop {
+        let command_len = match recv_u64_or_eof(fd)? {
+            Some(len) => len,
+            None => return Ok(()),
+        };
+        if command_len > MAX_COMMAND_LEN {
+            return Err(format!(
+                "Command length {} exceeds the {} byte sanity ceiling",
+                command_len, MAX_COMMAND_LEN
+            ));
+        }
+
+        let mut command_buf = vec![0u8; command_len as usize];
+        recv_loop(fd, &mut command_buf, command_len)?;
+        let command_str = String::from_utf8(command_buf)
+            .map_err(|err| format!("The received bytes are not UTF-8: {:?}", err))?;
+
+        let response = parse_command(&command_str).unwrap_or_else(|e| crate::server_logic::Response {
+            success: false,
+            data: None,
+            error: Some(e),
+        });
+
+        let response_bytes = serde_json::to_vec(&response)
+            .map_err(|err| format!("Failed to serialize response: {:?}", err))?;
+        let response_len = response_bytes.len() as u64;
+        send_u64(fd, response_len)?;
+        send_loop(fd, &response_bytes, response_len)?;
+    }
+}
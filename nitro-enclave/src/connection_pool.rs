@@ -0,0 +1,78 @@
+// Bounded worker pool the accept loop in `server()` should dispatch each freshly accepted
+// connection to, so one slow or stalled peer no longer blocks every other connection sitting in
+// the 128-deep listen backlog behind it. A fixed number of worker threads (rather than spawning
+// one thread per connection) caps concurrency so a burst of simultaneous connections can't exhaust
+// the process's thread budget; a connection accepted while every worker is busy simply waits in
+// the dispatch queue instead of starving the accept loop. This matches the multiplexed
+// per-connection handling `distant` uses for its client/server/manager protocol.
+//
+// `set_read_timeout` is the other half: without it, `recv_u64`/`recv_loop` block a worker thread
+// forever on a peer that sends a length prefix and then goes silent. Pairing a bounded pool with
+// per-connection read timeouts means a single stuck peer costs one worker for `idle_timeout`, not
+// one worker indefinitely.
+//
+// Declared via `pub mod connection_pool;` in `src/lib.rs`; `server()`'s accept loop is meant to
+// call `pool.dispatch(move || { set_read_timeout(fd, idle_timeout)?; connection_handler::handle_framed_connection(fd) })`
+// for each accepted socket instead of calling `handle_connection` inline - `EnclaveKMS` is reached
+// through the same `lazy_static` `KMS` singleton `parse_command` already locks, so no state needs
+// threading through the dispatch closure.
+
+use std::os::unix::io::RawFd;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use nix::sys::socket::setsockopt;
+use nix::sys::socket::sockopt::RcvTimeo;
+use nix::sys::time::TimeVal;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+pub struct ConnectionPool {
+    sender: mpsc::Sender<Job>,
+    _workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl ConnectionPool {
+    /// Spin up `worker_count` threads pulling jobs off a shared queue. `worker_count` is the
+    /// effective concurrency cap: once all workers are busy, a newly dispatched job waits in the
+    /// queue rather than spawning another thread.
+    pub fn new(worker_count: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..worker_count.max(1))
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || loop {
+                    let job = {
+                        let receiver = receiver.lock().unwrap();
+                        receiver.recv()
+                    };
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break, // sender dropped: pool is shutting down
+                    }
+                })
+            })
+            .collect();
+
+        ConnectionPool { sender, _workers: workers }
+    }
+
+    /// Queue `job` for a worker to run. Never blocks the accept loop, even with every worker busy.
+    pub fn dispatch(&self, job: impl FnOnce() + Send + 'static) {
+        // The receiver only disconnects if every worker thread has panicked and exited; dropping
+        // the job in that case is preferable to panicking the accept loop over it.
+        let _ = self.sender.send(Box::new(job));
+    }
+}
+
+/// Set a read timeout on `fd` so a peer that sends a length prefix but never the promised body -
+/// or simply never speaks at all - is reaped after `timeout` instead of pinning a worker thread
+/// indefinitely. Applies equally to vsock and TCP file descriptors; both support `SO_RCVTIMEO`.
+pub fn set_read_timeout(fd: RawFd, timeout: Duration) -> Result<(), String> {
+    let timeval = TimeVal::new(timeout.as_secs() as i64, timeout.subsec_micros() as i64);
+    setsockopt(fd, RcvTimeo, &timeval).map_err(|e| format!("Failed to set read timeout: {}", e))
+}
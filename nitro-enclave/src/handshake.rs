@@ -0,0 +1,266 @@
+// Mutual authenticated key exchange run before any command is processed on the vsock link,
+// inspired by the Scuttlebutt secret handshake (SHS, as implemented by kuska-ssb): `server()`'s
+// accept loop and `client()` both invoke this first and drop the connection outright on any
+// failure, so nothing ever reaches `handle_connection`/`parse_command` without both peers having
+// proven possession of a long-term `Identity` key pinned to the same `NETWORK_ID`.
+//
+// Four messages:
+//   1. client -> server: HMAC(NETWORK_ID, client_eph_pub) || client_eph_pub
+//   2. server -> client: HMAC(NETWORK_ID, server_eph_pub) || server_eph_pub
+//   3. client -> server: box(client's Ed25519 public key || its signature over the transcript so
+//      far), sealed under a key derived from the ephemeral ECDH secret
+//   4. server -> client: box(server's signature over the transcript plus message 3), binding the
+//      server's proof to this exact session so a captured signature can't be replayed elsewhere
+// Both sides then derive directional ChaCha20-Poly1305 keys from the same ECDH secret for
+// `protocol_helpers::send_encrypted_frame`/`recv_encrypted_frame` to use for the rest of the
+// connection, and the connection is dropped if any HMAC check, box decryption, or signature
+// verification fails - or if the caller's `is_authorized` predicate rejects the peer's identity.
+//
+// `ed25519-dalek` and `hmac` aren't added to a Cargo.toml because this tree has none to extend -
+// see `wallet_client`'s module comment for the same caveat. Declared via `pub mod handshake;` in
+// `src/lib.rs`, invoked at the top of both `client()` and the accept loop in `server()`.
+
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key as ChaChaKey, KeyInit, Nonce as ChaChaNonce};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::os::unix::io::RawFd;
+use x25519_dalek::{EphemeralSecret, PublicKey as X25519Public};
+use zeroize::Zeroize;
+
+use crate::protocol_helpers::{recv_loop, recv_u64, send_loop, send_u64};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Pins both ends of the handshake to this application's protocol, the same role Scuttlebutt's
+/// "network key" plays: a peer that doesn't derive the same value can't even produce a message 1
+/// this side recognizes, regardless of whether it holds a legitimate long-term identity key.
+fn network_id() -> [u8; 32] {
+    Sha256::digest(b"pass-wallet-enclave-secret-handshake-network-id-v1").into()
+}
+
+/// Long-term Ed25519 identity whose signature in messages 3/4 is what actually proves who a peer
+/// is; the ephemeral X25519 keys exchanged in messages 1/2 exist only to derive a fresh shared
+/// secret per connection and carry no identity of their own.
+pub struct Identity {
+    signing_key: SigningKey,
+}
+
+impl Identity {
+    pub fn generate() -> Self {
+        Identity { signing_key: SigningKey::generate(&mut rand::thread_rng()) }
+    }
+
+    pub fn from_bytes(bytes: &[u8; 32]) -> Self {
+        Identity { signing_key: SigningKey::from_bytes(bytes) }
+    }
+
+    pub fn public(&self) -> VerifyingKey {
+        self.signing_key.verifying_key()
+    }
+}
+
+/// Directional ChaCha20-Poly1305 keys for `protocol_helpers::send_encrypted_frame`/
+/// `recv_encrypted_frame`: distinct per direction so each side's independently counted nonces
+/// never collide with the other side's under the same key.
+pub struct SessionKeys {
+    pub send_key: [u8; 32],
+    pub recv_key: [u8; 32],
+}
+
+impl Drop for SessionKeys {
+    fn drop(&mut self) {
+        self.send_key.zeroize();
+        self.recv_key.zeroize();
+    }
+}
+
+fn hkdf_key(shared_secret: &[u8; 32], info: &[u8]) -> [u8; 32] {
+    let hkdf = Hkdf::<Sha256>::new(Some(&network_id()), shared_secret);
+    let mut key = [0u8; 32];
+    hkdf.expand(info, &mut key).expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+fn send_message(fd: RawFd, bytes: &[u8]) -> Result<(), String> {
+    let len = bytes.len() as u64;
+    send_u64(fd, len)?;
+    send_loop(fd, bytes, len)
+}
+
+fn recv_message(fd: RawFd) -> Result<Vec<u8>, String> {
+    let len = recv_u64(fd)?;
+    let mut buf = vec![0u8; len as usize];
+    recv_loop(fd, &mut buf, len)?;
+    Ok(buf)
+}
+
+/// Build message 1/2: `HMAC(network_id, eph_pub) || eph_pub`, tagged so a peer that doesn't share
+/// `network_id` can't produce a tag this side will accept.
+fn tag_ephemeral_key(eph_pub: &X25519Public) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(&network_id()).expect("HMAC accepts any key length");
+    mac.update(eph_pub.as_bytes());
+    let tag = mac.finalize().into_bytes();
+
+    let mut message = Vec::with_capacity(32 + 32);
+    message.extend_from_slice(&tag);
+    message.extend_from_slice(eph_pub.as_bytes());
+    message
+}
+
+/// Verify and unpack a message built by `tag_ephemeral_key`.
+fn untag_ephemeral_key(message: &[u8]) -> Result<X25519Public, String> {
+    if message.len() != 64 {
+        return Err("Malformed ephemeral-key announcement".to_string());
+    }
+    let (tag, eph_pub_bytes) = message.split_at(32);
+
+    let mut mac = HmacSha256::new_from_slice(&network_id()).expect("HMAC accepts any key length");
+    mac.update(eph_pub_bytes);
+    mac.verify_slice(tag)
+        .map_err(|_| "Peer is not pinned to this protocol's network id".to_string())?;
+
+    let eph_pub_array: [u8; 32] = eph_pub_bytes.try_into().unwrap();
+    Ok(X25519Public::from(eph_pub_array))
+}
+
+/// Seal `plaintext` for message 3/4's box with a fresh random nonce: unlike the per-message
+/// counters `protocol_helpers` uses once a session is live, each handshake runs this at most once
+/// per direction, so there is no counter to reuse and a random nonce is simplest.
+fn seal_box(key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(key));
+    let mut nonce_bytes = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce_bytes);
+    let ciphertext = cipher
+        .encrypt(ChaChaNonce::from_slice(&nonce_bytes), plaintext)
+        .expect("ChaCha20-Poly1305 encryption of a bounded handshake message cannot fail");
+
+    let mut boxed = nonce_bytes.to_vec();
+    boxed.extend_from_slice(&ciphertext);
+    boxed
+}
+
+fn open_box(key: &[u8; 32], boxed: &[u8]) -> Result<Vec<u8>, String> {
+    if boxed.len() < 12 {
+        return Err("Handshake box shorter than its nonce".to_string());
+    }
+    let (nonce_bytes, ciphertext) = boxed.split_at(12);
+    let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(key));
+    cipher
+        .decrypt(ChaChaNonce::from_slice(nonce_bytes), ciphertext)
+        .map_err(|_| "Failed to open handshake box: wrong key or tampered message".to_string())
+}
+
+fn pack_identity_proof(identity: &Identity, transcript: &[u8]) -> Vec<u8> {
+    let signature = identity.signing_key.sign(transcript);
+    let mut payload = Vec::with_capacity(32 + 64);
+    payload.extend_from_slice(identity.public().as_bytes());
+    payload.extend_from_slice(&signature.to_bytes());
+    payload
+}
+
+/// Verify an identity proof built by `pack_identity_proof` against `transcript`, returning the
+/// peer's long-term public key once its signature checks out.
+fn unpack_identity_proof(payload: &[u8], transcript: &[u8]) -> Result<VerifyingKey, String> {
+    if payload.len() != 32 + 64 {
+        return Err("Malformed identity proof".to_string());
+    }
+    let (public_bytes, signature_bytes) = payload.split_at(32);
+
+    let public = VerifyingKey::from_bytes(public_bytes.try_into().unwrap())
+        .map_err(|e| format!("Malformed peer public key: {}", e))?;
+    let signature = Signature::from_slice(signature_bytes)
+        .map_err(|e| format!("Malformed peer signature: {}", e))?;
+    public
+        .verify(transcript, &signature)
+        .map_err(|_| "Peer failed to prove possession of its long-term identity key".to_string())?;
+
+    Ok(public)
+}
+
+fn session_keys(shared_secret: &[u8; 32], we_are_client: bool) -> SessionKeys {
+    let client_to_server = hkdf_key(shared_secret, b"pass-wallet-handshake-client-to-server");
+    let server_to_client = hkdf_key(shared_secret, b"pass-wallet-handshake-server-to-client");
+    if we_are_client {
+        SessionKeys { send_key: client_to_server, recv_key: server_to_client }
+    } else {
+        SessionKeys { send_key: server_to_client, recv_key: client_to_server }
+    }
+}
+
+/// Run the client side of the handshake. `is_authorized` is handed the server's long-term public
+/// key once its signature has been verified, so the caller can pin it to a known identity (the
+/// same role pinning a TLS certificate plays) rather than trusting whoever answers the socket.
+pub fn client_handshake(
+    fd: RawFd,
+    local: &Identity,
+    is_authorized: impl Fn(&VerifyingKey) -> bool,
+) -> Result<SessionKeys, String> {
+    let client_eph_secret = EphemeralSecret::random_from_rng(rand::thread_rng());
+    let client_eph_public = X25519Public::from(&client_eph_secret);
+    send_message(fd, &tag_ephemeral_key(&client_eph_public))?;
+
+    let msg2 = recv_message(fd)?;
+    let server_eph_public = untag_ephemeral_key(&msg2)?;
+
+    let shared_secret: [u8; 32] = *client_eph_secret.diffie_hellman(&server_eph_public).as_bytes();
+    let mut transcript = network_id().to_vec();
+    transcript.extend_from_slice(client_eph_public.as_bytes());
+    transcript.extend_from_slice(server_eph_public.as_bytes());
+
+    let msg3_key = hkdf_key(&shared_secret, b"pass-wallet-handshake-msg3");
+    let msg3_payload = pack_identity_proof(local, &transcript);
+    send_message(fd, &seal_box(&msg3_key, &msg3_payload))?;
+
+    transcript.extend_from_slice(&msg3_payload);
+    let msg4_key = hkdf_key(&shared_secret, b"pass-wallet-handshake-msg4");
+    let msg4 = recv_message(fd)?;
+    let msg4_payload = open_box(&msg4_key, &msg4)?;
+    let server_public = unpack_identity_proof(&msg4_payload, &transcript)?;
+
+    if !is_authorized(&server_public) {
+        return Err("Server's long-term identity is not on the client's allow list".to_string());
+    }
+
+    Ok(session_keys(&shared_secret, true))
+}
+
+/// Run the server side of the handshake for one freshly accepted connection. `is_authorized` is
+/// handed the client's long-term public key once its signature has been verified, so the caller
+/// can reject a connection from a key it doesn't recognize even though the cryptography checked
+/// out. Returns the session keys plus the client's now-authenticated public key.
+pub fn server_handshake(
+    fd: RawFd,
+    local: &Identity,
+    is_authorized: impl Fn(&VerifyingKey) -> bool,
+) -> Result<(SessionKeys, VerifyingKey), String> {
+    let msg1 = recv_message(fd)?;
+    let client_eph_public = untag_ephemeral_key(&msg1)?;
+
+    let server_eph_secret = EphemeralSecret::random_from_rng(rand::thread_rng());
+    let server_eph_public = X25519Public::from(&server_eph_secret);
+    send_message(fd, &tag_ephemeral_key(&server_eph_public))?;
+
+    let shared_secret: [u8; 32] = *server_eph_secret.diffie_hellman(&client_eph_public).as_bytes();
+    let mut transcript = network_id().to_vec();
+    transcript.extend_from_slice(client_eph_public.as_bytes());
+    transcript.extend_from_slice(server_eph_public.as_bytes());
+
+    let msg3_key = hkdf_key(&shared_secret, b"pass-wallet-handshake-msg3");
+    let msg3 = recv_message(fd)?;
+    let msg3_payload = open_box(&msg3_key, &msg3)?;
+    let client_public = unpack_identity_proof(&msg3_payload, &transcript)?;
+
+    if !is_authorized(&client_public) {
+        return Err("Client's long-term identity is not on the server's allow list".to_string());
+    }
+
+    transcript.extend_from_slice(&msg3_payload);
+    let msg4_key = hkdf_key(&shared_secret, b"pass-wallet-handshake-msg4");
+    let msg4_payload = pack_identity_proof(local, &transcript);
+    send_message(fd, &seal_box(&msg4_key, &msg4_payload))?;
+
+    Ok((session_keys(&shared_secret, false), client_public))
+}
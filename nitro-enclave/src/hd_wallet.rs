@@ -0,0 +1,149 @@
+// BIP-39 mnemonic generation plus BIP-32/44 hierarchical-deterministic account derivation, so a
+// wallet can be backed up and restored from a single phrase instead of each `EnclaveKMS` key being
+// an isolated random secret with no recovery story. `key_manager::EnclaveKMS` is meant to call
+// `generate_mnemonic`/`derive_account` from its `Command::KeygenHd`/`Command::DeriveNext` handlers
+// (see `server_logic::Command`), storing the returned seed encrypted the same way a single private
+// key is stored today and deriving accounts along `m/44'/60'/0'/0/i` on demand.
+//
+// Declared via `pub mod hd_wallet;` in `src/lib.rs`, next to `key_manager`.
+
+use anyhow::{anyhow, Result};
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::elliptic_curve::{generic_array::GenericArray, group::ff::Field, PrimeField};
+use k256::{Scalar, SecretKey};
+use sha2::Sha512;
+
+use crate::key_manager::public_key_to_address;
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// A single derived HD account. `private_key` is only ever meant to feed straight into the same
+/// in-enclave storage an isolated `generate_ethereum_account` key would use - it never needs to
+/// leave the enclave.
+pub struct HdAccount {
+    pub index: u32,
+    pub derivation_path: String,
+    pub private_key: [u8; 32],
+    pub address: String,
+}
+
+/// 128-256 bits of entropy (16/20/24/28/32 bytes) mapped to a checksummed BIP-39 English mnemonic.
+pub fn generate_mnemonic(entropy_bits: usize) -> Result<String> {
+    if entropy_bits % 32 != 0 || !(128..=256).contains(&entropy_bits) {
+        return Err(anyhow!("Entropy must be 128-256 bits in multiples of 32"));
+    }
+    let mut entropy = vec![0u8; entropy_bits / 8];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut entropy);
+    let mnemonic = Mnemonic::from_entropy(&entropy).map_err(|e| anyhow!("Failed to build mnemonic: {}", e))?;
+    Ok(mnemonic.to_string())
+}
+
+/// PBKDF2-HMAC-SHA512 over the mnemonic's normalized words, 2048 iterations, salt `"mnemonic"` -
+/// the standard BIP-39 seed derivation (no optional passphrase support here, matching the enclave's
+/// single-phrase backup story).
+pub fn mnemonic_to_seed(mnemonic: &str) -> Result<[u8; 64]> {
+    let mnemonic = Mnemonic::parse_normalized(mnemonic).map_err(|e| anyhow!("Invalid mnemonic: {}", e))?;
+    let mut seed = [0u8; 64];
+    pbkdf2::pbkdf2_hmac::<Sha512>(mnemonic.to_string().as_bytes(), b"mnemonic", 2048, &mut seed);
+    Ok(seed)
+}
+
+/// BIP-32 master key: `HMAC-SHA512(key="Bitcoin seed", data=seed)` split into a 32-byte private
+/// key and a 32-byte chain code.
+fn master_key_from_seed(seed: &[u8]) -> Result<([u8; 32], [u8; 32])> {
+    let mut mac = HmacSha512::new_from_slice(b"Bitcoin seed").map_err(|e| anyhow!("HMAC init failed: {}", e))?;
+    mac.update(seed);
+    let result = mac.finalize().into_bytes();
+    let mut key = [0u8; 32];
+    let mut chain_code = [0u8; 32];
+    key.copy_from_slice(&result[0..32]);
+    chain_code.copy_from_slice(&result[32..64]);
+    Ok((key, chain_code))
+}
+
+fn scalar_from_bytes(bytes: &[u8; 32]) -> Result<Scalar> {
+    Option::<Scalar>::from(Scalar::from_repr(GenericArray::clone_from_slice(bytes)))
+        .ok_or_else(|| anyhow!("Derived scalar is not a valid secp256k1 field element"))
+}
+
+/// One BIP-32 child-key-derivation step. `index >= 2^31` is a hardened derivation (the HMAC input
+/// is `0x00 || parent_priv || index`); otherwise it's normal derivation (`parent_pubkey_compressed
+/// || index`). The child private key is `(IL + parent_priv) mod n`, rejected (per BIP-32) if it
+/// overflows to zero - astronomically unlikely in practice but still checked rather than silently
+/// producing an invalid key.
+fn derive_child(parent_key: &[u8; 32], parent_chain_code: &[u8; 32], index: u32) -> Result<([u8; 32], [u8; 32])> {
+    let mut data = Vec::with_capacity(37);
+    if index >= 0x8000_0000 {
+        data.push(0u8);
+        data.extend_from_slice(parent_key);
+    } else {
+        let secret_key = SecretKey::from_bytes(parent_key.into()).map_err(|e| anyhow!("Invalid parent key: {}", e))?;
+        let public_key = secret_key.public_key();
+        data.extend_from_slice(public_key.to_encoded_point(true).as_bytes());
+    }
+    data.extend_from_slice(&index.to_be_bytes());
+
+    let mut mac = HmacSha512::new_from_slice(parent_chain_code).map_err(|e| anyhow!("HMAC init failed: {}", e))?;
+    mac.update(&data);
+    let result = mac.finalize().into_bytes();
+
+    let il_scalar = scalar_from_bytes(&result[0..32].try_into().unwrap())?;
+    let parent_scalar = scalar_from_bytes(parent_key)?;
+    let child_scalar = il_scalar + parent_scalar;
+    if bool::from(child_scalar.is_zero()) {
+        return Err(anyhow!("Derived child key is invalid (IL + parent = 0 mod n); caller should retry with index + 1"));
+    }
+
+    let mut child_key = [0u8; 32];
+    child_key.copy_from_slice(child_scalar.to_repr().as_slice());
+    let mut child_chain_code = [0u8; 32];
+    child_chain_code.copy_from_slice(&result[32..64]);
+
+    Ok((child_key, child_chain_code))
+}
+
+/// Parse `m/44'/60'/0'/0/i`-style paths into their raw (possibly-hardened) index sequence.
+fn parse_path(path: &str) -> Result<Vec<u32>> {
+    let mut segments = path.split('/');
+    match segments.next() {
+        Some("m") => {}
+        _ => return Err(anyhow!("Derivation path must start with 'm'")),
+    }
+    segments
+        .map(|segment| {
+            let (digits, hardened) = match segment.strip_suffix('\'') {
+                Some(digits) => (digits, true),
+                None => (segment, false),
+            };
+            let index: u32 = digits.parse().map_err(|_| anyhow!("Invalid path segment: {}", segment))?;
+            if hardened {
+                index.checked_add(0x8000_0000).ok_or_else(|| anyhow!("Path segment out of range: {}", segment))
+            } else {
+                Ok(index)
+            }
+        })
+        .collect()
+}
+
+/// Derive the Ethereum account at `m/44'/60'/0'/0/{account_index}` from `seed` (see
+/// `mnemonic_to_seed`), feeding the derived secret into the same `public_key_to_address` used by
+/// an isolated `generate_ethereum_account` key.
+pub fn derive_account(seed: &[u8], account_index: u32) -> Result<HdAccount> {
+    let path = format!("m/44'/60'/0'/0/{}", account_index);
+    let indices = parse_path(&path)?;
+
+    let (mut key, mut chain_code) = master_key_from_seed(seed)?;
+    for index in indices {
+        let (child_key, child_chain_code) = derive_child(&key, &chain_code, index)?;
+        key = child_key;
+        chain_code = child_chain_code;
+    }
+
+    let secret_key = SecretKey::from_bytes((&key).into()).map_err(|e| anyhow!("Invalid derived key: {}", e))?;
+    let public_key = secret_key.public_key();
+    let address = public_key_to_address(&public_key);
+
+    Ok(HdAccount { index: account_index, derivation_path: path, private_key: key, address })
+}
@@ -0,0 +1,103 @@
+// Encodes and decodes `PendingWithdrawal` (`pass_logic`'s on-disk outbox queue entry) as the
+// versioned `pass_wallet.outbox.QueuedWithdrawal` protobuf message defined in
+// `proto/outbox.proto`, compiled at build time by `build.rs`. Protobuf's numbered, optional
+// fields are what let the on-disk outbox stay readable across app upgrades: an entry written by
+// an older build is missing newer fields and decodes them to their defaults instead of failing
+// to parse, and the reverse holds too once an older build starts dropping fields it doesn't know.
+// This replaces the ad-hoc `serde_json::to_vec`/`from_slice` calls `pass_logic` used to make
+// directly against `PendingWithdrawal` at each outbox storage call site.
+//
+// Declared via `pub mod outbox_codec;` in `src/lib.rs`, next to `pass_logic`.
+
+use anyhow::{anyhow, Result};
+use prost::Message;
+
+use crate::pass_logic::{Amount, PendingWithdrawal, TransactionEnvelopeType, WithdrawalStatus};
+
+pub mod pb {
+    include!(concat!(env!("OUT_DIR"), "/pass_wallet.outbox.rs"));
+}
+
+fn amount_to_wire(amount: Amount) -> Vec<u8> {
+    let mut bytes = [0u8; 32];
+    amount.to_big_endian(&mut bytes);
+    bytes.to_vec()
+}
+
+fn amount_from_wire(bytes: &[u8]) -> Amount {
+    Amount::from_big_endian(bytes)
+}
+
+fn tx_type_from_wire(value: u32) -> TransactionEnvelopeType {
+    match value {
+        1 => TransactionEnvelopeType::AccessList,
+        2 => TransactionEnvelopeType::DynamicFee,
+        _ => TransactionEnvelopeType::Legacy,
+    }
+}
+
+fn status_from_wire(status: i32) -> WithdrawalStatus {
+    match pb::QueuedWithdrawalStatus::from_i32(status).unwrap_or(pb::QueuedWithdrawalStatus::Queued) {
+        pb::QueuedWithdrawalStatus::Queued => WithdrawalStatus::Queued,
+        pb::QueuedWithdrawalStatus::Broadcast => WithdrawalStatus::Broadcast,
+        pb::QueuedWithdrawalStatus::Confirmed => WithdrawalStatus::Confirmed,
+    }
+}
+
+impl From<&PendingWithdrawal> for pb::QueuedWithdrawal {
+    fn from(withdrawal: &PendingWithdrawal) -> Self {
+        let status = match withdrawal.status {
+            WithdrawalStatus::Queued => pb::QueuedWithdrawalStatus::Queued,
+            WithdrawalStatus::Broadcast => pb::QueuedWithdrawalStatus::Broadcast,
+            WithdrawalStatus::Confirmed => pb::QueuedWithdrawalStatus::Confirmed,
+        };
+        pb::QueuedWithdrawal {
+            wallet_address: withdrawal.wallet_address.clone(),
+            subaccount_id: withdrawal.subaccount_id.clone(),
+            asset_id: withdrawal.asset_id.clone(),
+            amount: amount_to_wire(withdrawal.amount),
+            destination: withdrawal.destination.clone(),
+            nonce: withdrawal.nonce,
+            signed_raw_transaction: withdrawal.signed_raw_transaction.clone(),
+            created_at: withdrawal.created_at,
+            tx_type: withdrawal.tx_type as u32,
+            effective_gas_price: withdrawal.effective_gas_price,
+            status: status as i32,
+            retry_count: withdrawal.retry_count,
+            memo: withdrawal.memo.clone(),
+        }
+    }
+}
+
+impl From<pb::QueuedWithdrawal> for PendingWithdrawal {
+    fn from(wire: pb::QueuedWithdrawal) -> Self {
+        PendingWithdrawal {
+            wallet_address: wire.wallet_address,
+            subaccount_id: wire.subaccount_id,
+            asset_id: wire.asset_id,
+            amount: amount_from_wire(&wire.amount),
+            destination: wire.destination,
+            nonce: wire.nonce,
+            signed_raw_transaction: wire.signed_raw_transaction,
+            created_at: wire.created_at,
+            tx_type: tx_type_from_wire(wire.tx_type),
+            effective_gas_price: wire.effective_gas_price,
+            status: status_from_wire(wire.status),
+            retry_count: wire.retry_count,
+            memo: wire.memo,
+        }
+    }
+}
+
+/// Encode a queued withdrawal as its versioned protobuf wire form for storage.
+pub fn encode_queued_withdrawal(withdrawal: &PendingWithdrawal) -> Vec<u8> {
+    pb::QueuedWithdrawal::from(withdrawal).encode_to_vec()
+}
+
+/// Decode a queued withdrawal from its stored protobuf wire form. Fields the schema has added
+/// since `bytes` was written decode to their defaults rather than failing the read.
+pub fn decode_queued_withdrawal(bytes: &[u8]) -> Result<PendingWithdrawal> {
+    pb::QueuedWithdrawal::decode(bytes)
+        .map(PendingWithdrawal::from)
+        .map_err(|e| anyhow!("Failed to decode queued withdrawal: {}", e))
+}
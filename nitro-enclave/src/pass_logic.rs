@@ -1,8 +1,79 @@
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
 use anyhow::{Result, anyhow};
+use argon2::Argon2;
+use base64::{engine::general_purpose::{STANDARD as BASE64_ENGINE, URL_SAFE_NO_PAD as BASE64_URL_ENGINE}, Engine as _};
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key as ChaChaKey, KeyInit, Nonce as ChaChaNonce};
+use crypto_box::{aead::Aead as _, SalsaBox};
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use primitive_types::U256;
+use zeroize::Zeroize;
 use crate::key_manager::EnclaveKMS;
+use crate::outbox_codec::{decode_queued_withdrawal, encode_queued_withdrawal};
+use crate::passes::{PassIssuer, PassSigningCertificate};
+use fd_lock::RwLock as FileLock;
+use std::fs::File;
+
+/// Version byte prefixed to every backup blob so future format changes can be detected on import
+const BACKUP_FORMAT_VERSION: u8 = 1;
+/// PBKDF2-HMAC-SHA256 iteration count used to derive the backup encryption key from the password
+const BACKUP_KDF_ITERATIONS: u32 = 210_000;
+const BACKUP_SALT_LEN: usize = 16;
+const BACKUP_NONCE_LEN: usize = 12;
+/// Version byte prefixed to every device-migration blob (`export_wallet_for_migration`)
+const MIGRATION_FORMAT_VERSION: u8 = 1;
+const MIGRATION_SALT_LEN: usize = 16;
+const MIGRATION_NONCE_LEN: usize = 24;
+/// Minimum fractional increase, in basis points, that a replacement withdrawal must offer over the
+/// gas price of the not-yet-broadcast transaction already queued at its nonce (geth's default 12.5%).
+const REPLACEMENT_BUMP_BPS: u64 = 1_250;
+/// Maximum number of queued withdrawals retained per wallet before the lowest-fee "future" entry
+/// (one with a nonce gap before it) is evicted to make room.
+const MAX_OUTBOX_PER_WALLET: usize = 256;
+/// Number of block confirmations a mined withdrawal must accumulate before it's pruned from the
+/// outbox queue as final.
+const CONFIRMATIONS_REQUIRED: u64 = 12;
+/// Reserved subaccount ID that `FeePolicy` fees are deducted into. Not a real user-facing
+/// subaccount - it never appears in `subaccounts`, it's just a `balances` key the wallet owner can
+/// read via `get_balance(FEE_SUBACCOUNT_ID, ..)` to see accrued fees.
+const FEE_SUBACCOUNT_ID: &str = "__fees__";
+/// Reserved subaccount ID that `withdrawal_fee_bps` proportional fees are deducted into - distinct
+/// from `FEE_SUBACCOUNT_ID` so the two revenue streams never commingle on a wallet that has both a
+/// fixed `fee_policy` and a proportional withdrawal fee configured. Read via
+/// `get_collected_fees`/swept out via `sweep_fees`, same as `FEE_SUBACCOUNT_ID`.
+const WITHDRAWAL_FEE_SUBACCOUNT_ID: &str = "__withdrawal_fees__";
+
+/// Intrinsic gas cost of a native ETH transfer (21000 per the yellow paper), used as the floor
+/// `estimate_withdrawal_gas` returns for `TokenType::ETH` withdrawals.
+const INTRINSIC_GAS_ETH_TRANSFER: u64 = 21_000;
+/// Intrinsic gas cost `estimate_withdrawal_gas` assumes for an ERC20 `transfer` call - higher than
+/// a native transfer to account for the token contract's own storage writes/state transitions.
+const INTRINSIC_GAS_ERC20_TRANSFER: u64 = 60_000;
+/// Safety margin `estimate_withdrawal_gas` adds on top of the intrinsic cost, so a withdrawal isn't
+/// queued with exactly the bare minimum gas a slightly more expensive execution path could exceed.
+const GAS_ESTIMATE_SAFETY_MARGIN_BPS: u64 = 1_000; // 10%
+/// Upper bound `estimate_withdrawal_gas` will never exceed, mirroring a conservative per-transaction
+/// block-gas cap so a miscalibrated estimate can't request an unreasonably large `gas_limit`.
+const GAS_ESTIMATE_CEILING: u64 = 1_000_000;
+
+/// Virtual shares `deposit_to_pool`/`withdraw_from_pool` add to `total_shares` on one side of
+/// their share-pricing ratio, and `POOL_VIRTUAL_ASSETS` adds to `pool_balance` on the other - the
+/// standard ERC4626 mitigation against a first-depositor donation attack. See `deposit_to_pool`.
+const POOL_VIRTUAL_SHARES: u64 = 1_000;
+/// Virtual balance paired with `POOL_VIRTUAL_SHARES`; see `deposit_to_pool`.
+const POOL_VIRTUAL_ASSETS: u64 = 1_000;
+
+/// Floor `PassWalletState::update_base_fee` will never adjust the base fee below, mirroring the
+/// fact a real chain's base fee can fall but conventionally doesn't go to zero.
+const BASE_FEE_FLOOR: u64 = 1_000_000_000; // 1 gwei
+/// Maximum fraction of the current base fee `update_base_fee` can move it by in a single step,
+/// matching EIP-1559's 1/8 (12.5%) per-block adjustment cap.
+const BASE_FEE_MAX_ADJUSTMENT_BPS: u64 = 1_250;
+
 // Helper function to convert string address to bytes
 fn parse_address(addr_str: &str) -> Result<Vec<u8>> {
     let clean_addr = addr_str.strip_prefix("0x").unwrap_or(addr_str);
@@ -12,6 +83,47 @@ fn parse_address(addr_str: &str) -> Result<Vec<u8>> {
     hex::decode(clean_addr).map_err(|e| anyhow!("Invalid address hex: {}", e))
 }
 
+/// Convert a human-readable display amount (e.g. "1.5") into base units using `decimals`,
+/// e.g. "1.5" with `decimals = 6` becomes 1_500_000. Withdrawal policies are always authored
+/// in display units and must go through this before being compared against raw `Amount` values.
+fn display_amount_to_base_units(display: &str, decimals: u32) -> Result<Amount> {
+    let mut parts = display.splitn(2, '.');
+    let integer_part = parts.next().unwrap_or("0");
+    let fraction_part = parts.next().unwrap_or("");
+
+    if fraction_part.len() > decimals as usize {
+        return Err(anyhow!(
+            "Amount {} has more fractional digits than the asset's {} decimals",
+            display, decimals
+        ));
+    }
+
+    let integer_value = Amount::from_dec_str(integer_part).map_err(|_| anyhow!("Invalid amount: {}", display))?;
+    let fraction_value = if fraction_part.is_empty() {
+        Amount::zero()
+    } else {
+        Amount::from_dec_str(fraction_part).map_err(|_| anyhow!("Invalid amount: {}", display))?
+    };
+
+    let fraction_scale = checked_pow10(decimals - fraction_part.len() as u32)
+        .ok_or_else(|| anyhow!("Decimals too large"))?;
+    let base_scale = checked_pow10(decimals).ok_or_else(|| anyhow!("Decimals too large"))?;
+
+    integer_value
+        .checked_mul(base_scale)
+        .and_then(|whole| whole.checked_add(fraction_value.checked_mul(fraction_scale)?))
+        .ok_or_else(|| anyhow!("Amount {} overflows base units", display))
+}
+
+/// 10^exponent as an `Amount`, or `None` if it overflows 256 bits
+fn checked_pow10(exponent: u32) -> Option<Amount> {
+    let mut result = Amount::from(1u64);
+    for _ in 0..exponent {
+        result = result.checked_mul(Amount::from(10u64))?;
+    }
+    Some(result)
+}
+
 // Helper function to convert u64 to big-endian bytes (removing leading zeros)
 fn u64_to_be_bytes_minimal(value: u64) -> Vec<u8> {
     if value == 0 {
@@ -28,6 +140,22 @@ fn u64_to_be_bytes_minimal(value: u64) -> Vec<u8> {
     bytes[start..].to_vec()
 }
 
+/// Encode a 256-bit `Amount` as minimal-length big-endian bytes (leading zero bytes stripped),
+/// matching the encoding Ethereum RLP expects for a transaction's `value` field
+fn amount_to_be_bytes_minimal(value: Amount) -> Vec<u8> {
+    if value.is_zero() {
+        return vec![0];
+    }
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(31);
+    bytes[start..].to_vec()
+}
+
+/// 256-bit amount type used for balances, deposits, transfers, and withdrawals, wide enough for
+/// ERC20 balances and ETH wei values that routinely exceed `u64::MAX`
+pub type Amount = U256;
+
 /// Asset type identifier (e.g., "ETH", "USDC", etc.)
 pub type AssetType = String;
 
@@ -40,6 +168,46 @@ pub type DepositId = String;
 /// External destination address for withdrawals
 pub type ExternalDestination = String;
 
+/// Structured failures from the balance/deposit paths (`transfer`, `withdraw`,
+/// `withdraw_to_external`, `inbox_deposit`, `claim_inbox`), carrying the quantities a caller needs
+/// to match programmatically or display an exact shortfall, rather than parsing an error string
+/// like `"Insufficient balance"`. Still converts into this crate's usual `anyhow::Error` via `?` -
+/// a caller that needs the structured variant back can `downcast_ref::<WalletError>()` on the
+/// returned error.
+///
+/// `NotEnoughGasFunds` is reserved for a future ledger-tracked ETH-for-gas balance:
+/// `withdraw_to_external` signs and broadcasts a real on-chain transaction, so gas is paid out of
+/// the wallet's actual ETH account by the network itself and is never deducted from the
+/// `balances` map this crate tracks - there is nothing to check against today.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WalletError {
+    NotEnoughBalance { asset_id: String, required: Amount, available: Amount },
+    NotEnoughGasFunds { required_eth: Amount, available_eth: Amount },
+    DuplicateDeposit { deposit_id: String },
+    UnknownDeposit { deposit_id: String },
+    UnknownAsset { asset_id: String },
+    UnknownSubaccount { subaccount_id: String },
+}
+
+impl std::fmt::Display for WalletError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WalletError::NotEnoughBalance { asset_id, required, available } => write!(
+                f, "Insufficient balance of {}: required {}, available {}", asset_id, required, available
+            ),
+            WalletError::NotEnoughGasFunds { required_eth, available_eth } => write!(
+                f, "Insufficient ETH for gas fees: required {}, available {}", required_eth, available_eth
+            ),
+            WalletError::DuplicateDeposit { deposit_id } => write!(f, "Deposit {} already exists", deposit_id),
+            WalletError::UnknownDeposit { deposit_id } => write!(f, "Deposit {} not found in inbox", deposit_id),
+            WalletError::UnknownAsset { asset_id } => write!(f, "Asset not found: {}", asset_id),
+            WalletError::UnknownSubaccount { subaccount_id } => write!(f, "Subaccount not found: {}", subaccount_id),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
 /// Wallet address (unique identifier for each PASS wallet)
 pub type WalletAddress = String;
 
@@ -63,6 +231,15 @@ pub struct Asset {
     pub decimals: u32,
 }
 
+/// Contract metadata for an ERC-20 token, supplied by a caller mirroring it into a wallet via
+/// `PassWalletManager::mirror_asset`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Erc20Metadata {
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u32,
+}
+
 /// Subaccount within a wallet
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Subaccount {
@@ -76,36 +253,373 @@ pub struct Subaccount {
 pub struct SubaccountBalance {
     pub subaccount_id: String,
     pub asset_id: String,
-    pub amount: u64,
+    pub amount: Amount,
 }
 
 /// Deposit entry in the inbox
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Deposit {
     pub asset_id: String,
-    pub amount: u64,
+    pub amount: Amount,
+    pub deposit_id: DepositId,
+    pub transaction_hash: String,
+    pub block_number: String,
+    pub from_address: String,
+    pub to_address: String,
+    /// Optional plaintext note carried in the inbox until `claim_inbox` learns which subaccount
+    /// is claiming it. `PassWalletManager::claim_inbox` seals it to that subaccount's own key
+    /// (see `encrypt_user_memo`) before it's ever written to `history` or returned to a caller -
+    /// this field itself never round-trips out of the enclave once claimed.
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// A memo sealed to one subaccount's own key (see `PassWalletManager::encrypt_user_memo`) by a
+/// claimed deposit or an `internal_transfer`, stored alongside the balance change it annotates
+/// rather than inline in `ProvenanceRecord` so a query over `history` never has to carry
+/// ciphertext it can't decrypt. Only `PassWalletManager::get_memos` - which holds the KMS key
+/// material needed to decrypt - ever reads these back out in plaintext.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredMemo {
+    pub ciphertext: Vec<u8>,
+    /// What this memo annotates, e.g. `"claim:{deposit_id}"` or `"transfer:{from_subaccount}"`,
+    /// surfaced by `get_memos` so a recipient can tell which movement a memo belongs to.
+    pub context: String,
+    pub created_at: u64,
+}
+
+/// A single on-chain deposit observed by an external indexer or recovery job, fed into
+/// `scan_and_recover` to reconcile this wallet's inbox against chain state independent of
+/// `start_deposit_sync`'s own RPC polling - e.g. after the enclave restarted and may have missed
+/// events while its polling loop wasn't running. `token_contract` is `None` for the chain's
+/// native asset; `Some(address)` is matched against `Asset::contract_address` to resolve which
+/// registered asset the deposit belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepositEvent {
     pub deposit_id: DepositId,
     pub transaction_hash: String,
     pub block_number: String,
     pub from_address: String,
     pub to_address: String,
+    pub amount: Amount,
+    #[serde(default)]
+    pub token_contract: Option<String>,
+}
+
+/// Outcome of reconciling a batch of `DepositEvent`s against this wallet's inbox/history via
+/// `scan_and_recover`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepositReconciliationReport {
+    /// Deposits not previously present in `inbox` or `history`, freshly queued into the inbox.
+    pub newly_discovered: Vec<DepositId>,
+    /// Deposits already present (either still sitting unclaimed in `inbox`, or already claimed
+    /// per a `Claim` history record) - reconciled as a no-op.
+    pub already_known: Vec<DepositId>,
+    /// Deposits whose `token_contract` doesn't match any asset registered on this wallet, so they
+    /// couldn't be queued - surfaced rather than silently dropped so an operator can register the
+    /// missing asset and re-run the scan.
+    pub orphaned: Vec<DepositId>,
 }
 
 /// Outbox entry for withdrawals
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutboxEntry {
     pub asset_id: String,
-    pub amount: u64,
+    pub amount: Amount,
     pub external_destination: ExternalDestination,
+    /// Unique id assigned from `PassWalletState::outbox_sequence` when this entry was queued -
+    /// not the wallet's `nonce`, which is shared with `_authorized` replay protection and
+    /// on-chain tx nonces and would collide across entries queued before the same drain.
+    pub nonce: u64,
+    /// Encrypted, fixed-length memo sealed by `PassWalletManager::encrypt_outbox_memo`, carried
+    /// through to the recipient on-chain - see `WithdrawSerializeType` for which formats can carry
+    /// one.
+    #[serde(default)]
+    pub memo: Option<Vec<u8>>,
+    /// The ERC-721 token_id being withdrawn, set only when `asset_id` is an NFT asset queued via
+    /// `withdraw_nft` rather than a fungible `withdraw`.
+    #[serde(default)]
+    pub token_id: Option<String>,
+}
+
+/// One outbox item failing `validate_outbox`'s pre-flight checks, naming the queued withdrawal by
+/// its `nonce` rather than requiring the caller to have kept the original `OutboxEntry` around.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxValidationError {
     pub nonce: u64,
+    pub reason: String,
+}
+
+impl std::fmt::Display for OutboxValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "outbox item (nonce {}): {}", self.nonce, self.reason)
+    }
+}
+
+/// Target encoding for a drained `OutboxEntry`, selected per target chain/relayer. Each variant's
+/// serialization is exactly the byte string the enclave signs over - see `SignedWithdrawal`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum WithdrawSerializeType {
+    /// Raw EVM transaction calldata: a minimal `transfer(address,uint256)`-style ABI encoding of
+    /// `(external_destination, amount)`.
+    EvmCalldata,
+    /// An EIP-712-style structured payload: a domain-separated digest over the entry's typed
+    /// fields, suitable for a relayer that verifies a `TypedData` hash rather than raw calldata.
+    Eip712,
+    /// Compact JSON form, for relayers that don't speak either EVM encoding.
+    CompactJson,
+}
+
+impl WithdrawSerializeType {
+    /// Whether this format has room for `OutboxEntry::memo`. `EvmCalldata`'s layout is a fixed
+    /// four-byte selector plus two 32-byte words with nothing left over for extra data, so it
+    /// can't carry one; the other two formats serialize (or hash) the whole entry, memo included.
+    fn carries_memo(self) -> bool {
+        !matches!(self, WithdrawSerializeType::EvmCalldata)
+    }
+}
+
+/// A drained `OutboxEntry` serialized for broadcast in a specific `WithdrawSerializeType`, with
+/// the enclave's signature over the exact `payload` bytes. Produced by
+/// `PassWalletManager::process_outbox_for_broadcast`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedWithdrawal {
+    pub entry: OutboxEntry,
+    pub format: WithdrawSerializeType,
+    /// The exact bytes the enclave signed - deterministic for a given `(entry, format)` pair.
+    pub payload: Vec<u8>,
+    pub signature: String,
+}
+
+/// On-disk hand-off produced by `PassWalletManager::sign_outbox` on an offline, air-gapped
+/// machine and consumed by `PassWalletManager::broadcast_outbox` on an online one: every
+/// `SignedWithdrawal` the offline side drained and signed, plus `chain_id` so the online side
+/// knows which network to submit to without ever touching (or re-deriving anything from) the
+/// signing keys.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxSigningBundle {
+    pub wallet_address: WalletAddress,
+    pub chain_id: u64,
+    pub format: WithdrawSerializeType,
+    pub signed_withdrawals: Vec<SignedWithdrawal>,
+}
+
+/// A drained `OutboxEntry` built into a genuine EIP-155 replay-protected Ethereum transaction and
+/// signed by `EnclaveKMS`, rather than a signature over a serialized description of it (contrast
+/// `SignedWithdrawal`). Produced by `PassWalletManager::process_outbox_signed`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedRawWithdrawal {
+    pub entry: OutboxEntry,
+    pub chain_id: u64,
+    /// The wallet's on-chain account nonce this transaction was signed under. Distinct and
+    /// monotonically increasing across every entry drained in the same call, so a batch of
+    /// withdrawals broadcast together never collides.
+    pub account_nonce: u64,
+    /// The fully RLP-encoded, EIP-155-signed raw transaction, hex-encoded and ready to broadcast.
+    pub raw_transaction: String,
 }
 
 /// Transaction operation types for provenance history
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TransactionOperation {
-    Claim { asset_id: String, amount: u64, deposit_id: DepositId, subaccount_id: String },
-    Transfer { asset_id: String, amount: u64, from_subaccount: String, to_subaccount: String },
-    Withdraw { asset_id: String, amount: u64, subaccount_id: String, destination: ExternalDestination },
+    Claim { asset_id: String, amount: Amount, deposit_id: DepositId, subaccount_id: String },
+    Transfer {
+        asset_id: String,
+        amount: Amount,
+        from_subaccount: String,
+        to_subaccount: String,
+        #[serde(default)]
+        memo: Option<String>,
+    },
+    /// A cross-asset transfer via `internal_transfer_with_rate`: `from_amount` of `from_asset`
+    /// left `from_subaccount`, and the `quote`d `to_amount` of `to_asset` (at `rate`) landed in
+    /// `to_subaccount`. Distinct from `Transfer`, which only ever moves one asset at one amount.
+    RateTransfer {
+        from_asset: String,
+        from_amount: Amount,
+        to_asset: String,
+        to_amount: Amount,
+        rate: Rate,
+        from_subaccount: String,
+        to_subaccount: String,
+        #[serde(default)]
+        memo: Option<String>,
+    },
+    Withdraw {
+        asset_id: String,
+        amount: Amount,
+        subaccount_id: String,
+        destination: ExternalDestination,
+        #[serde(default)]
+        memo: Option<String>,
+        /// The ERC-721 token_id withdrawn, set only for an NFT withdrawal via `withdraw_nft`.
+        #[serde(default)]
+        token_id: Option<String>,
+    },
+    EscrowCreated {
+        escrow_id: String,
+        asset_id: String,
+        amount: Amount,
+        from_subaccount: String,
+        to_subaccount: String,
+    },
+    EscrowReleased {
+        escrow_id: String,
+        asset_id: String,
+        amount: Amount,
+        to_subaccount: String,
+    },
+    EscrowCancelled {
+        escrow_id: String,
+        asset_id: String,
+        amount: Amount,
+        from_subaccount: String,
+    },
+    RecoveryInitiated {
+        initiated_by: String,
+        waiting_period_secs: u64,
+    },
+    RecoveryCancelled {
+        cancelled_by: String,
+    },
+    RecoveryApproved {
+        approved_by: String,
+    },
+    RecoveryCompleted {
+        new_owner: String,
+    },
+    Swap {
+        asset_in: String,
+        amount_in: Amount,
+        asset_out: String,
+        amount_out: Amount,
+        subaccount_id: String,
+    },
+    /// A maker locked `give_amount` of `give_asset` into a peer-to-peer `propose_swap`, awaiting a
+    /// taker. Distinct from `Swap`, which records an `internal_swap` fill against this wallet's
+    /// own liquidity reserve rather than another party.
+    SwapProposed {
+        swap_id: String,
+        maker: String,
+        give_asset: String,
+        give_amount: Amount,
+        want_asset: String,
+        want_amount: Amount,
+    },
+    /// A pending `propose_swap` was filled by `taker` via `accept_swap`.
+    SwapAccepted {
+        swap_id: String,
+        maker: String,
+        taker: String,
+    },
+    /// The maker cancelled a pending `propose_swap` via `cancel_swap` before a taker accepted it.
+    SwapCancelled {
+        swap_id: String,
+        maker: String,
+    },
+    /// A proportional (basis-points) withdrawal fee deducted into `WITHDRAWAL_FEE_SUBACCOUNT_ID`,
+    /// recorded separately from the withdrawal's own `Withdraw` record so an auditor can see
+    /// exactly how much of a transfer was protocol revenue versus principal. See
+    /// `set_withdrawal_fee`.
+    FeeCollected {
+        asset_id: String,
+        amount: Amount,
+        subaccount_id: String,
+        fee_bps: u32,
+    },
+    /// Accrued fees moved out of `WITHDRAWAL_FEE_SUBACCOUNT_ID` into an ordinary subaccount. See
+    /// `sweep_fees`.
+    FeeSwept {
+        asset_id: String,
+        amount: Amount,
+        to_subaccount: String,
+    },
+}
+
+/// Maximum byte length of a memo attached to a transfer or withdrawal
+pub const MAX_MEMO_BYTES: usize = 256;
+
+/// Maximum plaintext byte length of the memo sealed onto a queued `OutboxEntry` (see
+/// `PassWalletManager::encrypt_outbox_memo`), matching the ~512-byte ceiling of a shielded
+/// `createToAddress(..., memo)` field.
+pub const MAX_OUTBOX_MEMO_BYTES: usize = 512;
+
+/// Maximum plaintext byte length of a memo sealed to a subaccount's own key by
+/// `PassWalletManager::encrypt_user_memo` - the same ~512-byte shielded-memo ceiling as
+/// `MAX_OUTBOX_MEMO_BYTES`, but kept as its own constant since the two are encrypted under
+/// unrelated keys and could reasonably diverge later.
+pub const MAX_USER_MEMO_BYTES: usize = 512;
+
+/// Validate a memo against the fixed byte-length cap shared by transfers and withdrawals
+fn validate_memo(memo: &Option<String>) -> Result<()> {
+    if let Some(m) = memo {
+        if m.len() > MAX_MEMO_BYTES {
+            return Err(anyhow!("Memo exceeds maximum length of {} bytes", MAX_MEMO_BYTES));
+        }
+    }
+    Ok(())
+}
+
+/// A single operation applied by `PassWalletManager::apply_batch`, mirroring the parameters of
+/// the matching individual method (`claim_inbox`, `internal_transfer`, `withdraw`,
+/// `inbox_deposit`) so a batch can interleave any mix of them under one lock acquisition.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WalletOp {
+    Claim {
+        deposit_id: String,
+        subaccount_id: String,
+    },
+    Transfer {
+        asset_id: String,
+        amount: Amount,
+        from_subaccount: String,
+        to_subaccount: String,
+        #[serde(default)]
+        memo: Option<String>,
+    },
+    Withdraw {
+        asset_id: String,
+        amount: Amount,
+        subaccount_id: String,
+        destination: String,
+        #[serde(default)]
+        memo: Option<String>,
+    },
+    Deposit(Deposit),
+}
+
+/// The outcome of a single `WalletOp` applied by `apply_batch`, returned in the same order as
+/// the `ops` vector it was given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OpResult {
+    Claim,
+    Transfer,
+    Withdraw {
+        destination: ExternalDestination,
+        nonce: u64,
+    },
+    Deposit,
+}
+
+/// Coarse-grained operation kind, used by `ProvenanceFilter::Operation` without requiring
+/// callers to supply the full variant's fields
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProvenanceOperationKind {
+    Claim,
+    Transfer,
+    Withdraw,
+    Swap,
+}
+
+/// Filter applied when paging through a wallet's provenance history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProvenanceFilter {
+    All,
+    Asset(String),
+    Subaccount(String),
+    Operation(ProvenanceOperationKind),
+    TimeRange(u64, u64),
+    HasMemo,
 }
 
 /// Provenance history entry
@@ -114,6 +628,215 @@ pub struct ProvenanceRecord {
     pub operation: TransactionOperation,
     pub timestamp: u64,
     pub block_number: Option<u64>,
+    /// Which `WithdrawalPolicy` (if any) was evaluated against this record, for audit trails
+    #[serde(default)]
+    pub limit_applied: Option<AppliedLimit>,
+    /// The outbox `tx_nonce` this record corresponds to, if it produced a signed transaction.
+    /// Lets `record_mined`/`revert_mined_block` find their way back to this entry.
+    #[serde(default)]
+    pub tx_nonce: Option<u64>,
+    /// The `OutboxEntry::nonce` this record's withdrawal was queued under, if it was queued via
+    /// the plain `withdraw`/`process_outbox` path rather than `withdraw_to_external`'s
+    /// global-nonce build-and-sign path (see `tx_nonce`). Lets
+    /// `PassWalletManager::process_outbox_signed` find its way back to this entry once drained.
+    #[serde(default)]
+    pub outbox_nonce: Option<u64>,
+    /// Set when a previously-mined `block_number` was rolled back by a chain reorg. `block_number`
+    /// is cleared back to `None` at the same time.
+    #[serde(default)]
+    pub reorged: bool,
+    /// The provenance hashchain head after this entry was applied - see `PassWalletState::append_history`.
+    /// Empty for entries recorded before the hashchain existed, which `verify_provenance_log`
+    /// treats as outside the chain it can verify.
+    #[serde(default)]
+    pub chain_head: String,
+    /// Hex-encoded, EIP-155-signed raw Ethereum transaction backfilled by
+    /// `PassWalletManager::process_outbox_signed` once this withdrawal's `OutboxEntry` is drained
+    /// and signed, for auditing. `None` until then, same as `block_number` before `record_mined`.
+    #[serde(default)]
+    pub signed_raw_tx: Option<String>,
+}
+
+/// Per-asset withdrawal limit, expressed in base units (already scaled by the asset's
+/// `decimals`) so it can be compared directly against raw `Amount` withdrawal amounts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawalPolicy {
+    /// Maximum amount allowed in a single withdrawal call, in base units
+    pub max_withdrawal: Amount,
+    /// Rolling window length in seconds over which `window_max` is enforced, if set
+    pub window_seconds: Option<u64>,
+    /// Maximum total withdrawn (base units) within the trailing `window_seconds`
+    pub window_max: Option<Amount>,
+    /// When set, `internal_transfer` is exempt from this policy entirely - only `withdraw` and
+    /// `withdraw_to_external` (money actually leaving the wallet) are limited. Defaults to `false`
+    /// so existing policies keep covering internal movement until an operator opts in.
+    #[serde(default)]
+    pub exempt_internal_transfers: bool,
+}
+
+/// Snapshot of the `WithdrawalPolicy` that was evaluated against a withdrawal, recorded onto its
+/// `ProvenanceRecord` so an auditor can see which limit applied without re-deriving it from
+/// policy state that may since have changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedLimit {
+    /// "wallet" for an asset-wide policy, or "subaccount:<id>" for a subaccount-specific override
+    pub scope: String,
+    pub max_withdrawal: Amount,
+    pub window_seconds: Option<u64>,
+    pub window_max: Option<Amount>,
+}
+
+/// A fixed per-transaction fee charged on `withdraw`/`internal_transfer`, deducted atomically
+/// alongside the operation into the reserved `FEE_SUBACCOUNT_ID` subaccount. `fee_asset_id` lets
+/// the fee be charged in a different asset than the one being moved (e.g. a stablecoin fee on an
+/// ETH withdrawal); when `None`, the fee is taken from the same asset and subaccount as the
+/// operation itself, which is why `withdraw`/`internal_transfer` validate `amount + fee <=
+/// balance` rather than just `amount <= balance` in that case.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeePolicy {
+    pub withdraw_fee: Amount,
+    pub transfer_fee: Amount,
+    pub fee_asset_id: Option<String>,
+}
+
+/// A labeled external destination address, validated (via `parse_address`) at insert time so
+/// later lookups are guaranteed to resolve to well-formed addresses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contact {
+    pub label: String,
+    pub address: ExternalDestination,
+    pub allow_listed: bool,
+}
+
+/// A conditional transfer held in escrow, modeled on the witness/timelock primitives of
+/// Solana's budget program: funds move out of `from_subaccount` immediately, then release into
+/// `to_subaccount` once either `release_after` elapses (`PassWalletState::release_escrow`) or
+/// `required_signatures` distinct `witnesses` approve (`PassWalletState::witness_approve`),
+/// whichever happens first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Escrow {
+    pub escrow_id: String,
+    pub asset_id: String,
+    pub amount: Amount,
+    pub from_subaccount: String,
+    pub to_subaccount: String,
+    pub release_after: Option<u64>,
+    pub witnesses: Vec<String>,
+    pub required_signatures: u32,
+    /// Witness addresses that have already approved, tracked as a set so a repeat submission
+    /// from the same witness doesn't count twice toward `required_signatures`
+    #[serde(default)]
+    pub approvals: HashSet<String>,
+    pub cancelable_by: Option<String>,
+    pub created_at: u64,
+}
+
+/// A proposed peer-to-peer atomic swap: `maker` locks `give_amount` of `give_asset` out of its
+/// balance the moment it's proposed (see `propose_swap`), so the lock is excluded from
+/// `get_balance` and can't be double-spent while the swap is pending. Settles in one step via
+/// `accept_swap`, or refunds the maker via `cancel_swap`. Distinct from `Escrow`, which is a
+/// unilateral time/signature-release lock rather than a two-party exchange, and from the
+/// constant-product `reserves` pool `internal_swap` fills against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Swap {
+    pub swap_id: String,
+    pub maker: String,
+    pub give_asset: String,
+    pub give_amount: Amount,
+    pub want_asset: String,
+    pub want_amount: Amount,
+    pub created_at: u64,
+}
+
+/// A signed proof that a PASS wallet authorized a specific withdrawal, borrowed from the
+/// Grin/Epic wallet's `PaymentProof` concept: binds the payment's destination, asset, amount,
+/// and nonce to a signature over that canonical tuple, so a recipient can verify the payment was
+/// authorized without needing any access to the wallet's internal state. Generated automatically
+/// by `PassWalletManager::withdraw` and retrieved via `get_payment_proof`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentProof {
+    pub wallet_address: WalletAddress,
+    pub destination: ExternalDestination,
+    pub asset_id: String,
+    pub amount: Amount,
+    pub nonce: u64,
+    /// Address recovered from `signature` over the canonical message at proof-creation time
+    pub signer_address: String,
+    pub signature: String,
+}
+
+impl PaymentProof {
+    /// The canonical message signed over `(destination, asset_id, amount, nonce)`. Both
+    /// proof creation and `verify_payment_proof` must hash exactly this string.
+    fn canonical_message(wallet_address: &str, destination: &str, asset_id: &str, amount: Amount, nonce: u64) -> String {
+        format!("payment-proof:{}:{}:{}:{}:{}", wallet_address, destination, asset_id, amount, nonce)
+    }
+}
+
+/// The canonical message a wallet's `owner_key` must sign to authorize a mutating operation via
+/// `PassWalletManager::withdraw_authorized`/`internal_transfer_authorized`/`claim_inbox_authorized`.
+/// Binds the operation name and every identifying parameter the same way `PaymentProof`'s message
+/// binds a payment, plus the wallet's current `nonce` so a captured signature can't be replayed
+/// once the nonce has moved on from under it.
+fn canonical_auth_message(
+    operation: &str,
+    wallet_address: &str,
+    asset_id: &str,
+    amount: Amount,
+    from_or_subaccount: &str,
+    to_or_destination: &str,
+    nonce: u64,
+) -> String {
+    format!(
+        "auth:{}:{}:{}:{}:{}:{}:{}",
+        operation, wallet_address, asset_id, amount, from_or_subaccount, to_or_destination, nonce
+    )
+}
+
+/// An exact, fraction-based exchange rate quoting one asset in terms of a reference asset,
+/// inspired by the swap-crate `Rate` type. Stored as `rate_numerator / rate_denominator` rather
+/// than a float so quoting is reproducible and never drifts from rounding error; `apply` checks
+/// every multiplication against u128 overflow instead of wrapping or truncating silently.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Rate {
+    pub rate_numerator: u128,
+    pub rate_denominator: u128,
+}
+
+impl Rate {
+    /// Convert `balance` (raw units of the priced asset, with `asset_decimals`) into raw units
+    /// of the reference asset (with `reference_decimals`), treating `self` as the exchange rate
+    /// between one human-readable unit of each.
+    fn apply(&self, balance: Amount, asset_decimals: u32, reference_decimals: u32) -> Result<u128> {
+        if self.rate_denominator == 0 {
+            return Err(anyhow!("Rate denominator cannot be zero"));
+        }
+
+        let balance: u128 = balance.try_into().map_err(|_| anyhow!("Balance too large to quote"))?;
+        let numerator = balance
+            .checked_mul(self.rate_numerator)
+            .ok_or_else(|| anyhow!("Overflow while applying rate"))?;
+
+        let scale_exponent = reference_decimals as i64 - asset_decimals as i64;
+        let (scaled_numerator, denominator) = if scale_exponent >= 0 {
+            let scale = 10u128
+                .checked_pow(scale_exponent as u32)
+                .ok_or_else(|| anyhow!("Overflow while scaling decimals"))?;
+            let scaled_numerator = numerator.checked_mul(scale).ok_or_else(|| anyhow!("Overflow while scaling decimals"))?;
+            (scaled_numerator, self.rate_denominator)
+        } else {
+            let scale = 10u128
+                .checked_pow((-scale_exponent) as u32)
+                .ok_or_else(|| anyhow!("Overflow while scaling decimals"))?;
+            let denominator = self
+                .rate_denominator
+                .checked_mul(scale)
+                .ok_or_else(|| anyhow!("Overflow while scaling decimals"))?;
+            (numerator, denominator)
+        };
+
+        Ok(scaled_numerator / denominator)
+    }
 }
 
 /// Individual PASS wallet state
@@ -122,14 +845,158 @@ pub struct PassWalletState {
     pub address: WalletAddress,
     pub name: String,
     pub owner: String,
+    /// Replay-protection nonce for `_authorized` operations (embedded in `canonical_auth_message`)
+    /// and the on-chain transaction nonce `withdraw_to_external`/`process_outbox_signed` sign
+    /// withdrawals under. Not used to identify outbox entries - see `outbox_sequence` for that.
     pub nonce: u64,
     pub inbox: Vec<Deposit>,
     pub outbox: VecDeque<OutboxEntry>,
+    /// Monotonic counter that assigns each `OutboxEntry` (and its correlated
+    /// `ProvenanceRecord::outbox_nonce`/`PaymentProof::nonce`) a unique id at queue time, in
+    /// `withdraw`/`withdraw_nft`. Deliberately separate from `nonce`: that field only advances for
+    /// `_authorized` calls and `withdraw_to_external`, so reusing it here would hand two
+    /// withdrawals queued back-to-back the same id.
+    #[serde(default)]
+    pub outbox_sequence: u64,
     pub assets: HashMap<String, Asset>,
     pub subaccounts: HashMap<String, Subaccount>,
-    pub balances: HashMap<String, u64>, // subaccount_id:asset_id -> amount
+    pub balances: HashMap<String, Amount>, // subaccount_id:asset_id -> amount
     pub history: Vec<ProvenanceRecord>,
     pub created_at: u64,
+    #[serde(default)]
+    pub withdrawal_policies: HashMap<String, WithdrawalPolicy>,
+    /// Proportional withdrawal fee in basis points, keyed by `asset_id`. Distinct from
+    /// `fee_policy`'s fixed `withdraw_fee`/`transfer_fee`: this scales with the withdrawal amount
+    /// and is only ever charged by `withdraw`/`withdraw_to_external`, never `internal_transfer`.
+    /// See `set_withdrawal_fee`/`get_collected_fees`/`sweep_fees`.
+    #[serde(default)]
+    pub withdrawal_fee_bps: HashMap<String, u32>,
+    /// Labeled external destinations, keyed by label
+    #[serde(default)]
+    pub contacts: HashMap<String, Contact>,
+    /// When true, `resolve_destination` rejects any destination that isn't an allow-listed contact
+    #[serde(default)]
+    pub require_allow_listed_destination: bool,
+    /// Conditional transfers currently held in escrow, keyed by a generated `escrow_id`. See
+    /// `create_conditional_transfer`/`release_escrow`/`witness_approve`/`cancel_conditional_transfer`.
+    #[serde(default)]
+    pub escrows: HashMap<String, Escrow>,
+    /// Monotonic counter used to generate `escrow_id`s, mirroring how `outbox_sequence` sequences
+    /// the outbox.
+    #[serde(default)]
+    pub escrow_nonce: u64,
+    /// Merkle root over `balances` and `inbox`, stamped by `PassWalletManager::update_wallet`
+    /// just before every persisted write. `verify_wallet_integrity` recomputes it fresh from
+    /// the loaded state and compares, catching storage-level drift or corruption. Empty for
+    /// wallet states written before this field existed.
+    #[serde(default)]
+    pub integrity_digest: String,
+    /// Signed payment proofs, keyed by the outbox nonce of the withdrawal they attest to. See
+    /// `PassWalletManager::get_payment_proof`/`verify_payment_proof`.
+    #[serde(default)]
+    pub proofs: HashMap<u64, PaymentProof>,
+    /// Exchange rates for valuing one asset in terms of another, keyed by `"{asset_id}:{reference_asset}"`.
+    /// See `set_asset_rate`/`get_asset_rate`.
+    #[serde(default)]
+    pub rates: HashMap<String, Rate>,
+    /// Addresses designated by the owner as emergency-recovery contacts. Any one of them may
+    /// call `initiate_recovery` if the owner loses access. See `add_recovery_contact`.
+    #[serde(default)]
+    pub recovery_contacts: HashSet<String>,
+    /// How long (in seconds) a recovery initiated by a contact waits before it can finalize on
+    /// its own, absent an owner cancellation. Set by the most recent `add_recovery_contact` call.
+    #[serde(default)]
+    pub recovery_waiting_period_secs: u64,
+    /// How many distinct recovery contacts must approve via `approve_recovery` to finalize a
+    /// pending recovery immediately, without waiting for `recovery_waiting_period_secs` to elapse.
+    #[serde(default)]
+    pub recovery_required_approvals: u32,
+    /// The in-progress ownership recovery, if a contact has called `initiate_recovery` and the
+    /// owner hasn't cancelled it yet. See `initiate_recovery`/`cancel_recovery`/`approve_recovery`.
+    #[serde(default)]
+    pub pending_recovery: Option<PendingRecovery>,
+    /// Running head of the tamper-evident provenance hashchain (`H_n = SHA256(H_{n-1} ||
+    /// canonical_serialize(entry_n))`), updated by `append_history` on every mutation that
+    /// records history. Seeded by `PassWalletManager::create_wallet` from the wallet address and
+    /// the enclave's own master secret (`EnclaveKMS::provenance_genesis`), so a host-side store
+    /// can't forge a plausible-looking genesis for a wallet it doesn't control the enclave for.
+    /// Empty for wallets created before the hashchain existed.
+    #[serde(default)]
+    pub chain_head: String,
+    /// Fixed per-transaction fee charged on `withdraw`/`internal_transfer`, if configured. See
+    /// `set_fee_policy`.
+    #[serde(default)]
+    pub fee_policy: Option<FeePolicy>,
+    /// Ethereum address the host must produce an `ecrecover`-able signature from to authorize a
+    /// mutating operation via `PassWalletManager::withdraw_authorized`/`internal_transfer_authorized`/
+    /// `claim_inbox_authorized`. `None` means no owner key has been registered - those `_authorized`
+    /// entry points refuse every call until `set_owner_key` configures one. See `canonical_auth_message`.
+    #[serde(default)]
+    pub owner_key: Option<String>,
+    /// NFT ownership ledger for ERC-721 assets: `"{asset_id}:{token_id}"` -> owning subaccount_id.
+    /// Populated by `claim_inbox`, moved by `transfer_nft`, and cleared by `withdraw_nft`. Kept
+    /// separate from `balances` since NFT ownership is a single owner per `token_id` rather than a
+    /// summable amount.
+    #[serde(default)]
+    pub nft_ownership: HashMap<String, String>,
+    /// Constant-product liquidity reserves backing `internal_swap`, keyed by `reserve_key(asset_a,
+    /// asset_b)` (the two asset_ids sorted so lookup doesn't care which order a caller names
+    /// them). The stored tuple is `(reserve of the alphabetically-smaller asset_id, reserve of the
+    /// other)` - see `reserve_key`. Seeded via `add_liquidity`.
+    #[serde(default)]
+    pub reserves: HashMap<String, (Amount, Amount)>,
+    /// Current EIP-1559-style base fee (wei per gas) this wallet signs dynamic-fee withdrawals
+    /// against, adjusted by `update_base_fee` after each dynamic-fee `withdraw_to_external` call.
+    /// Absent/missing on wallets created before this field existed, in which case it defaults to
+    /// `BASE_FEE_FLOOR`. See `current_base_fee`.
+    #[serde(default = "default_base_fee_per_gas")]
+    pub base_fee_per_gas: u64,
+    /// Contributor shares for proportional-pool subaccounts, keyed by `pool_key(subaccount_id,
+    /// asset_id)` then by contributing address. A pool's value is just that subaccount's ordinary
+    /// `balances` entry - there's no separate pooled ledger - so value credited into the
+    /// subaccount directly dilutes or inflates every holder's share pro-rata without touching
+    /// their individual share counts. See `deposit_to_pool`/`withdraw_from_pool`/`shares_of`.
+    #[serde(default)]
+    pub pool_shares: HashMap<String, HashMap<String, Amount>>,
+    /// Pending peer-to-peer atomic swaps proposed via `propose_swap`, keyed by `swap_id`. Removed
+    /// as soon as `accept_swap` or `cancel_swap` settles them, so a stale id simply isn't found.
+    #[serde(default)]
+    pub swaps: HashMap<String, Swap>,
+    /// Monotonic counter used to generate `swap_id`s, mirroring `escrow_nonce`.
+    #[serde(default)]
+    pub swap_nonce: u64,
+    /// Memos sealed to a subaccount's own key, keyed by `subaccount_id`, appended to by
+    /// `claim_inbox` and `internal_transfer` when the claim/transfer carried one. See
+    /// `StoredMemo`/`PassWalletManager::get_memos`.
+    #[serde(default)]
+    pub user_memos: HashMap<String, Vec<StoredMemo>>,
+}
+
+/// Default `PassWalletState::base_fee_per_gas` for wallets predating the field, and the starting
+/// point for a freshly created wallet.
+fn default_base_fee_per_gas() -> u64 {
+    BASE_FEE_FLOOR
+}
+
+/// An in-progress emergency ownership recovery. Resolves once `required_approvals` distinct
+/// recovery contacts approve (see `approve_recovery`), or once `waiting_period_secs` has elapsed
+/// since `initiated_at` with no owner cancellation (see `recovery_due`/`process_recovery_timeout`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingRecovery {
+    pub initiated_by: String,
+    pub initiated_at: u64,
+    pub waiting_period_secs: u64,
+    pub required_approvals: u32,
+    #[serde(default)]
+    pub approvals: HashSet<String>,
+}
+
+impl PendingRecovery {
+    /// Whether `waiting_period_secs` has elapsed since `initiated_at`, mirroring how
+    /// `Escrow.release_after` gates `release_escrow`.
+    fn is_due(&self) -> bool {
+        PassWalletState::get_timestamp() >= self.initiated_at + self.waiting_period_secs
+    }
 }
 
 impl PassWalletState {
@@ -142,12 +1009,206 @@ impl PassWalletState {
             nonce: 0,
             inbox: Vec::new(),
             outbox: VecDeque::new(),
+            outbox_sequence: 0,
             assets: HashMap::new(),
             subaccounts: HashMap::new(),
             balances: HashMap::new(),
+            withdrawal_policies: HashMap::new(),
+            withdrawal_fee_bps: HashMap::new(),
+            contacts: HashMap::new(),
+            require_allow_listed_destination: false,
             history: Vec::new(),
             created_at: Self::get_timestamp(),
+            escrows: HashMap::new(),
+            escrow_nonce: 0,
+            integrity_digest: String::new(),
+            proofs: HashMap::new(),
+            rates: HashMap::new(),
+            recovery_contacts: HashSet::new(),
+            recovery_waiting_period_secs: 0,
+            recovery_required_approvals: 1,
+            pending_recovery: None,
+            chain_head: String::new(),
+            fee_policy: None,
+            owner_key: None,
+            nft_ownership: HashMap::new(),
+            reserves: HashMap::new(),
+            base_fee_per_gas: default_base_fee_per_gas(),
+            pool_shares: HashMap::new(),
+            swaps: HashMap::new(),
+            swap_nonce: 0,
+            user_memos: HashMap::new(),
+        }
+    }
+
+    /// Recompute this wallet's integrity digest from its current balances and inbox deposits and
+    /// store it in `integrity_digest`. Called by `PassWalletManager::update_wallet` just before
+    /// every persisted write, so a later `verify_wallet_integrity` call can detect storage-level
+    /// drift or corruption by comparing against what's actually on disk.
+    fn recompute_integrity_digest(&mut self) {
+        self.integrity_digest = self.compute_integrity_digest();
+    }
+
+    /// Merkle root (SHA-256, leaves sorted for determinism, the last leaf duplicated to pair up
+    /// an odd count) over every `balances` entry and `inbox` deposit, hex-encoded.
+    fn compute_integrity_digest(&self) -> String {
+        let mut leaves: Vec<[u8; 32]> = Vec::new();
+
+        let mut balance_keys: Vec<&String> = self.balances.keys().collect();
+        balance_keys.sort();
+        for key in balance_keys {
+            let mut amount_bytes = [0u8; 32];
+            self.balances[key].to_big_endian(&mut amount_bytes);
+            let mut hasher = Sha256::new();
+            hasher.update(b"balance:");
+            hasher.update(key.as_bytes());
+            hasher.update(amount_bytes);
+            leaves.push(hasher.finalize().into());
+        }
+
+        let mut deposits: Vec<&Deposit> = self.inbox.iter().collect();
+        deposits.sort_by(|a, b| a.deposit_id.cmp(&b.deposit_id));
+        for deposit in deposits {
+            let mut amount_bytes = [0u8; 32];
+            deposit.amount.to_big_endian(&mut amount_bytes);
+            let mut hasher = Sha256::new();
+            hasher.update(b"deposit:");
+            hasher.update(deposit.deposit_id.as_bytes());
+            hasher.update(deposit.asset_id.as_bytes());
+            hasher.update(amount_bytes);
+            leaves.push(hasher.finalize().into());
+        }
+
+        if leaves.is_empty() {
+            return hex::encode(Sha256::digest(b"empty"));
+        }
+
+        while leaves.len() > 1 {
+            let mut next_level = Vec::with_capacity((leaves.len() + 1) / 2);
+            for pair in leaves.chunks(2) {
+                let mut hasher = Sha256::new();
+                hasher.update(pair[0]);
+                hasher.update(pair.get(1).unwrap_or(&pair[0]));
+                next_level.push(hasher.finalize().into());
+            }
+            leaves = next_level;
+        }
+
+        hex::encode(leaves[0])
+    }
+
+    /// Structural invariants a restored/imported wallet state must satisfy beyond what
+    /// `integrity_digest` alone catches (which ties `balances` to `inbox` cryptographically, but
+    /// says nothing about duplicate or malformed entries): no two inbox deposits share a
+    /// `deposit_id`, every queued outbox entry names a real asset with a non-zero amount, and the
+    /// stored integrity digest - if any - actually reconciles against the snapshot's own
+    /// balances/inbox.
+    fn validate_invariants(&self) -> Result<()> {
+        let mut seen_deposit_ids = HashSet::new();
+        for deposit in &self.inbox {
+            if !seen_deposit_ids.insert(&deposit.deposit_id) {
+                return Err(anyhow!("Duplicate deposit_id in inbox: {}", deposit.deposit_id));
+            }
+        }
+
+        for entry in &self.outbox {
+            if entry.asset_id.is_empty() {
+                return Err(anyhow!("Outbox entry with nonce {} has an empty asset_id", entry.nonce));
+            }
+            if entry.amount.is_zero() {
+                return Err(anyhow!("Outbox entry with nonce {} has a zero amount", entry.nonce));
+            }
+        }
+
+        if !self.integrity_digest.is_empty() && self.integrity_digest != self.compute_integrity_digest() {
+            return Err(anyhow!(
+                "Integrity digest mismatch for wallet {}: balances don't reconcile against the stored snapshot",
+                self.address
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Canonical bytes for a single provenance entry, chained by `append_history` into
+    /// `chain_head`. Serialized via `serde_json` rather than anything hand-rolled: every
+    /// `TransactionOperation` variant is plain fields (no maps), so field order - and therefore
+    /// the JSON encoding - is fixed by the struct/enum definition.
+    fn canonical_provenance_bytes(operation: &TransactionOperation, timestamp: u64) -> Vec<u8> {
+        serde_json::to_vec(&(operation, timestamp)).expect("provenance entry serializes")
+    }
+
+    /// Serialize a drained `OutboxEntry` for broadcast in `format`. Pure and deterministic - the
+    /// same `(entry, format)` pair always produces the same bytes, which is exactly what
+    /// `PassWalletManager::process_outbox_for_broadcast` signs over.
+    fn serialize_outbox_entry(entry: &OutboxEntry, format: WithdrawSerializeType) -> Result<Vec<u8>> {
+        if entry.memo.is_some() && !format.carries_memo() {
+            return Err(anyhow!(
+                "{:?} cannot carry a memo - drop the memo or broadcast in a format that supports it",
+                format
+            ));
+        }
+
+        Ok(match format {
+            WithdrawSerializeType::EvmCalldata => {
+                // `transfer(address,uint256)` selector followed by the 32-byte-padded destination
+                // and amount words, the same layout an ERC20 relayer would broadcast. No room in
+                // this fixed layout for a memo - see the `carries_memo` check above.
+                let mut payload = vec![0xa9, 0x05, 0x9c, 0xbb];
+                let destination_bytes =
+                    hex::decode(entry.external_destination.trim_start_matches("0x"))
+                        .unwrap_or_default();
+                let mut destination_word = [0u8; 32];
+                if destination_bytes.len() <= 32 {
+                    destination_word[32 - destination_bytes.len()..]
+                        .copy_from_slice(&destination_bytes);
+                }
+                payload.extend_from_slice(&destination_word);
+                let mut amount_word = [0u8; 32];
+                entry.amount.to_big_endian(&mut amount_word);
+                payload.extend_from_slice(&amount_word);
+                payload
+            }
+            WithdrawSerializeType::Eip712 => {
+                // A domain-separated digest over the entry's typed fields, in place of a full
+                // EIP-712 `TypedData` struct hash - a relayer that verifies `TypedData` hashes
+                // would recompute this the same way from the same fields.
+                let mut amount_word = [0u8; 32];
+                entry.amount.to_big_endian(&mut amount_word);
+                let mut hasher = Keccak256::new();
+                hasher.update(b"pass-wallet-withdrawal-v1");
+                hasher.update(entry.asset_id.as_bytes());
+                hasher.update(amount_word);
+                hasher.update(entry.external_destination.as_bytes());
+                hasher.update(entry.nonce.to_be_bytes());
+                hasher.update(entry.memo.as_deref().unwrap_or_default());
+                hasher.finalize().to_vec()
+            }
+            WithdrawSerializeType::CompactJson => {
+                serde_json::to_vec(entry).expect("outbox entry serializes")
+            }
+        })
+    }
+
+    /// Append `record` to `history`, folding it into the tamper-evident hashchain: `chain_head`
+    /// becomes `SHA256(chain_head || canonical_provenance_bytes(record))`, and the resulting head
+    /// is stamped onto `record` itself so `verify_provenance_log` can recompute the chain entry by
+    /// entry and compare against what's stored at each step, not just the final head. Every
+    /// mutating method that records history goes through this instead of pushing directly, so the
+    /// chain can never have a gap.
+    fn append_history(&mut self, mut record: ProvenanceRecord) {
+        let entry_bytes = Self::canonical_provenance_bytes(&record.operation, record.timestamp);
+
+        let mut hasher = Sha256::new();
+        if let Ok(prev_head) = hex::decode(&self.chain_head) {
+            hasher.update(&prev_head);
         }
+        hasher.update(&entry_bytes);
+        let head = hex::encode(hasher.finalize());
+
+        record.chain_head = head.clone();
+        self.chain_head = head;
+        self.history.push(record);
     }
 
     /// Add an asset to the wallet
@@ -155,28 +1216,109 @@ impl PassWalletState {
         self.assets.insert(asset_id, asset);
     }
 
+    /// Derive the canonical asset id for an ERC-20 `contract_address`, so any two callers
+    /// mirroring the same contract (even in different subaccounts or wallets) always agree on
+    /// the same id. Case-insensitive, since EVM addresses aren't.
+    fn mirrored_asset_id(contract_address: &str) -> String {
+        let digest = Sha256::digest(contract_address.to_lowercase().as_bytes());
+        format!("erc20-{}", hex::encode(&digest[..8]))
+    }
+
+    /// Mirror an ERC-20 contract's metadata into this wallet under its deterministic id. Errors
+    /// if this contract has already been mirrored, so callers can't end up with two asset
+    /// entries (and so two disjoint balances) for the same token.
+    pub fn mirror_asset(
+        &mut self,
+        contract_address: &str,
+        metadata: Erc20Metadata,
+    ) -> Result<String> {
+        let asset_id = Self::mirrored_asset_id(contract_address);
+        if self.assets.contains_key(&asset_id) {
+            return Err(anyhow!(
+                "Contract {} is already mirrored as asset {}",
+                contract_address,
+                asset_id
+            ));
+        }
+        self.assets.insert(
+            asset_id.clone(),
+            Asset {
+                token_type: TokenType::ERC20,
+                contract_address: Some(contract_address.to_string()),
+                token_id: None,
+                symbol: metadata.symbol,
+                name: metadata.name,
+                decimals: metadata.decimals,
+            },
+        );
+        Ok(asset_id)
+    }
+
     /// Add a subaccount to the wallet
     pub fn add_subaccount(&mut self, subaccount: Subaccount) {
         self.subaccounts.insert(subaccount.id.clone(), subaccount);
     }
 
     /// Get balance for a subaccount-asset pair
-    pub fn get_balance(&self, subaccount_id: &str, asset_id: &str) -> u64 {
+    pub fn get_balance(&self, subaccount_id: &str, asset_id: &str) -> Amount {
         let balance_key = format!("{}:{}", subaccount_id, asset_id);
-        self.balances.get(&balance_key).copied().unwrap_or(0)
+        self.balances.get(&balance_key).copied().unwrap_or(Amount::zero())
+    }
+
+    /// Storage key for `nft_ownership`: `"{asset_id}:{token_id}"`, mirroring the `balances` key scheme.
+    fn nft_key(asset_id: &str, token_id: &str) -> String {
+        format!("{}:{}", asset_id, token_id)
+    }
+
+    /// Subaccount currently owning `asset_id`'s `token_id`, if any.
+    pub fn get_nft_owner(&self, asset_id: &str, token_id: &str) -> Option<&str> {
+        self.nft_ownership.get(&Self::nft_key(asset_id, token_id)).map(|s| s.as_str())
+    }
+
+    /// Every `(asset_id, token_id)` pair `subaccount_id` currently owns, complementing
+    /// `get_subaccount_balances`'s fungible totals with this wallet's NFT holdings.
+    pub fn get_subaccount_nfts(&self, subaccount_id: &str) -> Vec<(String, String)> {
+        self.nft_ownership.iter()
+            .filter(|(_, owner)| owner.as_str() == subaccount_id)
+            .filter_map(|(key, _)| key.split_once(':').map(|(asset_id, token_id)| (asset_id.to_string(), token_id.to_string())))
+            .collect()
     }
 
     /// Set balance for a subaccount-asset pair
-    fn set_balance(&mut self, subaccount_id: &str, asset_id: &str, amount: u64) {
+    fn set_balance(&mut self, subaccount_id: &str, asset_id: &str, amount: Amount) {
         let balance_key = format!("{}:{}", subaccount_id, asset_id);
         self.balances.insert(balance_key, amount);
     }
 
+    /// Add `amount` to `subaccount_id`'s `asset_id` balance via checked 256-bit addition, erroring
+    /// rather than wrapping if the sum would overflow `Amount`/`U256`.
+    fn credit_balance(&mut self, subaccount_id: &str, asset_id: &str, amount: Amount) -> Result<()> {
+        let current = self.get_balance(subaccount_id, asset_id);
+        let new_balance = current.checked_add(amount)
+            .ok_or_else(|| anyhow!("Balance overflow crediting {} {} to {}", amount, asset_id, subaccount_id))?;
+        self.set_balance(subaccount_id, asset_id, new_balance);
+        Ok(())
+    }
+
+    /// Subtract `amount` from `subaccount_id`'s `asset_id` balance via checked 256-bit subtraction,
+    /// erroring rather than wrapping if the balance is insufficient.
+    fn debit_balance(&mut self, subaccount_id: &str, asset_id: &str, amount: Amount) -> Result<()> {
+        let current = self.get_balance(subaccount_id, asset_id);
+        let new_balance = current.checked_sub(amount)
+            .ok_or_else(|| WalletError::NotEnoughBalance {
+                asset_id: asset_id.to_string(),
+                required: amount,
+                available: current,
+            })?;
+        self.set_balance(subaccount_id, asset_id, new_balance);
+        Ok(())
+    }
+
     /// Add external deposit to inbox
     pub fn inbox_deposit(&mut self, deposit: Deposit) -> Result<()> {
         // Check if deposit ID already exists
         if self.inbox.iter().any(|d| d.deposit_id == deposit.deposit_id) {
-            return Err(anyhow!("Deposit ID already exists"));
+            return Err(WalletError::DuplicateDeposit { deposit_id: deposit.deposit_id }.into());
         }
         
         self.inbox.push(deposit);
@@ -184,19 +1326,37 @@ impl PassWalletState {
     }
 
     /// Claim deposit from inbox
-    pub fn claim_inbox(&mut self, deposit_id: &str, subaccount_id: &str) -> Result<()> {
+    /// Returns the deposit's plaintext `memo`, if it carried one, so
+    /// `PassWalletManager::claim_inbox` can seal it to `subaccount_id`'s own key and store it via
+    /// `store_user_memo` before this plaintext copy ever leaves the call stack.
+    pub fn claim_inbox(&mut self, deposit_id: &str, subaccount_id: &str) -> Result<Option<String>> {
         // Find and remove the deposit from inbox
         let deposit_index = self.inbox.iter().position(|d| d.deposit_id == deposit_id)
-            .ok_or_else(|| anyhow!("Deposit not found in inbox"))?;
-        
+            .ok_or_else(|| WalletError::UnknownDeposit { deposit_id: deposit_id.to_string() })?;
+
         let deposit = self.inbox.remove(deposit_index);
-        
-        // Update balance
-        let current_balance = self.get_balance(subaccount_id, &deposit.asset_id);
-        self.set_balance(subaccount_id, &deposit.asset_id, current_balance + deposit.amount);
-        
+        let memo = deposit.memo.clone();
+
+        let asset = self.assets.get(&deposit.asset_id)
+            .ok_or_else(|| WalletError::UnknownAsset { asset_id: deposit.asset_id.clone() })?;
+
+        if matches!(asset.token_type, TokenType::ERC721) {
+            // NFTs are tracked by ownership in `nft_ownership`, not by a summed balance - claiming
+            // the same token_id twice (e.g. a duplicate deposit re-queued for the same NFT) is
+            // rejected rather than silently reassigning ownership.
+            let token_id = asset.token_id.clone()
+                .ok_or_else(|| anyhow!("ERC721 asset {} has no token_id configured", deposit.asset_id))?;
+            let key = Self::nft_key(&deposit.asset_id, &token_id);
+            if self.nft_ownership.contains_key(&key) {
+                return Err(anyhow!("Token {} of asset {} is already claimed", token_id, deposit.asset_id));
+            }
+            self.nft_ownership.insert(key, subaccount_id.to_string());
+        } else {
+            self.credit_balance(subaccount_id, &deposit.asset_id, deposit.amount)?;
+        }
+
         // Add to provenance history
-        self.history.push(ProvenanceRecord {
+        self.append_history(ProvenanceRecord {
             operation: TransactionOperation::Claim {
                 asset_id: deposit.asset_id,
                 amount: deposit.amount,
@@ -205,684 +1365,6372 @@ impl PassWalletState {
             },
             timestamp: Self::get_timestamp(),
             block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        Ok(memo)
+    }
+
+    /// Append an already-sealed memo (see `PassWalletManager::encrypt_user_memo`) to
+    /// `subaccount_id`'s memo list. Takes pre-encrypted ciphertext rather than a plaintext memo,
+    /// the same split `withdraw`/`encrypt_outbox_memo` use, since sealing requires the KMS-backed
+    /// key derivation only `PassWalletManager` has access to.
+    fn store_user_memo(&mut self, subaccount_id: &str, ciphertext: Vec<u8>, context: String) {
+        self.user_memos.entry(subaccount_id.to_string()).or_default().push(StoredMemo {
+            ciphertext,
+            context,
+            created_at: Self::get_timestamp(),
         });
-        
-        Ok(())
+    }
+
+    /// Every memo sealed to `subaccount_id`, in the order they were stored. Ciphertext only -
+    /// `PassWalletManager::get_memos` is the only caller with the key material to decrypt these.
+    pub fn user_memos(&self, subaccount_id: &str) -> &[StoredMemo] {
+        self.user_memos.get(subaccount_id).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// Resolve a `DepositEvent::token_contract` to the `asset_id` of a registered asset backed by
+    /// that contract, or `"ETH"` (the convention `scan_deposits_once_inner` already uses for the
+    /// native asset) when `token_contract` is `None`.
+    fn resolve_deposit_asset(&self, token_contract: Option<&str>) -> Option<String> {
+        match token_contract {
+            None => Some("ETH".to_string()),
+            Some(contract) => self.assets.iter()
+                .find(|(_, asset)| asset.contract_address.as_deref()
+                    .map(|a| a.eq_ignore_ascii_case(contract))
+                    .unwrap_or(false))
+                .map(|(asset_id, _)| asset_id.clone()),
+        }
+    }
+
+    /// Whether `deposit_id` is already accounted for: either still sitting unclaimed in `inbox`,
+    /// or already claimed per a `Claim` history record.
+    fn deposit_already_known(&self, deposit_id: &str) -> bool {
+        self.inbox.iter().any(|d| d.deposit_id == deposit_id)
+            || self.history.iter().any(|record| matches!(
+                &record.operation,
+                TransactionOperation::Claim { deposit_id: id, .. } if id == deposit_id
+            ))
+    }
+
+    /// Idempotently reconcile a batch of externally-observed `DepositEvent`s against this
+    /// wallet's inbox/history, queuing any genuinely new one into `inbox` via `inbox_deposit` -
+    /// deduplicating on `deposit_id` so replaying the same batch (or an overlapping one from a
+    /// re-run recovery job) is always safe. Events whose `token_contract` doesn't resolve to a
+    /// registered asset are reported as orphaned instead of erroring the whole batch, so one
+    /// unrecognized event doesn't block recovery of the rest.
+    pub fn scan_and_recover(&mut self, deposit_events: Vec<DepositEvent>) -> DepositReconciliationReport {
+        let mut report = DepositReconciliationReport {
+            newly_discovered: Vec::new(),
+            already_known: Vec::new(),
+            orphaned: Vec::new(),
+        };
+
+        for event in deposit_events {
+            if self.deposit_already_known(&event.deposit_id) {
+                report.already_known.push(event.deposit_id);
+                continue;
+            }
+
+            let asset_id = match self.resolve_deposit_asset(event.token_contract.as_deref()) {
+                Some(asset_id) => asset_id,
+                None => {
+                    report.orphaned.push(event.deposit_id);
+                    continue;
+                }
+            };
+
+            let deposit_id = event.deposit_id.clone();
+            let deposit = Deposit {
+                asset_id,
+                amount: event.amount,
+                deposit_id: event.deposit_id,
+                transaction_hash: event.transaction_hash,
+                block_number: event.block_number,
+                from_address: event.from_address,
+                to_address: event.to_address,
+                memo: None,
+            };
+
+            // Already checked `deposit_already_known` above, so this can only fail if two events
+            // in the same batch share a `deposit_id` - treat the second as already-known rather
+            // than erroring the batch.
+            match self.inbox_deposit(deposit) {
+                Ok(()) => report.newly_discovered.push(deposit_id),
+                Err(_) => report.already_known.push(deposit_id),
+            }
+        }
+
+        report
+    }
+
+    /// Deposits currently sitting unclaimed in `inbox` whose `block_number` is at least
+    /// `min_block_age` blocks behind `current_block_number` - i.e. observed on-chain long enough
+    /// ago that they're a recovery gap worth surfacing (an operator dashboard, an alerting job)
+    /// rather than a deposit still in-flight toward being claimed. A `block_number` that fails to
+    /// parse as a number is surfaced unconditionally, since its age can't be judged.
+    pub fn recover_gap_deposits(&self, current_block_number: u64, min_block_age: u64) -> Vec<&Deposit> {
+        self.inbox.iter()
+            .filter(|deposit| match deposit.block_number.parse::<u64>() {
+                Ok(block) => current_block_number.saturating_sub(block) >= min_block_age,
+                Err(_) => true,
+            })
+            .collect()
     }
 
     /// Check if a subaccount is allowed to perform a transaction
-    pub fn check_allow(&self, subaccount_id: &str, asset_id: &str, amount: u64) -> bool {
+    pub fn check_allow(&self, subaccount_id: &str, asset_id: &str, amount: Amount) -> bool {
         self.get_balance(subaccount_id, asset_id) >= amount
     }
 
     /// Internal transfer between subaccounts
-    pub fn internal_transfer(&mut self, asset_id: &str, amount: u64, from_subaccount: &str, to_subaccount: &str) -> Result<()> {
+    /// `encrypted_memo`, if given, is already sealed to `to_subaccount`'s own key (see
+    /// `PassWalletManager::encrypt_user_memo`) and is stored via `store_user_memo` once the
+    /// transfer succeeds; `memo` stays plaintext and only goes into the shared provenance record,
+    /// same split as `withdraw`'s `memo`/`outbox_memo`.
+    pub fn internal_transfer(&mut self, asset_id: &str, amount: Amount, from_subaccount: &str, to_subaccount: &str, memo: Option<String>, encrypted_memo: Option<Vec<u8>>) -> Result<()> {
+        validate_memo(&memo)?;
+
+        if !self.subaccounts.contains_key(from_subaccount) {
+            return Err(WalletError::UnknownSubaccount { subaccount_id: from_subaccount.to_string() }.into());
+        }
+        if !self.subaccounts.contains_key(to_subaccount) {
+            return Err(WalletError::UnknownSubaccount { subaccount_id: to_subaccount.to_string() }.into());
+        }
+
+        let (fee_asset_id, fee) = self.fee_for(asset_id, false);
+        self.check_fee_affordable(from_subaccount, asset_id, amount, &fee_asset_id, fee)?;
+
         // Check if sender has sufficient balance
-        if !self.check_allow(from_subaccount, asset_id, amount) {
-            return Err(anyhow!("Insufficient balance"));
+        let available = self.get_balance(from_subaccount, asset_id);
+        if available < amount {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: asset_id.to_string(),
+                required: amount,
+                available,
+            }.into());
         }
-        
-        // Update balances
-        let from_balance = self.get_balance(from_subaccount, asset_id);
-        let to_balance = self.get_balance(to_subaccount, asset_id);
-        
-        self.set_balance(from_subaccount, asset_id, from_balance - amount);
-        self.set_balance(to_subaccount, asset_id, to_balance + amount);
-        
+
+        let limit_applied = self.check_withdrawal_policy(asset_id, from_subaccount, amount, true)?;
+
+        // Update balances
+        self.debit_balance(from_subaccount, asset_id, amount)?;
+        self.credit_balance(to_subaccount, asset_id, amount)?;
+        self.charge_fee(from_subaccount, &fee_asset_id, fee);
+
         // Add to provenance history
-        self.history.push(ProvenanceRecord {
+        self.append_history(ProvenanceRecord {
             operation: TransactionOperation::Transfer {
                 asset_id: asset_id.to_string(),
                 amount,
                 from_subaccount: from_subaccount.to_string(),
                 to_subaccount: to_subaccount.to_string(),
+                memo,
             },
             timestamp: Self::get_timestamp(),
             block_number: None,
+            limit_applied,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
         });
-        
+
+        if let Some(ciphertext) = encrypted_memo {
+            self.store_user_memo(to_subaccount, ciphertext, format!("transfer:{}", from_subaccount));
+        }
+
         Ok(())
     }
 
-    /// Withdraw to external destination
-    pub fn withdraw(&mut self, asset_id: &str, amount: u64, subaccount_id: &str, external_destination: &str) -> Result<()> {
-        // Check if subaccount has sufficient balance
-        if !self.check_allow(subaccount_id, asset_id, amount) {
-            return Err(anyhow!("Insufficient balance"));
+    /// Rate-converting counterpart to `internal_transfer`: debits `from_amount` of `from_asset`
+    /// from `from_subaccount` and credits the `quote`d equivalent of `to_asset` to
+    /// `to_subaccount`, at the given `rate`, in one atomic step. Returns the credited amount.
+    /// Fee is charged in `from_asset`, same as `internal_transfer`. `encrypted_memo`, if given,
+    /// is already sealed to `to_subaccount`'s own key, same split as `internal_transfer`'s
+    /// `memo`/`encrypted_memo`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn internal_transfer_with_rate(
+        &mut self,
+        from_asset: &str,
+        from_amount: Amount,
+        to_asset: &str,
+        rate: Rate,
+        from_subaccount: &str,
+        to_subaccount: &str,
+        memo: Option<String>,
+        encrypted_memo: Option<Vec<u8>>,
+    ) -> Result<Amount> {
+        validate_memo(&memo)?;
+
+        if !self.subaccounts.contains_key(from_subaccount) {
+            return Err(WalletError::UnknownSubaccount { subaccount_id: from_subaccount.to_string() }.into());
         }
-        
-        // Update balance
-        let current_balance = self.get_balance(subaccount_id, asset_id);
-        self.set_balance(subaccount_id, asset_id, current_balance - amount);
-        
-        // Add to outbox
-        self.outbox.push_back(OutboxEntry {
-            asset_id: asset_id.to_string(),
-            amount,
-            external_destination: external_destination.to_string(),
-            nonce: self.nonce,
+        if !self.subaccounts.contains_key(to_subaccount) {
+            return Err(WalletError::UnknownSubaccount { subaccount_id: to_subaccount.to_string() }.into());
+        }
+
+        let to_amount = Amount::from(self.quote(from_asset, to_asset, from_amount, rate)?);
+
+        let (fee_asset_id, fee) = self.fee_for(from_asset, false);
+        self.check_fee_affordable(from_subaccount, from_asset, from_amount, &fee_asset_id, fee)?;
+
+        let available = self.get_balance(from_subaccount, from_asset);
+        if available < from_amount {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: from_asset.to_string(),
+                required: from_amount,
+                available,
+            }.into());
+        }
+
+        let limit_applied = self.check_withdrawal_policy(from_asset, from_subaccount, from_amount, true)?;
+
+        self.debit_balance(from_subaccount, from_asset, from_amount)?;
+        self.credit_balance(to_subaccount, to_asset, to_amount)?;
+        self.charge_fee(from_subaccount, &fee_asset_id, fee);
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::RateTransfer {
+                from_asset: from_asset.to_string(),
+                from_amount,
+                to_asset: to_asset.to_string(),
+                to_amount,
+                rate,
+                from_subaccount: from_subaccount.to_string(),
+                to_subaccount: to_subaccount.to_string(),
+                memo,
+            },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
         });
-        
+
+        if let Some(ciphertext) = encrypted_memo {
+            self.store_user_memo(to_subaccount, ciphertext, format!("transfer:{}", from_subaccount));
+        }
+
+        Ok(to_amount)
+    }
+
+    /// NFT-aware counterpart to `internal_transfer`: moves ownership of a specific ERC-721
+    /// `token_id` between subaccounts instead of summing a fungible `amount`. Rejects the call if
+    /// `from_subaccount` doesn't currently own that token.
+    pub fn transfer_nft(&mut self, asset_id: &str, token_id: &str, from_subaccount: &str, to_subaccount: &str) -> Result<()> {
+        let key = Self::nft_key(asset_id, token_id);
+        match self.nft_ownership.get(&key) {
+            Some(owner) if owner == from_subaccount => {}
+            Some(_) => return Err(anyhow!(
+                "Subaccount {} does not own token {} of asset {}", from_subaccount, token_id, asset_id
+            )),
+            None => return Err(anyhow!("Token {} of asset {} is not owned by any subaccount", token_id, asset_id)),
+        }
+        self.nft_ownership.insert(key, to_subaccount.to_string());
+
         // Add to provenance history
-        self.history.push(ProvenanceRecord {
-            operation: TransactionOperation::Withdraw {
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::Transfer {
                 asset_id: asset_id.to_string(),
-                amount,
-                subaccount_id: subaccount_id.to_string(),
-                destination: external_destination.to_string(),
+                amount: Amount::from(1u64),
+                from_subaccount: from_subaccount.to_string(),
+                to_subaccount: to_subaccount.to_string(),
+                memo: None,
             },
             timestamp: Self::get_timestamp(),
             block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
         });
-        
+
         Ok(())
     }
 
-    /// Process outbox (periodic or on-demand)
-    pub fn process_outbox(&mut self) -> Result<Vec<OutboxEntry>> {
-        let mut processed = Vec::new();
-        
-        while let Some(entry) = self.outbox.pop_front() {
-            processed.push(entry);
-            self.nonce += 1;
+    /// Storage key for `reserves`: the two asset_ids sorted lexicographically and joined with
+    /// `:`, so `add_liquidity`/`internal_swap` find the same entry regardless of argument order.
+    /// Returns the key along with whether `asset_a` is the *second* slot of the stored tuple (i.e.
+    /// the arguments came in swapped relative to the canonical order).
+    fn reserve_key(asset_a: &str, asset_b: &str) -> (String, bool) {
+        if asset_a <= asset_b {
+            (format!("{}:{}", asset_a, asset_b), false)
+        } else {
+            (format!("{}:{}", asset_b, asset_a), true)
         }
-        
-        Ok(processed)
     }
 
-    /// Get all balances for a subaccount
-    pub fn get_subaccount_balances(&self, subaccount_id: &str) -> HashMap<String, u64> {
-        self.balances.iter()
-            .filter_map(|(key, amount)| {
-                if let Some((sub_id, asset_id)) = key.split_once(':') {
-                    if sub_id == subaccount_id {
-                        Some((asset_id.to_string(), *amount))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .collect()
-    }
+    /// Seed (or top up) the constant-product liquidity reserve between `asset_a` and `asset_b`,
+    /// backing `internal_swap` between them. Funds come from wherever the caller's ledger tracks
+    /// the wallet's own reserves - this only bumps the `(reserve_a, reserve_b)` accounting, it
+    /// doesn't move any subaccount balance.
+    pub fn add_liquidity(&mut self, asset_a: &str, amount_a: Amount, asset_b: &str, amount_b: Amount) -> Result<()> {
+        if asset_a == asset_b {
+            return Err(anyhow!("Cannot seed liquidity between an asset and itself"));
+        }
+        let (key, swapped) = Self::reserve_key(asset_a, asset_b);
+        let (add_0, add_1) = if swapped { (amount_b, amount_a) } else { (amount_a, amount_b) };
 
-    /// Get wallet state summary
-    pub fn get_state_summary(&self) -> serde_json::Value {
-        serde_json::json!({
-            "address": self.address,
-            "name": self.name,
-            "owner": self.owner,
-            "nonce": self.nonce,
-            "inbox_count": self.inbox.len(),
-            "outbox_count": self.outbox.len(),
-            "assets_count": self.assets.len(),
-            "subaccounts_count": self.subaccounts.len(),
-            "history_count": self.history.len(),
-            "created_at": self.created_at
-        })
+        let (reserve_0, reserve_1) = self.reserves.get(&key).copied().unwrap_or((Amount::zero(), Amount::zero()));
+        let reserve_0 = reserve_0.checked_add(add_0).ok_or_else(|| anyhow!("Reserve overflow"))?;
+        let reserve_1 = reserve_1.checked_add(add_1).ok_or_else(|| anyhow!("Reserve overflow"))?;
+        self.reserves.insert(key, (reserve_0, reserve_1));
+        Ok(())
     }
 
-    /// Helper function to get current timestamp
-    fn get_timestamp() -> u64 {
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-    }
-}
+    /// Exchange `amount_in` of `asset_in` held by `subaccount_id` for `asset_out`, against this
+    /// wallet's own liquidity reserve rather than an external DEX, using the constant-product
+    /// invariant `amount_out = (reserve_out * amount_in_with_fee) / (reserve_in +
+    /// amount_in_with_fee)` with a 0.3% fee (`amount_in_with_fee = amount_in * 997 / 1000`).
+    /// `min_out` is slippage protection: the call fails rather than filling at a worse price.
+    /// Returns the amount of `asset_out` credited.
+    pub fn internal_swap(&mut self, subaccount_id: &str, asset_in: &str, amount_in: Amount, asset_out: &str, min_out: Amount) -> Result<Amount> {
+        if asset_in == asset_out {
+            return Err(anyhow!("Cannot swap an asset for itself"));
+        }
+        if amount_in.is_zero() {
+            return Err(anyhow!("Swap amount must be nonzero"));
+        }
+        if !self.check_allow(subaccount_id, asset_in, amount_in) {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: asset_in.to_string(),
+                required: amount_in,
+                available: self.get_balance(subaccount_id, asset_in),
+            }.into());
+        }
 
-/// Pending withdrawal transaction with signed data
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PendingWithdrawal {
-    pub wallet_address: WalletAddress,
-    pub subaccount_id: String,
-    pub asset_id: String,
-    pub amount: u64,
-    pub destination: String,
-    pub nonce: u64,
-    pub signed_raw_transaction: String,
-    pub created_at: u64,
-}
+        let (key, swapped) = Self::reserve_key(asset_in, asset_out);
+        let (reserve_0, reserve_1) = self.reserves.get(&key).copied()
+            .ok_or_else(|| anyhow!("No liquidity reserve for {}/{}", asset_in, asset_out))?;
+        let (reserve_in, reserve_out) = if swapped { (reserve_1, reserve_0) } else { (reserve_0, reserve_1) };
 
-/// PASS Wallet Manager - manages multiple PASS wallets
-pub struct PassWalletManager {
-    kms: Arc<Mutex<EnclaveKMS>>,
-    wallets: Arc<Mutex<HashMap<WalletAddress, PassWalletState>>>,
-    /// Global nonce counter for transaction sequencing
-    global_nonce: Arc<Mutex<u64>>,
-    /// Outbox queue for pending withdrawal transactions
-    outbox_queue: Arc<Mutex<VecDeque<PendingWithdrawal>>>,
-}
+        let amount_in_with_fee = amount_in.checked_mul(Amount::from(997u64))
+            .ok_or_else(|| anyhow!("Amount overflow"))?
+            / Amount::from(1000u64);
+        let numerator = reserve_out.checked_mul(amount_in_with_fee).ok_or_else(|| anyhow!("Amount overflow"))?;
+        let denominator = reserve_in.checked_add(amount_in_with_fee).ok_or_else(|| anyhow!("Amount overflow"))?;
+        if denominator.is_zero() {
+            return Err(anyhow!("No liquidity reserve for {}/{}", asset_in, asset_out));
+        }
+        let amount_out = numerator / denominator;
 
-impl PassWalletManager {
-    /// Create a new PASS wallet manager
-    pub fn new(kms: Arc<Mutex<EnclaveKMS>>) -> Self {
-        PassWalletManager {
-            kms,
-            wallets: Arc::new(Mutex::new(HashMap::new())),
-            global_nonce: Arc::new(Mutex::new(0)),
-            outbox_queue: Arc::new(Mutex::new(VecDeque::new())),
+        if amount_out < min_out {
+            return Err(anyhow!(
+                "Swap would return {} {}, below the minimum {} requested", amount_out, asset_out, min_out
+            ));
+        }
+        if amount_out >= reserve_out {
+            return Err(anyhow!("Swap would drain the {} reserve to zero", asset_out));
         }
+
+        self.debit_balance(subaccount_id, asset_in, amount_in)?;
+        self.credit_balance(subaccount_id, asset_out, amount_out)?;
+
+        let new_reserve_in = reserve_in.checked_add(amount_in).ok_or_else(|| anyhow!("Reserve overflow"))?;
+        let new_reserve_out = reserve_out.checked_sub(amount_out).ok_or_else(|| anyhow!("Reserve underflow"))?;
+        let (new_0, new_1) = if swapped { (new_reserve_out, new_reserve_in) } else { (new_reserve_in, new_reserve_out) };
+        self.reserves.insert(key, (new_0, new_1));
+
+        // Add to provenance history
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::Swap {
+                asset_in: asset_in.to_string(),
+                amount_in,
+                asset_out: asset_out.to_string(),
+                amount_out,
+                subaccount_id: subaccount_id.to_string(),
+            },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        Ok(amount_out)
     }
 
-    /// Create a new PASS wallet
-    pub fn create_wallet(&self, name: String, owner: String) -> Result<WalletAddress> {
-        // Generate a new Ethereum account using the existing KMS
-        let account = {
-            let mut kms = self.kms.lock().unwrap();
-            kms.handle_keygen()?
-        };
+    /// Storage key for `pool_shares`: `"{subaccount_id}:{asset_id}"`, mirroring the `balances`
+    /// and NFT `nft_key` scheme.
+    fn pool_key(subaccount_id: &str, asset_id: &str) -> String {
+        format!("{}:{}", subaccount_id, asset_id)
+    }
 
-        let address = account.address.clone();
-        let wallet_state = PassWalletState::new(address.clone(), name, owner);
+    /// Total shares outstanding across every contributor to `subaccount_id`'s `asset_id` pool.
+    pub fn total_shares(&self, subaccount_id: &str, asset_id: &str) -> Amount {
+        self.pool_shares
+            .get(&Self::pool_key(subaccount_id, asset_id))
+            .map(|holders| holders.values().fold(Amount::zero(), |acc, s| acc.saturating_add(*s)))
+            .unwrap_or_else(Amount::zero)
+    }
 
-        // Store the wallet
-        {
-            let mut wallets = self.wallets.lock().unwrap();
-            wallets.insert(address.clone(), wallet_state);
+    /// Shares `contributor` holds in `subaccount_id`'s `asset_id` pool.
+    pub fn shares_of(&self, subaccount_id: &str, asset_id: &str, contributor: &str) -> Amount {
+        self.pool_shares
+            .get(&Self::pool_key(subaccount_id, asset_id))
+            .and_then(|holders| holders.get(contributor))
+            .copied()
+            .unwrap_or_else(Amount::zero)
+    }
+
+    /// Deposit `amount` of `asset_id` into `subaccount_id`'s pool on `contributor`'s behalf: the
+    /// pool's ordinary balance is credited as usual, and `contributor` mints `amount *
+    /// (total_shares + POOL_VIRTUAL_SHARES) / (pool_balance + POOL_VIRTUAL_ASSETS)` shares (~1:1
+    /// for the pool's first-ever deposit), the same constant-value-per-share rule
+    /// `internal_swap`'s reserves use for pricing, plus OpenZeppelin's ERC4626 virtual-offset
+    /// mitigation against the classic first-depositor donation attack: without it, a first
+    /// depositor could mint a single share for a negligible `amount`, then directly inflate
+    /// `pool_balance` (a fee sweep, a plain transfer - anything that bypasses this method)
+    /// to dilute every later depositor's mint toward zero. The virtual offset means an attacker's
+    /// donation dilutes their own inflated share of the pool by the same proportion it dilutes
+    /// everyone else's, making the attack a net loss rather than a profit. Because a pool's value
+    /// is just its subaccount balance, anything credited into it outside this method is still
+    /// distributed pro-rata to existing holders without their share counts changing.
+    pub fn deposit_to_pool(&mut self, subaccount_id: &str, asset_id: &str, contributor: &str, amount: Amount) -> Result<()> {
+        if amount.is_zero() {
+            return Err(anyhow!("Pool deposit amount must be nonzero"));
         }
 
-        Ok(address)
-    }
+        let pool_balance = self.get_balance(subaccount_id, asset_id);
+        let total_shares = self.total_shares(subaccount_id, asset_id);
 
-    /// Get a wallet by address
-    pub fn get_wallet(&self, address: &str) -> Option<PassWalletState> {
-        let wallets = self.wallets.lock().unwrap();
-        wallets.get(address).cloned()
+        let virtual_shares = total_shares.checked_add(Amount::from(POOL_VIRTUAL_SHARES))
+            .ok_or_else(|| anyhow!("Share mint amount overflows"))?;
+        let virtual_balance = pool_balance.checked_add(Amount::from(POOL_VIRTUAL_ASSETS))
+            .ok_or_else(|| anyhow!("Share mint amount overflows"))?;
+        let minted = amount
+            .checked_mul(virtual_shares)
+            .ok_or_else(|| anyhow!("Share mint amount overflows"))?
+            / virtual_balance;
+        if minted.is_zero() {
+            return Err(anyhow!("Deposit too small to mint a nonzero share of the pool"));
+        }
+
+        self.credit_balance(subaccount_id, asset_id, amount)?;
+
+        let key = Self::pool_key(subaccount_id, asset_id);
+        let holders = self.pool_shares.entry(key).or_default();
+        let held = holders.get(contributor).copied().unwrap_or_else(Amount::zero);
+        let new_held = held.checked_add(minted).ok_or_else(|| anyhow!("Share balance overflow"))?;
+        holders.insert(contributor.to_string(), new_held);
+
+        Ok(())
     }
 
-    /// Update a wallet
-    pub fn update_wallet(&self, address: &str, wallet_state: PassWalletState) -> Result<()> {
-        let mut wallets = self.wallets.lock().unwrap();
-        if wallets.contains_key(address) {
-            wallets.insert(address.to_string(), wallet_state);
-            Ok(())
+    /// Burn `shares` of `contributor`'s stake in `subaccount_id`'s `asset_id` pool and pay out
+    /// `shares * (pool_balance + POOL_VIRTUAL_ASSETS) / (total_shares + POOL_VIRTUAL_SHARES)`
+    /// into `recipient_subaccount` - the same virtual-offset pricing `deposit_to_pool` mints
+    /// against, so the two can never price a share differently. The payout is floored by integer
+    /// division, so the sum of every possible payout can never exceed the pool balance - any
+    /// rounding dust is left behind for the remaining holders rather than overdrawing the pool.
+    /// Returns the amount paid out.
+    pub fn withdraw_from_pool(&mut self, subaccount_id: &str, asset_id: &str, contributor: &str, shares: Amount, recipient_subaccount: &str) -> Result<Amount> {
+        if shares.is_zero() {
+            return Err(anyhow!("Withdrawal shares must be nonzero"));
+        }
+
+        let held = self.shares_of(subaccount_id, asset_id, contributor);
+        if shares > held {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: format!("{}:shares", asset_id),
+                required: shares,
+                available: held,
+            }.into());
+        }
+
+        let pool_balance = self.get_balance(subaccount_id, asset_id);
+        let total_shares = self.total_shares(subaccount_id, asset_id);
+        let virtual_shares = total_shares.checked_add(Amount::from(POOL_VIRTUAL_SHARES))
+            .ok_or_else(|| anyhow!("Payout amount overflows"))?;
+        let virtual_balance = pool_balance.checked_add(Amount::from(POOL_VIRTUAL_ASSETS))
+            .ok_or_else(|| anyhow!("Payout amount overflows"))?;
+        let payout = shares
+            .checked_mul(virtual_balance)
+            .ok_or_else(|| anyhow!("Payout amount overflows"))?
+            / virtual_shares;
+
+        let key = Self::pool_key(subaccount_id, asset_id);
+        let holders = self.pool_shares.entry(key).or_default();
+        let remaining = held.checked_sub(shares).ok_or_else(|| anyhow!("Share balance underflow"))?;
+        if remaining.is_zero() {
+            holders.remove(contributor);
         } else {
-            Err(anyhow!("Wallet not found"))
+            holders.insert(contributor.to_string(), remaining);
         }
-    }
 
-    /// List all wallet addresses
-    pub fn list_wallets(&self) -> Vec<WalletAddress> {
-        let wallets = self.wallets.lock().unwrap();
-        wallets.keys().cloned().collect()
+        self.debit_balance(subaccount_id, asset_id, payout)?;
+        self.credit_balance(recipient_subaccount, asset_id, payout)?;
+
+        Ok(payout)
     }
 
-    /// Sign a message using a wallet's private key
-    pub fn sign_message(&self, wallet_address: &str, domain: &str, message: &str) -> Result<String> {
-        // Use the existing KMS to sign the message
-        let kms = self.kms.lock().unwrap();
-        let full_message = format!("{}:{}", domain, message);
-        
-        match kms.sign_message(&full_message, wallet_address)? {
-            Some(signature) => Ok(signature),
-            None => Err(anyhow!("Failed to sign message - wallet not found")),
+    /// Propose a peer-to-peer atomic swap: lock `give_amount` of `give_asset` out of `maker`'s
+    /// balance into a pending `Swap` entry and return its generated `swap_id`. The lock debits
+    /// `maker` immediately, the same way `create_conditional_transfer` locks its `from_subaccount`
+    /// - so the locked amount is excluded from `get_balance` and can't be spent elsewhere while
+    /// the swap waits for a taker.
+    pub fn propose_swap(&mut self, maker: &str, give_asset: &str, give_amount: Amount, want_asset: &str, want_amount: Amount) -> Result<String> {
+        if give_asset == want_asset {
+            return Err(anyhow!("Cannot swap an asset for itself"));
+        }
+        if give_amount.is_zero() || want_amount.is_zero() {
+            return Err(anyhow!("Swap amounts must be nonzero"));
         }
-    }
 
-    /// Execute inbox deposit
-    pub fn inbox_deposit(&self, wallet_address: &str, deposit: Deposit) -> Result<()> {
-        let mut wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        wallet_state.inbox_deposit(deposit)?;
-        self.update_wallet(wallet_address, wallet_state)?;
-        Ok(())
+        let available = self.get_balance(maker, give_asset);
+        if available < give_amount {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: give_asset.to_string(),
+                required: give_amount,
+                available,
+            }.into());
+        }
+
+        self.debit_balance(maker, give_asset, give_amount)?;
+
+        let swap_id = format!("swap-{}", self.swap_nonce);
+        self.swap_nonce += 1;
+        self.swaps.insert(swap_id.clone(), Swap {
+            swap_id: swap_id.clone(),
+            maker: maker.to_string(),
+            give_asset: give_asset.to_string(),
+            give_amount,
+            want_asset: want_asset.to_string(),
+            want_amount,
+            created_at: Self::get_timestamp(),
+        });
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::SwapProposed {
+                swap_id: swap_id.clone(),
+                maker: maker.to_string(),
+                give_asset: give_asset.to_string(),
+                give_amount,
+                want_asset: want_asset.to_string(),
+                want_amount,
+            },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        Ok(swap_id)
     }
 
-    /// Execute claim inbox
-    pub fn claim_inbox(&self, wallet_address: &str, deposit_id: &str, subaccount_id: &str) -> Result<()> {
-        let mut wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        wallet_state.claim_inbox(deposit_id, subaccount_id)?;
-        self.update_wallet(wallet_address, wallet_state)?;
+    /// Accept a pending swap: `taker` must hold `want_amount` of `want_asset`. Every fallible step
+    /// (the taker's balance check, and the three balance updates this settlement makes) is
+    /// computed up front before anything is mutated, so a failure - insufficient balance, or an
+    /// astronomically unlikely overflow crediting one of the parties - leaves every balance
+    /// exactly as it was; there's no partial settlement to roll back.
+    pub fn accept_swap(&mut self, swap_id: &str, taker: &str) -> Result<()> {
+        let swap = self.swaps.get(swap_id)
+            .ok_or_else(|| anyhow!("Swap {} not found", swap_id))?
+            .clone();
+
+        let taker_want_balance = self.get_balance(taker, &swap.want_asset);
+        if taker_want_balance < swap.want_amount {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: swap.want_asset.clone(),
+                required: swap.want_amount,
+                available: taker_want_balance,
+            }.into());
+        }
+
+        let taker_want_new = taker_want_balance.checked_sub(swap.want_amount)
+            .ok_or_else(|| anyhow!("Balance underflow debiting taker"))?;
+        let maker_want_new = self.get_balance(&swap.maker, &swap.want_asset)
+            .checked_add(swap.want_amount)
+            .ok_or_else(|| anyhow!("Balance overflow crediting maker"))?;
+        let taker_give_new = self.get_balance(taker, &swap.give_asset)
+            .checked_add(swap.give_amount)
+            .ok_or_else(|| anyhow!("Balance overflow crediting taker"))?;
+
+        self.set_balance(taker, &swap.want_asset, taker_want_new);
+        self.set_balance(&swap.maker, &swap.want_asset, maker_want_new);
+        self.set_balance(taker, &swap.give_asset, taker_give_new);
+
+        self.swaps.remove(swap_id);
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::SwapAccepted {
+                swap_id: swap_id.to_string(),
+                maker: swap.maker.clone(),
+                taker: taker.to_string(),
+            },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
         Ok(())
     }
 
-    /// Execute internal transfer
-    pub fn internal_transfer(&self, wallet_address: &str, asset_id: &str, amount: u64, from_subaccount: &str, to_subaccount: &str) -> Result<()> {
-        let mut wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        wallet_state.internal_transfer(asset_id, amount, from_subaccount, to_subaccount)?;
-        self.update_wallet(wallet_address, wallet_state)?;
+    /// Cancel a still-pending swap and refund its locked leg to `maker`. Only the proposing
+    /// `maker` may cancel; a swap that's already been accepted no longer exists in `swaps`
+    /// (`accept_swap` removes it), so cancelling after acceptance fails the same way cancelling an
+    /// unknown id does.
+    pub fn cancel_swap(&mut self, swap_id: &str, maker: &str) -> Result<()> {
+        let swap = self.swaps.get(swap_id)
+            .ok_or_else(|| anyhow!("Swap {} not found", swap_id))?
+            .clone();
+
+        if swap.maker != maker {
+            return Err(anyhow!("Only the maker ({}) may cancel swap {}", swap.maker, swap_id));
+        }
+
+        self.credit_balance(&swap.maker, &swap.give_asset, swap.give_amount)?;
+        self.swaps.remove(swap_id);
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::SwapCancelled {
+                swap_id: swap_id.to_string(),
+                maker: maker.to_string(),
+            },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
         Ok(())
     }
 
-    /// Execute withdrawal
-    pub fn withdraw(&self, wallet_address: &str, asset_id: &str, amount: u64, subaccount_id: &str, destination: &str) -> Result<()> {
-        let mut wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        wallet_state.withdraw(asset_id, amount, subaccount_id, destination)?;
-        self.update_wallet(wallet_address, wallet_state)?;
+    /// Configure a withdrawal policy. `max_withdrawal_display` and `window_max_display` are
+    /// authored in human-readable display units (e.g. "1.5") and are scaled to base units using
+    /// the asset's `decimals` before being stored. When `subaccount_id` is `None` the policy
+    /// applies wallet-wide to every subaccount holding `asset_id`; when set, it overrides the
+    /// wallet-wide policy for that one subaccount only.
+    pub fn set_withdrawal_policy(
+        &mut self,
+        asset_id: &str,
+        subaccount_id: Option<&str>,
+        max_withdrawal_display: &str,
+        window_seconds: Option<u64>,
+        window_max_display: Option<&str>,
+    ) -> Result<()> {
+        let decimals = self.assets.get(asset_id)
+            .ok_or_else(|| anyhow!("Asset not found"))?
+            .decimals;
+
+        let max_withdrawal = display_amount_to_base_units(max_withdrawal_display, decimals)?;
+        let window_max = window_max_display
+            .map(|display| display_amount_to_base_units(display, decimals))
+            .transpose()?;
+
+        self.withdrawal_policies.insert(Self::withdrawal_policy_key(asset_id, subaccount_id), WithdrawalPolicy {
+            max_withdrawal,
+            window_seconds,
+            window_max,
+            exempt_internal_transfers: false,
+        });
         Ok(())
     }
 
-    /// Process outbox
-    pub fn process_outbox(&self, wallet_address: &str) -> Result<Vec<OutboxEntry>> {
-        let mut wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        let processed = wallet_state.process_outbox()?;
-        self.update_wallet(wallet_address, wallet_state)?;
-        Ok(processed)
+    /// The wallet-wide per-operation withdrawal cap configured for `asset_id` via
+    /// `set_withdrawal_policy`/`PassWalletManager::set_withdrawal_limit`, in base units, or `None`
+    /// if unconfigured. Unlike `remaining_withdrawal_limit`, this is the static configured cap,
+    /// not what's left of the current rolling window.
+    pub fn withdrawal_limit(&self, asset_id: &str) -> Option<Amount> {
+        self.withdrawal_policies.get(asset_id).map(|policy| policy.max_withdrawal)
     }
 
-    /// Add asset to wallet
-    pub fn add_asset(&self, wallet_address: &str, asset_id: String, asset: Asset) -> Result<()> {
-        let mut wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        wallet_state.add_asset(asset_id, asset);
-        self.update_wallet(wallet_address, wallet_state)?;
+    /// Flip whether an already-configured `WithdrawalPolicy` also applies to `internal_transfer`
+    /// (see `WithdrawalPolicy::exempt_internal_transfers`). Does not affect `withdraw`/
+    /// `withdraw_to_external`, which are never exempt.
+    pub fn set_policy_exempts_internal_transfers(&mut self, asset_id: &str, subaccount_id: Option<&str>, exempt: bool) -> Result<()> {
+        let policy = self.withdrawal_policies.get_mut(&Self::withdrawal_policy_key(asset_id, subaccount_id))
+            .ok_or_else(|| anyhow!("No withdrawal policy configured for asset {}", asset_id))?;
+        policy.exempt_internal_transfers = exempt;
         Ok(())
     }
 
-    /// Add subaccount to wallet
-    pub fn add_subaccount(&self, wallet_address: &str, subaccount: Subaccount) -> Result<()> {
-        let mut wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        wallet_state.add_subaccount(subaccount);
-        self.update_wallet(wallet_address, wallet_state)?;
-        Ok(())
+    /// Storage key for `withdrawal_policies`: `subaccount_id:asset_id` for a subaccount-specific
+    /// override, or bare `asset_id` for a wallet-wide policy — mirrors the `balances` key scheme.
+    fn withdrawal_policy_key(asset_id: &str, subaccount_id: Option<&str>) -> String {
+        match subaccount_id {
+            Some(subaccount_id) => format!("{}:{}", subaccount_id, asset_id),
+            None => asset_id.to_string(),
+        }
     }
 
-    /// Get balance for a subaccount
-    pub fn get_balance(&self, wallet_address: &str, subaccount_id: &str, asset_id: &str) -> Result<u64> {
-        let wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        Ok(wallet_state.get_balance(subaccount_id, asset_id))
-    }
+    /// Enforce the configured `WithdrawalPolicy` for `asset_id`/`subaccount_id`, if any, against a
+    /// prospective movement of `amount` base units. A subaccount-specific policy takes precedence
+    /// over a wallet-wide one for the same asset. `is_internal_transfer` distinguishes a call made
+    /// from `internal_transfer` (which skips policies with `exempt_internal_transfers` set) from
+    /// one made by `withdraw`/`withdraw_to_external` (never exempt). Returns the policy that was
+    /// applied (if any) so the caller can attach it to the resulting `ProvenanceRecord` for audits.
+    fn check_withdrawal_policy(&self, asset_id: &str, subaccount_id: &str, amount: Amount, is_internal_transfer: bool) -> Result<Option<AppliedLimit>> {
+        let (policy, scope, scoped_to_subaccount) = match self.withdrawal_policies.get(&Self::withdrawal_policy_key(asset_id, Some(subaccount_id))) {
+            Some(policy) => (policy, format!("subaccount:{}", subaccount_id), true),
+            None => match self.withdrawal_policies.get(asset_id) {
+                Some(policy) => (policy, "wallet".to_string(), false),
+                None => return Ok(None),
+            },
+        };
 
-    /// Get all balances for a subaccount
-    pub fn get_subaccount_balances(&self, wallet_address: &str, subaccount_id: &str) -> Result<HashMap<String, u64>> {
-        let wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        Ok(wallet_state.get_subaccount_balances(subaccount_id))
-    }
+        if is_internal_transfer && policy.exempt_internal_transfers {
+            return Ok(None);
+        }
 
-    /// Get wallet state summary
-    pub fn get_wallet_state(&self, wallet_address: &str) -> Result<serde_json::Value> {
-        let wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        Ok(wallet_state.get_state_summary())
-    }
+        if amount > policy.max_withdrawal {
+            return Err(anyhow!(
+                "Withdrawal of {} exceeds the per-transaction limit of {} for asset {} ({})",
+                amount, policy.max_withdrawal, asset_id, scope
+            ));
+        }
 
-    /// Get all assets from a wallet's asset ledger with total balances across all subaccounts
-    pub fn get_wallet_assets(&self, wallet_address: &str) -> Result<serde_json::Value> {
-        let wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        let mut assets_with_balances = serde_json::Map::new();
-        
-        for (asset_id, asset) in &wallet_state.assets {
-            // Calculate total balance for this asset across all subaccounts
-            let total_balance: u64 = wallet_state.balances
-                .iter()
-                .filter_map(|(balance_key, amount)| {
-                    if let Some((_subaccount_id, balance_asset_id)) = balance_key.split_once(':') {
-                        if balance_asset_id == asset_id {
-                            Some(*amount)
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                })
-                .sum();
+        if !is_internal_transfer {
+            if let (Some(window_seconds), Some(window_max)) = (policy.window_seconds, policy.window_max) {
+                let window_start = Self::get_timestamp().saturating_sub(window_seconds);
+                let already_withdrawn: Amount = self.history.iter()
+                    .filter_map(|record| match &record.operation {
+                        TransactionOperation::Withdraw { asset_id: a, subaccount_id: s, amount, .. }
+                            if a == asset_id
+                                && record.timestamp >= window_start
+                                && (!scoped_to_subaccount || s == subaccount_id) => Some(*amount),
+                        _ => None,
+                    })
+                    .fold(Amount::zero(), |acc, amount| acc.saturating_add(amount));
 
-            // Get per-subaccount balances for this asset
-            let mut subaccount_balances = serde_json::Map::new();
-            for (balance_key, amount) in &wallet_state.balances {
-                if let Some((subaccount_id, balance_asset_id)) = balance_key.split_once(':') {
-                    if balance_asset_id == asset_id && *amount > 0 {
-                        subaccount_balances.insert(subaccount_id.to_string(), serde_json::Value::Number(serde_json::Number::from(*amount)));
-                    }
+                if already_withdrawn.saturating_add(amount) > window_max {
+                    return Err(anyhow!(
+                        "Withdrawal would exceed the rolling {}s window limit of {} for asset {} ({}, {} already withdrawn)",
+                        window_seconds, window_max, asset_id, scope, already_withdrawn
+                    ));
                 }
             }
-
-            assets_with_balances.insert(asset_id.clone(), serde_json::json!({
-                "token_type": asset.token_type,
-                "contract_address": asset.contract_address,
-                "token_id": asset.token_id,
-                "symbol": asset.symbol,
-                "name": asset.name,
-                "decimals": asset.decimals,
-                "total_balance": total_balance,
-                "subaccount_balances": subaccount_balances
-            }));
         }
-        
-        Ok(serde_json::json!({
-            "wallet_address": wallet_address,
-            "assets": assets_with_balances
-        }))
-    }
 
-    /// Get full provenance log for a wallet
-    pub fn get_provenance_log(&self, wallet_address: &str) -> Result<serde_json::Value> {
-        let wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        Ok(serde_json::json!({
-            "wallet_address": wallet_address,
-            "provenance_records": wallet_state.history
+        Ok(Some(AppliedLimit {
+            scope,
+            max_withdrawal: policy.max_withdrawal,
+            window_seconds: policy.window_seconds,
+            window_max: policy.window_max,
         }))
     }
 
-    /// Get provenance log filtered by asset
-    pub fn get_provenance_by_asset(&self, wallet_address: &str, asset_id: &str) -> Result<serde_json::Value> {
-        let wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        let filtered_records: Vec<&ProvenanceRecord> = wallet_state.history.iter()
-            .filter(|record| {
-                match &record.operation {
-                    TransactionOperation::Claim { asset_id: a, .. } => a == asset_id,
-                    TransactionOperation::Transfer { asset_id: a, .. } => a == asset_id,
-                    TransactionOperation::Withdraw { asset_id: a, .. } => a == asset_id,
-                }
+    /// Base-unit budget still available in the current rolling window under `asset_id`'s
+    /// wallet-wide withdrawal policy (see `set_withdrawal_policy`), or `None` if that asset has no
+    /// window limit configured at all (unbounded). Sums `Withdraw` history records for `asset_id`
+    /// across every subaccount within the window, mirroring `check_withdrawal_policy`'s own
+    /// accounting, so a limit added after some withdrawals already occurred is honored immediately.
+    pub fn remaining_withdrawal_limit(&self, asset_id: &str) -> Option<Amount> {
+        let policy = self.withdrawal_policies.get(asset_id)?;
+        let window_seconds = policy.window_seconds?;
+        let window_max = policy.window_max?;
+
+        let window_start = Self::get_timestamp().saturating_sub(window_seconds);
+        let already_withdrawn: Amount = self.history.iter()
+            .filter_map(|record| match &record.operation {
+                TransactionOperation::Withdraw { asset_id: a, amount, .. }
+                    if a == asset_id && record.timestamp >= window_start => Some(*amount),
+                _ => None,
             })
-            .collect();
-        
-        Ok(serde_json::json!({
-            "wallet_address": wallet_address,
-            "asset_id": asset_id,
-            "provenance_records": filtered_records
-        }))
+            .fold(Amount::zero(), |acc, amount| acc.saturating_add(amount));
+
+        Some(window_max.saturating_sub(already_withdrawn))
     }
 
-    /// Get provenance log filtered by subaccount
-    pub fn get_provenance_by_subaccount(&self, wallet_address: &str, subaccount_id: &str) -> Result<serde_json::Value> {
-        let wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        let filtered_records: Vec<&ProvenanceRecord> = wallet_state.history.iter()
-            .filter(|record| {
-                match &record.operation {
-                    TransactionOperation::Claim { subaccount_id: s, .. } => s == subaccount_id,
-                    TransactionOperation::Transfer { from_subaccount, to_subaccount, .. } => {
-                        from_subaccount == subaccount_id || to_subaccount == subaccount_id
-                    },
-                    TransactionOperation::Withdraw { subaccount_id: s, .. } => s == subaccount_id,
-                }
-            })
-            .collect();
-        
-        Ok(serde_json::json!({
-            "wallet_address": wallet_address,
-            "subaccount_id": subaccount_id,
-            "provenance_records": filtered_records
-        }))
+    /// Configure (or clear, via `None`) this wallet's fixed per-transaction fee policy. Applies to
+    /// `withdraw`/`internal_transfer` calls made after this point; already-recorded history is
+    /// unaffected.
+    pub fn set_fee_policy(&mut self, policy: Option<FeePolicy>) {
+        self.fee_policy = policy;
     }
 
-    /// Withdraw assets to external address - builds and signs transaction
-    pub fn withdraw_to_external(&self, 
-        wallet_address: &str, 
-        subaccount_id: &str, 
-        asset_id: &str, 
-        amount: u64, 
-        destination: &str,
-        gas_price: Option<u64>,
-        gas_limit: Option<u64>,
-        chain_id: u64
-    ) -> Result<(String, u64, u64, u64)> {
-        // Parse destination address first (no locks needed)
-        let to_address = parse_address(destination)?;
-        
-        // CRITICAL: Lock the entire withdrawal process to ensure atomicity and sequencing
-        let mut global_nonce = self.global_nonce.lock().unwrap();
-        
-        // Get and validate wallet state
-        let mut wallet_state = self.get_wallet(wallet_address)
-            .ok_or_else(|| anyhow!("Wallet not found"))?;
-        
-        // Check sufficient balance
-        let current_balance = wallet_state.get_balance(subaccount_id, asset_id);
-        if current_balance < amount {
-            return Err(anyhow!("Insufficient balance: {} available, {} requested", current_balance, amount));
+    /// The `(fee_asset_id, fee_amount)` to charge for an operation moving `asset_id`, per the
+    /// configured `fee_policy` (`withdraw_fee` or `transfer_fee`, selected by `is_withdrawal`).
+    /// Returns a zero fee in `asset_id` itself when no policy is configured, so callers can charge
+    /// the fee unconditionally rather than branching on whether a policy exists.
+    fn fee_for(&self, asset_id: &str, is_withdrawal: bool) -> (String, Amount) {
+        match &self.fee_policy {
+            None => (asset_id.to_string(), Amount::zero()),
+            Some(policy) => {
+                let fee = if is_withdrawal {
+                    policy.withdraw_fee
+                } else {
+                    policy.transfer_fee
+                };
+                let fee_asset_id = policy
+                    .fee_asset_id
+                    .clone()
+                    .unwrap_or_else(|| asset_id.to_string());
+                (fee_asset_id, fee)
+            }
         }
-        
-        // Get asset info
-        let asset = wallet_state.assets.get(asset_id)
-            .ok_or_else(|| anyhow!("Asset not found"))?;
-        
-        // Increment wallet nonce for this transaction
-        wallet_state.nonce += 1;
-        let wallet_nonce = wallet_state.nonce;
-        
-        // Get global nonce for transaction ordering
-        *global_nonce += 1;
-        let tx_nonce = *global_nonce;
-        drop(global_nonce);
-        
-        // Build transaction based on asset type
-        let (raw_transaction, actual_gas_price, actual_gas_limit) = match asset.token_type {
-            TokenType::ETH => {
-                let gas_price_final = gas_price.unwrap_or(20_000_000_000); // 20 gwei default
-                let gas_limit_final = gas_limit.unwrap_or(21_000); // standard ETH transfer
-                let tx = self.build_eth_transaction(
-                    to_address,
-                    amount,
-                    asset.decimals,
-                    wallet_nonce,
-                    gas_price_final,
-                    gas_limit_final,
-                    chain_id,
-                    wallet_address,
-                )?;
-                (tx, gas_price_final, gas_limit_final)
-            },
-            TokenType::ERC20 => {
-                let contract_address = parse_address(
-                    asset.contract_address
-                        .as_ref()
-                        .ok_or_else(|| anyhow!("ERC20 contract address not found"))?
-                )?;
-                
-                let gas_price_final = gas_price.unwrap_or(20_000_000_000); // 20 gwei default
-                let gas_limit_final = gas_limit.unwrap_or(60_000); // standard ERC20 transfer
-                let tx = self.build_erc20_transaction(
-                    contract_address,
-                    to_address.clone(),
-                    amount,
-                    wallet_nonce,
-                    gas_price_final,
-                    gas_limit_final,
-                    chain_id,
-                    wallet_address,
-                )?;
-                (tx, gas_price_final, gas_limit_final)
+    }
+
+    /// Configure (or clear, with `fee_bps == 0`) a proportional withdrawal fee for `asset_id`, in
+    /// basis points (1 bps = 0.01%). Charged on top of any fixed `fee_policy` fee, deducted into
+    /// `WITHDRAWAL_FEE_SUBACCOUNT_ID` - its own reserved fee-collector subaccount, kept separate
+    /// from `fee_policy`'s `FEE_SUBACCOUNT_ID` so the two fee streams never commingle - and
+    /// recorded as a distinct `FeeCollected` provenance entry so it's auditable separately from
+    /// the principal withdrawal. Caps at 10_000 bps (100%) since a fee can't exceed the amount
+    /// it's charged on.
+    pub fn set_withdrawal_fee(&mut self, asset_id: &str, fee_bps: u32) -> Result<()> {
+        if fee_bps > 10_000 {
+            return Err(anyhow!("fee_bps {} exceeds 10000 (100%)", fee_bps));
+        }
+        if fee_bps == 0 {
+            self.withdrawal_fee_bps.remove(asset_id);
+        } else {
+            self.withdrawal_fee_bps.insert(asset_id.to_string(), fee_bps);
+        }
+        Ok(())
+    }
+
+    /// Base-unit protocol fee owed on a withdrawal of `amount` of `asset_id`, per
+    /// `set_withdrawal_fee`, or zero if no fee is configured for that asset.
+    fn withdrawal_fee_for(&self, asset_id: &str, amount: Amount) -> Amount {
+        match self.withdrawal_fee_bps.get(asset_id) {
+            Some(&bps) if bps > 0 => amount
+                .checked_mul(Amount::from(bps))
+                .map(|scaled| scaled / Amount::from(10_000u32))
+                .unwrap_or(Amount::zero()),
+            _ => Amount::zero(),
+        }
+    }
+
+    /// Deduct the configured proportional withdrawal fee for `asset_id` from `subaccount_id` and
+    /// accrue it into `WITHDRAWAL_FEE_SUBACCOUNT_ID`, recording a `FeeCollected` provenance entry.
+    /// Assumes the caller has already verified `subaccount_id` can afford `amount + fee` (the
+    /// fixed-fee affordability check in `check_fee_affordable` runs before this, against the same
+    /// balance, so a caller must fold `withdrawal_fee_for` into its own affordability check
+    /// first).
+    fn charge_withdrawal_fee(&mut self, subaccount_id: &str, asset_id: &str, fee: Amount) {
+        if fee.is_zero() {
+            return;
+        }
+        let balance = self.get_balance(subaccount_id, asset_id);
+        self.set_balance(subaccount_id, asset_id, balance.saturating_sub(fee));
+        let collector_balance = self.get_balance(WITHDRAWAL_FEE_SUBACCOUNT_ID, asset_id);
+        self.set_balance(WITHDRAWAL_FEE_SUBACCOUNT_ID, asset_id, collector_balance.saturating_add(fee));
+
+        let fee_bps = self.withdrawal_fee_bps.get(asset_id).copied().unwrap_or(0);
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::FeeCollected {
+                asset_id: asset_id.to_string(),
+                amount: fee,
+                subaccount_id: subaccount_id.to_string(),
+                fee_bps,
             },
-            _ => {
-                return Err(anyhow!("Withdrawal not supported for asset type: {:?}", asset.token_type));
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+    }
+
+    /// Validate that `subaccount_id` can afford both `amount` of `asset_id` and `fee` of
+    /// `fee_asset_id` - the `amount + fee <= balance` rule described on `FeePolicy`. When the fee
+    /// is charged in the same asset being moved, this is one combined balance check against a
+    /// single balance rather than two independent ones.
+    fn check_fee_affordable(&self, subaccount_id: &str, asset_id: &str, amount: Amount, fee_asset_id: &str, fee: Amount) -> Result<()> {
+        if fee_asset_id == asset_id {
+            let required = amount.checked_add(fee).ok_or_else(|| anyhow!("Amount plus fee overflows"))?;
+            if !self.check_allow(subaccount_id, asset_id, required) {
+                return Err(WalletError::NotEnoughBalance {
+                    asset_id: asset_id.to_string(),
+                    required,
+                    available: self.get_balance(subaccount_id, asset_id),
+                }.into());
+            }
+        } else if !fee.is_zero() && !self.check_allow(subaccount_id, fee_asset_id, fee) {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: fee_asset_id.to_string(),
+                required: fee,
+                available: self.get_balance(subaccount_id, fee_asset_id),
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// Deduct `fee` of `fee_asset_id` from `subaccount_id` into the reserved fee subaccount, if
+    /// `fee` is nonzero. Assumes the caller has already checked `subaccount_id` can afford it.
+    fn charge_fee(&mut self, subaccount_id: &str, fee_asset_id: &str, fee: Amount) {
+        if fee.is_zero() {
+            return;
+        }
+        let payer_balance = self.get_balance(subaccount_id, fee_asset_id);
+        self.set_balance(subaccount_id, fee_asset_id, payer_balance.saturating_sub(fee));
+        let fee_balance = self.get_balance(FEE_SUBACCOUNT_ID, fee_asset_id);
+        self.set_balance(FEE_SUBACCOUNT_ID, fee_asset_id, fee_balance.saturating_add(fee));
+    }
+
+    /// Add or replace a labeled contact. The address is parsed eagerly so a bad address is
+    /// rejected here rather than surfacing later at withdrawal time.
+    pub fn add_contact(&mut self, label: &str, address: &str, allow_listed: bool) -> Result<()> {
+        parse_address(address)?;
+        self.contacts.insert(label.to_string(), Contact {
+            label: label.to_string(),
+            address: address.to_string(),
+            allow_listed,
+        });
+        Ok(())
+    }
+
+    /// Remove a contact by label
+    pub fn remove_contact(&mut self, label: &str) -> Result<()> {
+        self.contacts.remove(label).ok_or_else(|| anyhow!("Contact not found"))?;
+        Ok(())
+    }
+
+    /// List all contacts
+    pub fn list_contacts(&self) -> Vec<&Contact> {
+        self.contacts.values().collect()
+    }
+
+    /// Resolve a withdrawal `destination` argument, which may be either a raw `0x…` address or a
+    /// stored contact label. When `require_allow_listed_destination` is set, only allow-listed
+    /// contacts resolve successfully, giving an operational guardrail against withdrawing to an
+    /// arbitrary or fat-fingered address.
+    pub fn resolve_destination(&self, destination: &str) -> Result<String> {
+        if let Some(contact) = self.contacts.get(destination) {
+            if self.require_allow_listed_destination && !contact.allow_listed {
+                return Err(anyhow!("Contact '{}' is not allow-listed for withdrawals", destination));
             }
+            return Ok(contact.address.clone());
+        }
+
+        if self.require_allow_listed_destination {
+            return Err(anyhow!(
+                "Destination must be an allow-listed contact label, got raw address '{}'",
+                destination
+            ));
+        }
+
+        parse_address(destination)?;
+        Ok(destination.to_string())
+    }
+
+    /// Cheap lower-bound `gas_limit` for a withdrawal of `asset_id`, ignoring `amount`/
+    /// destination: the asset type's intrinsic cost plus `GAS_ESTIMATE_SAFETY_MARGIN_BPS`,
+    /// clamped to `GAS_ESTIMATE_CEILING`. Used by `PassWalletManager::withdraw_to_external`'s own
+    /// internal default and for the base-fee feedback loop, where a quick fixed-shape estimate is
+    /// enough. A caller that wants the tighter, amount/destination-aware figure before committing
+    /// to a withdrawal should call `PassWalletManager::estimate_withdrawal_gas` instead, which
+    /// binary-searches the real minimal `gas_limit` against a simulated withdrawal.
+    pub fn estimate_withdrawal_gas(&self, asset_id: &str) -> Result<u64> {
+        let asset = self.assets.get(asset_id)
+            .ok_or_else(|| anyhow!("Asset not found: {}", asset_id))?;
+
+        let intrinsic = match asset.token_type {
+            TokenType::ETH => INTRINSIC_GAS_ETH_TRANSFER,
+            TokenType::ERC20 => INTRINSIC_GAS_ERC20_TRANSFER,
+            _ => return Err(anyhow!("Gas estimation not supported for asset type: {:?}", asset.token_type)),
         };
-        
-        // Update wallet balance
-        wallet_state.set_balance(subaccount_id, asset_id, current_balance - amount);
-        
+
+        let with_margin = intrinsic
+            .checked_mul(10_000 + GAS_ESTIMATE_SAFETY_MARGIN_BPS)
+            .and_then(|scaled| scaled.checked_div(10_000))
+            .ok_or_else(|| anyhow!("Gas estimate overflow"))?;
+
+        if with_margin > GAS_ESTIMATE_CEILING {
+            return Err(anyhow!(
+                "Estimated gas {} for {} exceeds the {} ceiling - withdrawal would always fail",
+                with_margin, asset_id, GAS_ESTIMATE_CEILING
+            ));
+        }
+
+        Ok(with_margin)
+    }
+
+    /// This wallet's current EIP-1559-style base fee (wei per gas), last adjusted by
+    /// `update_base_fee`. `withdraw`'s dynamic-fee path requires `max_fee_per_gas` to at least
+    /// cover this before it will sign a transaction.
+    pub fn current_base_fee(&self) -> u64 {
+        self.base_fee_per_gas
+    }
+
+    /// Recompute `base_fee_per_gas` from `gas_used` against `gas_target`, using the same rule a
+    /// real EIP-1559 chain applies between blocks: `base_fee_next = base_fee * (1 + (1/8) *
+    /// (gas_used - gas_target) / gas_target)`, clamped so it moves at most
+    /// `BASE_FEE_MAX_ADJUSTMENT_BPS` (12.5%) in either direction per step and never below
+    /// `BASE_FEE_FLOOR`. Called by `withdraw_to_external` after each dynamic-fee withdrawal, with
+    /// the withdrawal's own `gas_limit` as `gas_used` and its `estimate_withdrawal_gas` baseline as
+    /// `gas_target`, so requesting more gas than the intrinsic baseline nudges the base fee up and
+    /// requesting less lets it drift down. Returns the new base fee.
+    pub fn update_base_fee(&mut self, gas_used: u64, gas_target: u64) -> u64 {
+        if gas_target == 0 {
+            return self.base_fee_per_gas;
+        }
+
+        let base_fee = self.base_fee_per_gas as i128;
+        let gas_used = gas_used as i128;
+        let gas_target = gas_target as i128;
+
+        let delta = base_fee * (gas_used - gas_target) / (8 * gas_target);
+        let max_move = base_fee * BASE_FEE_MAX_ADJUSTMENT_BPS as i128 / 10_000;
+        let clamped_delta = delta.clamp(-max_move, max_move);
+
+        let next = (base_fee + clamped_delta).max(BASE_FEE_FLOOR as i128);
+        self.base_fee_per_gas = next as u64;
+        self.base_fee_per_gas
+    }
+
+    /// Withdraw to external destination. `outbox_memo`, if given, is already encrypted (see
+    /// `PassWalletManager::encrypt_outbox_memo`) and is attached to the queued `OutboxEntry`
+    /// verbatim; `memo` stays plaintext and only goes into the provenance record. Returns the
+    /// resolved destination and the outbox nonce this withdrawal was queued under, so the caller
+    /// can mint a `PaymentProof` for it.
+    pub fn withdraw(&mut self, asset_id: &str, amount: Amount, subaccount_id: &str, external_destination: &str, memo: Option<String>, outbox_memo: Option<Vec<u8>>) -> Result<(ExternalDestination, u64)> {
+        validate_memo(&memo)?;
+        let external_destination = self.resolve_destination(external_destination)?;
+        let external_destination = external_destination.as_str();
+
+        let withdrawal_fee = self.withdrawal_fee_for(asset_id, amount);
+        let amount_plus_withdrawal_fee = amount.checked_add(withdrawal_fee)
+            .ok_or_else(|| anyhow!("Amount plus withdrawal fee overflows"))?;
+
+        let (fee_asset_id, fee) = self.fee_for(asset_id, true);
+        self.check_fee_affordable(subaccount_id, asset_id, amount_plus_withdrawal_fee, &fee_asset_id, fee)?;
+
+        // Check if subaccount has sufficient balance
+        if !self.check_allow(subaccount_id, asset_id, amount_plus_withdrawal_fee) {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: asset_id.to_string(),
+                required: amount_plus_withdrawal_fee,
+                available: self.get_balance(subaccount_id, asset_id),
+            }.into());
+        }
+
+        let limit_applied = self.check_withdrawal_policy(asset_id, subaccount_id, amount, false)?;
+
+        // Update balance
+        let current_balance = self.get_balance(subaccount_id, asset_id);
+        let new_balance = current_balance.checked_sub(amount)
+            .ok_or_else(|| WalletError::NotEnoughBalance {
+                asset_id: asset_id.to_string(),
+                required: amount,
+                available: current_balance,
+            })?;
+        self.set_balance(subaccount_id, asset_id, new_balance);
+        self.charge_fee(subaccount_id, &fee_asset_id, fee);
+        self.charge_withdrawal_fee(subaccount_id, asset_id, withdrawal_fee);
+
+        // Add to outbox, under a dedicated id unique to this queued entry - see `outbox_sequence`.
+        let outbox_id = self.outbox_sequence;
+        self.outbox_sequence += 1;
+        self.outbox.push_back(OutboxEntry {
+            asset_id: asset_id.to_string(),
+            amount,
+            external_destination: external_destination.to_string(),
+            nonce: outbox_id,
+            memo: outbox_memo,
+            token_id: None,
+        });
+
         // Add to provenance history
-        wallet_state.history.push(ProvenanceRecord {
+        self.append_history(ProvenanceRecord {
             operation: TransactionOperation::Withdraw {
                 asset_id: asset_id.to_string(),
                 amount,
                 subaccount_id: subaccount_id.to_string(),
-                destination: destination.to_string(),
+                destination: external_destination.to_string(),
+                memo,
+                token_id: None,
             },
-            timestamp: PassWalletState::get_timestamp(),
-            block_number: None, // Will be filled when transaction is mined
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            // This record's `OutboxEntry` was just queued under `outbox_id` - stash it so
+            // `process_outbox_signed` can find its way back to this record once the entry is
+            // drained and signed, the same way `tx_nonce` lets `record_mined` find its way back
+            // to a `withdraw_to_external` record.
+            outbox_nonce: Some(outbox_id),
+            signed_raw_tx: None,
         });
-        
-        // Save updated wallet state
-        self.update_wallet(wallet_address, wallet_state)?;
-        
-        // Create pending withdrawal record
-        let pending_withdrawal = PendingWithdrawal {
-            wallet_address: wallet_address.to_string(),
-            subaccount_id: subaccount_id.to_string(),
+
+        Ok((external_destination.to_string(), outbox_id))
+    }
+
+    /// NFT-aware counterpart to `withdraw`: removes ownership of a specific ERC-721 `token_id`
+    /// from `subaccount_id` (rejecting if it isn't currently owned there) and queues an
+    /// `OutboxEntry` carrying `token_id` instead of a fungible `amount`. Returns the resolved
+    /// destination and the outbox nonce this withdrawal was queued under, same as `withdraw`.
+    pub fn withdraw_nft(&mut self, asset_id: &str, token_id: &str, subaccount_id: &str, external_destination: &str, memo: Option<String>) -> Result<(ExternalDestination, u64)> {
+        validate_memo(&memo)?;
+        let external_destination = self.resolve_destination(external_destination)?;
+        let external_destination = external_destination.as_str();
+
+        let key = Self::nft_key(asset_id, token_id);
+        match self.nft_ownership.get(&key) {
+            Some(owner) if owner == subaccount_id => {}
+            Some(_) => return Err(anyhow!(
+                "Subaccount {} does not own token {} of asset {}", subaccount_id, token_id, asset_id
+            )),
+            None => return Err(anyhow!("Token {} of asset {} is not owned by any subaccount", token_id, asset_id)),
+        }
+        self.nft_ownership.remove(&key);
+
+        // Add to outbox, under a dedicated id unique to this queued entry - see `outbox_sequence`.
+        let outbox_id = self.outbox_sequence;
+        self.outbox_sequence += 1;
+        self.outbox.push_back(OutboxEntry {
+            asset_id: asset_id.to_string(),
+            amount: Amount::from(1u64),
+            external_destination: external_destination.to_string(),
+            nonce: outbox_id,
+            memo: None,
+            token_id: Some(token_id.to_string()),
+        });
+
+        // Add to provenance history
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::Withdraw {
+                asset_id: asset_id.to_string(),
+                amount: Amount::from(1u64),
+                subaccount_id: subaccount_id.to_string(),
+                destination: external_destination.to_string(),
+                memo,
+                token_id: Some(token_id.to_string()),
+            },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: Some(outbox_id),
+            signed_raw_tx: None,
+        });
+
+        Ok((external_destination.to_string(), outbox_id))
+    }
+
+    /// Pre-flight sanity pass over every item currently queued in `outbox`, run automatically at
+    /// the start of `process_outbox` so a malformed entry never reaches a signed on-chain
+    /// transaction. Checks a nonzero amount, destination address well-formedness, that `asset_id`
+    /// is still known to the wallet, and that no two queued entries share a `nonce` - each entry's
+    /// `nonce` is assigned uniquely from `outbox_sequence` at queue time, so a collision here means
+    /// corrupted state rather than two ordinary withdrawals queued back-to-back. Does *not*
+    /// re-check the originating subaccount's balance: `withdraw`/`withdraw_nft` already debit that
+    /// balance atomically before an entry is ever queued, so by the time an item sits in the
+    /// outbox there is no longer a subaccount balance left to compare it against - the debit
+    /// already happened. Returns every failing item without mutating `outbox`.
+    pub fn validate_outbox(&self) -> Vec<OutboxValidationError> {
+        let mut errors = Vec::new();
+        let mut seen_nonces = HashSet::new();
+
+        for entry in &self.outbox {
+            if entry.amount.is_zero() {
+                errors.push(OutboxValidationError { nonce: entry.nonce, reason: "amount is zero".to_string() });
+                continue;
+            }
+            if parse_address(&entry.external_destination).is_err() {
+                errors.push(OutboxValidationError {
+                    nonce: entry.nonce,
+                    reason: format!("invalid destination address: {}", entry.external_destination),
+                });
+                continue;
+            }
+            if !self.assets.contains_key(&entry.asset_id) {
+                errors.push(OutboxValidationError {
+                    nonce: entry.nonce,
+                    reason: format!("asset {} is no longer known to this wallet", entry.asset_id),
+                });
+                continue;
+            }
+            if !seen_nonces.insert(entry.nonce) {
+                errors.push(OutboxValidationError { nonce: entry.nonce, reason: "duplicate nonce already queued".to_string() });
+            }
+        }
+
+        errors
+    }
+
+    /// Process outbox (periodic or on-demand). Runs `validate_outbox` first and skips only the
+    /// items it flags, so one malformed entry doesn't block every other pending withdrawal from
+    /// settling; skipped entries stay queued for a future `process_outbox` call once corrected.
+    pub fn process_outbox(&mut self) -> Result<Vec<OutboxEntry>> {
+        let invalid: HashSet<u64> = self.validate_outbox().into_iter().map(|e| e.nonce).collect();
+
+        let mut processed = Vec::new();
+        let mut skipped = VecDeque::new();
+
+        while let Some(entry) = self.outbox.pop_front() {
+            if invalid.contains(&entry.nonce) {
+                skipped.push_back(entry);
+                continue;
+            }
+            processed.push(entry);
+            self.nonce += 1;
+        }
+        self.outbox = skipped;
+
+        Ok(processed)
+    }
+
+    /// Move `amount` of `asset_id` out of `from_subaccount` into escrow for `to_subaccount`,
+    /// releasing only once `release_after` elapses (`release_escrow`) or `required_signatures`
+    /// distinct `witnesses` approve (`witness_approve`), whichever happens first. At least one of
+    /// the two release conditions must actually be reachable. Returns the generated `escrow_id`.
+    pub fn create_conditional_transfer(
+        &mut self,
+        asset_id: &str,
+        amount: Amount,
+        from_subaccount: &str,
+        to_subaccount: &str,
+        release_after: Option<u64>,
+        witnesses: Vec<String>,
+        required_signatures: u32,
+        cancelable_by: Option<String>,
+    ) -> Result<String> {
+        if release_after.is_none() && required_signatures == 0 {
+            return Err(anyhow!("Conditional transfer needs a release_after timestamp, required_signatures > 0, or both"));
+        }
+        if required_signatures as usize > witnesses.len() {
+            return Err(anyhow!(
+                "required_signatures ({}) exceeds the number of witnesses ({})",
+                required_signatures, witnesses.len()
+            ));
+        }
+        if !self.check_allow(from_subaccount, asset_id, amount) {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: asset_id.to_string(),
+                required: amount,
+                available: self.get_balance(from_subaccount, asset_id),
+            }.into());
+        }
+
+        let current_balance = self.get_balance(from_subaccount, asset_id);
+        let new_balance = current_balance.checked_sub(amount)
+            .ok_or_else(|| WalletError::NotEnoughBalance {
+                asset_id: asset_id.to_string(),
+                required: amount,
+                available: current_balance,
+            })?;
+        self.set_balance(from_subaccount, asset_id, new_balance);
+
+        let escrow_id = format!("escrow-{}", self.escrow_nonce);
+        self.escrow_nonce += 1;
+
+        self.escrows.insert(escrow_id.clone(), Escrow {
+            escrow_id: escrow_id.clone(),
             asset_id: asset_id.to_string(),
             amount,
-            destination: destination.to_string(),
-            nonce: tx_nonce,
-            signed_raw_transaction: raw_transaction.clone(),
-            created_at: PassWalletState::get_timestamp(),
+            from_subaccount: from_subaccount.to_string(),
+            to_subaccount: to_subaccount.to_string(),
+            release_after,
+            witnesses,
+            required_signatures,
+            approvals: HashSet::new(),
+            cancelable_by,
+            created_at: Self::get_timestamp(),
+        });
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::EscrowCreated {
+                escrow_id: escrow_id.clone(),
+                asset_id: asset_id.to_string(),
+                amount,
+                from_subaccount: from_subaccount.to_string(),
+                to_subaccount: to_subaccount.to_string(),
+            },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        Ok(escrow_id)
+    }
+
+    /// Release an escrow into its destination subaccount once `release_after` has passed. Fails
+    /// if the escrow doesn't exist, has no `release_after` condition, or that time hasn't
+    /// arrived yet.
+    pub fn release_escrow(&mut self, escrow_id: &str) -> Result<()> {
+        let release_after = self.escrows.get(escrow_id)
+            .ok_or_else(|| anyhow!("Escrow not found"))?
+            .release_after
+            .ok_or_else(|| anyhow!("Escrow {} has no release_after condition", escrow_id))?;
+
+        if Self::get_timestamp() < release_after {
+            return Err(anyhow!(
+                "Escrow {} is not yet releasable: release_after {} has not elapsed",
+                escrow_id, release_after
+            ));
+        }
+
+        self.finalize_escrow_release(escrow_id)
+    }
+
+    /// Record `witness`'s approval of an escrow. Once `required_signatures` distinct witnesses
+    /// have approved, the escrow releases immediately. Returns whether this call triggered the
+    /// release.
+    pub fn witness_approve(&mut self, escrow_id: &str, witness: &str) -> Result<bool> {
+        let escrow = self.escrows.get_mut(escrow_id)
+            .ok_or_else(|| anyhow!("Escrow not found"))?;
+
+        if !escrow.witnesses.iter().any(|w| w == witness) {
+            return Err(anyhow!("{} is not a registered witness for escrow {}", witness, escrow_id));
+        }
+        escrow.approvals.insert(witness.to_string());
+
+        if escrow.approvals.len() as u32 >= escrow.required_signatures {
+            self.finalize_escrow_release(escrow_id)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Move an escrow's funds into its destination subaccount and drop it from `escrows`,
+    /// recording provenance. Shared by `release_escrow` and `witness_approve` once their
+    /// respective release condition is met.
+    fn finalize_escrow_release(&mut self, escrow_id: &str) -> Result<()> {
+        let escrow = self.escrows.remove(escrow_id)
+            .ok_or_else(|| anyhow!("Escrow not found"))?;
+
+        self.credit_balance(&escrow.to_subaccount, &escrow.asset_id, escrow.amount)?;
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::EscrowReleased {
+                escrow_id: escrow.escrow_id,
+                asset_id: escrow.asset_id,
+                amount: escrow.amount,
+                to_subaccount: escrow.to_subaccount,
+            },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel an escrow and return its funds to the source subaccount. Only the address named in
+    /// `cancelable_by` may do this; an escrow created without one can never be cancelled.
+    pub fn cancel_conditional_transfer(&mut self, escrow_id: &str, requester: &str) -> Result<()> {
+        match self.escrows.get(escrow_id).ok_or_else(|| anyhow!("Escrow not found"))?.cancelable_by.as_deref() {
+            Some(cancelable_by) if cancelable_by == requester => {}
+            Some(_) => return Err(anyhow!("{} is not authorized to cancel escrow {}", requester, escrow_id)),
+            None => return Err(anyhow!("Escrow {} is not cancelable", escrow_id)),
+        }
+
+        let escrow = self.escrows.remove(escrow_id).ok_or_else(|| anyhow!("Escrow not found"))?;
+
+        self.credit_balance(&escrow.from_subaccount, &escrow.asset_id, escrow.amount)?;
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::EscrowCancelled {
+                escrow_id: escrow.escrow_id,
+                asset_id: escrow.asset_id,
+                amount: escrow.amount,
+                from_subaccount: escrow.from_subaccount,
+            },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        Ok(())
+    }
+
+    /// Designate `contact` as an emergency-recovery contact, able to call `initiate_recovery` if
+    /// the owner ever loses access. Also (re)sets the wallet-wide waiting period and quorum used
+    /// by the next recovery either this or any other registered contact initiates.
+    pub fn add_recovery_contact(&mut self, contact: &str, waiting_period_secs: u64, required_approvals: u32) -> Result<()> {
+        if required_approvals == 0 {
+            return Err(anyhow!("required_approvals must be at least 1"));
+        }
+        self.recovery_contacts.insert(contact.to_string());
+        self.recovery_waiting_period_secs = waiting_period_secs;
+        self.recovery_required_approvals = required_approvals;
+        Ok(())
+    }
+
+    /// Start an emergency recovery. Only a registered recovery contact may call this, and only
+    /// one recovery may be pending at a time. Resolves via `approve_recovery` reaching quorum, or
+    /// via `process_recovery_timeout` once `recovery_waiting_period_secs` has elapsed, unless the
+    /// owner cancels it first with `cancel_recovery`.
+    pub fn initiate_recovery(&mut self, contact: &str) -> Result<()> {
+        if !self.recovery_contacts.contains(contact) {
+            return Err(anyhow!("{} is not a registered recovery contact", contact));
+        }
+        if self.pending_recovery.is_some() {
+            return Err(anyhow!("A recovery is already pending for this wallet"));
+        }
+
+        let now = Self::get_timestamp();
+        self.pending_recovery = Some(PendingRecovery {
+            initiated_by: contact.to_string(),
+            initiated_at: now,
+            waiting_period_secs: self.recovery_waiting_period_secs,
+            required_approvals: self.recovery_required_approvals,
+            approvals: HashSet::new(),
+        });
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::RecoveryInitiated {
+                initiated_by: contact.to_string(),
+                waiting_period_secs: self.recovery_waiting_period_secs,
+            },
+            timestamp: now,
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a pending recovery. Only the current owner may do this - the whole point of the
+    /// waiting period is to give a still-in-control owner a chance to stop an unauthorized claim.
+    pub fn cancel_recovery(&mut self, requester: &str) -> Result<()> {
+        if requester != self.owner {
+            return Err(anyhow!("Only the current owner can cancel a pending recovery"));
+        }
+        if self.pending_recovery.take().is_none() {
+            return Err(anyhow!("No recovery is pending for this wallet"));
+        }
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::RecoveryCancelled { cancelled_by: requester.to_string() },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        Ok(())
+    }
+
+    /// Record `contact`'s approval of the pending recovery. Once `required_approvals` distinct
+    /// contacts have approved, ownership transfers immediately. Returns whether this call
+    /// triggered that transfer.
+    pub fn approve_recovery(&mut self, contact: &str) -> Result<bool> {
+        if !self.recovery_contacts.contains(contact) {
+            return Err(anyhow!("{} is not a registered recovery contact", contact));
+        }
+
+        let quorum_reached = {
+            let pending = self.pending_recovery.as_mut()
+                .ok_or_else(|| anyhow!("No recovery is pending for this wallet"))?;
+            pending.approvals.insert(contact.to_string());
+            pending.approvals.len() as u32 >= pending.required_approvals
+        };
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::RecoveryApproved { approved_by: contact.to_string() },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        if quorum_reached {
+            self.finalize_recovery()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Finalize a pending recovery once `process_recovery_timeout` has observed its waiting
+    /// period has elapsed without an owner cancellation.
+    pub fn process_recovery_timeout(&mut self) -> Result<()> {
+        let due = self.pending_recovery.as_ref()
+            .ok_or_else(|| anyhow!("No recovery is pending for this wallet"))?
+            .is_due();
+        if !due {
+            return Err(anyhow!("The pending recovery's waiting period has not elapsed yet"));
+        }
+        self.finalize_recovery()
+    }
+
+    /// Reassign wallet ownership to the contact who initiated the pending recovery and clear it.
+    /// Shared by `approve_recovery` (quorum reached) and `process_recovery_timeout` (time elapsed).
+    fn finalize_recovery(&mut self) -> Result<()> {
+        let pending = self.pending_recovery.take()
+            .ok_or_else(|| anyhow!("No recovery is pending for this wallet"))?;
+
+        self.owner = pending.initiated_by.clone();
+
+        self.append_history(ProvenanceRecord {
+            operation: TransactionOperation::RecoveryCompleted { new_owner: pending.initiated_by },
+            timestamp: Self::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        Ok(())
+    }
+
+    /// Get all balances for a subaccount
+    pub fn get_subaccount_balances(&self, subaccount_id: &str) -> HashMap<String, Amount> {
+        self.balances.iter()
+            .filter_map(|(key, amount)| {
+                if let Some((sub_id, asset_id)) = key.split_once(':') {
+                    if sub_id == subaccount_id {
+                        Some((asset_id.to_string(), *amount))
+                    } else {
+                        None
+                    }
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn rate_key(asset_id: &str, reference_asset: &str) -> String {
+        format!("{}:{}", asset_id, reference_asset)
+    }
+
+    /// Set (or replace) the exchange rate used to value `asset_id` in terms of `reference_asset`.
+    pub fn set_asset_rate(&mut self, asset_id: &str, reference_asset: &str, rate_numerator: u128, rate_denominator: u128) -> Result<()> {
+        if rate_denominator == 0 {
+            return Err(anyhow!("Rate denominator cannot be zero"));
+        }
+        self.rates.insert(Self::rate_key(asset_id, reference_asset), Rate { rate_numerator, rate_denominator });
+        Ok(())
+    }
+
+    /// The exchange rate from `asset_id` into `reference_asset`, or the trivial `1/1` rate if
+    /// they're the same asset.
+    fn get_asset_rate(&self, asset_id: &str, reference_asset: &str) -> Option<Rate> {
+        if asset_id == reference_asset {
+            return Some(Rate { rate_numerator: 1, rate_denominator: 1 });
+        }
+        self.rates.get(&Self::rate_key(asset_id, reference_asset)).copied()
+    }
+
+    /// Convert `from_amount` (raw units of `from_asset`) into the equivalent raw units of
+    /// `to_asset` at `rate`, honoring both assets' `decimals`. Thin wrapper over `Rate::apply` -
+    /// the same checked-arithmetic path `get_subaccount_balance_values` uses against a *stored*
+    /// rate - for a caller quoting an explicit, one-off rate instead (see
+    /// `internal_transfer_with_rate`).
+    pub fn quote(&self, from_asset: &str, to_asset: &str, from_amount: Amount, rate: Rate) -> Result<u128> {
+        let from_decimals = self.assets.get(from_asset)
+            .ok_or_else(|| WalletError::UnknownAsset { asset_id: from_asset.to_string() })?
+            .decimals;
+        let to_decimals = self.assets.get(to_asset)
+            .ok_or_else(|| WalletError::UnknownAsset { asset_id: to_asset.to_string() })?
+            .decimals;
+        rate.apply(from_amount, from_decimals, to_decimals)
+    }
+
+    /// Quote every non-zero balance in `subaccount_id` in terms of `reference_asset`, honoring
+    /// each asset's `decimals`. Returns an error - rather than silently omitting that asset -
+    /// if any balance lacks a stored rate or the quoting math would overflow u128.
+    fn get_subaccount_balance_values(&self, subaccount_id: &str, reference_asset: &str) -> Result<Vec<(String, Amount, u128)>> {
+        let reference_decimals = self
+            .assets
+            .get(reference_asset)
+            .ok_or_else(|| anyhow!("Unknown reference asset: {}", reference_asset))?
+            .decimals;
+
+        let mut valued = Vec::new();
+        for (asset_id, balance) in self.get_subaccount_balances(subaccount_id) {
+            if balance.is_zero() {
+                continue;
+            }
+
+            let asset_decimals = self
+                .assets
+                .get(&asset_id)
+                .ok_or_else(|| anyhow!("Unknown asset: {}", asset_id))?
+                .decimals;
+            let rate = self
+                .get_asset_rate(&asset_id, reference_asset)
+                .ok_or_else(|| anyhow!("No rate set for {} in terms of {}", asset_id, reference_asset))?;
+
+            let value = rate.apply(balance, asset_decimals, reference_decimals)?;
+            valued.push((asset_id, balance, value));
+        }
+
+        Ok(valued)
+    }
+
+    /// Get wallet state summary
+    pub fn get_state_summary(&self) -> serde_json::Value {
+        serde_json::json!({
+            "address": self.address,
+            "name": self.name,
+            "owner": self.owner,
+            "nonce": self.nonce,
+            "inbox_count": self.inbox.len(),
+            "outbox_count": self.outbox.len(),
+            "assets_count": self.assets.len(),
+            "subaccounts_count": self.subaccounts.len(),
+            "history_count": self.history.len(),
+            "created_at": self.created_at,
+            "pending_escrows": self.escrows.values().collect::<Vec<_>>(),
+            "recovery_contacts": self.recovery_contacts.iter().collect::<Vec<_>>(),
+            "pending_recovery": self.pending_recovery,
+            // Counts only - the memos themselves are sealed per-subaccount and only decryptable
+            // via `PassWalletManager::get_memos`, not readable from the summary.
+            "memo_counts": self.user_memos.iter().map(|(k, v)| (k.clone(), v.len())).collect::<HashMap<_, _>>(),
+        })
+    }
+
+    /// Helper function to get current timestamp
+    fn get_timestamp() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+}
+
+/// Gas pricing mode for an outgoing transaction: a legacy single `gas_price`, or an EIP-1559
+/// `max_fee_per_gas` / `max_priority_fee_per_gas` pair. The withdrawal path selects `Dynamic`
+/// whenever the caller supplies both fee fields, falling back to `Legacy` otherwise.
+#[derive(Debug, Clone, Copy)]
+pub enum FeeParams {
+    Legacy { gas_price: u64 },
+    Dynamic { max_fee_per_gas: u64, max_priority_fee_per_gas: u64 },
+}
+
+impl FeeParams {
+    /// Flatten into the `(gas_price, gas_limit, max_fee_per_gas, max_priority_fee_per_gas)`
+    /// tuple reported back to callers, with unused legs left as `None`.
+    fn with_gas_limit(self, gas_limit: u64) -> (u64, u64, Option<u64>, Option<u64>) {
+        match self {
+            FeeParams::Legacy { gas_price } => (gas_price, gas_limit, None, None),
+            FeeParams::Dynamic { max_fee_per_gas, max_priority_fee_per_gas } => {
+                (max_fee_per_gas, gas_limit, Some(max_fee_per_gas), Some(max_priority_fee_per_gas))
+            }
+        }
+    }
+}
+
+/// Pending withdrawal transaction with signed data. Persisted (and read back) via `outbox_codec`'s
+/// versioned protobuf encoding rather than a direct `serde_json` dump, so a field added here in a
+/// later release still decodes an entry a previous release queued, and vice versa.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingWithdrawal {
+    pub wallet_address: WalletAddress,
+    pub subaccount_id: String,
+    pub asset_id: String,
+    pub amount: Amount,
+    pub destination: String,
+    pub nonce: u64,
+    pub signed_raw_transaction: String,
+    pub created_at: u64,
+    /// Which EIP-2718 envelope `signed_raw_transaction` uses, so a submitter knows how to broadcast it
+    #[serde(default)]
+    pub tx_type: TransactionEnvelopeType,
+    /// Gas price (legacy) or max fee per gas (dynamic fee) this transaction was signed with, used to
+    /// decide whether a resubmission at the same nonce qualifies as a fee-bump replacement
+    #[serde(default)]
+    pub effective_gas_price: u64,
+    /// Lifecycle stage, advanced by `mark_broadcast` and `record_mined` as the withdrawal moves
+    /// through the outbox
+    #[serde(default)]
+    pub status: WithdrawalStatus,
+    /// Number of times this `(wallet_address, nonce)` slot has been replaced by a fee-bumped
+    /// resubmission, bumped each time `enqueue_pending_withdrawal` accepts a replacement
+    #[serde(default)]
+    pub retry_count: u32,
+    /// Reserved for a caller-supplied note on the withdrawal; not populated by this crate today,
+    /// carried through so it round-trips once something starts setting it
+    #[serde(default)]
+    pub memo: Option<Vec<u8>>,
+}
+
+/// One request in a `PassWalletManager::batch_withdraw` call - the same parameters
+/// `withdraw_to_external` takes for a single withdrawal, minus the ones shared across the whole
+/// batch (`wallet_address`, `chain_id`). `gas_limit` stays per-request since different
+/// assets/destinations can need different amounts of gas.
+#[derive(Debug, Clone)]
+pub struct WithdrawRequest {
+    pub asset_id: String,
+    pub amount: Amount,
+    pub subaccount_id: String,
+    pub destination: String,
+    pub gas_limit: Option<u64>,
+}
+
+/// Successful outcome of one `WithdrawRequest` within a `batch_withdraw` call.
+#[derive(Debug, Clone)]
+pub struct WithdrawReceipt {
+    pub raw_transaction: String,
+    pub tx_nonce: u64,
+    pub gas_price: u64,
+    pub gas_limit: u64,
+}
+
+/// EIP-2718 transaction envelope type emitted for a withdrawal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum TransactionEnvelopeType {
+    #[default]
+    Legacy,
+    AccessList,
+    DynamicFee,
+}
+
+/// Lifecycle stage of a `PendingWithdrawal` in the outbox queue
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum WithdrawalStatus {
+    #[default]
+    Queued,
+    Broadcast,
+    Confirmed,
+}
+
+/// Tracks a withdrawal that's been observed mined, until it accumulates `CONFIRMATIONS_REQUIRED`
+/// confirmations. Kept even after the withdrawal is pruned from `outbox_queue` (`finalized` flips
+/// to `true`) so a later reorg can re-queue it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MinedTransaction {
+    withdrawal: PendingWithdrawal,
+    block_number: u64,
+    /// Set once `CONFIRMATIONS_REQUIRED` confirmations accumulated and the withdrawal was removed
+    /// from `outbox_queue`
+    finalized: bool,
+}
+
+impl FeeParams {
+    /// The envelope type this fee mode produces, given whether an access list was supplied
+    fn envelope_type(&self, has_access_list: bool) -> TransactionEnvelopeType {
+        match (self, has_access_list) {
+            (FeeParams::Dynamic { .. }, _) => TransactionEnvelopeType::DynamicFee,
+            (FeeParams::Legacy { .. }, true) => TransactionEnvelopeType::AccessList,
+            (FeeParams::Legacy { .. }, false) => TransactionEnvelopeType::Legacy,
+        }
+    }
+}
+
+/// Key-value persistence backend for `PassWalletManager`, abstracting over where wallet state,
+/// the global nonce counter, and the outbox queue actually live. Swapping implementations lets
+/// the same manager logic run against an in-memory map (tests), an encrypted enclave-sealed
+/// store (production), or a host-provided KV store — without changing any business logic.
+pub trait Storage: Send + Sync {
+    /// Read the raw bytes stored at `key`, or `None` if absent.
+    fn read(&self, key: &str) -> Option<Vec<u8>>;
+    /// Write `bytes` to `key`, overwriting any previous value.
+    fn write(&self, key: &str, bytes: Vec<u8>);
+    /// Delete `key`, if present.
+    fn remove(&self, key: &str);
+    /// All `(key, bytes)` pairs whose key starts with `prefix`, in key order.
+    fn scan_prefix(&self, prefix: &str) -> Vec<(String, Vec<u8>)>;
+}
+
+/// Plain in-memory `Storage` backend. Used by tests and as `PassWalletManager`'s default; state
+/// does not survive process restart.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    data: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn read(&self, key: &str) -> Option<Vec<u8>> {
+        self.data.lock().unwrap().get(key).cloned()
+    }
+
+    fn write(&self, key: &str, bytes: Vec<u8>) {
+        self.data.lock().unwrap().insert(key.to_string(), bytes);
+    }
+
+    fn remove(&self, key: &str) {
+        self.data.lock().unwrap().remove(key);
+    }
+
+    fn scan_prefix(&self, prefix: &str) -> Vec<(String, Vec<u8>)> {
+        self.data
+            .lock()
+            .unwrap()
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, bytes)| (key.clone(), bytes.clone()))
+            .collect()
+    }
+}
+
+lazy_static::lazy_static! {
+    /// Canonicalized data-directory paths currently backing an open `FileStorage`, process-wide.
+    /// Mirrors how Bitcoin Core's wallet loader tracks a global set of opened file IDs so the same
+    /// underlying wallet can't be loaded twice under two different-looking paths (`./w1` and
+    /// `/home/u/w1` resolving to the same directory, say) and corrupt each other with racing writes.
+    static ref OPEN_DATA_DIRS: Mutex<HashSet<std::path::PathBuf>> = Mutex::new(HashSet::new());
+}
+
+/// File-backed `Storage` implementation: one file per key under a data directory, with keys'
+/// `/`-separated segments becoming nested subdirectories so `wallet/0xabc` and `outbox/0xabc/7`
+/// don't collide. Registers its canonicalized directory in `OPEN_DATA_DIRS` for the lifetime of
+/// the handle and unregisters it on drop, so two `FileStorage`s can never point at the same
+/// on-disk wallet data at once - see `open`.
+pub struct FileStorage {
+    root: std::path::PathBuf,
+}
+
+impl FileStorage {
+    /// Open `data_dir` as a wallet data directory, creating it if absent. Fails if `data_dir`
+    /// canonicalizes to a directory another live `FileStorage` in this process already has open,
+    /// even if the two paths are spelled differently (a relative path vs. its absolute form, or a
+    /// path through a symlink).
+    pub fn open(data_dir: impl AsRef<std::path::Path>) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir).map_err(|e| {
+            anyhow!("Failed to create wallet data directory {:?}: {}", data_dir.as_ref(), e)
+        })?;
+        let canonical = std::fs::canonicalize(&data_dir).map_err(|e| {
+            anyhow!("Failed to canonicalize wallet data directory {:?}: {}", data_dir.as_ref(), e)
+        })?;
+
+        let mut open_dirs = OPEN_DATA_DIRS.lock().unwrap();
+        if !open_dirs.insert(canonical.clone()) {
+            return Err(anyhow!(
+                "Wallet data directory {:?} is already open in this process - refusing to load the same wallet twice",
+                canonical
+            ));
+        }
+
+        Ok(FileStorage { root: canonical })
+    }
+
+    fn path_for_key(&self, key: &str) -> std::path::PathBuf {
+        let mut path = self.root.clone();
+        for segment in key.split('/') {
+            path.push(segment);
+        }
+        path
+    }
+}
+
+impl Drop for FileStorage {
+    fn drop(&mut self) {
+        OPEN_DATA_DIRS.lock().unwrap().remove(&self.root);
+    }
+}
+
+impl Storage for FileStorage {
+    fn read(&self, key: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.path_for_key(key)).ok()
+    }
+
+    fn write(&self, key: &str, bytes: Vec<u8>) {
+        let path = self.path_for_key(key);
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = std::fs::write(path, bytes);
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = std::fs::remove_file(self.path_for_key(key));
+    }
+
+    fn scan_prefix(&self, prefix: &str) -> Vec<(String, Vec<u8>)> {
+        let mut out = Vec::new();
+        let prefix_dir = self.path_for_key(prefix.trim_end_matches('/'));
+        let walk_root = if prefix.ends_with('/') || prefix_dir.is_dir() {
+            prefix_dir
+        } else {
+            match prefix_dir.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => return out,
+            }
+        };
+        Self::walk(&self.root, &walk_root, prefix, &mut out);
+        out
+    }
+}
+
+impl FileStorage {
+    fn walk(root: &std::path::Path, dir: &std::path::Path, prefix: &str, out: &mut Vec<(String, Vec<u8>)>) {
+        let Ok(entries) = std::fs::read_dir(dir) else { return };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                Self::walk(root, &path, prefix, out);
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(root) else { continue };
+            let key = relative.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/");
+            if key.starts_with(prefix) {
+                if let Ok(bytes) = std::fs::read(&path) {
+                    out.push((key, bytes));
+                }
+            }
+        }
+    }
+}
+
+/// PASS Wallet Manager - manages multiple PASS wallets
+pub struct PassWalletManager<S: Storage = InMemoryStorage> {
+    kms: Arc<Mutex<EnclaveKMS>>,
+    /// Wallet state, the global nonce counter, and the outbox queue all live here, keyed as
+    /// `wallet/<addr>`, `global_nonce`, and `outbox/<addr>/<zero-padded nonce>` respectively, so
+    /// they survive a restart when `S` is a durable backend.
+    storage: Arc<S>,
+    /// Serializes the nonce-allocation critical section; kept separate from `storage` because the
+    /// `Storage` trait itself makes no atomicity guarantee across a read-modify-write.
+    nonce_lock: Mutex<()>,
+    /// Handle to the running background outbox-processing task, if started
+    background_worker: Mutex<Option<tokio::task::JoinHandle<()>>>,
+    /// Status snapshot updated by the background worker on each pass
+    worker_status: Arc<Mutex<WorkerStatus>>,
+    /// Withdrawals observed mined, keyed by `(wallet_address, tx_nonce)`, tracked until finalized
+    /// or reorged away; see `record_mined`/`advance_chain_tip`/`revert_mined_block`
+    mined: Arc<Mutex<HashMap<(WalletAddress, u64), MinedTransaction>>>,
+    /// Highest block height seen so far, used to compute confirmation depth
+    latest_block_height: Arc<Mutex<u64>>,
+    /// Handles for running `start_deposit_sync` tasks, keyed by wallet address
+    deposit_sync_handles: Mutex<HashMap<WalletAddress, tokio::task::JoinHandle<()>>>,
+    /// Status snapshot updated by each deposit sync task on every poll
+    deposit_sync_status: Arc<Mutex<HashMap<WalletAddress, DepositSyncStatus>>>,
+    /// Per-wallet stack of open `begin_checkpoint` snapshots, innermost last; see
+    /// `begin_checkpoint`/`commit_checkpoint`/`revert_checkpoint`/`with_transaction`.
+    checkpoints: Mutex<HashMap<WalletAddress, Vec<(CheckpointId, PassWalletState)>>>,
+    /// Monotonic counter behind `begin_checkpoint`'s `CheckpointId` allocation. Process-lifetime
+    /// only, not persisted to `storage` - a checkpoint never outlives the request that opened it.
+    next_checkpoint_id: Mutex<u64>,
+}
+
+/// Opaque handle for a checkpoint opened by `begin_checkpoint`, scoped to the wallet it was opened
+/// against. `commit_checkpoint`/`revert_checkpoint` reject an `id` that isn't the innermost open
+/// checkpoint for that wallet, so a caller can't accidentally close an outer transaction while an
+/// inner one is still open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointId(u64);
+
+/// Status snapshot for `start_background_worker`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WorkerStatus {
+    pub last_run_at: Option<u64>,
+    pub pending_counts: HashMap<WalletAddress, usize>,
+    pub last_errors: HashMap<WalletAddress, String>,
+}
+
+/// Status snapshot for a wallet's background deposit sync, started by `start_deposit_sync`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepositSyncStatus {
+    pub rpc_url: String,
+    pub poll_interval_secs: u64,
+    pub watched_addresses: Vec<String>,
+    pub last_block_scanned: u64,
+    pub last_deposits_ingested: usize,
+    pub last_error: Option<String>,
+}
+
+/// Minimal subset of an `eth_getBlockByNumber(_, true)` transaction object needed to detect
+/// native ETH transfers into a watched address
+#[derive(Debug, Clone, Deserialize)]
+struct RpcTransaction {
+    hash: String,
+    from: String,
+    to: Option<String>,
+    value: String,
+}
+
+impl PassWalletManager<InMemoryStorage> {
+    /// Create a new PASS wallet manager backed by a fresh, process-local `InMemoryStorage`.
+    pub fn new(kms: Arc<Mutex<EnclaveKMS>>) -> Self {
+        Self::with_storage(kms, Arc::new(InMemoryStorage::new()))
+    }
+}
+
+impl<S: Storage> PassWalletManager<S> {
+    /// Create a new PASS wallet manager backed by `storage`.
+    pub fn with_storage(kms: Arc<Mutex<EnclaveKMS>>, storage: Arc<S>) -> Self {
+        PassWalletManager {
+            kms,
+            storage,
+            nonce_lock: Mutex::new(()),
+            background_worker: Mutex::new(None),
+            worker_status: Arc::new(Mutex::new(WorkerStatus::default())),
+            mined: Arc::new(Mutex::new(HashMap::new())),
+            latest_block_height: Arc::new(Mutex::new(0)),
+            deposit_sync_handles: Mutex::new(HashMap::new()),
+            deposit_sync_status: Arc::new(Mutex::new(HashMap::new())),
+            checkpoints: Mutex::new(HashMap::new()),
+            next_checkpoint_id: Mutex::new(0),
+        }
+    }
+
+    fn wallet_key(address: &str) -> String {
+        format!("wallet/{}", address)
+    }
+
+    fn outbox_prefix(wallet_address: &str) -> String {
+        format!("outbox/{}/", wallet_address)
+    }
+
+    /// Zero-padded so lexicographic key order (what `scan_prefix` returns) matches nonce order.
+    fn outbox_key(wallet_address: &str, nonce: u64) -> String {
+        format!("outbox/{}/{:020}", wallet_address, nonce)
+    }
+
+    fn last_broadcast_key(wallet_address: &str) -> String {
+        format!("last_broadcast/{}", wallet_address)
+    }
+
+    fn load_global_nonce(&self) -> u64 {
+        self.storage
+            .read("global_nonce")
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_be_bytes)
+            .unwrap_or(0)
+    }
+
+    fn save_global_nonce(&self, value: u64) {
+        self.storage.write("global_nonce", value.to_be_bytes().to_vec());
+    }
+
+    /// Allocate and persist the next global nonce, serialized against concurrent callers.
+    fn allocate_global_nonce(&self) -> u64 {
+        let _guard = self.nonce_lock.lock().unwrap();
+        let next = self.load_global_nonce() + 1;
+        self.save_global_nonce(next);
+        next
+    }
+
+    fn load_last_broadcast(&self, wallet_address: &str) -> u64 {
+        self.storage
+            .read(&Self::last_broadcast_key(wallet_address))
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_be_bytes)
+            .unwrap_or(0)
+    }
+
+    fn save_last_broadcast(&self, wallet_address: &str, value: u64) {
+        self.storage.write(&Self::last_broadcast_key(wallet_address), value.to_be_bytes().to_vec());
+    }
+
+    fn load_outbox_for_wallet(&self, wallet_address: &str) -> Vec<PendingWithdrawal> {
+        self.storage
+            .scan_prefix(&Self::outbox_prefix(wallet_address))
+            .into_iter()
+            .filter_map(|(_, bytes)| decode_queued_withdrawal(&bytes).ok())
+            .collect()
+    }
+
+    /// Start a background task that periodically drains every wallet's outbox, building and
+    /// signing withdrawal transactions via the KMS and pushing them onto `outbox_queue`. One
+    /// wallet's failure is recorded in the status snapshot but does not abort the rest of the
+    /// pass. `global_nonce` sequencing stays centralized here rather than in per-call paths.
+    pub fn start_background_worker(self: &Arc<Self>, interval: std::time::Duration) {
+        let manager = Arc::clone(self);
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                manager.run_outbox_sweep();
+            }
+        });
+
+        let mut worker = self.background_worker.lock().unwrap();
+        if let Some(old) = worker.replace(handle) {
+            old.abort();
+        }
+    }
+
+    /// Stop the background outbox worker started by `start_background_worker`, if running.
+    pub fn stop_background_worker(&self) {
+        if let Some(handle) = self.background_worker.lock().unwrap().take() {
+            handle.abort();
+        }
+    }
+
+    /// Current status of the background worker: last run time and per-wallet pending counts.
+    pub fn get_worker_status(&self) -> WorkerStatus {
+        self.worker_status.lock().unwrap().clone()
+    }
+
+    fn deposit_sync_block_key(wallet_address: &str) -> String {
+        format!("deposit_sync_block/{}", wallet_address)
+    }
+
+    fn load_deposit_sync_block(&self, wallet_address: &str) -> u64 {
+        self.storage
+            .read(&Self::deposit_sync_block_key(wallet_address))
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_be_bytes)
+            .unwrap_or(0)
+    }
+
+    fn save_deposit_sync_block(&self, wallet_address: &str, value: u64) {
+        self.storage.write(&Self::deposit_sync_block_key(wallet_address), value.to_be_bytes().to_vec());
+    }
+
+    /// Start a background task that polls `rpc_url` for native ETH transfers into any of
+    /// `watched_addresses`, ingesting each newly observed one via `inbox_deposit` (deduplicated by
+    /// `deposit_id`, here the transaction hash). Resumes from the block persisted by a previous
+    /// run rather than rescanning from genesis, so a restart picks up where it left off. Replaces
+    /// any sync already running for this wallet.
+    pub fn start_deposit_sync(
+        self: &Arc<Self>,
+        wallet_address: &str,
+        rpc_url: String,
+        poll_interval_secs: u64,
+        watched_addresses: Vec<String>,
+    ) -> Result<()> {
+        if self.get_wallet(wallet_address).is_none() {
+            return Err(anyhow!("Wallet not found"));
+        }
+
+        self.stop_deposit_sync(wallet_address);
+
+        let last_block_scanned = self.load_deposit_sync_block(wallet_address);
+        self.deposit_sync_status.lock().unwrap().insert(
+            wallet_address.to_string(),
+            DepositSyncStatus {
+                rpc_url: rpc_url.clone(),
+                poll_interval_secs,
+                watched_addresses: watched_addresses.clone(),
+                last_block_scanned,
+                last_deposits_ingested: 0,
+                last_error: None,
+            },
+        );
+
+        let manager = Arc::clone(self);
+        let task_wallet_address = wallet_address.to_string();
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(poll_interval_secs.max(1)));
+            loop {
+                ticker.tick().await;
+                manager.scan_deposits_once(&task_wallet_address, &rpc_url, &watched_addresses).await;
+            }
+        });
+
+        self.deposit_sync_handles.lock().unwrap().insert(wallet_address.to_string(), handle);
+        Ok(())
+    }
+
+    /// Stop the background deposit sync for `wallet_address`, if running.
+    pub fn stop_deposit_sync(&self, wallet_address: &str) {
+        if let Some(handle) = self.deposit_sync_handles.lock().unwrap().remove(wallet_address) {
+            handle.abort();
+        }
+        self.deposit_sync_status.lock().unwrap().remove(wallet_address);
+    }
+
+    /// Current deposit sync status for `wallet_address`, if a sync has been started for it.
+    pub fn get_deposit_sync_status(&self, wallet_address: &str) -> Option<DepositSyncStatus> {
+        self.deposit_sync_status.lock().unwrap().get(wallet_address).cloned()
+    }
+
+    /// One polling pass for `start_deposit_sync`: fetches the current chain height from
+    /// `rpc_url`, scans every block since the last persisted one for native ETH transfers into
+    /// `watched_addresses`, ingests each as a `Deposit`, and persists the new high-water mark.
+    /// Errors (RPC unreachable, malformed response) are recorded in the status snapshot rather
+    /// than propagated, so one bad poll doesn't kill the task.
+    async fn scan_deposits_once(&self, wallet_address: &str, rpc_url: &str, watched_addresses: &[String]) {
+        if let Err(e) = self.scan_deposits_once_inner(wallet_address, rpc_url, watched_addresses).await {
+            if let Some(status) = self.deposit_sync_status.lock().unwrap().get_mut(wallet_address) {
+                status.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    async fn scan_deposits_once_inner(
+        &self,
+        wallet_address: &str,
+        rpc_url: &str,
+        watched_addresses: &[String],
+    ) -> Result<()> {
+        let client = reqwest::Client::new();
+        let latest_block = Self::rpc_block_number(&client, rpc_url).await?;
+        let last_block_scanned = self.load_deposit_sync_block(wallet_address);
+
+        if latest_block <= last_block_scanned {
+            return Ok(());
+        }
+
+        let watched: Vec<String> = watched_addresses
+            .iter()
+            .map(|address| address.trim_start_matches("0x").to_lowercase())
+            .collect();
+        let mut ingested = 0usize;
+
+        for block_number in (last_block_scanned + 1)..=latest_block {
+            let transactions = Self::rpc_block_transactions(&client, rpc_url, block_number).await?;
+            for tx in transactions {
+                let to = match &tx.to {
+                    Some(to) => to.trim_start_matches("0x").to_lowercase(),
+                    None => continue,
+                };
+                if !watched.contains(&to) {
+                    continue;
+                }
+
+                let amount = Amount::from_str_radix(tx.value.trim_start_matches("0x"), 16)
+                    .map_err(|e| anyhow!("Invalid transfer value in tx {}: {}", tx.hash, e))?;
+
+                let deposit = Deposit {
+                    asset_id: "ETH".to_string(),
+                    amount,
+                    deposit_id: tx.hash.clone(),
+                    transaction_hash: tx.hash,
+                    block_number: block_number.to_string(),
+                    from_address: tx.from,
+                    to_address: tx.to.unwrap_or_default(),
+                    memo: None,
+                };
+
+                // A duplicate `deposit_id` means this deposit was already ingested on a prior
+                // poll; skip it rather than surfacing an error for an already-handled transfer.
+                if self.inbox_deposit(wallet_address, deposit).is_ok() {
+                    ingested += 1;
+                }
+            }
+        }
+
+        self.save_deposit_sync_block(wallet_address, latest_block);
+        if let Some(status) = self.deposit_sync_status.lock().unwrap().get_mut(wallet_address) {
+            status.last_block_scanned = latest_block;
+            status.last_deposits_ingested = ingested;
+            status.last_error = None;
+        }
+
+        Ok(())
+    }
+
+    async fn rpc_block_number(client: &reqwest::Client, rpc_url: &str) -> Result<u64> {
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_blockNumber",
+            "params": []
+        });
+        let response: serde_json::Value = client.post(rpc_url).json(&body).send().await?.json().await?;
+        let hex_block = response
+            .get("result")
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| anyhow!("Malformed eth_blockNumber response"))?;
+        u64::from_str_radix(hex_block.trim_start_matches("0x"), 16)
+            .map_err(|e| anyhow!("Invalid block number in eth_blockNumber response: {}", e))
+    }
+
+    async fn rpc_block_transactions(
+        client: &reqwest::Client,
+        rpc_url: &str,
+        block_number: u64,
+    ) -> Result<Vec<RpcTransaction>> {
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getBlockByNumber",
+            "params": [format!("0x{:x}", block_number), true]
+        });
+        let response: serde_json::Value = client.post(rpc_url).json(&body).send().await?.json().await?;
+        let transactions = response
+            .get("result")
+            .and_then(|result| result.get("transactions"))
+            .cloned()
+            .unwrap_or(serde_json::Value::Array(Vec::new()));
+        Ok(serde_json::from_value(transactions)?)
+    }
+
+    /// Split one wallet's queued withdrawals into the "ready" run (contiguous nonces starting right
+    /// after its last broadcast nonce) and "future" entries (everything with a gap before it).
+    fn partition_wallet_queue(&self, wallet_address: &str) -> (Vec<PendingWithdrawal>, Vec<PendingWithdrawal>) {
+        let last_broadcast = self.load_last_broadcast(wallet_address);
+        let mut expected = last_broadcast + 1;
+        let mut ready = Vec::new();
+        let mut future = Vec::new();
+
+        let mut entries = self.load_outbox_for_wallet(wallet_address);
+        entries.sort_by_key(|withdrawal| withdrawal.nonce);
+        for withdrawal in entries {
+            if withdrawal.nonce == expected {
+                ready.push(withdrawal);
+                expected += 1;
+            } else {
+                future.push(withdrawal);
+            }
+        }
+        (ready, future)
+    }
+
+    /// Insert a signed withdrawal into the outbox, allowing replacement of a not-yet-broadcast
+    /// transaction at the same `(wallet_address, nonce)` only when it raises `effective_gas_price`
+    /// by at least `REPLACEMENT_BUMP_BPS` (bumping `retry_count` on the replacement), then evicts
+    /// the lowest-fee future entry if the wallet's queue now exceeds `MAX_OUTBOX_PER_WALLET`.
+    fn enqueue_pending_withdrawal(&self, mut withdrawal: PendingWithdrawal) -> Result<()> {
+        let key = Self::outbox_key(&withdrawal.wallet_address, withdrawal.nonce);
+
+        if let Some(existing_bytes) = self.storage.read(&key) {
+            let existing = decode_queued_withdrawal(&existing_bytes)?;
+            let min_bump = existing
+                .effective_gas_price
+                .saturating_mul(REPLACEMENT_BUMP_BPS)
+                / 10_000;
+            let required = existing.effective_gas_price.saturating_add(min_bump);
+            if withdrawal.effective_gas_price < required {
+                return Err(anyhow!(
+                    "replacement transaction for wallet {} nonce {} must raise gas price to at least {} (got {})",
+                    withdrawal.wallet_address, withdrawal.nonce, required, withdrawal.effective_gas_price
+                ));
+            }
+            withdrawal.retry_count = existing.retry_count.saturating_add(1);
+        }
+
+        let wallet_address = withdrawal.wallet_address.clone();
+        let bytes = encode_queued_withdrawal(&withdrawal);
+        self.storage.write(&key, bytes);
+
+        let wallet_count = self.storage.scan_prefix(&Self::outbox_prefix(&wallet_address)).len();
+        if wallet_count > MAX_OUTBOX_PER_WALLET {
+            let (_, future) = self.partition_wallet_queue(&wallet_address);
+            if let Some(lowest) = future.iter().min_by_key(|w| w.effective_gas_price) {
+                self.storage.remove(&Self::outbox_key(&wallet_address, lowest.nonce));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Withdrawals ready to broadcast now: each wallet's contiguous run of nonces starting right
+    /// after its last broadcast nonce, in nonce order.
+    pub fn get_ready(&self) -> Result<Vec<PendingWithdrawal>> {
+        let mut ready = Vec::new();
+        for wallet_address in self.list_wallets() {
+            ready.extend(self.partition_wallet_queue(&wallet_address).0);
+        }
+        Ok(ready)
+    }
+
+    /// Withdrawals stuck behind a nonce gap — not broadcastable until the missing nonce(s) land.
+    pub fn get_future(&self) -> Result<Vec<PendingWithdrawal>> {
+        let mut future = Vec::new();
+        for wallet_address in self.list_wallets() {
+            future.extend(self.partition_wallet_queue(&wallet_address).1);
+        }
+        Ok(future)
+    }
+
+    /// Mark `nonce` as broadcast for `wallet_address`, advancing its ready-set boundary so the next
+    /// contiguous nonce (if queued) becomes ready, and flipping the queued entry's `status` to
+    /// `Broadcast` if it's still sitting in the outbox.
+    pub fn mark_broadcast(&self, wallet_address: &str, nonce: u64) -> Result<()> {
+        if nonce > self.load_last_broadcast(wallet_address) {
+            self.save_last_broadcast(wallet_address, nonce);
+        }
+
+        let key = Self::outbox_key(wallet_address, nonce);
+        if let Some(bytes) = self.storage.read(&key) {
+            let mut withdrawal = decode_queued_withdrawal(&bytes)?;
+            withdrawal.status = WithdrawalStatus::Broadcast;
+            self.storage.write(&key, encode_queued_withdrawal(&withdrawal));
+        }
+        Ok(())
+    }
+
+    /// Drain every wallet's outbox once, recording per-wallet success/failure in `worker_status`.
+    fn run_outbox_sweep(&self) {
+        let addresses = self.list_wallets();
+        let mut pending_counts = HashMap::new();
+        let mut last_errors = HashMap::new();
+
+        for address in addresses {
+            match self.process_outbox(&address) {
+                Ok(entries) => {
+                    for entry in entries {
+                        let tx_nonce = self.allocate_global_nonce();
+
+                        if let Err(e) = self.enqueue_pending_withdrawal(PendingWithdrawal {
+                            wallet_address: address.clone(),
+                            subaccount_id: String::new(),
+                            asset_id: entry.asset_id,
+                            amount: entry.amount,
+                            destination: entry.external_destination,
+                            nonce: tx_nonce,
+                            signed_raw_transaction: String::new(),
+                            created_at: PassWalletState::get_timestamp(),
+                            tx_type: TransactionEnvelopeType::Legacy,
+                            effective_gas_price: 0,
+                            status: WithdrawalStatus::Queued,
+                            retry_count: 0,
+                            memo: None,
+                        }) {
+                            last_errors.insert(address.clone(), e.to_string());
+                        }
+                    }
+                    let wallet_pending = self
+                        .get_ready()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .chain(self.get_future().unwrap_or_default())
+                        .filter(|w| w.wallet_address == address)
+                        .count();
+                    pending_counts.insert(address.clone(), wallet_pending);
+                }
+                Err(e) => {
+                    last_errors.insert(address.clone(), e.to_string());
+                }
+            }
+        }
+
+        let mut status = self.worker_status.lock().unwrap();
+        status.last_run_at = Some(PassWalletState::get_timestamp());
+        status.pending_counts = pending_counts;
+        status.last_errors = last_errors;
+    }
+
+    /// Create a new PASS wallet
+    pub fn create_wallet(&self, name: String, owner: String) -> Result<WalletAddress> {
+        // Generate a new Ethereum account using the existing KMS
+        let account = {
+            let mut kms = self.kms.lock().unwrap();
+            kms.handle_keygen()?
+        };
+
+        let address = account.address.clone();
+        let mut wallet_state = PassWalletState::new(address.clone(), name, owner);
+        wallet_state.chain_head = {
+            let kms = self.kms.lock().unwrap();
+            kms.provenance_genesis(&address)
+        };
+        wallet_state.recompute_integrity_digest();
+
+        // Store the wallet
+        let bytes = serde_json::to_vec(&wallet_state)
+            .map_err(|e| anyhow!("Failed to serialize wallet state: {}", e))?;
+        self.storage.write(&Self::wallet_key(&address), bytes);
+
+        Ok(address)
+    }
+
+    /// Get a wallet by address
+    pub fn get_wallet(&self, address: &str) -> Option<PassWalletState> {
+        self.storage
+            .read(&Self::wallet_key(address))
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+    }
+
+    /// Update a wallet
+    pub fn update_wallet(&self, address: &str, mut wallet_state: PassWalletState) -> Result<()> {
+        if self.storage.read(&Self::wallet_key(address)).is_none() {
+            return Err(anyhow!("Wallet not found"));
+        }
+        wallet_state.recompute_integrity_digest();
+        let bytes = serde_json::to_vec(&wallet_state)
+            .map_err(|e| anyhow!("Failed to serialize wallet state: {}", e))?;
+        self.storage.write(&Self::wallet_key(address), bytes);
+        Ok(())
+    }
+
+    /// Cheaply capture a wallet's current persisted state, for `Command::Batch` rollback.
+    /// Returns `None` if the wallet doesn't exist - nothing to roll back to.
+    pub fn snapshot_wallet(&self, wallet_address: &str) -> Option<PassWalletState> {
+        self.get_wallet(wallet_address)
+    }
+
+    /// Restore a wallet to a state captured by `snapshot_wallet`, overwriting whatever is there.
+    pub fn restore_wallet(&self, wallet_address: &str, snapshot: PassWalletState) -> Result<()> {
+        self.update_wallet(wallet_address, snapshot)
+    }
+
+    /// Open a new checkpoint against `wallet_address`, capturing its current persisted state so a
+    /// later `revert_checkpoint` can restore it exactly. Checkpoints nest: calling this again for
+    /// the same wallet before the first one commits/reverts just pushes another snapshot onto that
+    /// wallet's stack. The "journal" is the wallet's whole persisted `PassWalletState` - the same
+    /// capture `Command::Batch` already uses via `snapshot_wallet`/`restore_wallet` - since cloning
+    /// it is cheap and trivially satisfies the invariant that a revert leaves `get_wallet`
+    /// byte-identical to the pre-checkpoint state, rather than hand-tracking every individual
+    /// balance/inbox/outbox/history/nonce mutation site.
+    pub fn begin_checkpoint(&self, wallet_address: &str) -> Result<CheckpointId> {
+        let state = self.get_wallet(wallet_address).ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let id = {
+            let mut next_id = self.next_checkpoint_id.lock().unwrap();
+            *next_id += 1;
+            CheckpointId(*next_id)
+        };
+
+        let mut checkpoints = self.checkpoints.lock().unwrap();
+        checkpoints.entry(wallet_address.to_string()).or_default().push((id, state));
+        Ok(id)
+    }
+
+    /// Discard checkpoint `id` without restoring anything, keeping every mutation made since it
+    /// opened. If an outer checkpoint is still open on the same wallet, that outer checkpoint's
+    /// own captured state still predates both, so it remains able to revert the combined effect of
+    /// everything committed inside it.
+    pub fn commit_checkpoint(&self, wallet_address: &str, id: CheckpointId) -> Result<()> {
+        let mut checkpoints = self.checkpoints.lock().unwrap();
+        Self::pop_checkpoint(&mut checkpoints, wallet_address, id)?;
+        Ok(())
+    }
+
+    /// Restore `wallet_address` to exactly the state it was in when checkpoint `id` was opened,
+    /// discarding every mutation made since - including any checkpoints nested inside it, which are
+    /// popped along with it.
+    pub fn revert_checkpoint(&self, wallet_address: &str, id: CheckpointId) -> Result<()> {
+        let state = {
+            let mut checkpoints = self.checkpoints.lock().unwrap();
+            Self::pop_checkpoint(&mut checkpoints, wallet_address, id)?
+        };
+        self.restore_wallet(wallet_address, state)
+    }
+
+    /// Pop `id` off `wallet_address`'s checkpoint stack, requiring it to be the innermost (most
+    /// recently opened, not yet closed) checkpoint for that wallet. Returns the state captured when
+    /// it was opened.
+    fn pop_checkpoint(
+        checkpoints: &mut HashMap<WalletAddress, Vec<(CheckpointId, PassWalletState)>>,
+        wallet_address: &str,
+        id: CheckpointId,
+    ) -> Result<PassWalletState> {
+        let stack = checkpoints
+            .get_mut(wallet_address)
+            .ok_or_else(|| anyhow!("No open checkpoint for wallet {}", wallet_address))?;
+
+        match stack.last() {
+            Some((top_id, _)) if *top_id == id => Ok(stack.pop().unwrap().1),
+            Some(_) => Err(anyhow!("Checkpoint is not the innermost open checkpoint for this wallet")),
+            None => Err(anyhow!("No open checkpoint for wallet {}", wallet_address)),
+        }
+    }
+
+    /// Run `f` inside a fresh checkpoint on `wallet_address`, committing on `Ok` and reverting on
+    /// `Err`, so a composite flow like "transfer then withdraw" is all-or-nothing: either every
+    /// mutation `f` makes is observed, or none of them are.
+    pub fn with_transaction<T>(&self, wallet_address: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let checkpoint = self.begin_checkpoint(wallet_address)?;
+        match f() {
+            Ok(value) => {
+                self.commit_checkpoint(wallet_address, checkpoint)?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.revert_checkpoint(wallet_address, checkpoint)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// List all wallet addresses
+    pub fn list_wallets(&self) -> Vec<WalletAddress> {
+        self.storage
+            .scan_prefix("wallet/")
+            .into_iter()
+            .map(|(key, _)| key.trim_start_matches("wallet/").to_string())
+            .collect()
+    }
+
+    /// Sign a message using a wallet's private key
+    pub fn sign_message(&self, wallet_address: &str, domain: &str, message: &str) -> Result<String> {
+        // Use the existing KMS to sign the message
+        let kms = self.kms.lock().unwrap();
+        let full_message = format!("{}:{}", domain, message);
+        
+        match kms.sign_message(&full_message, wallet_address)? {
+            Some(signature) => Ok(signature),
+            None => Err(anyhow!("Failed to sign message - wallet not found")),
+        }
+    }
+
+    /// Execute inbox deposit
+    pub fn inbox_deposit(&self, wallet_address: &str, deposit: Deposit) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        
+        wallet_state.inbox_deposit(deposit)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Execute claim inbox. If the claimed deposit carried a plaintext `memo`, it's sealed to
+    /// `subaccount_id`'s own key (see `encrypt_user_memo`) and stored before this plaintext copy
+    /// is dropped - only `get_memos` can recover it afterward, and only for that subaccount.
+    pub fn claim_inbox(&self, wallet_address: &str, deposit_id: &str, subaccount_id: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let memo = wallet_state.claim_inbox(deposit_id, subaccount_id)?;
+        if let Some(memo) = memo {
+            let ciphertext = self.encrypt_user_memo(wallet_address, subaccount_id, &memo)?;
+            wallet_state.store_user_memo(subaccount_id, ciphertext, format!("claim:{}", deposit_id));
+        }
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Reconcile a batch of externally-observed `DepositEvent`s against `wallet_address`'s
+    /// inbox/history, idempotently queuing any genuinely new one. See
+    /// `PassWalletState::scan_and_recover`.
+    pub fn scan_and_recover(&self, wallet_address: &str, deposit_events: Vec<DepositEvent>) -> Result<DepositReconciliationReport> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let report = wallet_state.scan_and_recover(deposit_events);
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(report)
+    }
+
+    /// Unclaimed inbox deposits at least `min_block_age` blocks behind `current_block_number`.
+    /// See `PassWalletState::recover_gap_deposits`.
+    pub fn recover_gap_deposits(&self, wallet_address: &str, current_block_number: u64, min_block_age: u64) -> Result<Vec<Deposit>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        Ok(wallet_state.recover_gap_deposits(current_block_number, min_block_age).into_iter().cloned().collect())
+    }
+
+    /// Execute internal transfer. `memo`, if given, is recorded in provenance as plaintext and
+    /// also sealed (see `encrypt_user_memo`) to `to_subaccount`'s own key so only that subaccount
+    /// can recover it later via `get_memos`, same split as `withdraw`'s `memo`/`outbox_memo`.
+    pub fn internal_transfer(&self, wallet_address: &str, asset_id: &str, amount: Amount, from_subaccount: &str, to_subaccount: &str, memo: Option<String>) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let encrypted_memo = memo.as_deref().map(|m| self.encrypt_user_memo(wallet_address, to_subaccount, m)).transpose()?;
+        wallet_state.internal_transfer(asset_id, amount, from_subaccount, to_subaccount, memo, encrypted_memo)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Rate-converting counterpart to `internal_transfer`. See
+    /// `PassWalletState::internal_transfer_with_rate`. Returns the credited `to_asset` amount.
+    #[allow(clippy::too_many_arguments)]
+    pub fn internal_transfer_with_rate(
+        &self,
+        wallet_address: &str,
+        from_asset: &str,
+        from_amount: Amount,
+        to_asset: &str,
+        rate: Rate,
+        from_subaccount: &str,
+        to_subaccount: &str,
+        memo: Option<String>,
+    ) -> Result<Amount> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let encrypted_memo = memo.as_deref().map(|m| self.encrypt_user_memo(wallet_address, to_subaccount, m)).transpose()?;
+        let to_amount = wallet_state.internal_transfer_with_rate(
+            from_asset, from_amount, to_asset, rate, from_subaccount, to_subaccount, memo, encrypted_memo,
+        )?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(to_amount)
+    }
+
+    /// NFT-aware counterpart to `internal_transfer`. See `PassWalletState::transfer_nft`.
+    pub fn transfer_nft(&self, wallet_address: &str, asset_id: &str, token_id: &str, from_subaccount: &str, to_subaccount: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.transfer_nft(asset_id, token_id, from_subaccount, to_subaccount)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Execute withdrawal. `memo`, if given, is recorded in provenance as plaintext and also
+    /// sealed (see `encrypt_outbox_memo`) onto the queued `OutboxEntry` so it rides along to
+    /// whichever format `process_outbox_for_broadcast` later serializes it in.
+    pub fn withdraw(&self, wallet_address: &str, asset_id: &str, amount: Amount, subaccount_id: &str, destination: &str, memo: Option<String>) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let outbox_memo = memo.as_deref().map(|m| self.encrypt_outbox_memo(wallet_address, m)).transpose()?;
+        let (resolved_destination, nonce) = wallet_state.withdraw(asset_id, amount, subaccount_id, destination, memo, outbox_memo)?;
+        let proof = self.sign_payment_proof(wallet_address, &resolved_destination, asset_id, amount, nonce)?;
+        wallet_state.proofs.insert(nonce, proof);
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// NFT-aware counterpart to `withdraw`. See `PassWalletState::withdraw_nft`.
+    pub fn withdraw_nft(&self, wallet_address: &str, asset_id: &str, token_id: &str, subaccount_id: &str, destination: &str, memo: Option<String>) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.withdraw_nft(asset_id, token_id, subaccount_id, destination, memo)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Seed (or top up) the constant-product liquidity reserve backing `internal_swap` between
+    /// `asset_a` and `asset_b`. See `PassWalletState::add_liquidity`.
+    pub fn add_liquidity(&self, wallet_address: &str, asset_a: &str, amount_a: Amount, asset_b: &str, amount_b: Amount) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.add_liquidity(asset_a, amount_a, asset_b, amount_b)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Exchange `amount_in` of `asset_in` for `asset_out` against this wallet's own liquidity
+    /// reserve. See `PassWalletState::internal_swap`.
+    pub fn internal_swap(&self, wallet_address: &str, subaccount_id: &str, asset_in: &str, amount_in: Amount, asset_out: &str, min_out: Amount) -> Result<Amount> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let amount_out = wallet_state.internal_swap(subaccount_id, asset_in, amount_in, asset_out, min_out)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(amount_out)
+    }
+
+    /// Credit `subaccount_id`'s pool balance and mint `contributor` a proportional share of it.
+    /// See `PassWalletState::deposit_to_pool`.
+    pub fn deposit_to_pool(&self, wallet_address: &str, subaccount_id: &str, asset_id: &str, contributor: &str, amount: Amount) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.deposit_to_pool(subaccount_id, asset_id, contributor, amount)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Burn `contributor`'s pool shares and pay out their proportional value. See
+    /// `PassWalletState::withdraw_from_pool`.
+    pub fn withdraw_from_pool(&self, wallet_address: &str, subaccount_id: &str, asset_id: &str, contributor: &str, shares: Amount, recipient_subaccount: &str) -> Result<Amount> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let payout = wallet_state.withdraw_from_pool(subaccount_id, asset_id, contributor, shares, recipient_subaccount)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(payout)
+    }
+
+    /// Shares `contributor` holds in `subaccount_id`'s `asset_id` pool. See
+    /// `PassWalletState::shares_of`.
+    pub fn shares_of(&self, wallet_address: &str, subaccount_id: &str, asset_id: &str, contributor: &str) -> Result<Amount> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        Ok(wallet_state.shares_of(subaccount_id, asset_id, contributor))
+    }
+
+    /// Lock `give_amount` of `give_asset` out of `maker`'s balance into a pending peer-to-peer
+    /// swap awaiting a taker. See `PassWalletState::propose_swap`.
+    pub fn propose_swap(&self, wallet_address: &str, maker: &str, give_asset: &str, give_amount: Amount, want_asset: &str, want_amount: Amount) -> Result<String> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let swap_id = wallet_state.propose_swap(maker, give_asset, give_amount, want_asset, want_amount)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(swap_id)
+    }
+
+    /// Settle a pending swap against `taker`'s balance. See `PassWalletState::accept_swap`.
+    pub fn accept_swap(&self, wallet_address: &str, swap_id: &str, taker: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.accept_swap(swap_id, taker)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Cancel a still-pending swap and refund its maker. See `PassWalletState::cancel_swap`.
+    pub fn cancel_swap(&self, wallet_address: &str, swap_id: &str, maker: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.cancel_swap(swap_id, maker)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Apply a batch of `WalletOp`s against one wallet under a single `get_wallet`/`update_wallet`
+    /// round trip instead of one per op. All-or-nothing: `ops` are applied to a local, un-persisted
+    /// clone of the wallet state, and `update_wallet` is only called if every op succeeds, so a
+    /// failure partway through simply drops the local clone without having touched storage - there
+    /// is no explicit rollback step because nothing was written until every op already succeeded.
+    pub fn apply_batch(&self, wallet_address: &str, ops: Vec<WalletOp>) -> Result<Vec<OpResult>> {
+        let mut wallet_state = self
+            .get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let mut results = Vec::with_capacity(ops.len());
+        let mut pending_proofs = Vec::new();
+        for op in ops {
+            match op {
+                WalletOp::Claim {
+                    deposit_id,
+                    subaccount_id,
+                } => {
+                    let memo = wallet_state.claim_inbox(&deposit_id, &subaccount_id)?;
+                    if let Some(memo) = memo {
+                        let ciphertext = self.encrypt_user_memo(wallet_address, &subaccount_id, &memo)?;
+                        wallet_state.store_user_memo(&subaccount_id, ciphertext, format!("claim:{}", deposit_id));
+                    }
+                    results.push(OpResult::Claim);
+                }
+                WalletOp::Transfer {
+                    asset_id,
+                    amount,
+                    from_subaccount,
+                    to_subaccount,
+                    memo,
+                } => {
+                    let encrypted_memo = memo.as_deref().map(|m| self.encrypt_user_memo(wallet_address, &to_subaccount, m)).transpose()?;
+                    wallet_state.internal_transfer(
+                        &asset_id,
+                        amount,
+                        &from_subaccount,
+                        &to_subaccount,
+                        memo,
+                        encrypted_memo,
+                    )?;
+                    results.push(OpResult::Transfer);
+                }
+                WalletOp::Withdraw {
+                    asset_id,
+                    amount,
+                    subaccount_id,
+                    destination,
+                    memo,
+                } => {
+                    let outbox_memo = memo.as_deref().map(|m| self.encrypt_outbox_memo(wallet_address, m)).transpose()?;
+                    let (resolved_destination, nonce) = wallet_state.withdraw(
+                        &asset_id,
+                        amount,
+                        &subaccount_id,
+                        &destination,
+                        memo,
+                        outbox_memo,
+                    )?;
+                    let proof = self.sign_payment_proof(
+                        wallet_address,
+                        &resolved_destination,
+                        &asset_id,
+                        amount,
+                        nonce,
+                    )?;
+                    pending_proofs.push((nonce, proof));
+                    results.push(OpResult::Withdraw {
+                        destination: resolved_destination,
+                        nonce,
+                    });
+                }
+                WalletOp::Deposit(deposit) => {
+                    wallet_state.inbox_deposit(deposit)?;
+                    results.push(OpResult::Deposit);
+                }
+            }
+        }
+
+        for (nonce, proof) in pending_proofs {
+            wallet_state.proofs.insert(nonce, proof);
+        }
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(results)
+    }
+
+    /// Sign a `PaymentProof` over `(destination, asset_id, amount, nonce)` under `wallet_address`'s
+    /// KMS-managed key, recovering the signer address from the resulting signature.
+    fn sign_payment_proof(&self, wallet_address: &str, destination: &str, asset_id: &str, amount: Amount, nonce: u64) -> Result<PaymentProof> {
+        let message = PaymentProof::canonical_message(wallet_address, destination, asset_id, amount, nonce);
+        let kms = self.kms.lock().unwrap();
+        let signature = kms.sign_message(&message, wallet_address)?
+            .ok_or_else(|| anyhow!("Failed to sign payment proof - wallet not found"))?;
+        let signer_address = kms.recover_address(&message, &signature)?;
+
+        Ok(PaymentProof {
+            wallet_address: wallet_address.to_string(),
+            destination: destination.to_string(),
+            asset_id: asset_id.to_string(),
+            amount,
+            nonce,
+            signer_address,
+            signature,
+        })
+    }
+
+    /// Retrieve the signed payment proof recorded for a specific withdrawal, identified by the
+    /// outbox nonce it was queued under.
+    pub fn get_payment_proof(&self, wallet_address: &str, nonce: u64) -> Result<PaymentProof> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.proofs.get(&nonce).cloned()
+            .ok_or_else(|| anyhow!("No payment proof found for wallet {} nonce {}", wallet_address, nonce))
+    }
+
+    /// Validate a `PaymentProof` independently of any wallet state: recompute the canonical
+    /// message and confirm `proof.signature` recovers to `proof.wallet_address`.
+    pub fn verify_payment_proof(&self, proof: &PaymentProof) -> Result<bool> {
+        let message = PaymentProof::canonical_message(
+            &proof.wallet_address,
+            &proof.destination,
+            &proof.asset_id,
+            proof.amount,
+            proof.nonce,
+        );
+        let kms = self.kms.lock().unwrap();
+        kms.verify_message(&message, &proof.signature, &proof.wallet_address)
+    }
+
+    /// Designate a recovery contact for a wallet. `signature` must be an address-recoverable
+    /// signature (see `EnclaveKMS::verify_message`) over `recovery-add-contact:{wallet_address}:
+    /// {contact}:{waiting_period_secs}:{required_approvals}` from the wallet's current `owner` -
+    /// without this, any caller could name themselves as a one-approval recovery contact and walk
+    /// straight into `initiate_recovery`/`approve_recovery`. See
+    /// `PassWalletState::add_recovery_contact`.
+    pub fn add_recovery_contact(&self, wallet_address: &str, contact: &str, waiting_period_secs: u64, required_approvals: u32, signature: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let message = format!(
+            "recovery-add-contact:{}:{}:{}:{}",
+            wallet_address, contact, waiting_period_secs, required_approvals
+        );
+        let is_valid = {
+            let kms = self.kms.lock().unwrap();
+            kms.verify_message(&message, signature, &wallet_state.owner)?
+        };
+        if !is_valid {
+            return Err(anyhow!("Invalid owner signature for add_recovery_contact"));
+        }
+
+        wallet_state.add_recovery_contact(contact, waiting_period_secs, required_approvals)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Start an emergency recovery. `signature` must be an address-recoverable signature over
+    /// `recovery-initiate:{wallet_address}:{contact}` from `contact` itself - proving the caller
+    /// actually controls the recovery contact's key, not merely knows its address. See
+    /// `PassWalletState::initiate_recovery`.
+    pub fn initiate_recovery(&self, wallet_address: &str, contact: &str, signature: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let message = format!("recovery-initiate:{}:{}", wallet_address, contact);
+        let is_valid = {
+            let kms = self.kms.lock().unwrap();
+            kms.verify_message(&message, signature, contact)?
+        };
+        if !is_valid {
+            return Err(anyhow!("Invalid contact signature for initiate_recovery"));
+        }
+
+        wallet_state.initiate_recovery(contact)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Cancel a pending recovery. `signature` must be an address-recoverable signature over
+    /// `recovery-cancel:{wallet_address}:{requester}` from `requester` - `PassWalletState::
+    /// cancel_recovery` already rejects anyone but the current owner, but without this a caller
+    /// could simply pass the owner's address as `requester` without proving they control it. See
+    /// `PassWalletState::cancel_recovery`.
+    pub fn cancel_recovery(&self, wallet_address: &str, requester: &str, signature: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let message = format!("recovery-cancel:{}:{}", wallet_address, requester);
+        let is_valid = {
+            let kms = self.kms.lock().unwrap();
+            kms.verify_message(&message, signature, requester)?
+        };
+        if !is_valid {
+            return Err(anyhow!("Invalid requester signature for cancel_recovery"));
+        }
+
+        wallet_state.cancel_recovery(requester)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Approve a pending recovery, finalizing it immediately once quorum is reached. `signature`
+    /// must be an address-recoverable signature over `recovery-approve:{wallet_address}:{contact}`
+    /// from `contact`, same rationale as `initiate_recovery`. See
+    /// `PassWalletState::approve_recovery`.
+    pub fn approve_recovery(&self, wallet_address: &str, contact: &str, signature: &str) -> Result<bool> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let message = format!("recovery-approve:{}:{}", wallet_address, contact);
+        let is_valid = {
+            let kms = self.kms.lock().unwrap();
+            kms.verify_message(&message, signature, contact)?
+        };
+        if !is_valid {
+            return Err(anyhow!("Invalid contact signature for approve_recovery"));
+        }
+
+        let completed = wallet_state.approve_recovery(contact)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(completed)
+    }
+
+    /// Finalize a pending recovery whose waiting period has elapsed. See
+    /// `PassWalletState::process_recovery_timeout`.
+    pub fn process_recovery_timeout(&self, wallet_address: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.process_recovery_timeout()?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Execute a conditional transfer: move funds into escrow pending release. See
+    /// `PassWalletState::create_conditional_transfer`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_conditional_transfer(
+        &self,
+        wallet_address: &str,
+        asset_id: &str,
+        amount: Amount,
+        from_subaccount: &str,
+        to_subaccount: &str,
+        release_after: Option<u64>,
+        witnesses: Vec<String>,
+        required_signatures: u32,
+        cancelable_by: Option<String>,
+    ) -> Result<String> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let escrow_id = wallet_state.create_conditional_transfer(
+            asset_id, amount, from_subaccount, to_subaccount, release_after, witnesses, required_signatures, cancelable_by,
+        )?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(escrow_id)
+    }
+
+    /// Release an escrow whose `release_after` timestamp has elapsed. See
+    /// `PassWalletState::release_escrow`.
+    pub fn release_escrow(&self, wallet_address: &str, escrow_id: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.release_escrow(escrow_id)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Record a witness's approval of an escrow, verifying `signature` over the escrow against
+    /// `witness`'s KMS-managed key before it counts toward `required_signatures`. Releases the
+    /// escrow (and returns `true`) once enough distinct witnesses have approved.
+    pub fn witness_approve(&self, wallet_address: &str, escrow_id: &str, witness: &str, signature: &str) -> Result<bool> {
+        let message = format!("escrow-approve:{}:{}", wallet_address, escrow_id);
+        let is_valid = {
+            let kms = self.kms.lock().unwrap();
+            kms.verify_message(&message, signature, witness)?
+        };
+        if !is_valid {
+            return Err(anyhow!("Invalid witness signature for escrow {}", escrow_id));
+        }
+
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let released = wallet_state.witness_approve(escrow_id, witness)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(released)
+    }
+
+    /// Cancel an escrow, returning its funds to the source subaccount. See
+    /// `PassWalletState::cancel_conditional_transfer`.
+    pub fn cancel_conditional_transfer(&self, wallet_address: &str, escrow_id: &str, requester: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.cancel_conditional_transfer(escrow_id, requester)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// List escrows still pending release for a wallet
+    pub fn get_pending_escrows(&self, wallet_address: &str) -> Result<Vec<Escrow>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        Ok(wallet_state.escrows.values().cloned().collect())
+    }
+
+    /// Open (creating if absent) the advisory lockfile guarding `wallet_address`'s outbox, shared
+    /// by path across every process on the host so two concurrently launched instances of the
+    /// app can never both drain the same outbox. The returned `FileLock` owns the open file
+    /// descriptor; callers must keep it alive (via `try_write`'s guard) for exactly as long as the
+    /// drain, and let it drop afterward - the advisory lock (and the fd it rides on) is released
+    /// the instant that happens, so a crash mid-drain can never leave a stale lock behind.
+    fn open_outbox_lock(wallet_address: &str) -> Result<FileLock<File>> {
+        let lock_path =
+            std::env::temp_dir().join(format!("pass-wallet-outbox-{}.lock", wallet_address));
+        let file = File::create(&lock_path).map_err(|e| {
+            anyhow!(
+                "Failed to open outbox lockfile for wallet {}: {}",
+                wallet_address,
+                e
+            )
+        })?;
+        Ok(FileLock::new(file))
+    }
+
+    /// Derive the symmetric key `encrypt_outbox_memo` seals outbox memos under: per-wallet, so
+    /// compromising one wallet's memos doesn't expose another's, and reproducible from the
+    /// enclave's own provenance genesis rather than stored anywhere new.
+    fn outbox_memo_key(&self, wallet_address: &str) -> [u8; 32] {
+        let genesis = {
+            let kms = self.kms.lock().unwrap();
+            kms.provenance_genesis(wallet_address)
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(b"pass-wallet-outbox-memo-key");
+        hasher.update(genesis.as_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Seal `memo` for storage in `OutboxEntry::memo`. Validated against `MAX_OUTBOX_MEMO_BYTES`,
+    /// then zero-padded to that fixed length (prefixed with its true length) before encryption, so
+    /// ciphertext size never leaks how long the real memo was.
+    fn encrypt_outbox_memo(&self, wallet_address: &str, memo: &str) -> Result<Vec<u8>> {
+        let plaintext = memo.as_bytes();
+        if plaintext.len() > MAX_OUTBOX_MEMO_BYTES {
+            return Err(anyhow!("Outbox memo exceeds maximum length of {} bytes", MAX_OUTBOX_MEMO_BYTES));
+        }
+
+        let mut padded = vec![0u8; 2 + MAX_OUTBOX_MEMO_BYTES];
+        padded[0..2].copy_from_slice(&(plaintext.len() as u16).to_be_bytes());
+        padded[2..2 + plaintext.len()].copy_from_slice(plaintext);
+
+        let key = self.outbox_memo_key(wallet_address);
+        let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(&key));
+        let mut nonce_bytes = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+        let ciphertext = cipher
+            .encrypt(ChaChaNonce::from_slice(&nonce_bytes), padded.as_ref())
+            .map_err(|_| anyhow!("Failed to encrypt outbox memo"))?;
+
+        let mut sealed = nonce_bytes.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Derive the symmetric key `encrypt_user_memo`/`decrypt_user_memo` seal `StoredMemo`s under:
+    /// per-subaccount rather than per-wallet like `outbox_memo_key`, so one subaccount in a
+    /// multi-subaccount wallet can't decrypt a memo sealed to another.
+    fn user_memo_key(&self, wallet_address: &str, subaccount_id: &str) -> [u8; 32] {
+        let genesis = {
+            let kms = self.kms.lock().unwrap();
+            kms.provenance_genesis(wallet_address)
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(b"pass-wallet-user-memo-key");
+        hasher.update(genesis.as_bytes());
+        hasher.update(subaccount_id.as_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Seal `memo` to `subaccount_id`'s own key for storage in a `StoredMemo`. Same fixed-length
+    /// padding scheme as `encrypt_outbox_memo`, validated against `MAX_USER_MEMO_BYTES`.
+    fn encrypt_user_memo(&self, wallet_address: &str, subaccount_id: &str, memo: &str) -> Result<Vec<u8>> {
+        let plaintext = memo.as_bytes();
+        if plaintext.len() > MAX_USER_MEMO_BYTES {
+            return Err(anyhow!("Memo exceeds maximum length of {} bytes", MAX_USER_MEMO_BYTES));
+        }
+
+        let mut padded = vec![0u8; 2 + MAX_USER_MEMO_BYTES];
+        padded[0..2].copy_from_slice(&(plaintext.len() as u16).to_be_bytes());
+        padded[2..2 + plaintext.len()].copy_from_slice(plaintext);
+
+        let key = self.user_memo_key(wallet_address, subaccount_id);
+        let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(&key));
+        let mut nonce_bytes = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+        let ciphertext = cipher
+            .encrypt(ChaChaNonce::from_slice(&nonce_bytes), padded.as_ref())
+            .map_err(|_| anyhow!("Failed to encrypt memo"))?;
+
+        let mut sealed = nonce_bytes.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Inverse of `encrypt_user_memo`: unseal a `StoredMemo::ciphertext` back to its original
+    /// plaintext, trusting only `subaccount_id`'s own key to open it.
+    fn decrypt_user_memo(&self, wallet_address: &str, subaccount_id: &str, ciphertext: &[u8]) -> Result<String> {
+        if ciphertext.len() < 12 {
+            return Err(anyhow!("Memo ciphertext too short"));
+        }
+        let (nonce_bytes, sealed) = ciphertext.split_at(12);
+
+        let key = self.user_memo_key(wallet_address, subaccount_id);
+        let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(&key));
+        let padded = cipher
+            .decrypt(ChaChaNonce::from_slice(nonce_bytes), sealed)
+            .map_err(|_| anyhow!("Failed to decrypt memo"))?;
+
+        if padded.len() < 2 {
+            return Err(anyhow!("Decrypted memo malformed"));
+        }
+        let len = u16::from_be_bytes([padded[0], padded[1]]) as usize;
+        let plaintext = padded.get(2..2 + len).ok_or_else(|| anyhow!("Decrypted memo malformed"))?;
+        String::from_utf8(plaintext.to_vec()).map_err(|_| anyhow!("Decrypted memo is not valid UTF-8"))
+    }
+
+    /// Every memo sealed to `subaccount_id` (by a claimed deposit or an `internal_transfer`),
+    /// decrypted and returned in the order they were stored. Only `subaccount_id`'s own key can
+    /// open these - a memo sealed to a different subaccount errors rather than returning garbage.
+    pub fn get_memos(&self, wallet_address: &str, subaccount_id: &str) -> Result<Vec<String>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.user_memos(subaccount_id).iter()
+            .map(|stored| self.decrypt_user_memo(wallet_address, subaccount_id, &stored.ciphertext))
+            .collect()
+    }
+
+    /// Process outbox
+    pub fn process_outbox(&self, wallet_address: &str) -> Result<Vec<OutboxEntry>> {
+        let mut outbox_lock = Self::open_outbox_lock(wallet_address)?;
+        let _guard = outbox_lock.try_write().map_err(|_| {
+            anyhow!(
+                "Wallet {} is already in use by another process",
+                wallet_address
+            )
+        })?;
+
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let processed = wallet_state.process_outbox()?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(processed)
+    }
+
+    /// Pre-flight check every item currently queued in `wallet_address`'s outbox without draining
+    /// or mutating anything. See `PassWalletState::validate_outbox`.
+    pub fn validate_outbox(&self, wallet_address: &str) -> Result<Vec<OutboxValidationError>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        Ok(wallet_state.validate_outbox())
+    }
+
+    /// Drain every wallet's outbox in turn, so a process managing several wallets doesn't need a
+    /// caller to enumerate `list_wallets()` and call `process_outbox` itself one at a time. One
+    /// wallet's drain failing (e.g. its advisory lock is held by another process) doesn't stop the
+    /// rest - the failure is recorded against that wallet's address and the sweep continues.
+    pub fn process_all_outboxes(&self) -> HashMap<WalletAddress, Result<Vec<OutboxEntry>, String>> {
+        self.list_wallets()
+            .into_iter()
+            .map(|address| {
+                let result = self.process_outbox(&address).map_err(|e| e.to_string());
+                (address, result)
+            })
+            .collect()
+    }
+
+    /// Drain `wallet_address`'s outbox and serialize each entry in `format`, signing over the
+    /// exact serialized bytes so the signature attests to what will actually be broadcast. The
+    /// drain and the signing happen against a single `get_wallet`/`update_wallet` round trip, so
+    /// calling this with a different `format` can never re-drain (and so double-spend) a
+    /// withdrawal that was already processed. Held for the same duration is the advisory outbox
+    /// lock from `open_outbox_lock`, so a second, concurrently launched instance of the app can
+    /// never race this drain either.
+    pub fn process_outbox_for_broadcast(
+        &self,
+        wallet_address: &str,
+        format: WithdrawSerializeType,
+    ) -> Result<Vec<SignedWithdrawal>> {
+        let mut outbox_lock = Self::open_outbox_lock(wallet_address)?;
+        let _guard = outbox_lock.try_write().map_err(|_| {
+            anyhow!(
+                "Wallet {} is already in use by another process",
+                wallet_address
+            )
+        })?;
+
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let processed = wallet_state.process_outbox()?;
+        let mut signed = Vec::with_capacity(processed.len());
+        for entry in processed {
+            let payload = PassWalletState::serialize_outbox_entry(&entry, format)?;
+            let message = hex::encode(&payload);
+            let signature = {
+                let kms = self.kms.lock().unwrap();
+                kms.sign_message(&message, wallet_address)?
+                    .ok_or_else(|| anyhow!("Failed to sign withdrawal - wallet not found"))?
+            };
+            signed.push(SignedWithdrawal {
+                entry,
+                format,
+                payload,
+                signature,
+            });
+        }
+
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(signed)
+    }
+
+    /// Offline phase of the air-gapped outbox workflow: drain and sign `wallet_address`'s outbox
+    /// exactly as `process_outbox_for_broadcast` does, then write the result plus `chain_id` to
+    /// `output_path` as an `OutboxSigningBundle` instead of returning it. Never makes a network
+    /// call, so it can run on a machine that holds signing keys but has no network access;
+    /// `broadcast_outbox` on an online machine later reads the file back and submits it. Returns
+    /// the number of withdrawals written.
+    pub fn sign_outbox(
+        &self,
+        wallet_address: &str,
+        format: WithdrawSerializeType,
+        chain_id: u64,
+        output_path: &std::path::Path,
+    ) -> Result<usize> {
+        let signed_withdrawals = self.process_outbox_for_broadcast(wallet_address, format)?;
+        let bundle = OutboxSigningBundle {
+            wallet_address: wallet_address.to_string(),
+            chain_id,
+            format,
+            signed_withdrawals,
+        };
+
+        let bytes = serde_json::to_vec_pretty(&bundle)
+            .map_err(|e| anyhow!("Failed to serialize outbox signing bundle: {}", e))?;
+        std::fs::write(output_path, &bytes)
+            .map_err(|e| anyhow!("Failed to write outbox signing bundle {:?}: {}", output_path, e))?;
+        Ok(bundle.signed_withdrawals.len())
+    }
+
+    /// Online phase of the air-gapped outbox workflow: read a bundle written by `sign_outbox` and
+    /// hand each already-signed withdrawal to `submit` for broadcast, without touching the
+    /// enclave or any signing key. Stops at the first submission `submit` rejects, since later
+    /// entries may be nonce-ordered after it.
+    pub fn broadcast_outbox(
+        &self,
+        input_path: &std::path::Path,
+        mut submit: impl FnMut(&SignedWithdrawal) -> Result<()>,
+    ) -> Result<Vec<SignedWithdrawal>> {
+        let bytes = std::fs::read(input_path)
+            .map_err(|e| anyhow!("Failed to read outbox signing bundle {:?}: {}", input_path, e))?;
+        let bundle: OutboxSigningBundle = serde_json::from_slice(&bytes)
+            .map_err(|e| anyhow!("Failed to parse outbox signing bundle {:?}: {}", input_path, e))?;
+
+        for signed in &bundle.signed_withdrawals {
+            submit(signed)?;
+        }
+        Ok(bundle.signed_withdrawals)
+    }
+
+    /// Drain `wallet_address`'s outbox and, for each entry, build and sign a genuine EIP-155
+    /// Ethereum transaction through `EnclaveKMS` - the legacy `[nonce, gasPrice, gasLimit, to,
+    /// value, data]` RLP list, keccak-hashed and signed with `v = recovery_id + chain_id*2 + 35` -
+    /// rather than `process_outbox_for_broadcast`'s signature over a serialized description of the
+    /// entry. ETH entries transfer `amount` straight to `entry.external_destination`; ERC20
+    /// entries target the asset's contract with `transfer(address,uint256)` calldata, built and
+    /// signed via the same `build_eth_transaction`/`build_erc20_transaction` helpers
+    /// `withdraw_to_external` uses. Every entry bumps the wallet's on-chain account nonce so a
+    /// batch drained together never collides on broadcast, and the resulting raw transaction is
+    /// backfilled onto the matching `ProvenanceRecord` (found by `outbox_nonce`) for auditing, the
+    /// same way `record_mined` backfills `block_number`. Held for the same duration as
+    /// `process_outbox` is the advisory outbox lock, so a concurrent drain can't double-spend.
+    pub fn process_outbox_signed(
+        &self,
+        wallet_address: &str,
+        chain_id: u64,
+        gas_price: Option<u64>,
+    ) -> Result<Vec<SignedRawWithdrawal>> {
+        let mut outbox_lock = Self::open_outbox_lock(wallet_address)?;
+        let _guard = outbox_lock.try_write().map_err(|_| {
+            anyhow!(
+                "Wallet {} is already in use by another process",
+                wallet_address
+            )
+        })?;
+
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let base_nonce = wallet_state.nonce;
+        let processed = wallet_state.process_outbox()?;
+        let fee_params = FeeParams::Legacy { gas_price: gas_price.unwrap_or(20_000_000_000) };
+
+        let mut signed = Vec::with_capacity(processed.len());
+        for (index, entry) in processed.into_iter().enumerate() {
+            let asset = wallet_state.assets.get(&entry.asset_id)
+                .ok_or_else(|| anyhow!("Asset not found: {}", entry.asset_id))?
+                .clone();
+            let to_address = parse_address(&entry.external_destination)?;
+            let account_nonce = base_nonce + index as u64 + 1;
+
+            let raw_transaction = match asset.token_type {
+                TokenType::ETH => self.build_eth_transaction(
+                    to_address,
+                    entry.amount,
+                    asset.decimals,
+                    account_nonce,
+                    fee_params,
+                    21_000,
+                    chain_id,
+                    wallet_address,
+                )?,
+                TokenType::ERC20 => {
+                    let contract_address = parse_address(
+                        asset.contract_address.as_ref()
+                            .ok_or_else(|| anyhow!("ERC20 contract address not found"))?
+                    )?;
+                    self.build_erc20_transaction(
+                        contract_address,
+                        to_address,
+                        entry.amount,
+                        account_nonce,
+                        fee_params,
+                        60_000,
+                        chain_id,
+                        wallet_address,
+                        Vec::new(),
+                    )?
+                }
+                other => {
+                    return Err(anyhow!("Outbox signing not supported for asset type: {:?}", other));
+                }
+            };
+
+            if let Some(record) = wallet_state.history.iter_mut().rev()
+                .find(|record| record.outbox_nonce == Some(entry.nonce))
+            {
+                record.signed_raw_tx = Some(raw_transaction.clone());
+            }
+
+            signed.push(SignedRawWithdrawal {
+                entry,
+                chain_id,
+                account_nonce,
+                raw_transaction,
+            });
+        }
+
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(signed)
+    }
+
+    /// Build a signed Apple Wallet receipt for `entry` (an entry just returned by
+    /// `process_outbox`/`process_outbox_for_broadcast`, identified by `tx_id`) and write it as
+    /// `<tx_id>.pkpass` under `output_dir`, so the pass can be opened straight into iOS/watchOS
+    /// Wallet.
+    pub fn export_payment_receipt_pass(
+        &self,
+        cert: &PassSigningCertificate,
+        issuer: &PassIssuer,
+        tx_id: &str,
+        entry: &OutboxEntry,
+        output_dir: &std::path::Path,
+    ) -> Result<std::path::PathBuf> {
+        let bytes = crate::passes::build_payment_receipt_pass(
+            cert,
+            issuer,
+            tx_id,
+            entry,
+            PassWalletState::get_timestamp(),
+        )?;
+
+        std::fs::create_dir_all(output_dir)
+            .map_err(|e| anyhow!("Failed to create pass output directory {:?}: {}", output_dir, e))?;
+        let path = output_dir.join(format!("{}.pkpass", tx_id));
+        std::fs::write(&path, bytes)
+            .map_err(|e| anyhow!("Failed to write pkpass {:?}: {}", path, e))?;
+        Ok(path)
+    }
+
+    /// Build a signed Apple Wallet "receive" card showing `wallet_address` as a barcode, and
+    /// write it as `<wallet_address>-receive.pkpass` under `output_dir`.
+    pub fn export_receive_card_pass(
+        &self,
+        cert: &PassSigningCertificate,
+        issuer: &PassIssuer,
+        wallet_address: &str,
+        output_dir: &std::path::Path,
+    ) -> Result<std::path::PathBuf> {
+        if self.get_wallet(wallet_address).is_none() {
+            return Err(anyhow!("Wallet not found"));
+        }
+
+        let bytes = crate::passes::build_receive_card_pass(cert, issuer, &wallet_address.to_string())?;
+
+        std::fs::create_dir_all(output_dir)
+            .map_err(|e| anyhow!("Failed to create pass output directory {:?}: {}", output_dir, e))?;
+        let path = output_dir.join(format!("{}-receive.pkpass", wallet_address));
+        std::fs::write(&path, bytes)
+            .map_err(|e| anyhow!("Failed to write pkpass {:?}: {}", path, e))?;
+        Ok(path)
+    }
+
+    /// Add asset to wallet
+    pub fn add_asset(&self, wallet_address: &str, asset_id: String, asset: Asset) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        
+        wallet_state.add_asset(asset_id, asset);
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Mirror an ERC-20 contract into a wallet under a deterministic asset id derived from
+    /// `contract_address`. See `PassWalletState::mirror_asset`.
+    pub fn mirror_asset(
+        &self,
+        wallet_address: &str,
+        contract_address: &str,
+        metadata: Erc20Metadata,
+    ) -> Result<String> {
+        let mut wallet_state = self
+            .get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let asset_id = wallet_state.mirror_asset(contract_address, metadata)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(asset_id)
+    }
+
+    /// Configure a withdrawal policy on a wallet, optionally scoped to one subaccount. See
+    /// `PassWalletState::set_withdrawal_policy`.
+    pub fn set_withdrawal_policy(
+        &self,
+        wallet_address: &str,
+        asset_id: &str,
+        subaccount_id: Option<&str>,
+        max_withdrawal_display: &str,
+        window_seconds: Option<u64>,
+        window_max_display: Option<&str>,
+    ) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.set_withdrawal_policy(asset_id, subaccount_id, max_withdrawal_display, window_seconds, window_max_display)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Configure a simple rolling-window withdrawal velocity limit for `asset_id`, shared across
+    /// every subaccount of the wallet (e.g. "max 5000 USDC per 24h"). `amount_human` is authored
+    /// in display units and scaled by the asset's `decimals`, same as `set_withdrawal_policy`. A
+    /// thin, asset-wide convenience over `set_withdrawal_policy`: the window cap doubles as the
+    /// per-transaction cap, since no single withdrawal should be able to exceed the whole window's
+    /// budget anyway.
+    pub fn set_withdrawal_limit(&self, wallet_address: &str, asset_id: &str, amount_human: &str, window_secs: u64) -> Result<()> {
+        self.set_withdrawal_policy(wallet_address, asset_id, None, amount_human, Some(window_secs), Some(amount_human))
+    }
+
+    /// Base-unit budget still available in the current rolling window for `asset_id`'s wallet-wide
+    /// withdrawal limit (see `set_withdrawal_limit`), or `None` if no window limit is configured
+    /// for that asset. See `PassWalletState::remaining_withdrawal_limit`.
+    pub fn get_remaining_limit(&self, wallet_address: &str, asset_id: &str) -> Result<Option<Amount>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        Ok(wallet_state.remaining_withdrawal_limit(asset_id))
+    }
+
+    /// The static per-operation withdrawal cap configured for `asset_id` (see
+    /// `set_withdrawal_limit`), in base units, or `None` if unconfigured. See
+    /// `PassWalletState::withdrawal_limit`.
+    pub fn withdrawal_limit(&self, wallet_address: &str, asset_id: &str) -> Result<Option<Amount>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        Ok(wallet_state.withdrawal_limit(asset_id))
+    }
+
+    /// Configure a proportional (basis-points) withdrawal fee for `asset_id`. See
+    /// `PassWalletState::set_withdrawal_fee`.
+    pub fn set_withdrawal_fee(&self, wallet_address: &str, asset_id: &str, fee_bps: u32) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.set_withdrawal_fee(asset_id, fee_bps)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Base units of `asset_id` accrued into the wallet's reserved withdrawal-fee subaccount
+    /// (`WITHDRAWAL_FEE_SUBACCOUNT_ID`) via `set_withdrawal_fee`, awaiting `sweep_fees`. Doesn't
+    /// include anything accrued by a fixed `fee_policy`, which accrues separately into
+    /// `FEE_SUBACCOUNT_ID`.
+    pub fn get_collected_fees(&self, wallet_address: &str, asset_id: &str) -> Result<Amount> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        Ok(wallet_state.get_balance(WITHDRAWAL_FEE_SUBACCOUNT_ID, asset_id))
+    }
+
+    /// Move every base unit of `asset_id` currently accrued in the withdrawal-fee subaccount into
+    /// `to_subaccount`, recording a `FeeSwept` provenance entry, and return the swept amount. A
+    /// no-op (returns zero) if nothing has accrued. Doesn't touch `FEE_SUBACCOUNT_ID`'s fixed-fee
+    /// balance - that's a separate pool with its own lifecycle.
+    pub fn sweep_fees(&self, wallet_address: &str, asset_id: &str, to_subaccount: &str) -> Result<Amount> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let collected = wallet_state.get_balance(WITHDRAWAL_FEE_SUBACCOUNT_ID, asset_id);
+        if collected.is_zero() {
+            return Ok(Amount::zero());
+        }
+
+        wallet_state.set_balance(WITHDRAWAL_FEE_SUBACCOUNT_ID, asset_id, Amount::zero());
+        let destination_balance = wallet_state.get_balance(to_subaccount, asset_id);
+        wallet_state.set_balance(to_subaccount, asset_id, destination_balance.saturating_add(collected));
+
+        wallet_state.append_history(ProvenanceRecord {
+            operation: TransactionOperation::FeeSwept {
+                asset_id: asset_id.to_string(),
+                amount: collected,
+                to_subaccount: to_subaccount.to_string(),
+            },
+            timestamp: PassWalletState::get_timestamp(),
+            block_number: None,
+            limit_applied: None,
+            tx_nonce: None,
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(collected)
+    }
+
+    /// Configure a wallet's fixed per-transaction fee policy. See `PassWalletState::set_fee_policy`.
+    pub fn set_fee_policy(&self, wallet_address: &str, policy: FeePolicy) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.set_fee_policy(Some(policy));
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Register `owner_key` (an Ethereum address) as the key the host must produce an
+    /// `ecrecover`-able signature from to authorize a mutating operation through
+    /// `withdraw_authorized`/`internal_transfer_authorized`/`claim_inbox_authorized`. Typically
+    /// called once right after `create_wallet`; calling it again rotates the key.
+    pub fn set_owner_key(&self, wallet_address: &str, owner_key: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.owner_key = Some(owner_key.to_string());
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Recover the signer of `owner_signature` over `message` and check it against
+    /// `wallet_state.owner_key`, failing closed if no owner key has been registered at all.
+    /// Shared by every `_authorized` entry point.
+    fn verify_owner_signature(&self, wallet_state: &PassWalletState, message: &str, owner_signature: &[u8]) -> Result<()> {
+        let owner_key = wallet_state.owner_key.as_ref()
+            .ok_or_else(|| anyhow!("AuthError: wallet has no owner key registered - call set_owner_key first"))?;
+
+        let kms = self.kms.lock().unwrap();
+        let recovered = kms.recover_address(message, &hex::encode(owner_signature))
+            .map_err(|e| anyhow!("AuthError: failed to recover signer: {}", e))?;
+
+        if !recovered.eq_ignore_ascii_case(owner_key) {
+            return Err(anyhow!(
+                "AuthError: recovered signer {} does not match registered owner key {}",
+                recovered, owner_key
+            ));
+        }
+        Ok(())
+    }
+
+    /// Authorized variant of `claim_inbox`: `owner_signature` must be an `ecrecover`-able
+    /// signature over `canonical_auth_message("claim_inbox", wallet_address, deposit_id,
+    /// 0, subaccount_id, "", wallet's current nonce)` from the wallet's registered `owner_key`
+    /// (see `set_owner_key`). The embedded nonce must match `wallet_state.nonce` exactly - this is
+    /// what stops a captured signature from being replayed - and is bumped by one on success.
+    pub fn claim_inbox_authorized(&self, wallet_address: &str, deposit_id: &str, subaccount_id: &str, owner_signature: Vec<u8>) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let message = canonical_auth_message("claim_inbox", wallet_address, deposit_id, Amount::zero(), subaccount_id, "", wallet_state.nonce);
+        self.verify_owner_signature(&wallet_state, &message, &owner_signature)?;
+        wallet_state.nonce += 1;
+
+        let memo = wallet_state.claim_inbox(deposit_id, subaccount_id)?;
+        if let Some(memo) = memo {
+            let ciphertext = self.encrypt_user_memo(wallet_address, subaccount_id, &memo)?;
+            wallet_state.store_user_memo(subaccount_id, ciphertext, format!("claim:{}", deposit_id));
+        }
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Authorized variant of `internal_transfer` - see `claim_inbox_authorized` for the signature
+    /// and replay-protection scheme. The signed message is
+    /// `canonical_auth_message("internal_transfer", wallet_address, asset_id, amount,
+    /// from_subaccount, to_subaccount, wallet's current nonce)`.
+    pub fn internal_transfer_authorized(
+        &self,
+        wallet_address: &str,
+        asset_id: &str,
+        amount: Amount,
+        from_subaccount: &str,
+        to_subaccount: &str,
+        memo: Option<String>,
+        owner_signature: Vec<u8>,
+    ) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let message = canonical_auth_message("internal_transfer", wallet_address, asset_id, amount, from_subaccount, to_subaccount, wallet_state.nonce);
+        self.verify_owner_signature(&wallet_state, &message, &owner_signature)?;
+        wallet_state.nonce += 1;
+
+        let encrypted_memo = memo.as_deref().map(|m| self.encrypt_user_memo(wallet_address, to_subaccount, m)).transpose()?;
+        wallet_state.internal_transfer(asset_id, amount, from_subaccount, to_subaccount, memo, encrypted_memo)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Authorized variant of `withdraw` - see `claim_inbox_authorized` for the signature and
+    /// replay-protection scheme. The signed message is `canonical_auth_message("withdraw",
+    /// wallet_address, asset_id, amount, subaccount_id, destination, wallet's current nonce)`.
+    pub fn withdraw_authorized(
+        &self,
+        wallet_address: &str,
+        asset_id: &str,
+        amount: Amount,
+        subaccount_id: &str,
+        destination: &str,
+        memo: Option<String>,
+        owner_signature: Vec<u8>,
+    ) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let message = canonical_auth_message("withdraw", wallet_address, asset_id, amount, subaccount_id, destination, wallet_state.nonce);
+        self.verify_owner_signature(&wallet_state, &message, &owner_signature)?;
+        wallet_state.nonce += 1;
+
+        let outbox_memo = memo.as_deref().map(|m| self.encrypt_outbox_memo(wallet_address, m)).transpose()?;
+        let (resolved_destination, nonce) = wallet_state.withdraw(asset_id, amount, subaccount_id, destination, memo, outbox_memo)?;
+        let proof = self.sign_payment_proof(wallet_address, &resolved_destination, asset_id, amount, nonce)?;
+        wallet_state.proofs.insert(nonce, proof);
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Add or replace a labeled contact on a wallet's address book
+    pub fn add_contact(&self, wallet_address: &str, label: &str, address: &str, allow_listed: bool) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.add_contact(label, address, allow_listed)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Remove a labeled contact from a wallet's address book
+    pub fn remove_contact(&self, wallet_address: &str, label: &str) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.remove_contact(label)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// List a wallet's contacts
+    pub fn list_contacts(&self, wallet_address: &str) -> Result<Vec<Contact>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        Ok(wallet_state.contacts.values().cloned().collect())
+    }
+
+    /// Toggle whether withdrawals from this wallet must target an allow-listed contact
+    pub fn set_require_allow_listed_destination(&self, wallet_address: &str, required: bool) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.require_allow_listed_destination = required;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Add subaccount to wallet
+    pub fn add_subaccount(&self, wallet_address: &str, subaccount: Subaccount) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        
+        wallet_state.add_subaccount(subaccount);
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Get balance for a subaccount
+    pub fn get_balance(&self, wallet_address: &str, subaccount_id: &str, asset_id: &str) -> Result<Amount> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        Ok(wallet_state.get_balance(subaccount_id, asset_id))
+    }
+
+    /// Compute the real, amount/destination-dependent gas cost of withdrawing `amount` of
+    /// `asset_id` from `subaccount_id` to `destination`, rather than reserving a flat multiple of
+    /// the asset's intrinsic cost (see `PassWalletState::estimate_withdrawal_gas` for that
+    /// cheaper, amount-agnostic estimate). For an ERC20 transfer, the real cost varies with
+    /// `amount`'s and `destination`'s byte patterns - EIP-2028 charges 4 gas per zero calldata
+    /// byte and 16 per non-zero one - so a fixed formula either over-reserves ETH or underfunds
+    /// the transaction. There's no search to do: `withdraw_to_external` takes no `gas_limit`-
+    /// dependent failure path, so the calldata cost *is* the required gas. What this does check,
+    /// by simulating the withdrawal against a clone of the real (uncredited) wallet state, is
+    /// that the withdrawal would actually succeed at all - insufficient balance, a withdrawal
+    /// policy violation, or a disallowed destination all surface here as the real error, rather
+    /// than silently handing back a gas estimate for a withdrawal that was never going to happen.
+    /// Pads the computed cost by `GAS_ESTIMATE_SAFETY_MARGIN_BPS`; fails if that exceeds
+    /// `GAS_ESTIMATE_CEILING`.
+    pub fn estimate_withdrawal_gas(
+        &self, wallet_address: &str, asset_id: &str, amount: Amount, subaccount_id: &str, destination: &str,
+    ) -> Result<u64> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let asset = wallet_state.assets.get(asset_id)
+            .ok_or_else(|| anyhow!("Asset not found: {}", asset_id))?;
+        let resolved_destination = wallet_state.resolve_destination(destination)?;
+        let to_address = parse_address(&resolved_destination)?;
+
+        let required_gas = match asset.token_type {
+            TokenType::ETH => INTRINSIC_GAS_ETH_TRANSFER,
+            TokenType::ERC20 => {
+                // Mirrors the calldata `build_erc20_transaction` would actually submit: a
+                // `transfer(address,uint256)` selector plus the 32-byte-padded destination and
+                // amount, without needing to sign anything just to measure it.
+                let mut call_data = Vec::with_capacity(68);
+                call_data.extend_from_slice(&[0xa9, 0x05, 0x9c, 0xbb]);
+                let mut addr_bytes = [0u8; 32];
+                addr_bytes[12..32].copy_from_slice(&to_address);
+                call_data.extend_from_slice(&addr_bytes);
+                let mut amount_bytes = [0u8; 32];
+                amount.to_big_endian(&mut amount_bytes);
+                call_data.extend_from_slice(&amount_bytes);
+
+                let calldata_cost: u64 = call_data.iter()
+                    .map(|&b| if b == 0 { 4 } else { 16 })
+                    .sum();
+                INTRINSIC_GAS_ERC20_TRANSFER + calldata_cost
+            }
+            _ => return Err(anyhow!("Gas estimation not supported for asset type: {:?}", asset.token_type)),
+        };
+
+        wallet_state.clone()
+            .withdraw(asset_id, amount, subaccount_id, destination, None, None)
+            .map_err(|e| anyhow!(
+                "Withdrawing {} of {} to {} would fail, so no gas estimate applies: {}",
+                amount, asset_id, destination, e
+            ))?;
+
+        let with_margin = required_gas
+            .checked_mul(10_000 + GAS_ESTIMATE_SAFETY_MARGIN_BPS)
+            .and_then(|scaled| scaled.checked_div(10_000))
+            .ok_or_else(|| anyhow!("Gas estimate overflow"))?;
+
+        if with_margin > GAS_ESTIMATE_CEILING {
+            return Err(anyhow!(
+                "Estimated gas {} for {} exceeds the {} ceiling - withdrawal would always fail",
+                with_margin, asset_id, GAS_ESTIMATE_CEILING
+            ));
+        }
+
+        Ok(with_margin)
+    }
+
+    /// Current EIP-1559-style base fee `withdraw_to_external`'s dynamic-fee path requires
+    /// `max_fee_per_gas` to cover. See `PassWalletState::current_base_fee`.
+    pub fn get_base_fee(&self, wallet_address: &str) -> Result<u64> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        Ok(wallet_state.current_base_fee())
+    }
+
+    /// Get all balances for a subaccount
+    pub fn get_subaccount_balances(&self, wallet_address: &str, subaccount_id: &str) -> Result<HashMap<String, Amount>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        
+        Ok(wallet_state.get_subaccount_balances(subaccount_id))
+    }
+
+    /// Every `(asset_id, token_id)` pair `subaccount_id` currently owns, complementing
+    /// `get_subaccount_balances`'s fungible totals. See `PassWalletState::get_subaccount_nfts`.
+    pub fn get_subaccount_nfts(&self, wallet_address: &str, subaccount_id: &str) -> Result<Vec<(String, String)>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        Ok(wallet_state.get_subaccount_nfts(subaccount_id))
+    }
+
+    /// Set the exchange rate used to value `asset_id` in terms of `reference_asset`, stored as
+    /// an exact `rate_numerator / rate_denominator` fraction.
+    pub fn set_asset_rate(
+        &self,
+        wallet_address: &str,
+        asset_id: &str,
+        reference_asset: &str,
+        rate_numerator: u128,
+        rate_denominator: u128,
+    ) -> Result<()> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.set_asset_rate(asset_id, reference_asset, rate_numerator, rate_denominator)?;
+        self.update_wallet(wallet_address, wallet_state)?;
+        Ok(())
+    }
+
+    /// Like `get_subaccount_balances`, but additionally quoting each non-zero balance in
+    /// `reference_asset`. Returns `(asset_id, balance, value)` triples.
+    pub fn get_subaccount_balances_valued(
+        &self,
+        wallet_address: &str,
+        subaccount_id: &str,
+        reference_asset: &str,
+    ) -> Result<Vec<(String, Amount, u128)>> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        wallet_state.get_subaccount_balance_values(subaccount_id, reference_asset)
+    }
+
+    /// Total value of every asset balance in `subaccount_id`, quoted in `reference_asset`.
+    /// Returns an error rather than a partial total if any balance lacks a stored rate or the
+    /// quoting math would overflow u128.
+    pub fn get_portfolio_value(&self, wallet_address: &str, subaccount_id: &str, reference_asset: &str) -> Result<u128> {
+        let valued = self.get_subaccount_balances_valued(wallet_address, subaccount_id, reference_asset)?;
+
+        let mut total: u128 = 0;
+        for (_, _, value) in valued {
+            total = total.checked_add(value).ok_or_else(|| anyhow!("Overflow while summing portfolio value"))?;
+        }
+
+        Ok(total)
+    }
+
+    /// Get wallet state summary
+    pub fn get_wallet_state(&self, wallet_address: &str) -> Result<serde_json::Value> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let mut summary = wallet_state.get_state_summary();
+        if let Some(status) = self.get_deposit_sync_status(wallet_address) {
+            if let Some(summary) = summary.as_object_mut() {
+                summary.insert("deposit_sync".to_string(), serde_json::to_value(status)?);
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Get all assets from a wallet's asset ledger with total balances across all subaccounts
+    pub fn get_wallet_assets(&self, wallet_address: &str) -> Result<serde_json::Value> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        
+        let mut assets_with_balances = serde_json::Map::new();
+        
+        for (asset_id, asset) in &wallet_state.assets {
+            // Calculate total balance for this asset across all subaccounts. 256-bit amounts can
+            // exceed what a JSON number can represent losslessly, so balances are rendered as
+            // decimal strings.
+            let total_balance: Amount = wallet_state.balances
+                .iter()
+                .filter_map(|(balance_key, amount)| {
+                    if let Some((_subaccount_id, balance_asset_id)) = balance_key.split_once(':') {
+                        if balance_asset_id == asset_id {
+                            Some(*amount)
+                        } else {
+                            None
+                        }
+                    } else {
+                        None
+                    }
+                })
+                .fold(Amount::zero(), |acc, amount| acc.saturating_add(amount));
+
+            // Get per-subaccount balances for this asset
+            let mut subaccount_balances = serde_json::Map::new();
+            for (balance_key, amount) in &wallet_state.balances {
+                if let Some((subaccount_id, balance_asset_id)) = balance_key.split_once(':') {
+                    if balance_asset_id == asset_id && !amount.is_zero() {
+                        subaccount_balances.insert(subaccount_id.to_string(), serde_json::Value::String(amount.to_string()));
+                    }
+                }
+            }
+
+            assets_with_balances.insert(asset_id.clone(), serde_json::json!({
+                "token_type": asset.token_type,
+                "contract_address": asset.contract_address,
+                "token_id": asset.token_id,
+                "symbol": asset.symbol,
+                "name": asset.name,
+                "decimals": asset.decimals,
+                "total_balance": total_balance.to_string(),
+                "subaccount_balances": subaccount_balances
+            }));
+        }
+        
+        Ok(serde_json::json!({
+            "wallet_address": wallet_address,
+            "assets": assets_with_balances
+        }))
+    }
+
+    /// Get full provenance log for a wallet
+    pub fn get_provenance_log(&self, wallet_address: &str) -> Result<serde_json::Value> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        
+        Ok(serde_json::json!({
+            "wallet_address": wallet_address,
+            "provenance_records": wallet_state.history
+        }))
+    }
+
+    /// The current tip of a wallet's provenance hashchain, so an external verifier can pin it
+    /// (e.g. record it out-of-band) and later confirm `get_provenance_log` hasn't been rolled
+    /// back to an earlier, shorter history.
+    pub fn provenance_head(&self, wallet_address: &str) -> Result<String> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        Ok(wallet_state.chain_head)
+    }
+
+    /// `provenance_head`, decoded to the raw 32-byte digest rather than its hex encoding. A wallet
+    /// that predates the hashchain (empty `chain_head`) reports the all-zero genesis hash, the
+    /// same value `append_history` would have chained the first real entry off of.
+    pub fn head_hash(&self, wallet_address: &str) -> Result<[u8; 32]> {
+        let head = self.provenance_head(wallet_address)?;
+        if head.is_empty() {
+            return Ok([0u8; 32]);
+        }
+        let bytes = hex::decode(&head).map_err(|e| anyhow!("Corrupt chain head: {}", e))?;
+        bytes
+            .try_into()
+            .map_err(|_| anyhow!("Chain head is not a 32-byte SHA-256 digest"))
+    }
+
+    /// Recompute a wallet's provenance hashchain from genesis and error on the first entry whose
+    /// stored `chain_head` doesn't match the recomputed one - reordering, deletion, or insertion of
+    /// a history record all move that entry's index, so this pins down exactly which record went
+    /// missing or was tampered with rather than just reporting the chain as a whole is broken.
+    /// A wallet with an empty stored `chain_head` predates the hashchain and is not covered.
+    pub fn verify_history(&self, wallet_address: &str) -> Result<()> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        if wallet_state.chain_head.is_empty() {
+            return Ok(());
+        }
+
+        let head = self.recompute_provenance_head(wallet_address, &wallet_state)?;
+
+        if head != wallet_state.chain_head {
+            return Err(anyhow!(
+                "Provenance hashchain head mismatch: recomputed {} but wallet records {}",
+                head, wallet_state.chain_head
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Shared recomputation loop behind `verify_history` and `verify_provenance_log`: walks
+    /// `wallet_state.history` from genesis, hashing each entry onto the running head via
+    /// `canonical_provenance_bytes`, and errors with the broken entry's index as soon as a
+    /// stored `chain_head` stops matching what was just recomputed. Returns the final recomputed
+    /// head on success so callers can compare it against `wallet_state.chain_head` themselves -
+    /// `verify_history` turns a mismatch into a detailed error, `verify_provenance_log` turns it
+    /// into a plain `false`.
+    fn recompute_provenance_head(&self, wallet_address: &str, wallet_state: &PassWalletState) -> Result<String> {
+        let genesis = {
+            let kms = self.kms.lock().unwrap();
+            kms.provenance_genesis(wallet_address)
+        };
+
+        let mut head = genesis;
+        for (index, record) in wallet_state.history.iter().enumerate() {
+            let entry_bytes = PassWalletState::canonical_provenance_bytes(&record.operation, record.timestamp);
+            let mut hasher = Sha256::new();
+            hasher.update(hex::decode(&head).map_err(|e| anyhow!("Corrupt chain head: {}", e))?);
+            hasher.update(&entry_bytes);
+            head = hex::encode(hasher.finalize());
+
+            if !record.chain_head.is_empty() && record.chain_head != head {
+                return Err(anyhow!(
+                    "Provenance hashchain broken at history entry {}: expected {}, found {}",
+                    index, head, record.chain_head
+                ));
+            }
+        }
+
+        Ok(head)
+    }
+
+    /// Sign the wallet's current provenance head with its own KMS key, so a host holding only the
+    /// serialized `history` blob can be handed this signature and attest to the exact transaction
+    /// set it commits to, without trusting the blob itself or needing direct enclave access to
+    /// recompute the chain.
+    pub fn sign_provenance_head(&self, wallet_address: &str) -> Result<String> {
+        let head = self.provenance_head(wallet_address)?;
+        let kms = self.kms.lock().unwrap();
+        kms.sign_message(&head, wallet_address)?
+            .ok_or_else(|| anyhow!("Failed to sign provenance head - wallet not found in KMS"))
+    }
+
+    /// Alias for `sign_provenance_head` under the name an auditor-facing "seal the log" workflow
+    /// would reach for: the hashchain itself (`PassWalletState::chain_head`/`append_history`) and
+    /// its verification (`verify_history`/`verify_provenance_log`) already cover every mutating
+    /// operation this is meant to seal - this just gives the act of signing its tip an explicit,
+    /// intention-revealing name for an outside auditor handing only `history` + this signature to
+    /// `verify_history`.
+    pub fn seal_history(&self, wallet_address: &str) -> Result<String> {
+        self.sign_provenance_head(wallet_address)
+    }
+
+    /// Recompute a wallet's provenance hashchain from genesis and check it against the stored
+    /// head, the same way `verify_wallet_integrity` recomputes the balances/inbox Merkle root.
+    /// Returns `true` if every entry in `history` chains forward to the stored `chain_head`, in
+    /// order, with no gap or reordering. A wallet with an empty `chain_head` predates the
+    /// hashchain and is not covered - this returns `true` for it rather than a false failure.
+    pub fn verify_provenance_log(&self, wallet_address: &str) -> Result<bool> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        if wallet_state.chain_head.is_empty() {
+            return Ok(true);
+        }
+
+        match self.recompute_provenance_head(wallet_address, &wallet_state) {
+            Ok(head) => Ok(head == wallet_state.chain_head),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Get provenance log filtered by asset
+    pub fn get_provenance_by_asset(&self, wallet_address: &str, asset_id: &str) -> Result<serde_json::Value> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        
+        let filtered_records: Vec<&ProvenanceRecord> = wallet_state.history.iter()
+            .filter(|record| {
+                match &record.operation {
+                    TransactionOperation::Claim { asset_id: a, .. } => a == asset_id,
+                    TransactionOperation::Transfer { asset_id: a, .. } => a == asset_id,
+                    TransactionOperation::Withdraw { asset_id: a, .. } => a == asset_id,
+                    TransactionOperation::Swap { asset_in, asset_out, .. } => asset_in == asset_id || asset_out == asset_id,
+                }
+            })
+            .collect();
+
+        Ok(serde_json::json!({
+            "wallet_address": wallet_address,
+            "asset_id": asset_id,
+            "provenance_records": filtered_records
+        }))
+    }
+
+    /// Get provenance log filtered by subaccount
+    pub fn get_provenance_by_subaccount(&self, wallet_address: &str, subaccount_id: &str) -> Result<serde_json::Value> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        
+        let filtered_records: Vec<&ProvenanceRecord> = wallet_state.history.iter()
+            .filter(|record| {
+                match &record.operation {
+                    TransactionOperation::Claim { subaccount_id: s, .. } => s == subaccount_id,
+                    TransactionOperation::Transfer { from_subaccount, to_subaccount, .. } => {
+                        from_subaccount == subaccount_id || to_subaccount == subaccount_id
+                    },
+                    TransactionOperation::Withdraw { subaccount_id: s, .. } => s == subaccount_id,
+                    TransactionOperation::Swap { subaccount_id: s, .. } => s == subaccount_id,
+                }
+            })
+            .collect();
+        
+        Ok(serde_json::json!({
+            "wallet_address": wallet_address,
+            "subaccount_id": subaccount_id,
+            "provenance_records": filtered_records
+        }))
+    }
+
+    /// Filter applied to a cursor-based provenance page query
+    pub fn provenance_record_matches(record: &ProvenanceRecord, filter: &ProvenanceFilter) -> bool {
+        match filter {
+            ProvenanceFilter::All => true,
+            ProvenanceFilter::Asset(asset_id) => match &record.operation {
+                TransactionOperation::Claim { asset_id: a, .. }
+                | TransactionOperation::Transfer { asset_id: a, .. }
+                | TransactionOperation::Withdraw { asset_id: a, .. } => a == asset_id,
+                TransactionOperation::Swap { asset_in, asset_out, .. } => {
+                    asset_in == asset_id || asset_out == asset_id
+                }
+            },
+            ProvenanceFilter::Subaccount(subaccount_id) => match &record.operation {
+                TransactionOperation::Claim { subaccount_id: s, .. }
+                | TransactionOperation::Withdraw { subaccount_id: s, .. }
+                | TransactionOperation::Swap { subaccount_id: s, .. } => s == subaccount_id,
+                TransactionOperation::Transfer { from_subaccount, to_subaccount, .. } => {
+                    from_subaccount == subaccount_id || to_subaccount == subaccount_id
+                }
+            },
+            ProvenanceFilter::Operation(kind) => match (kind, &record.operation) {
+                (ProvenanceOperationKind::Claim, TransactionOperation::Claim { .. }) => true,
+                (ProvenanceOperationKind::Transfer, TransactionOperation::Transfer { .. }) => true,
+                (ProvenanceOperationKind::Withdraw, TransactionOperation::Withdraw { .. }) => true,
+                (ProvenanceOperationKind::Swap, TransactionOperation::Swap { .. }) => true,
+                _ => false,
+            },
+            ProvenanceFilter::TimeRange(from, to) => record.timestamp >= *from && record.timestamp <= *to,
+            ProvenanceFilter::HasMemo => match &record.operation {
+                TransactionOperation::Transfer { memo, .. } | TransactionOperation::Withdraw { memo, .. } => memo.is_some(),
+                TransactionOperation::Claim { .. } => false,
+            },
+        }
+    }
+
+    /// Cursor-based, paged provenance query. Records are addressed by their stable index within
+    /// `history`, so clients can resume by passing back `next_cursor` instead of re-fetching and
+    /// re-materializing the whole log on every call.
+    pub fn get_provenance_page(
+        &self,
+        wallet_address: &str,
+        filter: ProvenanceFilter,
+        after_index: usize,
+        limit: usize,
+    ) -> Result<serde_json::Value> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let mut page = Vec::with_capacity(limit.min(wallet_state.history.len()));
+        let mut next_cursor: Option<usize> = None;
+
+        for (index, record) in wallet_state.history.iter().enumerate().skip(after_index) {
+            if !Self::provenance_record_matches(record, &filter) {
+                continue;
+            }
+            if page.len() == limit {
+                next_cursor = Some(index);
+                break;
+            }
+            page.push(serde_json::json!({ "index": index, "record": record }));
+        }
+
+        Ok(serde_json::json!({
+            "wallet_address": wallet_address,
+            "records": page,
+            "next_cursor": next_cursor
+        }))
+    }
+
+    /// Withdraw assets to external address - builds and signs transaction
+    pub fn withdraw_to_external(&self,
+        wallet_address: &str,
+        subaccount_id: &str,
+        asset_id: &str,
+        amount: Amount,
+        destination: &str,
+        gas_price: Option<u64>,
+        gas_limit: Option<u64>,
+        chain_id: u64,
+        memo: Option<String>,
+        max_fee_per_gas: Option<u64>,
+        max_priority_fee_per_gas: Option<u64>,
+        access_list: Vec<crate::key_manager::AccessListEntry>,
+    ) -> Result<(String, u64, u64, u64, Option<u64>, Option<u64>)> {
+        validate_memo(&memo)?;
+
+        // Get and validate wallet state (needed to resolve a contact label, if any, before parsing)
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        let destination = wallet_state.resolve_destination(destination)?;
+        let destination = destination.as_str();
+        let to_address = parse_address(destination)?;
+
+        // CRITICAL: Lock the entire withdrawal process to ensure atomicity and sequencing
+        let nonce_guard = self.nonce_lock.lock().unwrap();
+
+        let withdrawal_fee = wallet_state.withdrawal_fee_for(asset_id, amount);
+        let amount_plus_withdrawal_fee = amount.checked_add(withdrawal_fee)
+            .ok_or_else(|| anyhow!("Amount plus withdrawal fee overflows"))?;
+
+        // Check sufficient balance
+        let current_balance = wallet_state.get_balance(subaccount_id, asset_id);
+        if current_balance < amount_plus_withdrawal_fee {
+            return Err(WalletError::NotEnoughBalance {
+                asset_id: asset_id.to_string(),
+                required: amount_plus_withdrawal_fee,
+                available: current_balance,
+            }.into());
+        }
+
+        let limit_applied = wallet_state.check_withdrawal_policy(asset_id, subaccount_id, amount, false)?;
+
+        // Get asset info
+        let asset = wallet_state.assets.get(asset_id)
+            .ok_or_else(|| anyhow!("Asset not found"))?;
+        
+        // Increment wallet nonce for this transaction
+        wallet_state.nonce += 1;
+        let wallet_nonce = wallet_state.nonce;
+        
+        // Get global nonce for transaction ordering
+        let tx_nonce = self.load_global_nonce() + 1;
+        self.save_global_nonce(tx_nonce);
+        drop(nonce_guard);
+        
+        // Choose legacy vs. EIP-1559 dynamic fee based on which the caller supplied
+        let fee_params = match (max_fee_per_gas, max_priority_fee_per_gas) {
+            (Some(max_fee), Some(max_priority)) => {
+                let base_fee = wallet_state.current_base_fee();
+                if max_fee < base_fee {
+                    return Err(anyhow!(
+                        "max_fee_per_gas {} is below the current base fee {} - withdrawal would be rejected",
+                        max_fee, base_fee
+                    ));
+                }
+                FeeParams::Dynamic {
+                    max_fee_per_gas: max_fee,
+                    max_priority_fee_per_gas: max_priority,
+                }
+            }
+            _ => FeeParams::Legacy { gas_price: gas_price.unwrap_or(20_000_000_000) }, // 20 gwei default
+        };
+
+        // Build transaction based on asset type
+        let (raw_transaction, effective_fee) = match asset.token_type {
+            TokenType::ETH => {
+                let gas_limit_final = match gas_limit {
+                    Some(limit) => limit,
+                    None => wallet_state.estimate_withdrawal_gas(asset_id)?,
+                };
+                let tx = self.build_eth_transaction(
+                    to_address,
+                    amount,
+                    asset.decimals,
+                    wallet_nonce,
+                    fee_params,
+                    gas_limit_final,
+                    chain_id,
+                    wallet_address,
+                )?;
+                (tx, fee_params.with_gas_limit(gas_limit_final))
+            },
+            TokenType::ERC20 => {
+                let contract_address = parse_address(
+                    asset.contract_address
+                        .as_ref()
+                        .ok_or_else(|| anyhow!("ERC20 contract address not found"))?
+                )?;
+
+                let gas_limit_final = match gas_limit {
+                    Some(limit) => limit,
+                    None => wallet_state.estimate_withdrawal_gas(asset_id)?,
+                };
+                let tx = self.build_erc20_transaction(
+                    contract_address,
+                    to_address.clone(),
+                    amount,
+                    wallet_nonce,
+                    fee_params,
+                    gas_limit_final,
+                    chain_id,
+                    wallet_address,
+                    access_list.clone(),
+                )?;
+                (tx, fee_params.with_gas_limit(gas_limit_final))
+            },
+            _ => {
+                return Err(anyhow!("Withdrawal not supported for asset type: {:?}", asset.token_type));
+            }
+        };
+        let (actual_gas_price, actual_gas_limit, actual_max_fee_per_gas, actual_max_priority_fee_per_gas) = effective_fee;
+
+        // Dynamic-fee withdrawals feed this one's actual gas usage back into the wallet's base
+        // fee, nudging it toward whatever this wallet's withdrawals actually tend to cost.
+        if matches!(fee_params, FeeParams::Dynamic { .. }) {
+            let gas_target = wallet_state.estimate_withdrawal_gas(asset_id)?;
+            wallet_state.update_base_fee(actual_gas_limit, gas_target);
+        }
+
+        // Update wallet balance
+        let new_balance = current_balance.checked_sub(amount)
+            .ok_or_else(|| WalletError::NotEnoughBalance {
+                asset_id: asset_id.to_string(),
+                required: amount,
+                available: current_balance,
+            })?;
+        wallet_state.set_balance(subaccount_id, asset_id, new_balance);
+        
+        // Add to provenance history
+        wallet_state.append_history(ProvenanceRecord {
+            operation: TransactionOperation::Withdraw {
+                asset_id: asset_id.to_string(),
+                amount,
+                subaccount_id: subaccount_id.to_string(),
+                destination: destination.to_string(),
+                memo,
+                token_id: None,
+            },
+            timestamp: PassWalletState::get_timestamp(),
+            block_number: None, // Backfilled by record_mined once the transaction is observed on-chain
+            limit_applied,
+            tx_nonce: Some(tx_nonce),
+            reorged: false,
+            chain_head: String::new(),
+            outbox_nonce: None,
+            signed_raw_tx: None,
+        });
+        wallet_state.charge_withdrawal_fee(subaccount_id, asset_id, withdrawal_fee);
+
+        // Save updated wallet state
+        self.update_wallet(wallet_address, wallet_state)?;
+        
+        // Create pending withdrawal record
+        let pending_withdrawal = PendingWithdrawal {
+            wallet_address: wallet_address.to_string(),
+            subaccount_id: subaccount_id.to_string(),
+            asset_id: asset_id.to_string(),
+            amount,
+            destination: destination.to_string(),
+            nonce: tx_nonce,
+            signed_raw_transaction: raw_transaction.clone(),
+            created_at: PassWalletState::get_timestamp(),
+            tx_type: fee_params.envelope_type(!access_list.is_empty()),
+            effective_gas_price: actual_max_fee_per_gas.unwrap_or(actual_gas_price),
+            status: WithdrawalStatus::Queued,
+            retry_count: 0,
+            memo: None,
+        };
+
+        // Add to the nonce-ordered outbox queue, honoring replace-by-fee and the per-wallet cap
+        self.enqueue_pending_withdrawal(pending_withdrawal)?;
+
+        Ok((raw_transaction, tx_nonce, actual_gas_price, actual_gas_limit, actual_max_fee_per_gas, actual_max_priority_fee_per_gas))
+    }
+
+    /// Attempt every `WithdrawRequest` independently via `withdraw_to_external`, in order. A
+    /// failing request (insufficient balance, insufficient ETH for gas, unknown asset, ...) is
+    /// captured in its slot rather than aborting the rest of the batch - state is only mutated for
+    /// the requests that actually succeed, and the returned vector preserves input order. This is
+    /// the natural primitive for draining many subaccounts (e.g. to cold storage) in one call
+    /// without letting one bad entry block the others.
+    pub fn batch_withdraw(&self, wallet_address: &str, requests: Vec<WithdrawRequest>, chain_id: u64) -> Vec<Result<WithdrawReceipt>> {
+        requests
+            .into_iter()
+            .map(|request| {
+                self.withdraw_to_external(
+                    wallet_address,
+                    &request.subaccount_id,
+                    &request.asset_id,
+                    request.amount,
+                    &request.destination,
+                    None,
+                    request.gas_limit,
+                    chain_id,
+                    None,
+                    None,
+                    None,
+                    Vec::new(),
+                )
+                .map(|(raw_transaction, tx_nonce, gas_price, gas_limit, _, _)| WithdrawReceipt {
+                    raw_transaction,
+                    tx_nonce,
+                    gas_price,
+                    gas_limit,
+                })
+            })
+            .collect()
+    }
+
+    /// Build and sign ETH transaction
+    fn build_eth_transaction(
+        &self,
+        to: Vec<u8>,
+        amount: Amount,
+        _decimals: u32,
+        nonce: u64,
+        fee_params: FeeParams,
+        gas_limit: u64,
+        chain_id: u64,
+        wallet_address: &str,
+    ) -> Result<String> {
+        let mut kms = self.kms.lock().unwrap();
+
+        let typed_tx = match fee_params {
+            FeeParams::Legacy { gas_price } => {
+                crate::key_manager::TypedTransaction::Legacy(crate::key_manager::LegacyTransaction {
+                    nonce,
+                    gas_price: u64_to_be_bytes_minimal(gas_price),
+                    gas_limit: u64_to_be_bytes_minimal(gas_limit),
+                    to: Some(to),
+                    value: amount_to_be_bytes_minimal(amount),
+                    data: Vec::new(),
+                })
+            }
+            FeeParams::Dynamic { max_fee_per_gas, max_priority_fee_per_gas } => {
+                crate::key_manager::TypedTransaction::Eip1559(crate::key_manager::DynamicFeeTransaction {
+                    nonce,
+                    max_priority_fee_per_gas: u64_to_be_bytes_minimal(max_priority_fee_per_gas),
+                    max_fee_per_gas: u64_to_be_bytes_minimal(max_fee_per_gas),
+                    gas_limit: u64_to_be_bytes_minimal(gas_limit),
+                    to: Some(to),
+                    value: amount_to_be_bytes_minimal(amount),
+                    data: Vec::new(),
+                    access_list: Vec::new(),
+                })
+            }
+        };
+        Ok(kms.sign_typed_transaction(wallet_address, &typed_tx, chain_id)?)
+    }
+    
+    /// Build and sign ERC20 transaction
+    fn build_erc20_transaction(
+        &self,
+        token_contract: Vec<u8>,
+        to: Vec<u8>,
+        amount: Amount,
+        nonce: u64,
+        fee_params: FeeParams,
+        gas_limit: u64,
+        chain_id: u64,
+        wallet_address: &str,
+        access_list: Vec<crate::key_manager::AccessListEntry>,
+    ) -> Result<String> {
+        // ERC20 transfer function signature: transfer(address,uint256)
+        let transfer_selector = [0xa9, 0x05, 0x9c, 0xbb]; // keccak256("transfer(address,uint256)")[0:4]
+        
+        // Encode function call data
+        let mut call_data = Vec::new();
+        call_data.extend_from_slice(&transfer_selector);
+        
+        // Encode address (32 bytes, left-padded)
+        let mut addr_bytes = [0u8; 32];
+        addr_bytes[12..32].copy_from_slice(&to);
+        call_data.extend_from_slice(&addr_bytes);
+        
+        // Encode amount as the ERC20 uint256 argument (32 bytes, big-endian)
+        let mut amount_bytes = [0u8; 32];
+        amount.to_big_endian(&mut amount_bytes);
+        call_data.extend_from_slice(&amount_bytes);
+        
+        let mut kms = self.kms.lock().unwrap();
+
+        let typed_tx = match fee_params {
+            FeeParams::Legacy { gas_price } if access_list.is_empty() => {
+                crate::key_manager::TypedTransaction::Legacy(crate::key_manager::LegacyTransaction {
+                    nonce,
+                    gas_price: u64_to_be_bytes_minimal(gas_price),
+                    gas_limit: u64_to_be_bytes_minimal(gas_limit),
+                    to: Some(token_contract),
+                    value: vec![0], // Zero value for ERC20 transfers
+                    data: call_data,
+                })
+            }
+            FeeParams::Legacy { gas_price } => {
+                // Access list present: pre-declare the token contract's storage to save gas via a type-1 envelope
+                crate::key_manager::TypedTransaction::Eip2930(crate::key_manager::AccessListTransaction {
+                    nonce,
+                    gas_price: u64_to_be_bytes_minimal(gas_price),
+                    gas_limit: u64_to_be_bytes_minimal(gas_limit),
+                    to: Some(token_contract),
+                    value: vec![0], // Zero value for ERC20 transfers
+                    data: call_data,
+                    access_list,
+                })
+            }
+            FeeParams::Dynamic { max_fee_per_gas, max_priority_fee_per_gas } => {
+                crate::key_manager::TypedTransaction::Eip1559(crate::key_manager::DynamicFeeTransaction {
+                    nonce,
+                    max_priority_fee_per_gas: u64_to_be_bytes_minimal(max_priority_fee_per_gas),
+                    max_fee_per_gas: u64_to_be_bytes_minimal(max_fee_per_gas),
+                    gas_limit: u64_to_be_bytes_minimal(gas_limit),
+                    to: Some(token_contract),
+                    value: vec![0], // Zero value for ERC20 transfers
+                    data: call_data,
+                    access_list,
+                })
+            }
+        };
+        Ok(kms.sign_typed_transaction(wallet_address, &typed_tx, chain_id)?)
+    }
+    
+    /// Export a wallet's full ledger state as a portable, password-encrypted backup blob.
+    ///
+    /// The blob is `version || salt || nonce || ciphertext`, base64-encoded. The encryption
+    /// key is derived from `password` via PBKDF2-HMAC-SHA256 with a random salt, and the
+    /// serialized `PassWalletState` is sealed with ChaCha20-Poly1305. The enclave-held signing
+    /// key is never included; `import_backup` re-associates the restored state with an
+    /// existing KMS-managed address.
+    pub fn export_backup(&self, wallet_address: &str, password: &str) -> Result<String> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        wallet_state.recompute_integrity_digest();
+
+        let mut plaintext = serde_json::to_vec(&wallet_state)?;
+
+        let mut salt = [0u8; BACKUP_SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let mut key_bytes = [0u8; 32];
+        pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, BACKUP_KDF_ITERATIONS, &mut key_bytes);
+
+        let mut nonce_bytes = [0u8; BACKUP_NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+
+        let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(&key_bytes));
+        let ciphertext = cipher
+            .encrypt(ChaChaNonce::from_slice(&nonce_bytes), plaintext.as_ref())
+            .map_err(|e| anyhow!("Backup encryption failed: {}", e))?;
+        plaintext.zeroize();
+
+        let mut blob = Vec::with_capacity(1 + salt.len() + nonce_bytes.len() + ciphertext.len());
+        blob.push(BACKUP_FORMAT_VERSION);
+        blob.extend_from_slice(&salt);
+        blob.extend_from_slice(&nonce_bytes);
+        blob.extend_from_slice(&ciphertext);
+
+        Ok(BASE64_ENGINE.encode(blob))
+    }
+
+    /// Restore a wallet from a blob produced by `export_backup`.
+    ///
+    /// The restored address must already be known to the enclave's `EnclaveKMS`, since the
+    /// signing key itself is never part of the backup; restoring to an unknown address is
+    /// rejected rather than silently creating an orphaned wallet with no signer.
+    pub fn import_backup(&self, blob: &str, password: &str) -> Result<WalletAddress> {
+        let raw = BASE64_ENGINE
+            .decode(blob)
+            .map_err(|e| anyhow!("Invalid backup encoding: {}", e))?;
+
+        let header_len = 1 + BACKUP_SALT_LEN + BACKUP_NONCE_LEN;
+        if raw.len() < header_len {
+            return Err(anyhow!("Backup blob is too short"));
+        }
+
+        let version = raw[0];
+        if version != BACKUP_FORMAT_VERSION {
+            return Err(anyhow!("Unsupported backup version: {}", version));
+        }
+        let salt = &raw[1..1 + BACKUP_SALT_LEN];
+        let nonce_bytes = &raw[1 + BACKUP_SALT_LEN..header_len];
+        let ciphertext = &raw[header_len..];
+
+        let mut key_bytes = [0u8; 32];
+        pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, BACKUP_KDF_ITERATIONS, &mut key_bytes);
+
+        let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(&key_bytes));
+        let mut plaintext = cipher
+            .decrypt(ChaChaNonce::from_slice(nonce_bytes), ciphertext)
+            .map_err(|_| anyhow!("Backup decryption failed: wrong password or corrupted blob"))?;
+
+        let wallet_state: PassWalletState = serde_json::from_slice(&plaintext)?;
+        plaintext.zeroize();
+
+        if !wallet_state.integrity_digest.is_empty()
+            && wallet_state.integrity_digest != wallet_state.compute_integrity_digest()
+        {
+            return Err(anyhow!(
+                "Backup integrity digest mismatch for wallet {}: snapshot may be corrupted",
+                wallet_state.address
+            ));
+        }
+
+        let known_to_kms = {
+            let kms = self.kms.lock().unwrap();
+            kms.list_addresses()?.iter().any(|a| a == &wallet_state.address)
+        };
+        if !known_to_kms {
+            return Err(anyhow!(
+                "Backup address {} is not known to this enclave's KMS",
+                wallet_state.address
+            ));
+        }
+
+        let address = wallet_state.address.clone();
+        let bytes = serde_json::to_vec(&wallet_state)
+            .map_err(|e| anyhow!("Failed to serialize wallet state: {}", e))?;
+        self.storage.write(&Self::wallet_key(&address), bytes);
+        Ok(address)
+    }
+
+    /// Derive a `crypto_box::SecretKey` from a passphrase and salt via Argon2, for use by
+    /// `export_wallet_for_migration`/`import_wallet_for_migration`. Unlike the PBKDF2 derivation
+    /// `export_backup` uses, the migration blob is sealed with `crypto_box` (X25519 +
+    /// XSalsa20-Poly1305), which needs a proper asymmetric keypair rather than a raw symmetric key.
+    fn derive_migration_key(passphrase: &str, salt: &[u8]) -> Result<crypto_box::SecretKey> {
+        let mut key_bytes = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key_bytes)
+            .map_err(|e| anyhow!("Passphrase key derivation failed: {}", e))?;
+        let secret = crypto_box::SecretKey::from(key_bytes);
+        key_bytes.zeroize();
+        Ok(secret)
+    }
+
+    /// Export a wallet's full ledger state (subaccounts, assets, provenance) as a portable,
+    /// passphrase-encrypted blob for moving a wallet to another device.
+    ///
+    /// This is deliberately separate from `export_backup`/`import_backup`: that pair is the
+    /// QR-code backup flow and is keyed by PBKDF2 + ChaCha20-Poly1305 under a KMS-known-address
+    /// invariant, while this flow seals the state with `crypto_box` (X25519 + XSalsa20-Poly1305,
+    /// self-boxed under a passphrase-derived keypair) and is meant for restoring onto a device
+    /// that has never seen this wallet before. The blob is `version || salt || nonce ||
+    /// ciphertext`, base64url-encoded (no padding, URL-safe) so it can be embedded in a link.
+    /// The passphrase never leaves the enclave: derivation and sealing both happen here, so the
+    /// untrusted host relay only ever sees the opaque blob.
+    pub fn export_wallet_for_migration(&self, wallet_address: &str, passphrase: &str) -> Result<String> {
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        wallet_state.recompute_integrity_digest();
+
+        let mut plaintext = serde_json::to_vec(&wallet_state)?;
+
+        let mut salt = [0u8; MIGRATION_SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let secret_key = Self::derive_migration_key(passphrase, &salt)?;
+        let public_key = secret_key.public_key();
+        let sealed_box = SalsaBox::new(&public_key, &secret_key);
+
+        let nonce = crypto_box::generate_nonce(&mut rand::thread_rng());
+        let ciphertext = sealed_box
+            .encrypt(&nonce, plaintext.as_ref())
+            .map_err(|e| anyhow!("Migration export encryption failed: {}", e))?;
+        plaintext.zeroize();
+
+        let mut blob = Vec::with_capacity(1 + salt.len() + nonce.len() + ciphertext.len());
+        blob.push(MIGRATION_FORMAT_VERSION);
+        blob.extend_from_slice(&salt);
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+
+        Ok(BASE64_URL_ENGINE.encode(blob))
+    }
+
+    /// Restore a wallet from a blob produced by `export_wallet_for_migration` on another device.
+    ///
+    /// Unlike `import_backup`, which requires the restored address to already be known to this
+    /// enclave's KMS, migration-import is for a wallet this device has never held before: it is
+    /// rejected if a wallet with the blob's embedded address already exists locally, so a stale or
+    /// mistakenly repeated import can never clobber newer local state.
+    pub fn import_wallet_for_migration(&self, blob: &str, passphrase: &str) -> Result<WalletAddress> {
+        let wallet_state = Self::decrypt_migration_snapshot(blob, passphrase)?;
+
+        if self.get_wallet(&wallet_state.address).is_some() {
+            return Err(anyhow!(
+                "Wallet {} already exists on this device; migration-import refuses to overwrite it",
+                wallet_state.address
+            ));
+        }
+
+        let address = wallet_state.address.clone();
+        let bytes = serde_json::to_vec(&wallet_state)
+            .map_err(|e| anyhow!("Failed to serialize wallet state: {}", e))?;
+        self.storage.write(&Self::wallet_key(&address), bytes);
+        Ok(address)
+    }
+
+    /// Decrypt and structurally validate a blob produced by `export_wallet_for_migration`,
+    /// shared by `import_wallet_for_migration` and `verify_migration_snapshot` so the two agree
+    /// exactly on what counts as a valid snapshot.
+    fn decrypt_migration_snapshot(blob: &str, passphrase: &str) -> Result<PassWalletState> {
+        let raw = BASE64_URL_ENGINE
+            .decode(blob)
+            .map_err(|e| anyhow!("Invalid migration blob encoding: {}", e))?;
+
+        let header_len = 1 + MIGRATION_SALT_LEN + MIGRATION_NONCE_LEN;
+        if raw.len() < header_len {
+            return Err(anyhow!("Migration blob is too short"));
+        }
+
+        let version = raw[0];
+        if version != MIGRATION_FORMAT_VERSION {
+            return Err(anyhow!("Unsupported migration blob version: {}", version));
+        }
+        let salt = &raw[1..1 + MIGRATION_SALT_LEN];
+        let nonce_bytes = &raw[1 + MIGRATION_SALT_LEN..header_len];
+        let ciphertext = &raw[header_len..];
+
+        let secret_key = Self::derive_migration_key(passphrase, salt)?;
+        let public_key = secret_key.public_key();
+        let sealed_box = SalsaBox::new(&public_key, &secret_key);
+        let nonce = crypto_box::Nonce::from_slice(nonce_bytes);
+
+        let mut plaintext = sealed_box
+            .decrypt(nonce, ciphertext)
+            .map_err(|_| anyhow!("Migration blob decryption failed: wrong passphrase or corrupted blob"))?;
+
+        let wallet_state: PassWalletState = serde_json::from_slice(&plaintext)?;
+        plaintext.zeroize();
+
+        wallet_state.validate_invariants()?;
+        Ok(wallet_state)
+    }
+
+    /// Check that a migration blob decrypts under `passphrase` and satisfies every structural
+    /// invariant `import_wallet_for_migration` would enforce - duplicate-free inbox, well-formed
+    /// outbox, a reconciling integrity digest - without writing anything to storage. Returns the
+    /// wallet address the blob would restore, so a caller can decide whether importing it would
+    /// collide with a wallet already present before actually committing to the import.
+    pub fn verify_migration_snapshot(&self, blob: &str, passphrase: &str) -> Result<WalletAddress> {
+        let wallet_state = Self::decrypt_migration_snapshot(blob, passphrase)?;
+        Ok(wallet_state.address)
+    }
+
+    /// Recompute a wallet's integrity digest from its current balances and inbox deposits and
+    /// compare it against the digest stamped at its last write, detecting storage-level drift or
+    /// corruption. Returns `true` if they match.
+    pub fn verify_wallet_integrity(&self, wallet_address: &str) -> Result<bool> {
+        let wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+
+        if wallet_state.integrity_digest.is_empty() {
+            return Ok(true);
+        }
+        Ok(wallet_state.integrity_digest == wallet_state.compute_integrity_digest())
+    }
+
+    /// Remove a mined or abandoned withdrawal from the outbox queue
+    pub fn remove_from_outbox(&self, wallet_address: &str, nonce: u64) -> Result<()> {
+        self.storage.remove(&Self::outbox_key(wallet_address, nonce));
+        Ok(())
+    }
+
+    /// Record that `tx_nonce`'s withdrawal for `wallet_address` was observed mined in
+    /// `block_number`: backfills the matching `ProvenanceRecord.block_number` and starts tracking
+    /// confirmations. The withdrawal stays in the outbox queue until `advance_chain_tip` confirms
+    /// it's accumulated `CONFIRMATIONS_REQUIRED` confirmations.
+    pub fn record_mined(&self, wallet_address: &str, tx_nonce: u64, block_number: u64) -> Result<()> {
+        let key = Self::outbox_key(wallet_address, tx_nonce);
+        let withdrawal_bytes = self.storage.read(&key)
+            .ok_or_else(|| anyhow!("No pending withdrawal found for wallet {} tx_nonce {}", wallet_address, tx_nonce))?;
+        let mut withdrawal = decode_queued_withdrawal(&withdrawal_bytes)?;
+        withdrawal.status = WithdrawalStatus::Confirmed;
+        self.storage.write(&key, encode_queued_withdrawal(&withdrawal));
+
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        let record = wallet_state.history.iter_mut().rev()
+            .find(|record| record.tx_nonce == Some(tx_nonce))
+            .ok_or_else(|| anyhow!("No provenance record found for wallet {} tx_nonce {}", wallet_address, tx_nonce))?;
+        record.block_number = Some(block_number);
+        record.reorged = false;
+        self.update_wallet(wallet_address, wallet_state)?;
+
+        self.mined.lock().unwrap().insert(
+            (wallet_address.to_string(), tx_nonce),
+            MinedTransaction { withdrawal, block_number, finalized: false },
+        );
+        Ok(())
+    }
+
+    /// Advance the known chain tip to `block_height`, pruning from the outbox any mined
+    /// withdrawal that has now accumulated `CONFIRMATIONS_REQUIRED` confirmations.
+    pub fn advance_chain_tip(&self, block_height: u64) -> Result<()> {
+        *self.latest_block_height.lock().unwrap() = block_height;
+
+        let mut mined = self.mined.lock().unwrap();
+        for ((wallet_address, tx_nonce), tx) in mined.iter_mut() {
+            if !tx.finalized && block_height.saturating_sub(tx.block_number) + 1 >= CONFIRMATIONS_REQUIRED {
+                self.storage.remove(&Self::outbox_key(wallet_address, *tx_nonce));
+                tx.finalized = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Current confirmation depth for a mined withdrawal (1 the block it was mined in, 2 the next
+    /// block, and so on), or `None` if it hasn't been observed mined yet or was reorged away.
+    pub fn get_confirmation_depth(&self, wallet_address: &str, tx_nonce: u64) -> Result<Option<u64>> {
+        let mined = self.mined.lock().unwrap();
+        let latest_block_height = *self.latest_block_height.lock().unwrap();
+        Ok(mined
+            .get(&(wallet_address.to_string(), tx_nonce))
+            .map(|tx| latest_block_height.saturating_sub(tx.block_number) + 1))
+    }
+
+    /// Handle a reorg that rolled back a previously-mined block: reverts the confirmation,
+    /// re-queues the `PendingWithdrawal` if it had already been pruned from the outbox as final,
+    /// and marks the matching `ProvenanceRecord` as reorged.
+    pub fn revert_mined_block(&self, wallet_address: &str, tx_nonce: u64) -> Result<()> {
+        let key = (wallet_address.to_string(), tx_nonce);
+        let tx = self.mined.lock().unwrap().remove(&key)
+            .ok_or_else(|| anyhow!("No mined transaction found for wallet {} tx_nonce {}", wallet_address, tx_nonce))?;
+
+        if tx.finalized {
+            let mut withdrawal = tx.withdrawal;
+            withdrawal.status = WithdrawalStatus::Broadcast;
+            let bytes = encode_queued_withdrawal(&withdrawal);
+            self.storage.write(&Self::outbox_key(wallet_address, tx_nonce), bytes);
+        }
+
+        let mut wallet_state = self.get_wallet(wallet_address)
+            .ok_or_else(|| anyhow!("Wallet not found"))?;
+        let record = wallet_state.history.iter_mut().rev()
+            .find(|record| record.tx_nonce == Some(tx_nonce))
+            .ok_or_else(|| anyhow!("No provenance record found for wallet {} tx_nonce {}", wallet_address, tx_nonce))?;
+        record.block_number = None;
+        record.reorged = true;
+        self.update_wallet(wallet_address, wallet_state)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_manager::EnclaveKMS;
+
+    #[test]
+    fn test_create_wallet_manager() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+        
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        assert!(!wallet_address.is_empty());
+        
+        let wallet_state = manager.get_wallet(&wallet_address).unwrap();
+        assert_eq!(wallet_state.name, "Test Wallet");
+        assert_eq!(wallet_state.owner, "alice");
+    }
+
+    #[test]
+    fn test_multiple_wallets() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+        
+        let wallet1 = manager.create_wallet("Wallet 1".to_string(), "alice".to_string()).unwrap();
+        let wallet2 = manager.create_wallet("Wallet 2".to_string(), "bob".to_string()).unwrap();
+        
+        assert_ne!(wallet1, wallet2);
+        
+        let wallets = manager.list_wallets();
+        assert_eq!(wallets.len(), 2);
+        assert!(wallets.contains(&wallet1));
+        assert!(wallets.contains(&wallet2));
+    }
+
+    #[test]
+    fn test_wallet_operations() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+        
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        
+        // Add asset
+        let asset = Asset {
+            token_type: TokenType::ETH,
+            contract_address: None,
+            token_id: None,
+            symbol: "ETH".to_string(),
+            name: "Ethereum".to_string(),
+            decimals: 18,
+        };
+        manager.add_asset(&wallet_address, "eth".to_string(), asset).unwrap();
+        
+        // Add subaccount
+        let subaccount = Subaccount {
+            id: "sub1".to_string(),
+            label: "Main Account".to_string(),
+            address: "0x123...".to_string(),
+        };
+        manager.add_subaccount(&wallet_address, subaccount).unwrap();
+        
+        // Test deposit
+        let deposit = Deposit {
+            asset_id: "eth".to_string(),
+            amount: Amount::from(1000u64),
+            deposit_id: "deposit1".to_string(),
+            transaction_hash: "0xabc...".to_string(),
+            block_number: "12345".to_string(),
+            from_address: "0x456...".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        };
+        manager.inbox_deposit(&wallet_address, deposit).unwrap();
+        
+        // Test claim
+        manager.claim_inbox(&wallet_address, "deposit1", "sub1").unwrap();
+        
+        // Check balance
+        let balance = manager.get_balance(&wallet_address, "sub1", "eth").unwrap();
+        assert_eq!(balance, Amount::from(1000u64));
+    }
+
+    #[test]
+    fn test_backup_export_import_round_trip() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+
+        let blob = manager.export_backup(&wallet_address, "correct horse battery staple").unwrap();
+        let restored_address = manager.import_backup(&blob, "correct horse battery staple").unwrap();
+
+        assert_eq!(restored_address, wallet_address);
+        let restored = manager.get_wallet(&wallet_address).unwrap();
+        assert!(restored.subaccounts.contains_key("sub1"));
+    }
+
+    #[test]
+    fn test_backup_import_rejects_wrong_password() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let blob = manager.export_backup(&wallet_address, "correct password").unwrap();
+
+        assert!(manager.import_backup(&blob, "wrong password").is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_policy_enforces_display_unit_limit() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let usdc = Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xa0b86a33e6776e7bb8c4c9f8d9b2d5f1c4e3f1d2".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
+        };
+        manager.add_asset(&wallet_address, "usdc".to_string(), usdc).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+
+        // Limit of "1.5" USDC with 6 decimals -> 1_500_000 base units
+        manager.set_withdrawal_policy(&wallet_address, "usdc", None, "1.5", None, None).unwrap();
+
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "usdc".to_string(),
+            amount: Amount::from(10_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        assert!(manager.withdraw(&wallet_address, "usdc", Amount::from(2_000_000u64), "sub1", "0xdead", None).is_err());
+        assert!(manager.withdraw(&wallet_address, "usdc", Amount::from(1_000_000u64), "sub1", "0xdead", None).is_ok());
+    }
+
+    #[test]
+    fn test_withdrawal_limit_reports_configured_cap_in_base_units() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let usdc = Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xa0b86a33e6776e7bb8c4c9f8d9b2d5f1c4e3f1d2".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
+        };
+        manager.add_asset(&wallet_address, "usdc".to_string(), usdc).unwrap();
+
+        // Unconfigured until a policy is set.
+        assert_eq!(manager.withdrawal_limit(&wallet_address, "usdc").unwrap(), None);
+
+        // "1.5" USDC with 6 decimals -> 1_500_000 base units.
+        manager.set_withdrawal_policy(&wallet_address, "usdc", None, "1.5", None, None).unwrap();
+        assert_eq!(manager.withdrawal_limit(&wallet_address, "usdc").unwrap(), Some(Amount::from(1_500_000u64)));
+    }
+
+    #[test]
+    fn test_withdraw_rejects_non_allow_listed_destination() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let eth = Asset {
+            token_type: TokenType::ETH,
+            contract_address: None,
+            token_id: None,
+            symbol: "ETH".to_string(),
+            name: "Ethereum".to_string(),
+            decimals: 18,
+        };
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(),
+            amount: Amount::from(1000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        manager.add_contact(&wallet_address, "exchange", "0x1111111111111111111111111111111111111111", true).unwrap();
+        manager.set_require_allow_listed_destination(&wallet_address, true).unwrap();
+
+        assert!(manager.withdraw(&wallet_address, "eth", Amount::from(100u64), "sub1", "0x2222222222222222222222222222222222222222", None).is_err());
+        assert!(manager.withdraw(&wallet_address, "eth", Amount::from(100u64), "sub1", "exchange", None).is_ok());
+    }
+
+    fn test_pending_withdrawal(wallet_address: &str, nonce: u64, effective_gas_price: u64) -> PendingWithdrawal {
+        PendingWithdrawal {
+            wallet_address: wallet_address.to_string(),
+            subaccount_id: "sub1".to_string(),
+            asset_id: "eth".to_string(),
+            amount: Amount::from(100u64),
+            destination: "0x2222222222222222222222222222222222222222".to_string(),
+            nonce,
+            signed_raw_transaction: format!("0xsigned{}", nonce),
+            created_at: 0,
+            tx_type: TransactionEnvelopeType::Legacy,
+            effective_gas_price,
+            status: WithdrawalStatus::Queued,
+            retry_count: 0,
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn test_outbox_queue_ready_vs_future_split() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        // Nonce 1 is ready; nonce 3 has a gap (2 is missing) so it lands in "future"
+        manager.enqueue_pending_withdrawal(test_pending_withdrawal("wallet_a", 1, 20_000_000_000)).unwrap();
+        manager.enqueue_pending_withdrawal(test_pending_withdrawal("wallet_a", 3, 20_000_000_000)).unwrap();
+
+        let ready: Vec<_> = manager.get_ready().unwrap();
+        let future: Vec<_> = manager.get_future().unwrap();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].nonce, 1);
+        assert_eq!(future.len(), 1);
+        assert_eq!(future[0].nonce, 3);
+
+        // Filling the gap moves nonce 3 into the ready set too
+        manager.enqueue_pending_withdrawal(test_pending_withdrawal("wallet_a", 2, 20_000_000_000)).unwrap();
+        assert_eq!(manager.get_ready().unwrap().len(), 3);
+        assert_eq!(manager.get_future().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_outbox_replacement_requires_fee_bump() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        manager.enqueue_pending_withdrawal(test_pending_withdrawal("wallet_a", 1, 20_000_000_000)).unwrap();
+
+        // A replacement below the 12.5% bump threshold is rejected
+        assert!(manager.enqueue_pending_withdrawal(test_pending_withdrawal("wallet_a", 1, 21_000_000_000)).is_err());
+
+        // A replacement meeting the bump is accepted and overwrites the queued entry
+        manager.enqueue_pending_withdrawal(test_pending_withdrawal("wallet_a", 1, 22_500_000_000)).unwrap();
+        let ready = manager.get_ready().unwrap();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].effective_gas_price, 22_500_000_000);
+    }
+
+    #[test]
+    fn test_outbox_replacement_increments_retry_count() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        manager.enqueue_pending_withdrawal(test_pending_withdrawal("wallet_a", 1, 20_000_000_000)).unwrap();
+        manager.enqueue_pending_withdrawal(test_pending_withdrawal("wallet_a", 1, 22_500_000_000)).unwrap();
+
+        let ready = manager.get_ready().unwrap();
+        assert_eq!(ready[0].retry_count, 1);
+    }
+
+    #[test]
+    fn test_queued_withdrawal_round_trips_through_protobuf_encoding() {
+        let withdrawal = test_pending_withdrawal("wallet_a", 7, 30_000_000_000);
+        let bytes = encode_queued_withdrawal(&withdrawal);
+        let decoded = decode_queued_withdrawal(&bytes).unwrap();
+
+        assert_eq!(decoded.wallet_address, withdrawal.wallet_address);
+        assert_eq!(decoded.nonce, withdrawal.nonce);
+        assert_eq!(decoded.amount, withdrawal.amount);
+        assert_eq!(decoded.effective_gas_price, withdrawal.effective_gas_price);
+    }
+
+    #[test]
+    fn test_queued_withdrawal_decodes_forward_compatibly_from_empty_bytes() {
+        // Stands in for a record written by a build of this schema before `status`/`retry_count`
+        // existed: an empty protobuf payload still decodes, landing the newer fields on their
+        // defaults, instead of the outbox failing to load the entry at all.
+        let withdrawal = decode_queued_withdrawal(&[]).unwrap();
+        assert_eq!(withdrawal.status, WithdrawalStatus::Queued);
+        assert_eq!(withdrawal.retry_count, 0);
+        assert!(withdrawal.memo.is_none());
+    }
+
+    #[test]
+    fn test_process_outbox_rejects_concurrent_drain_while_lock_held() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+
+        // Simulate another process already draining this wallet's outbox by holding the
+        // lockfile open ourselves.
+        let mut held_lock = PassWalletManager::<InMemoryStorage>::open_outbox_lock(&wallet_address).unwrap();
+        let _guard = held_lock.try_write().unwrap();
+
+        let err = manager.process_outbox(&wallet_address).unwrap_err();
+        assert!(err.to_string().contains("already in use"));
+    }
+
+    #[test]
+    fn test_process_all_outboxes_drains_every_wallet_and_isolates_failures() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_a = manager.create_wallet("A".to_string(), "alice".to_string()).unwrap();
+        let wallet_b = manager.create_wallet("B".to_string(), "bob".to_string()).unwrap();
+        manager.enqueue_pending_withdrawal(test_pending_withdrawal(&wallet_a, 1, 20_000_000_000)).unwrap();
+
+        // wallet_b's outbox is already locked by another "process" - its entry in the result map
+        // should record the error rather than stopping wallet_a's drain.
+        let mut held_lock = PassWalletManager::<InMemoryStorage>::open_outbox_lock(&wallet_b).unwrap();
+        let _guard = held_lock.try_write().unwrap();
+
+        let results = manager.process_all_outboxes();
+        assert_eq!(results.len(), 2);
+        assert!(results[&wallet_a].as_ref().unwrap().iter().any(|e| e.nonce == 1));
+        assert!(results[&wallet_b].as_ref().unwrap_err().contains("already in use"));
+    }
+
+    #[test]
+    fn test_file_storage_rejects_reopening_same_canonicalized_data_dir() {
+        let dir = std::env::temp_dir().join(format!("pass-wallet-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let first = FileStorage::open(&dir).unwrap();
+        let second = FileStorage::open(&dir);
+        assert!(second.is_err());
+
+        // Dropping the first handle frees the data dir to be reopened.
+        drop(first);
+        let third = FileStorage::open(&dir);
+        assert!(third.is_ok());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_sign_outbox_then_broadcast_outbox_round_trips_through_a_file() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let eth = Asset {
+            token_type: TokenType::ETH,
+            contract_address: None,
+            token_id: None,
+            symbol: "ETH".to_string(),
+            name: "Ethereum".to_string(),
+            decimals: 18,
+        };
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(),
+            amount: Amount::from(1_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+        manager.withdraw(
+            &wallet_address, "eth", Amount::from(1_000u64), "sub1",
+            "0x2222222222222222222222222222222222222222", None,
+        ).unwrap();
+
+        let bundle_path = std::env::temp_dir().join(format!("pass-wallet-outbox-bundle-{}.json", wallet_address));
+        let written = manager.sign_outbox(&wallet_address, WithdrawSerializeType::EvmCalldata, 1, &bundle_path).unwrap();
+        assert_eq!(written, 1);
+
+        let mut submitted = Vec::new();
+        let broadcasted = manager.broadcast_outbox(&bundle_path, |signed| {
+            submitted.push(signed.entry.nonce);
+            Ok(())
+        }).unwrap();
+        assert_eq!(broadcasted.len(), 1);
+        assert_eq!(submitted, vec![broadcasted[0].entry.nonce]);
+
+        // The outbox was already drained on the offline side - a second sign_outbox finds nothing left.
+        assert_eq!(manager.sign_outbox(&wallet_address, WithdrawSerializeType::EvmCalldata, 1, &bundle_path).unwrap(), 0);
+
+        std::fs::remove_file(&bundle_path).ok();
+    }
+
+    #[test]
+    fn test_outbox_memo_round_trips_in_compact_json_but_is_rejected_in_evm_calldata() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let eth = Asset {
+            token_type: TokenType::ETH,
+            contract_address: None,
+            token_id: None,
+            symbol: "ETH".to_string(),
+            name: "Ethereum".to_string(),
+            decimals: 18,
+        };
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(),
+            amount: Amount::from(1_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        // A memo over the cap is rejected up front, before anything is queued.
+        let oversized_memo = "x".repeat(MAX_OUTBOX_MEMO_BYTES + 1);
+        assert!(manager.withdraw(
+            &wallet_address, "eth", Amount::from(100u64), "sub1",
+            "0x2222222222222222222222222222222222222222", Some(oversized_memo),
+        ).is_err());
+
+        manager.withdraw(
+            &wallet_address, "eth", Amount::from(100u64), "sub1",
+            "0x2222222222222222222222222222222222222222", Some("thanks!".to_string()),
+        ).unwrap();
+
+        // EvmCalldata can't carry the memo that's now queued.
+        assert!(manager.process_outbox_for_broadcast(&wallet_address, WithdrawSerializeType::EvmCalldata).is_err());
+
+        // CompactJson serializes the whole entry, memo included, so it round-trips untouched.
+        let signed = manager.process_outbox_for_broadcast(&wallet_address, WithdrawSerializeType::CompactJson).unwrap();
+        assert_eq!(signed.len(), 1);
+        assert!(signed[0].entry.memo.is_some());
+    }
+
+    #[test]
+    fn test_process_outbox_signed_assigns_distinct_nonces_and_backfills_history() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let eth = Asset {
+            token_type: TokenType::ETH,
+            contract_address: None,
+            token_id: None,
+            symbol: "ETH".to_string(),
+            name: "Ethereum".to_string(),
+            decimals: 18,
+        };
+        let usdc = Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xa0b86a33e6776e7bb8c4c9f8d9b2d5f1c4e3f1d2".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
+        };
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_asset(&wallet_address, "usdc".to_string(), usdc).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(),
+            amount: Amount::from(1_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "usdc".to_string(),
+            amount: Amount::from(10_000_000u64),
+            deposit_id: "d2".to_string(),
+            transaction_hash: "0xdef".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+        manager.claim_inbox(&wallet_address, "d2", "sub1").unwrap();
+
+        manager.withdraw(
+            &wallet_address, "eth", Amount::from(1_000u64), "sub1",
+            "0x2222222222222222222222222222222222222222", None,
+        ).unwrap();
+        manager.withdraw(
+            &wallet_address, "usdc", Amount::from(500u64), "sub1",
+            "0x3333333333333333333333333333333333333333", None,
+        ).unwrap();
+
+        let signed = manager.process_outbox_signed(&wallet_address, 1, None).unwrap();
+        assert_eq!(signed.len(), 2);
+        assert_eq!(signed[0].chain_id, 1);
+        // Distinct, monotonically increasing account nonces across the batch.
+        assert_eq!(signed[1].account_nonce, signed[0].account_nonce + 1);
+        // Raw transactions are hex and non-empty for both the ETH and the ERC20 withdrawal.
+        assert!(!signed[0].raw_transaction.is_empty());
+        assert!(!signed[1].raw_transaction.is_empty());
+        assert_ne!(signed[0].raw_transaction, signed[1].raw_transaction);
+
+        // Each signed raw transaction is backfilled onto its matching `Withdraw` provenance record.
+        let state = manager.get_wallet(&wallet_address).unwrap();
+        for entry in &signed {
+            let record = state.history.iter()
+                .find(|r| r.outbox_nonce == Some(entry.entry.nonce))
+                .unwrap();
+            assert_eq!(record.signed_raw_tx, Some(entry.raw_transaction.clone()));
+        }
+
+        // The outbox is drained - a second call finds nothing left to sign.
+        assert!(manager.process_outbox_signed(&wallet_address, 1, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_withdraw_authorized_requires_a_valid_nonce_bound_owner_signature() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms.clone());
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let eth = Asset {
+            token_type: TokenType::ETH,
+            contract_address: None,
+            token_id: None,
+            symbol: "ETH".to_string(),
+            name: "Ethereum".to_string(),
+            decimals: 18,
+        };
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(),
+            amount: Amount::from(1000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        // No owner key registered yet - every authorized call is refused.
+        let nonce = manager.get_wallet(&wallet_address).unwrap().nonce;
+        let message = canonical_auth_message(
+            "withdraw", &wallet_address, "eth", Amount::from(100u64), "sub1",
+            "0x1111111111111111111111111111111111111111", nonce,
+        );
+        let bogus_signature = vec![0u8; 65];
+        assert!(manager.withdraw_authorized(
+            &wallet_address, "eth", Amount::from(100u64), "sub1",
+            "0x1111111111111111111111111111111111111111", None, bogus_signature.clone(),
+        ).is_err());
+
+        let owner_address = kms.lock().unwrap().handle_keygen().unwrap().address;
+        manager.set_owner_key(&wallet_address, &owner_address).unwrap();
+
+        // A garbage signature is still rejected once a key is registered.
+        assert!(manager.withdraw_authorized(
+            &wallet_address, "eth", Amount::from(100u64), "sub1",
+            "0x1111111111111111111111111111111111111111", None, bogus_signature,
+        ).is_err());
+
+        let signature = kms.lock().unwrap().sign_message(&message, &owner_address).unwrap().unwrap();
+        let owner_signature = hex::decode(&signature).unwrap();
+
+        manager.withdraw_authorized(
+            &wallet_address, "eth", Amount::from(100u64), "sub1",
+            "0x1111111111111111111111111111111111111111", None, owner_signature.clone(),
+        ).unwrap();
+        assert_eq!(manager.get_wallet(&wallet_address).unwrap().nonce, nonce + 1);
+
+        // Replaying the same signature fails now that the embedded nonce is stale.
+        assert!(manager.withdraw_authorized(
+            &wallet_address, "eth", Amount::from(100u64), "sub1",
+            "0x1111111111111111111111111111111111111111", None, owner_signature,
+        ).is_err());
+    }
+
+    #[test]
+    fn test_recovery_requires_signed_owner_and_contact_authorization() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms.clone());
+
+        let owner_address = kms.lock().unwrap().handle_keygen().unwrap().address;
+        let contact_address = kms.lock().unwrap().handle_keygen().unwrap().address;
+        let attacker_address = kms.lock().unwrap().handle_keygen().unwrap().address;
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), owner_address.clone()).unwrap();
+
+        // An attacker can't designate themselves as a recovery contact without the owner's
+        // signature - signing with their own key over the right message doesn't help either,
+        // since `add_recovery_contact` checks the signature against `wallet_state.owner`.
+        let add_contact_message = format!(
+            "recovery-add-contact:{}:{}:{}:{}", wallet_address, contact_address, 0u64, 1u32,
+        );
+        let attacker_signature = kms.lock().unwrap()
+            .sign_message(&add_contact_message, &attacker_address).unwrap().unwrap();
+        assert!(manager.add_recovery_contact(
+            &wallet_address, &contact_address, 0, 1, &attacker_signature,
+        ).is_err());
+
+        // The real owner's signature over the same message succeeds.
+        let owner_signature = kms.lock().unwrap()
+            .sign_message(&add_contact_message, &owner_address).unwrap().unwrap();
+        manager.add_recovery_contact(&wallet_address, &contact_address, 0, 1, &owner_signature).unwrap();
+
+        // An attacker claiming to be the recovery contact, without that contact's signature,
+        // can't initiate a recovery even though they know the contact's address.
+        let initiate_message = format!("recovery-initiate:{}:{}", wallet_address, contact_address);
+        let bogus_signature = kms.lock().unwrap()
+            .sign_message(&initiate_message, &attacker_address).unwrap().unwrap();
+        assert!(manager.initiate_recovery(&wallet_address, &contact_address, &bogus_signature).is_err());
+
+        // The real contact's signature succeeds.
+        let contact_signature = kms.lock().unwrap()
+            .sign_message(&initiate_message, &contact_address).unwrap().unwrap();
+        manager.initiate_recovery(&wallet_address, &contact_address, &contact_signature).unwrap();
+
+        // Approval likewise requires the contact's own signature, not just their address.
+        let approve_message = format!("recovery-approve:{}:{}", wallet_address, contact_address);
+        let bogus_signature = kms.lock().unwrap()
+            .sign_message(&approve_message, &attacker_address).unwrap().unwrap();
+        assert!(manager.approve_recovery(&wallet_address, &contact_address, &bogus_signature).is_err());
+
+        let contact_signature = kms.lock().unwrap()
+            .sign_message(&approve_message, &contact_address).unwrap().unwrap();
+        let completed = manager.approve_recovery(&wallet_address, &contact_address, &contact_signature).unwrap();
+        assert!(completed);
+        assert_eq!(manager.get_wallet(&wallet_address).unwrap().owner, contact_address);
+    }
+
+    #[test]
+    fn test_estimate_withdrawal_gas_tracks_real_calldata_cost_by_amount_and_destination() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        manager.add_asset(&wallet_address, "eth".to_string(), Asset {
+            token_type: TokenType::ETH,
+            contract_address: None,
+            token_id: None,
+            symbol: "ETH".to_string(),
+            name: "Ethereum".to_string(),
+            decimals: 18,
+        }).unwrap();
+        manager.add_asset(&wallet_address, "usdc".to_string(), Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xa0b86a33e6776e7bb8c4c9f8d9b2d5f1c4e3f1d2".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
+        }).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+
+        // Enough of both assets that a real withdrawal of any amount used below would succeed -
+        // the estimate now runs against the real, uncredited balance rather than a topped-up clone.
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(),
+            amount: Amount::from(1_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "usdc".to_string(),
+            amount: Amount::MAX,
+            deposit_id: "d2".to_string(),
+            transaction_hash: "0xdef".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+        manager.claim_inbox(&wallet_address, "d2", "sub1").unwrap();
+
+        let destination = "0x1111111111111111111111111111111111111111";
+
+        // A native ETH transfer's cost doesn't depend on `amount` - just the intrinsic floor.
+        let eth_gas = manager.estimate_withdrawal_gas(
+            &wallet_address, "eth", Amount::from(1_000u64), "sub1", destination,
+        ).unwrap();
+        assert_eq!(eth_gas, 23_100); // 21000 intrinsic * 1.1 margin
+
+        // An ERC20 transfer's cost tracks the real `transfer(address,uint256)` calldata EIP-2028
+        // would charge for, so two different amounts - with different zero/non-zero byte counts
+        // in their big-endian encoding - estimate to different gas, unlike a flat formula.
+        let usdc_gas_small = manager.estimate_withdrawal_gas(
+            &wallet_address, "usdc", Amount::from(1_000u64), "sub1", destination,
+        ).unwrap();
+        let usdc_gas_large = manager.estimate_withdrawal_gas(
+            &wallet_address, "usdc", Amount::from(999_999_999_999_999_999u64), "sub1", destination,
+        ).unwrap();
+        assert_eq!(usdc_gas_small, 66_642);
+        assert_eq!(usdc_gas_large, 66_721);
+        assert_ne!(usdc_gas_small, usdc_gas_large);
+        // Both comfortably exceed the flat intrinsic-only estimate the old formula used.
+        assert!(usdc_gas_small > 66_000);
+
+        // A withdrawal that would actually fail (insufficient balance) surfaces that failure
+        // rather than silently returning a gas estimate for a withdrawal that can never happen.
+        assert!(manager.estimate_withdrawal_gas(
+            &wallet_address, "eth", Amount::from(999_999_999_999u64), "sub1", destination,
+        ).is_err());
+    }
+
+    #[test]
+    fn test_pool_virtual_shares_defeat_first_depositor_donation_attack() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        manager.add_asset(&wallet_address, "usdc".to_string(), Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xa0b86a33e6776e7bb8c4c9f8d9b2d5f1c4e3f1d2".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
+        }).unwrap();
+        for id in ["vault", "attacker_sub", "victim_sub"] {
+            manager.add_subaccount(&wallet_address, Subaccount {
+                id: id.to_string(), label: id.to_string(), address: "0x123...".to_string(),
+            }).unwrap();
+        }
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "usdc".to_string(),
+            amount: Amount::from(2_000_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "attacker_sub").unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "usdc".to_string(),
+            amount: Amount::from(1_000_000_000u64),
+            deposit_id: "d2".to_string(),
+            transaction_hash: "0xdef".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d2", "victim_sub").unwrap();
+
+        // The attacker front-runs with a 1-unit deposit, minting a single share...
+        manager.deposit_to_pool(&wallet_address, "vault", "usdc", "attacker", Amount::from(1u64)).unwrap();
+        // ...then donates directly into the vault's balance, bypassing share accounting
+        // entirely, to try to inflate the price-per-share before the victim deposits.
+        manager.internal_transfer(
+            &wallet_address, "usdc", Amount::from(1_000_000_000u64), "attacker_sub", "vault", None,
+        ).unwrap();
+
+        // The victim's deposit still mints a proportional (non-zero) share instead of being
+        // rounded away to nothing by the inflated price-per-share.
+        manager.deposit_to_pool(&wallet_address, "vault", "usdc", "victim", Amount::from(1_000_000_000u64)).unwrap();
+        let victim_shares = manager.shares_of(&wallet_address, "vault", "usdc", "victim").unwrap();
+        assert!(!victim_shares.is_zero());
+
+        // And redeeming those shares pays the victim back close to what they put in - the
+        // attacker's donation doesn't let them walk away with the victim's deposit.
+        let victim_payout = manager.withdraw_from_pool(
+            &wallet_address, "vault", "usdc", "victim", victim_shares, "victim_sub",
+        ).unwrap();
+        let attacker_shares = manager.shares_of(&wallet_address, "vault", "usdc", "attacker").unwrap();
+        let attacker_payout = manager.withdraw_from_pool(
+            &wallet_address, "vault", "usdc", "attacker", attacker_shares, "attacker_sub",
+        ).unwrap();
+
+        // The attacker spent 1,000,000,001 units (1 deposit + 1,000,000,000 donation) to attack
+        // and gets back far less than that - the attack is a net loss rather than a profit.
+        assert!(attacker_payout < Amount::from(1_000_000_001u64));
+        // The victim's payout is close to their 1,000,000,000 deposit, not diluted toward zero.
+        assert!(victim_payout > Amount::from(900_000_000u64));
+    }
+
+    #[test]
+    fn test_withdrawal_fee_and_fixed_fee_policy_accrue_into_separate_subaccounts() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let usdc = Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xa0b86a33e6776e7bb8c4c9f8d9b2d5f1c4e3f1d2".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
+        };
+        manager.add_asset(&wallet_address, "usdc".to_string(), usdc).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "usdc".to_string(),
+            amount: Amount::from(10_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        // A fixed fee_policy (10 USDC per withdrawal) and a proportional withdrawal_fee_bps
+        // (5% = 500 bps) are both configured on the same asset at once.
+        manager.set_fee_policy(&wallet_address, FeePolicy {
+            withdraw_fee: Amount::from(10_000u64),
+            transfer_fee: Amount::zero(),
+            fee_asset_id: None,
+        }).unwrap();
+        manager.set_withdrawal_fee(&wallet_address, "usdc", 500).unwrap();
+
+        manager.withdraw(&wallet_address, "usdc", Amount::from(1_000_000u64), "sub1", "0x1111111111111111111111111111111111111111", None).unwrap();
+
+        // 5% of 1,000,000 = 50,000 units accrued as the proportional withdrawal fee, readable
+        // only through get_collected_fees/sweep_fees - never through the fixed fee_policy's pool.
+        let collected = manager.get_collected_fees(&wallet_address, "usdc").unwrap();
+        assert_eq!(collected, 50_000);
+
+        // Sweeping the proportional fee out must not touch the fixed fee_policy's accrued balance.
+        let fixed_fee_subaccount_balance_before = manager.get_balance(&wallet_address, "__fees__", "usdc").unwrap();
+        let swept = manager.sweep_fees(&wallet_address, "usdc", "sub1").unwrap();
+        assert_eq!(swept, 50_000);
+        assert_eq!(manager.get_collected_fees(&wallet_address, "usdc").unwrap(), 0);
+        assert_eq!(
+            manager.get_balance(&wallet_address, "__fees__", "usdc").unwrap(),
+            fixed_fee_subaccount_balance_before,
+        );
+    }
+
+    #[test]
+    fn test_batch_withdraw_is_independent_per_request_and_preserves_order() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let usdc = Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xa0b86a33e6776e7bb8c4c9f8d9b2d5f1c4e3f1d2".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
+        };
+        manager.add_asset(&wallet_address, "usdc".to_string(), usdc).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "usdc".to_string(),
+            amount: Amount::from(5_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        let requests = vec![
+            // Succeeds: well within the 5,000,000 balance.
+            WithdrawRequest {
+                asset_id: "usdc".to_string(),
+                amount: Amount::from(1_000_000u64),
+                subaccount_id: "sub1".to_string(),
+                destination: "0x1111111111111111111111111111111111111111".to_string(),
+                gas_limit: None,
+            },
+            // Fails: asset doesn't exist on the wallet.
+            WithdrawRequest {
+                asset_id: "unknown_asset".to_string(),
+                amount: Amount::from(1_000u64),
+                subaccount_id: "sub1".to_string(),
+                destination: "0x1111111111111111111111111111111111111111".to_string(),
+                gas_limit: None,
+            },
+            // Fails: more than the remaining balance, even though it would have fit before the
+            // first request's deduction.
+            WithdrawRequest {
+                asset_id: "usdc".to_string(),
+                amount: Amount::from(4_500_000u64),
+                subaccount_id: "sub1".to_string(),
+                destination: "0x1111111111111111111111111111111111111111".to_string(),
+                gas_limit: None,
+            },
+            // Succeeds: the rest of what's left after the first request.
+            WithdrawRequest {
+                asset_id: "usdc".to_string(),
+                amount: Amount::from(2_000_000u64),
+                subaccount_id: "sub1".to_string(),
+                destination: "0x1111111111111111111111111111111111111111".to_string(),
+                gas_limit: None,
+            },
+        ];
+
+        let receipts = manager.batch_withdraw(&wallet_address, requests, 1);
+
+        assert_eq!(receipts.len(), 4);
+        assert!(receipts[0].is_ok(), "first request should succeed");
+        assert!(receipts[1].is_err(), "unknown asset should fail");
+        assert!(receipts[2].is_err(), "over-balance request should fail");
+        assert!(receipts[3].is_ok(), "fourth request should succeed against the balance left after the first");
+
+        // Only the two successful requests (1,000,000 + 2,000,000) were actually deducted - the
+        // failing requests left the balance untouched.
+        let remaining = manager.get_balance(&wallet_address, "sub1", "usdc").unwrap();
+        assert_eq!(remaining, 2_000_000);
+    }
+
+    #[test]
+    fn test_subaccount_withdrawal_policy_overrides_wallet_policy_and_is_recorded() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let usdc = Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xa0b86a33e6776e7bb8c4c9f8d9b2d5f1c4e3f1d2".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
+        };
+        manager.add_asset(&wallet_address, "usdc".to_string(), usdc).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "usdc".to_string(),
+            amount: Amount::from(10_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        // Wallet-wide limit of 5 USDC, but sub1 is overridden down to 2 USDC
+        manager.set_withdrawal_policy(&wallet_address, "usdc", None, "5", None, None).unwrap();
+        manager.set_withdrawal_policy(&wallet_address, "usdc", Some("sub1"), "2", None, None).unwrap();
+
+        // 3 USDC is under the wallet-wide limit but over sub1's override
+        assert!(manager.withdraw(&wallet_address, "usdc", Amount::from(3_000_000u64), "sub1", "0x1111111111111111111111111111111111111111", None).is_err());
+
+        manager.withdraw(&wallet_address, "usdc", Amount::from(1_000_000u64), "sub1", "0x1111111111111111111111111111111111111111", None).unwrap();
+
+        let state = manager.get_wallet(&wallet_address).unwrap();
+        let applied = state.history.iter()
+            .find_map(|r| match &r.operation {
+                TransactionOperation::Withdraw { .. } => r.limit_applied.as_ref(),
+                _ => None,
+            })
+            .expect("withdrawal should record the applied limit");
+        assert_eq!(applied.scope, "subaccount:sub1");
+        assert_eq!(applied.max_withdrawal, 2_000_000);
+    }
+
+    #[test]
+    fn test_withdrawal_limit_tracks_a_rolling_window_across_subaccounts() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let usdc = Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xa0b86a33e6776e7bb8c4c9f8d9b2d5f1c4e3f1d2".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
         };
-        
-        // Add to outbox queue (FIFO)
-        {
-            let mut outbox = self.outbox_queue.lock().unwrap();
-            outbox.push_back(pending_withdrawal);
+        manager.add_asset(&wallet_address, "usdc".to_string(), usdc).unwrap();
+        for (sub, deposit_id) in [("sub1", "d1"), ("sub2", "d2")] {
+            manager.add_subaccount(&wallet_address, Subaccount {
+                id: sub.to_string(),
+                label: sub.to_string(),
+                address: "0x123...".to_string(),
+            }).unwrap();
+            manager.inbox_deposit(&wallet_address, Deposit {
+                asset_id: "usdc".to_string(),
+                amount: Amount::from(10_000_000u64),
+                deposit_id: deposit_id.to_string(),
+                transaction_hash: "0xabc".to_string(),
+                block_number: "1".to_string(),
+                from_address: "0x456".to_string(),
+                to_address: wallet_address.clone(),
+                memo: None,
+            }).unwrap();
+            manager.claim_inbox(&wallet_address, deposit_id, sub).unwrap();
         }
-        
-        Ok((raw_transaction, tx_nonce, actual_gas_price, actual_gas_limit))
+
+        // No limit configured yet - unbounded.
+        assert_eq!(manager.get_remaining_limit(&wallet_address, "usdc").unwrap(), None);
+
+        // 5000 USDC per 24h, human units scaled by the asset's 6 decimals.
+        manager.set_withdrawal_limit(&wallet_address, "usdc", "5", 86_400).unwrap();
+        assert_eq!(manager.get_remaining_limit(&wallet_address, "usdc").unwrap(), Some(Amount::from(5_000_000u64)));
+
+        // The window is shared across subaccounts, not per-subaccount.
+        manager.withdraw(&wallet_address, "usdc", Amount::from(3_000_000u64), "sub1", "0x1111111111111111111111111111111111111111", None).unwrap();
+        assert_eq!(manager.get_remaining_limit(&wallet_address, "usdc").unwrap(), Some(Amount::from(2_000_000u64)));
+
+        assert!(manager.withdraw(&wallet_address, "usdc", Amount::from(2_500_000u64), "sub2", "0x1111111111111111111111111111111111111111", None).is_err());
+
+        manager.withdraw(&wallet_address, "usdc", Amount::from(2_000_000u64), "sub2", "0x1111111111111111111111111111111111111111", None).unwrap();
+        assert_eq!(manager.get_remaining_limit(&wallet_address, "usdc").unwrap(), Some(Amount::zero()));
     }
-    
-    /// Build and sign ETH transaction
-    fn build_eth_transaction(
-        &self,
-        to: Vec<u8>,
-        amount: u64,
-        _decimals: u32,
-        nonce: u64,
-        gas_price: u64,
-        gas_limit: u64,
-        chain_id: u64,
-        wallet_address: &str,
-    ) -> Result<String> {
-        // Build transaction struct
-        let tx = crate::key_manager::LegacyTransaction {
-            nonce,
-            gas_price: u64_to_be_bytes_minimal(gas_price),
-            gas_limit: u64_to_be_bytes_minimal(gas_limit),
-            to: Some(to),
-            value: u64_to_be_bytes_minimal(amount),
-            data: Vec::new(),
-        };
-        
-        // Sign transaction using KMS
-        let signed_tx = {
-            let mut kms = self.kms.lock().unwrap();
-            kms.sign_transaction(wallet_address, &tx, chain_id)?
+
+    #[test]
+    fn test_nft_claim_transfer_and_withdraw_track_ownership_not_amount() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let bayc_1234 = Asset {
+            token_type: TokenType::ERC721,
+            contract_address: Some("0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d".to_string()),
+            token_id: Some("1234".to_string()),
+            symbol: "BAYC".to_string(),
+            name: "Bored Ape Yacht Club".to_string(),
+            decimals: 0,
         };
-        
-        Ok(signed_tx)
+        manager.add_asset(&wallet_address, "bayc-1234".to_string(), bayc_1234).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(), label: "Main".to_string(), address: "0x123...".to_string(),
+        }).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub2".to_string(), label: "Cold".to_string(), address: "0x456...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "bayc-1234".to_string(),
+            amount: Amount::from(1u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        // Claiming is ownership, not a summed balance - it never touches fungible balances.
+        let balances = manager.get_subaccount_balances(&wallet_address, "sub1").unwrap();
+        assert!(balances.get("bayc-1234").is_none());
+        let nfts = manager.get_subaccount_nfts(&wallet_address, "sub1").unwrap();
+        assert_eq!(nfts, vec![("bayc-1234".to_string(), "1234".to_string())]);
+
+        // A second claim of the same token_id is rejected, not silently reassigned.
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "bayc-1234".to_string(),
+            amount: Amount::from(1u64),
+            deposit_id: "d2".to_string(),
+            transaction_hash: "0xdef".to_string(),
+            block_number: "2".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        assert!(manager.claim_inbox(&wallet_address, "d2", "sub2").is_err());
+
+        // Transferring from a subaccount that doesn't own the token fails.
+        assert!(manager.transfer_nft(&wallet_address, "bayc-1234", "1234", "sub2", "sub1").is_err());
+
+        manager.transfer_nft(&wallet_address, "bayc-1234", "1234", "sub1", "sub2").unwrap();
+        assert!(manager.get_subaccount_nfts(&wallet_address, "sub1").unwrap().is_empty());
+        assert_eq!(
+            manager.get_subaccount_nfts(&wallet_address, "sub2").unwrap(),
+            vec![("bayc-1234".to_string(), "1234".to_string())],
+        );
+
+        manager.withdraw_nft(&wallet_address, "bayc-1234", "1234", "sub2", "0x1111111111111111111111111111111111111111", None).unwrap();
+        assert!(manager.get_subaccount_nfts(&wallet_address, "sub2").unwrap().is_empty());
+
+        // The outbox entry and provenance record both carry the withdrawn token_id.
+        let state = manager.get_wallet(&wallet_address).unwrap();
+        assert_eq!(state.outbox.back().unwrap().token_id, Some("1234".to_string()));
+        let withdraw_token_id = state.history.iter().rev().find_map(|r| match &r.operation {
+            TransactionOperation::Withdraw { token_id, .. } => Some(token_id.clone()),
+            _ => None,
+        }).unwrap();
+        assert_eq!(withdraw_token_id, Some("1234".to_string()));
+
+        // Withdrawing an already-withdrawn (unowned) token fails.
+        assert!(manager.withdraw_nft(&wallet_address, "bayc-1234", "1234", "sub2", "0x1111111111111111111111111111111111111111", None).is_err());
     }
-    
-    /// Build and sign ERC20 transaction
-    fn build_erc20_transaction(
-        &self,
-        token_contract: Vec<u8>,
-        to: Vec<u8>,
-        amount: u64,
-        nonce: u64,
-        gas_price: u64,
-        gas_limit: u64,
-        chain_id: u64,
-        wallet_address: &str,
-    ) -> Result<String> {
-        // ERC20 transfer function signature: transfer(address,uint256)
-        let transfer_selector = [0xa9, 0x05, 0x9c, 0xbb]; // keccak256("transfer(address,uint256)")[0:4]
-        
-        // Encode function call data
-        let mut call_data = Vec::new();
-        call_data.extend_from_slice(&transfer_selector);
-        
-        // Encode address (32 bytes, left-padded)
-        let mut addr_bytes = [0u8; 32];
-        addr_bytes[12..32].copy_from_slice(&to);
-        call_data.extend_from_slice(&addr_bytes);
-        
-        // Encode amount (32 bytes, big-endian)
-        let mut amount_bytes = [0u8; 32];
-        let amount_be = amount.to_be_bytes();
-        amount_bytes[24..].copy_from_slice(&amount_be);
-        call_data.extend_from_slice(&amount_bytes);
-        
-        // Build transaction struct
-        let tx = crate::key_manager::LegacyTransaction {
-            nonce,
-            gas_price: u64_to_be_bytes_minimal(gas_price),
-            gas_limit: u64_to_be_bytes_minimal(gas_limit),
-            to: Some(token_contract),
-            value: vec![0], // Zero value for ERC20 transfers
-            data: call_data,
+
+    #[test]
+    fn test_internal_swap_applies_constant_product_formula_and_respects_slippage() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let eth = Asset {
+            token_type: TokenType::ETH,
+            contract_address: None,
+            token_id: None,
+            symbol: "ETH".to_string(),
+            name: "Ethereum".to_string(),
+            decimals: 18,
         };
-        
-        // Sign transaction using KMS
-        let signed_tx = {
-            let mut kms = self.kms.lock().unwrap();
-            kms.sign_transaction(wallet_address, &tx, chain_id)?
+        let usdc = Asset {
+            token_type: TokenType::ERC20,
+            contract_address: Some("0xusdc".to_string()),
+            token_id: None,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
         };
-        
-        Ok(signed_tx)
-    }
-    
-    /// Get pending withdrawals from outbox queue
-    pub fn get_outbox_queue(&self) -> Result<Vec<PendingWithdrawal>> {
-        let outbox = self.outbox_queue.lock().unwrap();
-        Ok(outbox.iter().cloned().collect())
-    }
-    
-    /// Remove processed withdrawal from outbox queue
-    pub fn remove_from_outbox(&self, nonce: u64) -> Result<()> {
-        let mut outbox = self.outbox_queue.lock().unwrap();
-        outbox.retain(|w| w.nonce != nonce);
-        Ok(())
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_asset(&wallet_address, "usdc".to_string(), usdc).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(), label: "Main".to_string(), address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(),
+            amount: Amount::from(10_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        // No reserve configured yet: the swap has nothing to trade against.
+        assert!(manager.internal_swap(&wallet_address, "sub1", "eth", Amount::from(1_000u64), "usdc", Amount::zero()).is_err());
+
+        manager.add_liquidity(&wallet_address, "eth", Amount::from(100_000u64), "usdc", Amount::from(200_000u64)).unwrap();
+
+        // amount_in_with_fee = 1000 * 997 / 1000 = 997
+        // amount_out = (200_000 * 997) / (100_000 + 997) = 199_400_000 / 100_997 = 1974
+        let amount_out = manager.internal_swap(&wallet_address, "sub1", "eth", Amount::from(1_000u64), "usdc", Amount::from(1_900u64)).unwrap();
+        assert_eq!(amount_out, Amount::from(1974u64));
+
+        assert_eq!(manager.get_subaccount_balances(&wallet_address, "sub1").unwrap().get("eth").copied().unwrap_or(Amount::zero()), Amount::from(9_000u64));
+        assert_eq!(manager.get_subaccount_balances(&wallet_address, "sub1").unwrap().get("usdc").copied().unwrap_or(Amount::zero()), Amount::from(1974u64));
+
+        // Slippage protection: demanding more than the formula yields is rejected, and the
+        // rejected attempt must not have mutated balances or reserves.
+        assert!(manager.internal_swap(&wallet_address, "sub1", "eth", Amount::from(1_000u64), "usdc", Amount::from(10_000u64)).is_err());
+        assert_eq!(manager.get_subaccount_balances(&wallet_address, "sub1").unwrap().get("eth").copied().unwrap_or(Amount::zero()), Amount::from(9_000u64));
+
+        // Insufficient balance is rejected.
+        assert!(manager.internal_swap(&wallet_address, "sub1", "eth", Amount::from(1_000_000u64), "usdc", Amount::zero()).is_err());
+
+        // The new Swap record shows up in provenance filtering by both asset and subaccount.
+        let by_asset = manager.get_provenance_by_asset(&wallet_address, "usdc").unwrap();
+        assert_eq!(by_asset["provenance_records"].as_array().unwrap().len(), 1);
+        let by_subaccount = manager.get_provenance_by_subaccount(&wallet_address, "sub1").unwrap();
+        assert!(by_subaccount["provenance_records"].as_array().unwrap().iter().any(|r| r["operation"]["Swap"].is_object()));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::key_manager::EnclaveKMS;
+    #[test]
+    fn test_propose_accept_cancel_swap() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let eth = Asset {
+            token_type: TokenType::ETH, contract_address: None, token_id: None,
+            symbol: "ETH".to_string(), name: "Ethereum".to_string(), decimals: 18,
+        };
+        let btc = Asset {
+            token_type: TokenType::ERC20, contract_address: Some("0xbtc".to_string()), token_id: None,
+            symbol: "BTC".to_string(), name: "Wrapped Bitcoin".to_string(), decimals: 8,
+        };
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_asset(&wallet_address, "btc".to_string(), btc).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "alice".to_string(), label: "Alice".to_string(), address: "0x123...".to_string(),
+        }).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "bob".to_string(), label: "Bob".to_string(), address: "0x456...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(), amount: Amount::from(300u64), deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(), block_number: "1".to_string(),
+            from_address: "0x1".to_string(), to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "alice").unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "btc".to_string(), amount: Amount::from(30u64), deposit_id: "d2".to_string(),
+            transaction_hash: "0xdef".to_string(), block_number: "2".to_string(),
+            from_address: "0x2".to_string(), to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d2", "bob").unwrap();
+
+        let swap_id = manager.propose_swap(&wallet_address, "alice", "eth", Amount::from(300u64), "btc", Amount::from(50u64)).unwrap();
+
+        // The locked leg is debited immediately - excluded from get_balance while pending.
+        assert_eq!(manager.get_balance(&wallet_address, "alice", "eth").unwrap(), Amount::zero());
+
+        // Bob only holds 30 BTC; the swap wants 50.
+        let insufficient = manager.accept_swap(&wallet_address, &swap_id, "bob");
+        assert!(insufficient.is_err());
+        // A failed accept must not have touched either side's balance.
+        assert_eq!(manager.get_balance(&wallet_address, "bob", "btc").unwrap(), Amount::from(30u64));
+        assert_eq!(manager.get_balance(&wallet_address, "alice", "eth").unwrap(), Amount::zero());
+
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "btc".to_string(), amount: Amount::from(20u64), deposit_id: "d3".to_string(),
+            transaction_hash: "0xghi".to_string(), block_number: "3".to_string(),
+            from_address: "0x3".to_string(), to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d3", "bob").unwrap();
+
+        manager.accept_swap(&wallet_address, &swap_id, "bob").unwrap();
+        assert_eq!(manager.get_balance(&wallet_address, "alice", "btc").unwrap(), Amount::from(50u64));
+        assert_eq!(manager.get_balance(&wallet_address, "bob", "eth").unwrap(), Amount::from(300u64));
+        assert_eq!(manager.get_balance(&wallet_address, "bob", "btc").unwrap(), Amount::zero());
+
+        // A second accept of the same (now-settled) swap id fails - it's been removed.
+        assert!(manager.accept_swap(&wallet_address, &swap_id, "bob").is_err());
+
+        // Cancelling an already-accepted swap fails the same way cancelling an unknown id does.
+        assert!(manager.cancel_swap(&wallet_address, &swap_id, "alice").is_err());
+
+        // A fresh swap can still be cancelled by its maker before anyone accepts it, and the
+        // locked leg is refunded.
+        let swap_id_2 = manager.propose_swap(&wallet_address, "bob", "eth", Amount::from(100u64), "btc", Amount::from(10u64)).unwrap();
+        assert_eq!(manager.get_balance(&wallet_address, "bob", "eth").unwrap(), Amount::from(200u64));
+        manager.cancel_swap(&wallet_address, &swap_id_2, "bob").unwrap();
+        assert_eq!(manager.get_balance(&wallet_address, "bob", "eth").unwrap(), Amount::from(300u64));
+
+        // Only the maker may cancel.
+        let swap_id_3 = manager.propose_swap(&wallet_address, "bob", "eth", Amount::from(100u64), "btc", Amount::from(10u64)).unwrap();
+        assert!(manager.cancel_swap(&wallet_address, &swap_id_3, "alice").is_err());
+    }
 
     #[test]
-    fn test_create_wallet_manager() {
+    fn test_claimed_deposit_and_transfer_memos_are_sealed_to_the_recipient() {
         let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
         let manager = PassWalletManager::new(kms);
-        
+
         let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
-        assert!(!wallet_address.is_empty());
-        
-        let wallet_state = manager.get_wallet(&wallet_address).unwrap();
-        assert_eq!(wallet_state.name, "Test Wallet");
-        assert_eq!(wallet_state.owner, "alice");
+        let eth = Asset {
+            token_type: TokenType::ETH, contract_address: None, token_id: None,
+            symbol: "ETH".to_string(), name: "Ethereum".to_string(), decimals: 18,
+        };
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "alice".to_string(), label: "Alice".to_string(), address: "0x123...".to_string(),
+        }).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "bob".to_string(), label: "Bob".to_string(), address: "0x456...".to_string(),
+        }).unwrap();
+
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(), amount: Amount::from(500u64), deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(), block_number: "1".to_string(),
+            from_address: "0x1".to_string(), to_address: wallet_address.clone(),
+            memo: Some("invoice #42".to_string()),
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "alice").unwrap();
+
+        // The memo is recoverable by the claiming subaccount...
+        let alice_memos = manager.get_memos(&wallet_address, "alice").unwrap();
+        assert_eq!(alice_memos, vec!["invoice #42".to_string()]);
+        // ...but a different subaccount has none, and no plaintext copy leaks into the summary.
+        assert!(manager.get_memos(&wallet_address, "bob").unwrap().is_empty());
+        let summary = manager.get_wallet_state(&wallet_address).unwrap();
+        assert_eq!(summary["memo_counts"]["alice"], 1);
+        assert!(!summary.to_string().contains("invoice #42"));
+
+        manager.internal_transfer(&wallet_address, "eth", Amount::from(100u64), "alice", "bob", Some("rent".to_string())).unwrap();
+        let bob_memos = manager.get_memos(&wallet_address, "bob").unwrap();
+        assert_eq!(bob_memos, vec!["rent".to_string()]);
+        // The sender's own memo list is untouched by a transfer it made.
+        assert_eq!(manager.get_memos(&wallet_address, "alice").unwrap(), vec!["invoice #42".to_string()]);
     }
 
     #[test]
-    fn test_multiple_wallets() {
+    fn test_internal_transfer_with_rate_converts_between_assets_and_rejects_bad_rates() {
         let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
         let manager = PassWalletManager::new(kms);
-        
-        let wallet1 = manager.create_wallet("Wallet 1".to_string(), "alice".to_string()).unwrap();
-        let wallet2 = manager.create_wallet("Wallet 2".to_string(), "bob".to_string()).unwrap();
-        
-        assert_ne!(wallet1, wallet2);
-        
-        let wallets = manager.list_wallets();
-        assert_eq!(wallets.len(), 2);
-        assert!(wallets.contains(&wallet1));
-        assert!(wallets.contains(&wallet2));
+
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        let eth = Asset {
+            token_type: TokenType::ETH, contract_address: None, token_id: None,
+            symbol: "ETH".to_string(), name: "Ethereum".to_string(), decimals: 18,
+        };
+        let usdc = Asset {
+            token_type: TokenType::ERC20, contract_address: Some("0xusdc".to_string()), token_id: None,
+            symbol: "USDC".to_string(), name: "USD Coin".to_string(), decimals: 6,
+        };
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_asset(&wallet_address, "usdc".to_string(), usdc).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(), label: "Sub1".to_string(), address: "0x123...".to_string(),
+        }).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub2".to_string(), label: "Sub2".to_string(), address: "0x456...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(), amount: Amount::from(1_000_000_000_000_000_000u64), deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(), block_number: "1".to_string(),
+            from_address: "0x1".to_string(), to_address: wallet_address.clone(), memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        // 1 ETH (18 decimals) at 2000 USDC/ETH converts to 2000 USDC (6 decimals).
+        let rate = Rate { rate_numerator: 2000, rate_denominator: 1 };
+        let credited = manager.internal_transfer_with_rate(
+            &wallet_address, "eth", Amount::from(1_000_000_000_000_000_000u64), "usdc", rate, "sub1", "sub2", None,
+        ).unwrap();
+        assert_eq!(credited, Amount::from(2_000_000_000u64));
+        assert_eq!(manager.get_balance(&wallet_address, "sub2", "usdc").unwrap(), Amount::from(2_000_000_000u64));
+        assert_eq!(manager.get_balance(&wallet_address, "sub1", "eth").unwrap(), Amount::zero());
+
+        // A zero-denominator rate is rejected rather than dividing by zero.
+        let zero_rate = Rate { rate_numerator: 1, rate_denominator: 0 };
+        assert!(manager.internal_transfer_with_rate(
+            &wallet_address, "usdc", Amount::from(1u64), "eth", zero_rate, "sub2", "sub1", None,
+        ).is_err());
+
+        // A rate whose numerator multiplication overflows u128 is rejected rather than wrapping.
+        let overflow_rate = Rate { rate_numerator: u128::MAX, rate_denominator: 1 };
+        assert!(manager.internal_transfer_with_rate(
+            &wallet_address, "usdc", Amount::from(2u64), "eth", overflow_rate, "sub2", "sub1", None,
+        ).is_err());
+        // Neither rejected attempt moved any balance.
+        assert_eq!(manager.get_balance(&wallet_address, "sub2", "usdc").unwrap(), Amount::from(2_000_000_000u64));
     }
 
     #[test]
-    fn test_wallet_operations() {
+    fn test_record_mined_backfills_block_number_and_tracks_confirmations() {
         let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
         let manager = PassWalletManager::new(kms);
-        
+
         let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
-        
-        // Add asset
-        let asset = Asset {
+        let eth = Asset {
             token_type: TokenType::ETH,
             contract_address: None,
             token_id: None,
@@ -890,33 +7738,227 @@ mod tests {
             name: "Ethereum".to_string(),
             decimals: 18,
         };
-        manager.add_asset(&wallet_address, "eth".to_string(), asset).unwrap();
-        
-        // Add subaccount
-        let subaccount = Subaccount {
+        manager.add_asset(&wallet_address, "eth".to_string(), eth).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
             id: "sub1".to_string(),
-            label: "Main Account".to_string(),
+            label: "Main".to_string(),
             address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
+            asset_id: "eth".to_string(),
+            amount: Amount::from(1_000_000u64),
+            deposit_id: "d1".to_string(),
+            transaction_hash: "0xabc".to_string(),
+            block_number: "1".to_string(),
+            from_address: "0x456".to_string(),
+            to_address: wallet_address.clone(),
+            memo: None,
+        }).unwrap();
+        manager.claim_inbox(&wallet_address, "d1", "sub1").unwrap();
+
+        let (_, tx_nonce, ..) = manager.withdraw_to_external(
+            &wallet_address, "sub1", "eth", Amount::from(1000u64),
+            "0x2222222222222222222222222222222222222222",
+            None, None, 1, None, None, None, vec![],
+        ).unwrap();
+
+        // Not mined yet: no confirmation depth, still sitting in the outbox
+        assert_eq!(manager.get_confirmation_depth(&wallet_address, tx_nonce).unwrap(), None);
+        assert!(manager.get_ready().unwrap().iter().any(|w| w.nonce == tx_nonce));
+
+        manager.record_mined(&wallet_address, tx_nonce, 100).unwrap();
+        let state = manager.get_wallet(&wallet_address).unwrap();
+        let record = state.history.iter().find(|r| r.tx_nonce == Some(tx_nonce)).unwrap();
+        assert_eq!(record.block_number, Some(100));
+        assert_eq!(manager.get_confirmation_depth(&wallet_address, tx_nonce).unwrap(), Some(1));
+
+        // Not enough confirmations yet: withdrawal stays in the outbox
+        manager.advance_chain_tip(105).unwrap();
+        assert!(manager.get_ready().unwrap().iter().any(|w| w.nonce == tx_nonce));
+        assert_eq!(manager.get_confirmation_depth(&wallet_address, tx_nonce).unwrap(), Some(6));
+
+        // CONFIRMATIONS_REQUIRED reached: withdrawal is pruned from the outbox
+        manager.advance_chain_tip(111).unwrap();
+        assert!(!manager.get_ready().unwrap().iter().any(|w| w.nonce == tx_nonce));
+
+        // A reorg rolls back the mined block: the withdrawal is re-queued and provenance is flagged
+        manager.revert_mined_block(&wallet_address, tx_nonce).unwrap();
+        assert!(manager.get_ready().unwrap().iter().any(|w| w.nonce == tx_nonce));
+        assert_eq!(manager.get_confirmation_depth(&wallet_address, tx_nonce).unwrap(), None);
+        let state = manager.get_wallet(&wallet_address).unwrap();
+        let record = state.history.iter().find(|r| r.tx_nonce == Some(tx_nonce)).unwrap();
+        assert_eq!(record.block_number, None);
+        assert!(record.reorged);
+    }
+
+    #[test]
+    fn test_manager_state_survives_reconstruction_over_shared_storage() {
+        let storage = Arc::new(InMemoryStorage::new());
+
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let wallet_address = {
+            let manager = PassWalletManager::with_storage(kms.clone(), storage.clone());
+            let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+            manager.add_asset(&wallet_address, "eth".to_string(), Asset {
+                token_type: TokenType::ETH,
+                contract_address: None,
+                token_id: None,
+                symbol: "ETH".to_string(),
+                name: "Ethereum".to_string(),
+                decimals: 18,
+            }).unwrap();
+            wallet_address
         };
-        manager.add_subaccount(&wallet_address, subaccount).unwrap();
-        
-        // Test deposit
-        let deposit = Deposit {
+
+        // A fresh manager over the same storage sees the wallet created by the one above it.
+        let manager = PassWalletManager::with_storage(kms, storage);
+        assert_eq!(manager.list_wallets(), vec![wallet_address.clone()]);
+        assert!(manager.get_wallet(&wallet_address).unwrap().assets.contains_key("eth"));
+    }
+
+    fn checkpoint_test_wallet(manager: &PassWalletManager) -> WalletAddress {
+        let wallet_address = manager.create_wallet("Test Wallet".to_string(), "alice".to_string()).unwrap();
+        manager.add_asset(&wallet_address, "eth".to_string(), Asset {
+            token_type: TokenType::ETH,
+            contract_address: None,
+            token_id: None,
+            symbol: "ETH".to_string(),
+            name: "Ethereum".to_string(),
+            decimals: 18,
+        }).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub1".to_string(),
+            label: "Main Account".to_string(),
+            address: "0x123...".to_string(),
+        }).unwrap();
+        manager.inbox_deposit(&wallet_address, Deposit {
             asset_id: "eth".to_string(),
-            amount: 1000,
+            amount: Amount::from(1000u64),
             deposit_id: "deposit1".to_string(),
             transaction_hash: "0xabc...".to_string(),
             block_number: "12345".to_string(),
             from_address: "0x456...".to_string(),
             to_address: wallet_address.clone(),
-        };
-        manager.inbox_deposit(&wallet_address, deposit).unwrap();
-        
-        // Test claim
+            memo: None,
+        }).unwrap();
         manager.claim_inbox(&wallet_address, "deposit1", "sub1").unwrap();
-        
-        // Check balance
-        let balance = manager.get_balance(&wallet_address, "sub1", "eth").unwrap();
-        assert_eq!(balance, 1000);
+        wallet_address
+    }
+
+    #[test]
+    fn test_head_hash_and_verify_history_agree_after_mutations() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+        let wallet_address = checkpoint_test_wallet(&manager);
+
+        manager.verify_history(&wallet_address).unwrap();
+
+        let head = manager.head_hash(&wallet_address).unwrap();
+        assert_eq!(hex::encode(head), manager.provenance_head(&wallet_address).unwrap());
+
+        manager.internal_transfer(&wallet_address, "eth", Amount::from(100u64), "sub1", "sub1", None).unwrap();
+        manager.verify_history(&wallet_address).unwrap();
+        assert_ne!(manager.head_hash(&wallet_address).unwrap(), head);
+    }
+
+    #[test]
+    fn test_verify_history_detects_tampered_record() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+        let wallet_address = checkpoint_test_wallet(&manager);
+        manager.internal_transfer(&wallet_address, "eth", Amount::from(100u64), "sub1", "sub1", None).unwrap();
+
+        let mut wallet_state = manager.get_wallet(&wallet_address).unwrap();
+        match &mut wallet_state.history[1].operation {
+            TransactionOperation::Transfer { amount, .. } => *amount = Amount::from(999_999u64),
+            other => panic!("expected a Transfer record, got {:?}", other),
+        }
+        manager.update_wallet(&wallet_address, wallet_state).unwrap();
+
+        assert!(manager.verify_history(&wallet_address).is_err());
+    }
+
+    #[test]
+    fn test_sign_provenance_head_round_trips_through_kms_verification() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms.clone());
+        let wallet_address = checkpoint_test_wallet(&manager);
+
+        let head = manager.provenance_head(&wallet_address).unwrap();
+        let signature = manager.sign_provenance_head(&wallet_address).unwrap();
+
+        let kms = kms.lock().unwrap();
+        assert!(kms.verify_message(&head, &signature, &wallet_address).unwrap());
+    }
+
+    #[test]
+    fn test_revert_checkpoint_restores_byte_identical_state() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+        let wallet_address = checkpoint_test_wallet(&manager);
+
+        let before = manager.get_wallet(&wallet_address).unwrap();
+        let checkpoint = manager.begin_checkpoint(&wallet_address).unwrap();
+
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub2".to_string(),
+            label: "Second Account".to_string(),
+            address: "0x456...".to_string(),
+        }).unwrap();
+        manager.internal_transfer(&wallet_address, "eth", Amount::from(400u64), "sub1", "sub2", None).unwrap();
+        assert_eq!(manager.get_balance(&wallet_address, "sub2", "eth").unwrap(), Amount::from(400u64));
+
+        manager.revert_checkpoint(&wallet_address, checkpoint).unwrap();
+
+        let after = manager.get_wallet(&wallet_address).unwrap();
+        assert_eq!(serde_json::to_value(&before).unwrap(), serde_json::to_value(&after).unwrap());
+        assert_eq!(manager.get_balance(&wallet_address, "sub1", "eth").unwrap(), Amount::from(1000u64));
+    }
+
+    #[test]
+    fn test_commit_checkpoint_keeps_mutations() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+        let wallet_address = checkpoint_test_wallet(&manager);
+
+        let checkpoint = manager.begin_checkpoint(&wallet_address).unwrap();
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub2".to_string(),
+            label: "Second Account".to_string(),
+            address: "0x456...".to_string(),
+        }).unwrap();
+        manager.internal_transfer(&wallet_address, "eth", Amount::from(400u64), "sub1", "sub2", None).unwrap();
+        manager.commit_checkpoint(&wallet_address, checkpoint).unwrap();
+
+        assert_eq!(manager.get_balance(&wallet_address, "sub2", "eth").unwrap(), Amount::from(400u64));
+        // Committed, so the checkpoint is closed - reverting it again should fail rather than
+        // silently being a no-op.
+        assert!(manager.revert_checkpoint(&wallet_address, checkpoint).is_err());
+    }
+
+    #[test]
+    fn test_with_transaction_reverts_composite_flow_on_failure() {
+        let kms = Arc::new(Mutex::new(EnclaveKMS::new("test_secret").unwrap()));
+        let manager = PassWalletManager::new(kms);
+        let wallet_address = checkpoint_test_wallet(&manager);
+        manager.add_subaccount(&wallet_address, Subaccount {
+            id: "sub2".to_string(),
+            label: "Second Account".to_string(),
+            address: "0x456...".to_string(),
+        }).unwrap();
+
+        let before = manager.get_wallet(&wallet_address).unwrap();
+
+        // A two-step transfer chain where the second leg fails for insufficient balance - the
+        // whole composite flow must leave no trace of the first, already-successful leg.
+        let result: Result<()> = manager.with_transaction(&wallet_address, || {
+            manager.internal_transfer(&wallet_address, "eth", Amount::from(400u64), "sub1", "sub2", None)?;
+            manager.internal_transfer(&wallet_address, "eth", Amount::from(999_999u64), "sub2", "sub1", None)?;
+            Ok(())
+        });
+        assert!(result.is_err());
+
+        let after = manager.get_wallet(&wallet_address).unwrap();
+        assert_eq!(serde_json::to_value(&before).unwrap(), serde_json::to_value(&after).unwrap());
     }
 }
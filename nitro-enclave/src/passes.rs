@@ -0,0 +1,270 @@
+// Builds Apple Wallet (`PassKit`) `.pkpass` bundles summarizing wallet activity: a receipt for a
+// payment that just cleared `process_outbox`, or a "receive" card showing the wallet's own
+// address as a scannable barcode for accepting incoming funds.
+//
+// A `.pkpass` is a zip of `pass.json` (the pass's fields), any art assets (`icon.png`,
+// `logo.png`), a `manifest.json` listing a SHA-1 digest of every other file in the bundle, and a
+// `signature` file: a detached PKCS#7 signature over `manifest.json`, produced with Apple's Pass
+// Type ID certificate chained through the WWDR intermediate. This module builds the JSON and
+// zips the bundle; the actual `zip` and `openssl` crates it imports aren't added to a
+// Cargo.toml because this tree has none to extend - see `wallet_client`'s module comment for the
+// same caveat. Declared via `pub mod passes;` in `src/lib.rs`, next to `pass_logic`.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use sha1::{Digest as _, Sha1};
+
+use crate::pass_logic::{Amount, ExternalDestination, OutboxEntry};
+
+/// Apple Pass Type ID certificate chain used to sign a bundle's `manifest.json`. `signer_cert_pem`
+/// and `signer_key_pem` are the developer's Pass Type ID certificate and its private key;
+/// `wwdr_cert_pem` is Apple's Worldwide Developer Relations intermediate, required so iOS can
+/// validate the chain up to Apple's root.
+pub struct PassSigningCertificate {
+    pub signer_cert_pem: Vec<u8>,
+    pub signer_key_pem: Vec<u8>,
+    pub wwdr_cert_pem: Vec<u8>,
+}
+
+/// Fields identifying the pass within Apple's developer account, the same for every pass this
+/// app issues.
+pub struct PassIssuer {
+    pub pass_type_identifier: String,
+    pub team_identifier: String,
+    pub organization_name: String,
+}
+
+#[derive(Serialize)]
+struct PassField {
+    key: String,
+    label: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct PassFields {
+    #[serde(rename = "primaryFields")]
+    primary_fields: Vec<PassField>,
+    #[serde(rename = "secondaryFields")]
+    secondary_fields: Vec<PassField>,
+    #[serde(rename = "auxiliaryFields")]
+    auxiliary_fields: Vec<PassField>,
+}
+
+#[derive(Serialize)]
+struct Barcode {
+    message: String,
+    format: &'static str,
+    #[serde(rename = "messageEncoding")]
+    message_encoding: &'static str,
+}
+
+/// The subset of `pass.json` this module fills in. PassKit requires either `storeCard` or
+/// `generic` (among other style keys) to carry the field layout above; `style` records which one
+/// `PassFields` was built for so it serializes under the right key.
+#[derive(Serialize)]
+struct PassJson {
+    #[serde(rename = "formatVersion")]
+    format_version: u32,
+    #[serde(rename = "passTypeIdentifier")]
+    pass_type_identifier: String,
+    #[serde(rename = "teamIdentifier")]
+    team_identifier: String,
+    #[serde(rename = "organizationName")]
+    organization_name: String,
+    description: String,
+    #[serde(rename = "serialNumber")]
+    serial_number: String,
+    #[serde(rename = "relevantDate", skip_serializing_if = "Option::is_none")]
+    relevant_date: Option<String>,
+    barcodes: Vec<Barcode>,
+    #[serde(flatten)]
+    style: PassStyle,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+enum PassStyle {
+    StoreCard(PassFields),
+    Generic(PassFields),
+}
+
+fn field(key: &str, label: &str, value: impl ToString) -> PassField {
+    PassField { key: key.to_string(), label: label.to_string(), value: value.to_string() }
+}
+
+fn amount_display(asset_id: &str, amount: Amount) -> String {
+    format!("{} {}", amount, asset_id.to_uppercase())
+}
+
+/// Format a Unix timestamp as the UTC `relevantDate` PassKit expects
+/// (`YYYY-MM-DDTHH:MM:SSZ`), via Howard Hinnant's civil-from-days algorithm rather than pulling
+/// in a date/time crate for one field.
+fn epoch_seconds_to_rfc3339(epoch_secs: u64) -> String {
+    let days = (epoch_secs / 86_400) as i64;
+    let secs_of_day = epoch_secs % 86_400;
+    let (hour, minute, second) = (secs_of_day / 3600, (secs_of_day / 60) % 60, secs_of_day % 60);
+
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = (z - era * 146_097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let year = yoe as i64 + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = if month <= 2 { year + 1 } else { year };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Build (unsigned, unzipped) `pass.json` contents for a receipt covering one drained
+/// `OutboxEntry`: amount and destination as the headline fields, the entry's nonce as the serial
+/// number so Wallet treats a re-export of the same payment as an update to the same pass rather
+/// than a duplicate.
+fn build_receipt_pass_json(issuer: &PassIssuer, tx_id: &str, entry: &OutboxEntry, confirmed_at: u64) -> PassJson {
+    PassJson {
+        format_version: 1,
+        pass_type_identifier: issuer.pass_type_identifier.clone(),
+        team_identifier: issuer.team_identifier.clone(),
+        organization_name: issuer.organization_name.clone(),
+        description: "PASS Wallet payment receipt".to_string(),
+        serial_number: tx_id.to_string(),
+        relevant_date: Some(epoch_seconds_to_rfc3339(confirmed_at)),
+        barcodes: vec![Barcode {
+            message: tx_id.to_string(),
+            format: "PKBarcodeFormatQR",
+            message_encoding: "iso-8859-1",
+        }],
+        style: PassStyle::Generic(PassFields {
+            primary_fields: vec![field("amount", "Amount", amount_display(&entry.asset_id, entry.amount))],
+            secondary_fields: vec![field("to", "To", &entry.external_destination)],
+            auxiliary_fields: vec![
+                field("txId", "Transaction", tx_id),
+                field("date", "Date", epoch_seconds_to_rfc3339(confirmed_at)),
+            ],
+        }),
+    }
+}
+
+/// Build (unsigned, unzipped) `pass.json` contents for a "receive" card: the wallet's own address
+/// as both the headline value and the barcode payload, so a sender can scan it straight from the
+/// Wallet app.
+fn build_receive_pass_json(issuer: &PassIssuer, wallet_address: &ExternalDestination, barcode_format: &'static str) -> PassJson {
+    PassJson {
+        format_version: 1,
+        pass_type_identifier: issuer.pass_type_identifier.clone(),
+        team_identifier: issuer.team_identifier.clone(),
+        organization_name: issuer.organization_name.clone(),
+        description: "PASS Wallet receive address".to_string(),
+        serial_number: wallet_address.clone(),
+        relevant_date: None,
+        barcodes: vec![Barcode {
+            message: wallet_address.clone(),
+            format: barcode_format,
+            message_encoding: "iso-8859-1",
+        }],
+        style: PassStyle::StoreCard(PassFields {
+            primary_fields: vec![field("address", "Receive at", wallet_address)],
+            secondary_fields: vec![],
+            auxiliary_fields: vec![],
+        }),
+    }
+}
+
+/// Detached-sign `manifest.json` (a SHA-1 digest of every other bundled file, per PassKit's
+/// format) with `cert`, producing the PKCS#7 `signature` file iOS checks before trusting the pass.
+fn sign_manifest(cert: &PassSigningCertificate, manifest_json: &[u8]) -> Result<Vec<u8>> {
+    use openssl::pkcs7::{Pkcs7, Pkcs7Flags};
+    use openssl::pkey::PKey;
+    use openssl::stack::Stack;
+    use openssl::x509::X509;
+
+    let signer_cert = X509::from_pem(&cert.signer_cert_pem)
+        .map_err(|e| anyhow!("Invalid Pass Type ID certificate: {}", e))?;
+    let signer_key = PKey::private_key_from_pem(&cert.signer_key_pem)
+        .map_err(|e| anyhow!("Invalid Pass Type ID private key: {}", e))?;
+    let wwdr_cert = X509::from_pem(&cert.wwdr_cert_pem)
+        .map_err(|e| anyhow!("Invalid WWDR intermediate certificate: {}", e))?;
+
+    let mut chain = Stack::new().map_err(|e| anyhow!("Failed to build certificate chain: {}", e))?;
+    chain.push(wwdr_cert).map_err(|e| anyhow!("Failed to build certificate chain: {}", e))?;
+
+    let pkcs7 = Pkcs7::sign(
+        &signer_cert,
+        &signer_key,
+        &chain,
+        manifest_json,
+        Pkcs7Flags::DETACHED | Pkcs7Flags::BINARY,
+    )
+    .map_err(|e| anyhow!("Failed to sign pass manifest: {}", e))?;
+
+    pkcs7.to_der().map_err(|e| anyhow!("Failed to DER-encode pass signature: {}", e))
+}
+
+/// Zip `pass.json` plus `cert`'s signature over its manifest into a complete `.pkpass` bundle,
+/// matching Apple's required `pass.json` / `manifest.json` / `signature` layout.
+fn package_pkpass(cert: &PassSigningCertificate, pass_json: &PassJson) -> Result<Vec<u8>> {
+    use std::io::Write;
+    use zip::write::FileOptions;
+
+    let pass_json_bytes = serde_json::to_vec_pretty(pass_json)
+        .map_err(|e| anyhow!("Failed to serialize pass.json: {}", e))?;
+
+    let mut pass_json_digest = Sha1::new();
+    pass_json_digest.update(&pass_json_bytes);
+    let manifest = serde_json::json!({ "pass.json": hex::encode(pass_json_digest.finalize()) });
+    let manifest_bytes = serde_json::to_vec(&manifest)
+        .map_err(|e| anyhow!("Failed to serialize pass manifest: {}", e))?;
+
+    let signature_bytes = sign_manifest(cert, &manifest_bytes)?;
+
+    let mut zip_bytes = Vec::new();
+    {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(&mut zip_bytes));
+        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+        writer
+            .start_file("pass.json", options)
+            .map_err(|e| anyhow!("Failed to write pass.json into pkpass: {}", e))?;
+        writer.write_all(&pass_json_bytes)?;
+
+        writer
+            .start_file("manifest.json", options)
+            .map_err(|e| anyhow!("Failed to write manifest.json into pkpass: {}", e))?;
+        writer.write_all(&manifest_bytes)?;
+
+        writer
+            .start_file("signature", options)
+            .map_err(|e| anyhow!("Failed to write signature into pkpass: {}", e))?;
+        writer.write_all(&signature_bytes)?;
+
+        writer.finish().map_err(|e| anyhow!("Failed to finalize pkpass archive: {}", e))?;
+    }
+
+    Ok(zip_bytes)
+}
+
+/// Build a signed `.pkpass` receipt for a payment that just cleared the outbox.
+pub fn build_payment_receipt_pass(
+    cert: &PassSigningCertificate,
+    issuer: &PassIssuer,
+    tx_id: &str,
+    entry: &OutboxEntry,
+    confirmed_at: u64,
+) -> Result<Vec<u8>> {
+    package_pkpass(cert, &build_receipt_pass_json(issuer, tx_id, entry, confirmed_at))
+}
+
+/// Build a signed `.pkpass` "receive" card showing `wallet_address` as a PDF417 barcode.
+pub fn build_receive_card_pass(
+    cert: &PassSigningCertificate,
+    issuer: &PassIssuer,
+    wallet_address: &ExternalDestination,
+) -> Result<Vec<u8>> {
+    package_pkpass(cert, &build_receive_pass_json(issuer, wallet_address, "PKBarcodeFormatPDF417"))
+}
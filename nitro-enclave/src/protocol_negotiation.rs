@@ -0,0 +1,122 @@
+// Version/capability exchange run as the first framed message after connect - right after
+// `handshake` establishes the session keys and before `handle_connection` ever looks at a command.
+// `handle_connection()` used to silently assume the peer spoke today's ad-hoc JSON dialect (down
+// to special-casing the legacy `/keygen` string), which breaks the moment the wire format changes
+// out from under an old client. This is the version/capability-negotiation approach `distant`
+// adopted for its client/server/manager protocol: the client announces the newest version and
+// capability set it understands, the server answers with the highest version both sides support
+// and its own capability set, and either side refuses to proceed with a structured error if there
+// is no mutually supported version.
+//
+// `NegotiatedConnection` is what `handle_connection` should branch command handling on once this
+// module lands - new commands gated behind a capability, or a v2 wire format, can ship without
+// breaking a client that only negotiated v1. Declared via `pub mod protocol_negotiation;` in
+// `src/lib.rs`, next to `handshake`/`protocol_helpers`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol_helpers::{recv_loop, recv_u64, send_loop, send_u64};
+use crate::server_logic::Response;
+use std::os::unix::io::RawFd;
+
+/// Every protocol version this build can speak, oldest first. Bumped whenever the wire format
+/// changes in a way old clients can't parse; `negotiate_version` picks the highest entry both
+/// sides list.
+pub const SUPPORTED_VERSIONS: &[u32] = &[1];
+
+/// Named, independently gated features a peer may or may not support, so a new command can be
+/// added without bumping the protocol version at all as long as both sides agree it's available.
+pub const SERVER_CAPABILITIES: &[&str] = &["pass_wallet", "secure_channel", "outbox_broadcast"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionHello {
+    pub protocol_version: u32,
+    pub capabilities: Vec<String>,
+}
+
+/// What both sides agreed to after a successful negotiation: the version to speak for the rest
+/// of the connection, and the capabilities present on both ends (the only ones either side may
+/// rely on from here).
+#[derive(Debug, Clone)]
+pub struct NegotiatedConnection {
+    pub version: u32,
+    pub capabilities: Vec<String>,
+}
+
+fn highest_mutual_version(offered: &[u32], supported: &[u32]) -> Option<u32> {
+    offered.iter().copied().filter(|v| supported.contains(v)).max()
+}
+
+fn mutual_capabilities(offered: &[String], supported: &[&str]) -> Vec<String> {
+    offered.iter().filter(|cap| supported.contains(&cap.as_str())).cloned().collect()
+}
+
+fn send_json(fd: RawFd, value: &impl Serialize) -> Result<(), String> {
+    let bytes = serde_json::to_vec(value).map_err(|e| format!("Failed to serialize message: {}", e))?;
+    let len = bytes.len() as u64;
+    send_u64(fd, len)?;
+    send_loop(fd, &bytes, len)
+}
+
+fn recv_json<T: for<'de> Deserialize<'de>>(fd: RawFd) -> Result<T, String> {
+    let len = recv_u64(fd)?;
+    let mut buf = vec![0u8; len as usize];
+    recv_loop(fd, &mut buf, len)?;
+    serde_json::from_slice(&buf).map_err(|e| format!("Failed to parse message: {}", e))
+}
+
+fn no_overlap_response(reason: &str) -> Response {
+    Response { success: false, data: None, error: Some(reason.to_string()) }
+}
+
+/// Client side: announce the newest version and capabilities this build understands, then accept
+/// (or refuse) whatever the server negotiates down to.
+pub fn negotiate_as_client(fd: RawFd) -> Result<NegotiatedConnection, String> {
+    let hello = VersionHello {
+        protocol_version: *SUPPORTED_VERSIONS.iter().max().unwrap(),
+        capabilities: SERVER_CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+    };
+    send_json(fd, &hello)?;
+
+    let reply: Response = recv_json(fd)?;
+    if !reply.success {
+        return Err(reply.error.unwrap_or_else(|| "Protocol negotiation refused by server".to_string()));
+    }
+    let negotiated: VersionHello = reply
+        .data
+        .ok_or_else(|| "Server accepted negotiation but sent no version/capabilities".to_string())
+        .and_then(|v| serde_json::from_value(v).map_err(|e| format!("Malformed negotiation reply: {}", e)))?;
+
+    Ok(NegotiatedConnection { version: negotiated.protocol_version, capabilities: negotiated.capabilities })
+}
+
+/// Server side: read the client's offer, pick the highest mutually supported version and the
+/// intersection of capabilities, and reply. Refuses with a structured `Response` (rather than a
+/// framing-level error) if the client's offered versions share nothing with `SUPPORTED_VERSIONS`,
+/// so a client can tell "we don't speak a common version" apart from a transport failure.
+pub fn negotiate_as_server(fd: RawFd) -> Result<NegotiatedConnection, String> {
+    let hello: VersionHello = recv_json(fd)?;
+
+    let version = match highest_mutual_version(&[hello.protocol_version], SUPPORTED_VERSIONS) {
+        Some(version) => version,
+        None => {
+            send_json(fd, &no_overlap_response(&format!(
+                "No mutually supported protocol version: client offered {}, server supports {:?}",
+                hello.protocol_version, SUPPORTED_VERSIONS
+            )))?;
+            return Err("Client's protocol version is not supported by this server".to_string());
+        }
+    };
+
+    let capabilities = mutual_capabilities(&hello.capabilities, SERVER_CAPABILITIES);
+
+    let negotiated = VersionHello { protocol_version: version, capabilities: capabilities.clone() };
+    let reply = Response {
+        success: true,
+        data: Some(serde_json::to_value(&negotiated).map_err(|e| format!("Failed to encode negotiation reply: {}", e))?),
+        error: None,
+    };
+    send_json(fd, &reply)?;
+
+    Ok(NegotiatedConnection { version, capabilities })
+}
@@ -0,0 +1,136 @@
+// End-to-end encrypted command channel between a client and the code running inside the enclave.
+//
+// `http_client` relays commands to the enclave over vsock, but the relay itself runs on the
+// untrusted host EC2 instance - it can read and tamper with every command it forwards. This
+// module lets a client negotiate a session key directly with the enclave (via
+// `Command::InitSecureChannel`/`Command::SecureCommand`, dispatched in `server_logic`) so that
+// `http_client` only ever sees opaque ciphertext, the same way `init_api_secure` lets a
+// grin-wallet client and its owner API agree on a key without trusting the transport in between.
+//
+// Key agreement is X25519 ECDH with a fresh ephemeral keypair per session (forward secrecy - the
+// enclave's half of the handshake is never reused once a session closes), fed through
+// HKDF-SHA256 to derive a ChaCha20-Poly1305 key. Every encrypted message is prefixed with a
+// per-session, per-direction monotonic counter folded into the nonce; `decrypt` rejects any
+// counter that does not strictly advance, so a captured and replayed ciphertext is refused rather
+// than re-applied.
+
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key as ChaChaKey, KeyInit, Nonce as ChaChaNonce};
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use x25519_dalek::{EphemeralSecret, PublicKey};
+use zeroize::Zeroize;
+
+const SESSION_ID_LEN: usize = 16;
+const NONCE_COUNTER_LEN: usize = 8;
+const NONCE_RANDOM_LEN: usize = 4;
+const HKDF_INFO: &[u8] = b"pass-wallet-enclave-secure-channel-v1";
+
+struct SecureChannelSession {
+    key: [u8; 32],
+    send_counter: u64,
+    recv_counter: u64,
+}
+
+impl Drop for SecureChannelSession {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<String, SecureChannelSession>> = Mutex::new(HashMap::new());
+}
+
+fn random_session_id() -> String {
+    let mut bytes = [0u8; SESSION_ID_LEN];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Run the X25519 handshake for a new session: generate a fresh enclave-side ephemeral keypair,
+/// derive the shared ChaCha20-Poly1305 key from `client_public_key`, and store it keyed by a
+/// freshly minted session id. Returns `(session_id, enclave_public_key)`; the enclave's private
+/// half of the handshake is dropped here and never persisted.
+pub fn open_session(client_public_key: &[u8; 32]) -> Result<(String, [u8; 32]), String> {
+    let enclave_secret = EphemeralSecret::random_from_rng(rand::thread_rng());
+    let enclave_public = PublicKey::from(&enclave_secret);
+
+    let client_public = PublicKey::from(*client_public_key);
+    let mut shared_secret = enclave_secret.diffie_hellman(&client_public).to_bytes();
+
+    let hkdf = Hkdf::<Sha256>::new(None, &shared_secret);
+    let mut key = [0u8; 32];
+    hkdf.expand(HKDF_INFO, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    shared_secret.zeroize();
+
+    let session_id = random_session_id();
+    let mut sessions = SESSIONS.lock().unwrap();
+    sessions.insert(
+        session_id.clone(),
+        SecureChannelSession {
+            key,
+            send_counter: 0,
+            recv_counter: 0,
+        },
+    );
+
+    Ok((session_id, enclave_public.to_bytes()))
+}
+
+/// Decrypt a `{nonce, body}` envelope addressed to `session_id`, rejecting it outright if the
+/// nonce's counter prefix does not strictly exceed the last one accepted for this session.
+pub fn decrypt(session_id: &str, nonce: &[u8], body: &[u8]) -> Result<Vec<u8>, String> {
+    if nonce.len() != NONCE_COUNTER_LEN + NONCE_RANDOM_LEN {
+        return Err("Invalid nonce length".to_string());
+    }
+
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session = sessions
+        .get_mut(session_id)
+        .ok_or_else(|| "Unknown secure session".to_string())?;
+
+    let counter = u64::from_be_bytes(nonce[..NONCE_COUNTER_LEN].try_into().unwrap());
+    if counter <= session.recv_counter {
+        return Err("Rejected replayed or out-of-order nonce".to_string());
+    }
+
+    let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(&session.key));
+    let plaintext = cipher
+        .decrypt(ChaChaNonce::from_slice(nonce), body)
+        .map_err(|_| "Decryption failed: wrong key or corrupted envelope".to_string())?;
+
+    session.recv_counter = counter;
+    Ok(plaintext)
+}
+
+/// Encrypt `plaintext` for `session_id`, returning the `(nonce, ciphertext)` pair to wrap as the
+/// reply envelope. The nonce's counter prefix strictly advances with every call.
+pub fn encrypt(session_id: &str, plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session = sessions
+        .get_mut(session_id)
+        .ok_or_else(|| "Unknown secure session".to_string())?;
+
+    session.send_counter += 1;
+    let mut nonce = Vec::with_capacity(NONCE_COUNTER_LEN + NONCE_RANDOM_LEN);
+    nonce.extend_from_slice(&session.send_counter.to_be_bytes());
+    let mut random_suffix = [0u8; NONCE_RANDOM_LEN];
+    rand::thread_rng().fill_bytes(&mut random_suffix);
+    nonce.extend_from_slice(&random_suffix);
+
+    let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(&session.key));
+    let ciphertext = cipher
+        .encrypt(ChaChaNonce::from_slice(&nonce), plaintext)
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+
+    Ok((nonce, ciphertext))
+}
+
+/// Drop a session's key material, e.g. once a client signals it is done with the channel.
+pub fn close_session(session_id: &str) {
+    SESSIONS.lock().unwrap().remove(session_id);
+}
@@ -5,7 +5,9 @@ use std::sync::{Arc, Mutex};
 use anyhow::Result;
 
 use crate::key_manager::EnclaveKMS;
-use crate::pass_logic::{PassWalletManager, Asset, Subaccount, Deposit, TokenType};
+use crate::pass_logic::{PassWalletManager, Asset, Subaccount, Deposit, TokenType, Amount, PaymentProof, PassWalletState, WalletOp};
+use crate::secure_channel;
+use std::collections::HashMap;
 
 
 
@@ -17,13 +19,210 @@ lazy_static::lazy_static! {
     };
 }
 
-// Global PASS Wallet Manager instance
+// Global PASS Wallet Manager instance. Arc-wrapped (rather than a plain value) so that
+// `self: &Arc<Self>` methods such as `start_background_worker`/`start_deposit_sync` can be
+// called directly on it, the same as every other manager method via auto-deref.
 lazy_static::lazy_static! {
-    static ref PASS_WALLET_MANAGER: PassWalletManager = {
-        PassWalletManager::new(KMS.clone())
+    static ref PASS_WALLET_MANAGER: Arc<PassWalletManager> = {
+        Arc::new(PassWalletManager::new(KMS.clone()))
     };
 }
 
+// Serializes all command dispatch so a `Command::Batch { atomic: true }` can snapshot, run, and
+// (on failure) roll back its sub-commands without a concurrent single command interleaving in
+// the middle and observing - or clobbering - half-applied state.
+lazy_static::lazy_static! {
+    static ref COMMAND_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Error returned by a `CommandRegistry` handler. Kept to three broad buckets - the registry only
+/// needs enough detail to fill in `CommandEnvelope.error`, not a typed error per KMS operation.
+#[derive(Debug)]
+pub enum CommandError {
+    InvalidParams(String),
+    NotFound(String),
+    Internal(String),
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::InvalidParams(msg) => write!(f, "Invalid parameters: {}", msg),
+            CommandError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            CommandError::Internal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<anyhow::Error> for CommandError {
+    fn from(e: anyhow::Error) -> Self {
+        CommandError::Internal(e.to_string())
+    }
+}
+
+/// A single `CommandRegistry` entry: given the KMS and the command's JSON parameters, produce the
+/// JSON payload to report back under `CommandEnvelope.data`, or a `CommandError` to report under
+/// `CommandEnvelope.error`.
+type CommandHandler = fn(&mut EnclaveKMS, serde_json::Value) -> Result<serde_json::Value, CommandError>;
+
+/// What a `CommandRegistry`-dispatched command reports back, distinct from the legacy `Response`
+/// envelope above: it also names the command that produced it and echoes back the caller's
+/// correlation `id`, so a caller juggling several in-flight RPCs over one connection (see
+/// `connection_pool`) doesn't have to thread that correlation through itself or assume requests
+/// complete in order. This is the one shape every reply from `handle_connection()` should take -
+/// parse failures and unknown-command cases included - rather than the bare
+/// `"Command executed successfully"` / `"Error: ..."` strings the legacy `parse_command` fallback
+/// used to emit, which no programmatic client could reliably distinguish from a JSON payload.
+#[derive(Serialize, Deserialize)]
+pub struct CommandEnvelope {
+    /// Echoed verbatim from the request's optional `"id"` field (`null` if the caller omitted one,
+    /// or if the request didn't parse as JSON at all). Never generated server-side: correlation is
+    /// the caller's responsibility, the same as JSON-RPC's `id`.
+    pub id: Option<serde_json::Value>,
+    pub success: bool,
+    pub command: String,
+    pub data: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+/// Maps a command name to the handler that serves it, so adding an operation is "register a
+/// function" rather than "add a match arm to the socket loop". Seeded with the KMS-level
+/// operations the enclave exposes directly over RPC; the PASS Wallet commands above stay on the
+/// `Command` enum/`dispatch_command` match for now since they hang off `PASS_WALLET_MANAGER`
+/// rather than a bare `EnclaveKMS`. Modeled on the per-command handler modules NextGraph registers
+/// for its add_user/list_users/del_user operations.
+pub struct CommandRegistry {
+    handlers: HashMap<&'static str, CommandHandler>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        let mut registry = CommandRegistry { handlers: HashMap::new() };
+        registry.register("keygen", handle_keygen_command);
+        registry.register("eth_sign", handle_eth_sign_command);
+        registry.register("sign_transaction", handle_sign_transaction_command);
+        registry.register("list_accounts", handle_list_accounts_command);
+        registry.register("get_public_key", handle_get_public_key_command);
+        registry
+    }
+
+    pub fn register(&mut self, name: &'static str, handler: CommandHandler) {
+        self.handlers.insert(name, handler);
+    }
+
+    /// Look up `command`, run its handler against `kms`, and wrap the result (success or error)
+    /// in the standard envelope, echoing `id` back unchanged. An unregistered command name is
+    /// reported the same way as a handler error rather than bubbling up as a transport-level
+    /// failure.
+    pub fn dispatch(
+        &self,
+        command: &str,
+        kms: &mut EnclaveKMS,
+        params: serde_json::Value,
+        id: Option<serde_json::Value>,
+    ) -> CommandEnvelope {
+        let result = match self.handlers.get(command) {
+            Some(handler) => handler(kms, params),
+            None => Err(CommandError::NotFound(format!("Unknown command: {}", command))),
+        };
+
+        match result {
+            Ok(data) => CommandEnvelope { id, success: true, command: command.to_string(), data: Some(data), error: None },
+            Err(e) => CommandEnvelope { id, success: false, command: command.to_string(), data: None, error: Some(e.to_string()) },
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref COMMAND_REGISTRY: CommandRegistry = CommandRegistry::new();
+}
+
+/// The uniform entry point `handle_connection()` should call for every framed message it reads,
+/// replacing the old three-way split between a JSON `{"command": ...}` branch, a hardcoded
+/// `/keygen` legacy-string branch, and a final fallback through `parse_command` that returned a
+/// bare success/error string. `raw` is expected to be a JSON object `{ "id"?: any, "command":
+/// string, "params"?: object }`; the returned `CommandEnvelope` is what gets serialized and sent
+/// back over the wire, success or failure alike.
+pub fn dispatch_request(raw: &str, kms: &mut EnclaveKMS) -> CommandEnvelope {
+    let request: serde_json::Value = match serde_json::from_str(raw) {
+        Ok(value) => value,
+        Err(e) => {
+            return CommandEnvelope {
+                id: None,
+                success: false,
+                command: "unknown".to_string(),
+                data: None,
+                error: Some(format!("Failed to parse request as JSON: {}", e)),
+            };
+        }
+    };
+
+    let id = request.get("id").cloned();
+
+    let command = match request.get("command").and_then(serde_json::Value::as_str) {
+        Some(command) => command,
+        None => {
+            return CommandEnvelope {
+                id,
+                success: false,
+                command: "unknown".to_string(),
+                data: None,
+                error: Some("Malformed request: missing `command` field".to_string()),
+            };
+        }
+    };
+
+    let params = request.get("params").cloned().unwrap_or(serde_json::Value::Null);
+    COMMAND_REGISTRY.dispatch(command, kms, params, id)
+}
+
+fn handle_keygen_command(kms: &mut EnclaveKMS, _params: serde_json::Value) -> Result<serde_json::Value, CommandError> {
+    let account = kms.handle_keygen()?;
+    Ok(serde_json::json!({ "address": account.address, "private_key": account.private_key }))
+}
+
+fn param_str<'a>(params: &'a serde_json::Value, key: &str) -> Result<&'a str, CommandError> {
+    params
+        .get(key)
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| CommandError::InvalidParams(format!("missing `{}`", key)))
+}
+
+/// Sign a caller-supplied digest (e.g. the hash of an EIP-191 personal-sign payload) with the
+/// stored key for `address`, returning the raw ECDSA components rather than an assembled
+/// signature string, since callers of this RPC need `r`/`s`/`v` separately (contract calls that
+/// take them as independent arguments, bridges reconstructing a different signature encoding).
+fn handle_eth_sign_command(kms: &mut EnclaveKMS, params: serde_json::Value) -> Result<serde_json::Value, CommandError> {
+    let address = param_str(&params, "address")?;
+    let digest = param_str(&params, "digest")?;
+    let (r, s, v) = kms.sign_digest(address, digest)?;
+    Ok(serde_json::json!({ "r": r, "s": s, "v": v }))
+}
+
+/// Sign a caller-assembled RLP transaction payload with the stored key for `address`, again
+/// returning `r`/`s`/`v` rather than a re-encoded transaction: unlike `Command::Withdraw`'s
+/// higher-level flow, this RPC leaves assembling and broadcasting the signed transaction to the
+/// caller.
+fn handle_sign_transaction_command(kms: &mut EnclaveKMS, params: serde_json::Value) -> Result<serde_json::Value, CommandError> {
+    let address = param_str(&params, "address")?;
+    let rlp_payload = param_str(&params, "rlp_payload")?;
+    let (r, s, v) = kms.sign_rlp_payload(address, rlp_payload)?;
+    Ok(serde_json::json!({ "r": r, "s": s, "v": v }))
+}
+
+fn handle_list_accounts_command(kms: &mut EnclaveKMS, _params: serde_json::Value) -> Result<serde_json::Value, CommandError> {
+    let addresses = kms.list_addresses()?;
+    Ok(serde_json::json!(addresses))
+}
+
+fn handle_get_public_key_command(kms: &mut EnclaveKMS, params: serde_json::Value) -> Result<serde_json::Value, CommandError> {
+    let address = param_str(&params, "address")?;
+    let public_key = kms
+        .get_public_key(address)?
+        .ok_or_else(|| CommandError::NotFound(format!("No key stored for address {}", address)))?;
+    Ok(serde_json::json!({ "address": address, "public_key": public_key }))
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum Command {
     // Existing KMS commands
@@ -31,7 +230,60 @@ pub enum Command {
     Sign { address: String, message: String },
     List,
     Verify { address: String, message: String, signature: String },
-    
+
+    // KMS-level RPCs served through `CommandRegistry` rather than an inline match arm - see
+    // `CommandRegistry::new`.
+    EthSign { address: String, digest: String },
+    SignTransaction { address: String, rlp_payload: String },
+    ListAccounts,
+    GetPublicKey { address: String },
+
+    // Builds, RLP-encodes, and EIP-155-signs a full legacy Ethereum transaction inside the
+    // enclave from its structured fields, returning the broadcastable raw tx hex - distinct from
+    // `SignTransaction` above, which only signs an RLP payload the caller already assembled.
+    SignEthereumTransaction {
+        address: String,
+        nonce: u64,
+        gas_price: u64,
+        gas_limit: u64,
+        /// `None` for contract creation (an empty `to` field in the RLP encoding).
+        to: Option<String>,
+        value: Amount,
+        /// Hex-encoded calldata, `"0x"` or empty for a plain value transfer.
+        data: String,
+        chain_id: u64,
+    },
+
+    // BIP-39/BIP-32/44 HD wallet backup: generates (or extends) a mnemonic-backed account chain
+    // derived along `m/44'/60'/0'/0/i`, as an alternative to an isolated `Keygen` secret with no
+    // recovery story. See `hd_wallet`.
+    /// Generates a brand-new mnemonic, stores its seed encrypted, and derives `count` accounts
+    /// from it. The mnemonic is only ever returned in this one response - the caller is expected
+    /// to have the user write it down immediately.
+    KeygenHd { count: u32 },
+    /// Derives the next account index after the last one handed out for `mnemonic_id` (the
+    /// identifier `KeygenHd` returned alongside the mnemonic), without re-exposing the mnemonic
+    /// itself.
+    DeriveNext { mnemonic_id: String },
+
+    // EIP-712 structured-data signing (see `eip712`) - distinct from `Sign` above, which only
+    // produces the EIP-191 personal-message digest `hash_message` computes.
+    SignTypedData {
+        address: String,
+        /// The standard typed-data JSON document: `{"types", "primaryType", "domain", "message"}`.
+        typed_data: serde_json::Value,
+    },
+
+    // Vanity address search (see `vanity`), ported from the `ethkey` CLI's prefix-search idea:
+    // generate candidate accounts until one's address starts with `prefix`, or give up after
+    // `max_attempts`. The winning account is stored exactly like `Keygen` stores its account.
+    KeygenPrefix {
+        /// Hex prefix to match against, case-insensitive, with or without a leading `0x`. Rejected
+        /// if longer than 6 nibbles, since expected work grows as 16^n.
+        prefix: String,
+        max_attempts: u64,
+    },
+
     // PASS Wallet commands
     CreatePassWallet { name: String, owner: String },
     ListPassWallets,
@@ -61,7 +313,7 @@ pub enum Command {
     InboxDeposit { 
         wallet_address: String,
         asset_id: String,
-        amount: u64,
+        amount: Amount,
         deposit_id: String,
         transaction_hash: String,
         block_number: String,
@@ -75,21 +327,138 @@ pub enum Command {
     },
     
     // Transfer operations
-    InternalTransfer { 
+    InternalTransfer {
         wallet_address: String,
         asset_id: String,
-        amount: u64,
+        amount: Amount,
         from_subaccount: String,
         to_subaccount: String,
+        memo: Option<String>,
     },
-    Withdraw { 
+    Withdraw {
         wallet_address: String,
         asset_id: String,
-        amount: u64,
+        amount: Amount,
         subaccount_id: String,
         destination: String,
+        memo: Option<String>,
     },
-    
+
+    /// On-chain withdrawal: builds and signs a raw transaction via
+    /// `PassWalletManager::withdraw_to_external` rather than just moving an internal balance.
+    /// Supplying both `max_fee_per_gas` and `max_priority_fee_per_gas` produces an EIP-1559
+    /// type-0x02 transaction; otherwise `gas_price` (or its 20 gwei default) produces a legacy
+    /// one - see `FeeParams` in `pass_logic`.
+    WithdrawToExternal {
+        wallet_address: String,
+        subaccount_id: String,
+        asset_id: String,
+        amount: Amount,
+        destination: String,
+        gas_price: Option<u64>,
+        gas_limit: Option<u64>,
+        chain_id: u64,
+        memo: Option<String>,
+        #[serde(default)]
+        max_fee_per_gas: Option<u64>,
+        #[serde(default)]
+        max_priority_fee_per_gas: Option<u64>,
+        #[serde(default)]
+        access_list: Vec<crate::key_manager::AccessListEntry>,
+    },
+
+    // Conditional / time-locked transfers (escrow)
+    ConditionalTransfer {
+        wallet_address: String,
+        asset_id: String,
+        amount: Amount,
+        from_subaccount: String,
+        to_subaccount: String,
+        release_after: Option<u64>,
+        witnesses: Vec<String>,
+        required_signatures: u32,
+        cancelable_by: Option<String>,
+    },
+    TimeElapsed {
+        wallet_address: String,
+        escrow_id: String,
+    },
+    WitnessApprove {
+        wallet_address: String,
+        escrow_id: String,
+        witness: String,
+        signature: String,
+    },
+    CancelConditional {
+        wallet_address: String,
+        escrow_id: String,
+        requester: String,
+    },
+
+    // Backup, restore, and integrity verification
+    BackupWallet {
+        wallet_address: String,
+        passphrase: String,
+    },
+    RestoreWallet {
+        snapshot: String,
+        passphrase: String,
+    },
+    VerifyWalletIntegrity {
+        wallet_address: String,
+    },
+
+    // Tamper-evident provenance hashchain (see `PassWalletState::append_history`)
+    GetProvenanceHead {
+        wallet_address: String,
+    },
+    VerifyProvenanceHistory {
+        wallet_address: String,
+    },
+    SignProvenanceHead {
+        wallet_address: String,
+    },
+
+    // Device-migration export/import (see `PassWalletManager::export_wallet_for_migration`) -
+    // distinct from `BackupWallet`/`RestoreWallet` above: this pair seals the blob with
+    // `crypto_box` under a passphrase-derived keypair and targets restoring onto a device that
+    // has never held the wallet before, rather than re-associating with a KMS-known address.
+    ExportWalletMigration {
+        wallet_address: String,
+        passphrase: String,
+    },
+    ImportWalletMigration {
+        blob: String,
+        passphrase: String,
+    },
+    /// Decrypt and structurally validate a migration blob without importing it - lets a caller
+    /// confirm a passphrase and snapshot are good before committing to `ImportWalletMigration`,
+    /// which would otherwise be the first point a wrong passphrase or corrupted blob surfaces.
+    VerifyWalletMigration {
+        blob: String,
+        passphrase: String,
+    },
+
+    // Background deposit syncing
+    StartDepositSync {
+        wallet_address: String,
+        rpc_url: String,
+        poll_interval_secs: u64,
+        watched_addresses: Vec<String>,
+    },
+    StopDepositSync {
+        wallet_address: String,
+    },
+
+    // Payment proofs
+    GetPaymentProof {
+        wallet_address: String,
+        nonce: u64,
+    },
+    VerifyPaymentProof {
+        proof: PaymentProof,
+    },
+
     // Utility operations
     ProcessOutbox { wallet_address: String },
     GetBalance { 
@@ -97,11 +466,27 @@ pub enum Command {
         subaccount_id: String,
         asset_id: String,
     },
-    GetSubaccountBalances { 
+    GetSubaccountBalances {
         wallet_address: String,
         subaccount_id: String,
+        #[serde(default)]
+        reference_asset: Option<String>,
     },
-    
+
+    // Cross-asset valuation and rate quoting
+    SetAssetRate {
+        wallet_address: String,
+        asset_id: String,
+        reference_asset: String,
+        rate_numerator: u128,
+        rate_denominator: u128,
+    },
+    GetPortfolioValue {
+        wallet_address: String,
+        subaccount_id: String,
+        reference_asset: String,
+    },
+
     // Signing operations
     SignGSM { 
         wallet_address: String,
@@ -110,8 +495,58 @@ pub enum Command {
     },
     
     // Asset operations
-    GetAssets { 
+    GetAssets {
+        wallet_address: String,
+    },
+
+    // Batch execution
+    Batch {
+        commands: Vec<Command>,
+        atomic: bool,
+    },
+    // Same-wallet op batch applied under a single lock acquisition (see
+    // `PassWalletManager::apply_batch`) - narrower and cheaper than `Batch` for the common case of
+    // many claims/transfers/withdrawals/deposits against one wallet.
+    ApplyWalletBatch {
         wallet_address: String,
+        ops: Vec<WalletOp>,
+    },
+
+    // Social recovery / emergency access
+    AddRecoveryContact {
+        wallet_address: String,
+        contact: String,
+        waiting_period_secs: u64,
+        required_approvals: u32,
+        signature: String,
+    },
+    InitiateRecovery {
+        wallet_address: String,
+        contact: String,
+        signature: String,
+    },
+    CancelRecovery {
+        wallet_address: String,
+        requester: String,
+        signature: String,
+    },
+    ApproveRecovery {
+        wallet_address: String,
+        contact: String,
+        signature: String,
+    },
+    ProcessRecoveryTimeout {
+        wallet_address: String,
+    },
+
+    // End-to-end encrypted command channel (see `secure_channel`)
+    InitSecureChannel {
+        client_public_key: String,
+    },
+    SecureCommand {
+        session_id: String,
+        nonce: String,
+        body: String,
     },
 }
 
@@ -125,28 +560,108 @@ pub struct Response {
 pub fn parse_command(command: &str) -> Result<Response, String> {
     let command_data: Command = serde_json::from_str(command)
         .map_err(|e| format!("Failed to parse command: {}", e))?;
-    
+
+    let _guard = COMMAND_LOCK.lock().unwrap();
+    dispatch_command(command_data)
+}
+
+/// The wallet address a command targets, if any - used by `Command::Batch` to decide which
+/// wallets need snapshotting before an atomic batch runs. Commands with no wallet-scoped state
+/// (KMS-only commands, `CreatePassWallet`, `RestoreWallet`, `VerifyPaymentProof`, `Batch` itself)
+/// return `None` and are not covered by batch rollback.
+fn command_wallet_address(command: &Command) -> Option<&str> {
+    match command {
+        Command::Keygen
+        | Command::Sign { .. }
+        | Command::List
+        | Command::Verify { .. }
+        | Command::EthSign { .. }
+        | Command::SignTransaction { .. }
+        | Command::SignEthereumTransaction { .. }
+        | Command::KeygenHd { .. }
+        | Command::DeriveNext { .. }
+        | Command::SignTypedData { .. }
+        | Command::KeygenPrefix { .. }
+        | Command::ListAccounts
+        | Command::GetPublicKey { .. }
+        | Command::CreatePassWallet { .. }
+        | Command::ListPassWallets
+        | Command::RestoreWallet { .. }
+        | Command::ImportWalletMigration { .. }
+        | Command::VerifyWalletMigration { .. }
+        | Command::VerifyPaymentProof { .. }
+        | Command::Batch { .. }
+        | Command::InitSecureChannel { .. }
+        | Command::SecureCommand { .. } => None,
+        Command::GetPassWalletState { wallet_address }
+        | Command::AddAsset { wallet_address, .. }
+        | Command::AddSubaccount { wallet_address, .. }
+        | Command::InboxDeposit { wallet_address, .. }
+        | Command::ClaimInbox { wallet_address, .. }
+        | Command::InternalTransfer { wallet_address, .. }
+        | Command::Withdraw { wallet_address, .. }
+        | Command::WithdrawToExternal { wallet_address, .. }
+        | Command::ApplyWalletBatch { wallet_address, .. }
+        | Command::ConditionalTransfer { wallet_address, .. }
+        | Command::TimeElapsed { wallet_address, .. }
+        | Command::WitnessApprove { wallet_address, .. }
+        | Command::CancelConditional { wallet_address, .. }
+        | Command::BackupWallet { wallet_address, .. }
+        | Command::ExportWalletMigration { wallet_address, .. }
+        | Command::VerifyWalletIntegrity { wallet_address }
+        | Command::GetProvenanceHead { wallet_address }
+        | Command::VerifyProvenanceHistory { wallet_address }
+        | Command::SignProvenanceHead { wallet_address }
+        | Command::StartDepositSync { wallet_address, .. }
+        | Command::StopDepositSync { wallet_address }
+        | Command::GetPaymentProof { wallet_address, .. }
+        | Command::ProcessOutbox { wallet_address }
+        | Command::GetBalance { wallet_address, .. }
+        | Command::GetSubaccountBalances { wallet_address, .. }
+        | Command::SetAssetRate { wallet_address, .. }
+        | Command::GetPortfolioValue { wallet_address, .. }
+        | Command::SignGSM { wallet_address, .. }
+        | Command::GetAssets { wallet_address }
+        | Command::AddRecoveryContact { wallet_address, .. }
+        | Command::InitiateRecovery { wallet_address, .. }
+        | Command::CancelRecovery { wallet_address, .. }
+        | Command::ApproveRecovery { wallet_address, .. }
+        | Command::ProcessRecoveryTimeout { wallet_address } => Some(wallet_address),
+    }
+}
+
+/// Run `command` through `COMMAND_REGISTRY` against the global KMS, adapting its `CommandEnvelope`
+/// down to the legacy `Response` shape so `dispatch_command`'s non-registry arms don't have to
+/// change. `CommandEnvelope.command` is dropped in the adaptation; callers that need it should go
+/// through `CommandRegistry::dispatch` directly instead of via `Command`/`parse_command`.
+fn registry_dispatch(command: &str, params: serde_json::Value) -> Response {
+    let mut kms = KMS.lock().unwrap();
+    let envelope = COMMAND_REGISTRY.dispatch(command, &mut kms, params, None);
+    Response { success: envelope.success, data: envelope.data, error: envelope.error }
+}
+
+fn dispatch_command(command_data: Command) -> Result<Response, String> {
     match command_data {
         // Existing KMS commands
-        Command::Keygen => {
-            let mut kms = KMS.lock().unwrap();
-            match kms.handle_keygen() {
-                Ok(account) => Ok(Response {
-                    success: true,
-                    data: Some(serde_json::json!({
-                        "address": account.address,
-                        "private_key": account.private_key
-                    })),
-                    error: None,
-                }),
-                Err(e) => Ok(Response {
-                    success: false,
-                    data: None,
-                    error: Some(format!("Failed to generate account: {}", e)),
-                }),
-            }
-        }
-        
+        Command::Keygen => Ok(registry_dispatch("keygen", serde_json::Value::Null)),
+
+        Command::EthSign { address, digest } => Ok(registry_dispatch(
+            "eth_sign",
+            serde_json::json!({ "address": address, "digest": digest }),
+        )),
+
+        Command::SignTransaction { address, rlp_payload } => Ok(registry_dispatch(
+            "sign_transaction",
+            serde_json::json!({ "address": address, "rlp_payload": rlp_payload }),
+        )),
+
+        Command::ListAccounts => Ok(registry_dispatch("list_accounts", serde_json::Value::Null)),
+
+        Command::GetPublicKey { address } => Ok(registry_dispatch(
+            "get_public_key",
+            serde_json::json!({ "address": address }),
+        )),
+
         Command::Sign { address, message } => {
             let kms = KMS.lock().unwrap();
             match kms.sign_message(&message, &address) {
@@ -208,6 +723,106 @@ pub fn parse_command(command: &str) -> Result<Response, String> {
             }
         }
         
+        Command::SignEthereumTransaction { address, nonce, gas_price, gas_limit, to, value, data, chain_id } => {
+            let kms = KMS.lock().unwrap();
+            match kms.sign_legacy_transaction(&address, nonce, gas_price, gas_limit, to.as_deref(), value, &data, chain_id) {
+                Ok(raw_transaction) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({ "raw_transaction": raw_transaction })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Transaction signing error: {}", e)),
+                }),
+            }
+        }
+
+        Command::KeygenHd { count } => {
+            let mut kms = KMS.lock().unwrap();
+            match kms.generate_hd_wallet(count) {
+                Ok((mnemonic_id, mnemonic, accounts)) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "mnemonic_id": mnemonic_id,
+                        "mnemonic": mnemonic,
+                        "accounts": accounts,
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("HD wallet generation error: {}", e)),
+                }),
+            }
+        }
+
+        Command::DeriveNext { mnemonic_id } => {
+            let mut kms = KMS.lock().unwrap();
+            match kms.derive_next_hd_account(&mnemonic_id) {
+                Ok(account) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({ "account": account })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("HD account derivation error: {}", e)),
+                }),
+            }
+        }
+
+        Command::SignTypedData { address, typed_data } => {
+            let digest = match crate::eip712::typed_data_digest(
+                &match serde_json::from_value(typed_data) {
+                    Ok(typed_data) => typed_data,
+                    Err(e) => return Ok(Response { success: false, data: None, error: Some(format!("Invalid typed data: {}", e)) }),
+                },
+            ) {
+                Ok(digest) => digest,
+                Err(e) => return Ok(Response { success: false, data: None, error: Some(format!("Failed to hash typed data: {}", e)) }),
+            };
+
+            let kms = KMS.lock().unwrap();
+            match kms.sign_digest(&address, &hex::encode(digest)) {
+                Ok((r, s, v)) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({ "r": r, "s": s, "v": v })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Typed data signing error: {}", e)),
+                }),
+            }
+        }
+
+        Command::KeygenPrefix { prefix, max_attempts } => match crate::vanity::search_prefix(&prefix, max_attempts) {
+            Ok(found) => {
+                let mut kms = KMS.lock().unwrap();
+                match kms.store_account(&found.address, &found.private_key) {
+                    Ok(()) => Ok(Response {
+                        success: true,
+                        data: Some(serde_json::json!({
+                            "address": found.address,
+                            "attempts": found.attempts,
+                        })),
+                        error: None,
+                    }),
+                    Err(e) => Ok(Response {
+                        success: false,
+                        data: None,
+                        error: Some(format!("Failed to store vanity account: {}", e)),
+                    }),
+                }
+            }
+            Err(e) => Ok(Response { success: false, data: None, error: Some(e.to_string()) }),
+        },
+
         // PASS Wallet commands
         Command::CreatePassWallet { name, owner } => {
             match PASS_WALLET_MANAGER.create_wallet(name.clone(), owner.clone()) {
@@ -371,7 +986,7 @@ pub fn parse_command(command: &str) -> Result<Response, String> {
                     data: Some(serde_json::json!({
                         "wallet_address": wallet_address,
                         "asset_id": asset_id,
-                        "amount": amount,
+                        "amount": amount.to_string(),
                         "deposit_id": deposit_id,
                         "transaction_hash": transaction_hash
                     })),
@@ -419,31 +1034,32 @@ pub fn parse_command(command: &str) -> Result<Response, String> {
             }
         }
         
-        Command::InternalTransfer { 
+        Command::InternalTransfer {
             wallet_address,
             asset_id,
             amount,
             from_subaccount,
             to_subaccount,
+            memo,
         } => {
-            match PASS_WALLET_MANAGER.internal_transfer(&wallet_address, &asset_id, amount, &from_subaccount, &to_subaccount) {
+            match PASS_WALLET_MANAGER.internal_transfer(&wallet_address, &asset_id, amount, &from_subaccount, &to_subaccount, memo) {
                 Ok(()) => {
                     // Get updated balances
                     let from_balance = PASS_WALLET_MANAGER.get_balance(&wallet_address, &from_subaccount, &asset_id)
-                        .unwrap_or(0);
+                        .unwrap_or(Amount::zero());
                     let to_balance = PASS_WALLET_MANAGER.get_balance(&wallet_address, &to_subaccount, &asset_id)
-                        .unwrap_or(0);
-                    
+                        .unwrap_or(Amount::zero());
+
                     Ok(Response {
                         success: true,
                         data: Some(serde_json::json!({
                             "wallet_address": wallet_address,
                             "asset_id": asset_id,
-                            "amount": amount,
+                            "amount": amount.to_string(),
                             "from_subaccount": from_subaccount,
                             "to_subaccount": to_subaccount,
-                            "from_balance": from_balance,
-                            "to_balance": to_balance
+                            "from_balance": from_balance.to_string(),
+                            "to_balance": to_balance.to_string()
                         })),
                         error: None,
                     })
@@ -456,27 +1072,28 @@ pub fn parse_command(command: &str) -> Result<Response, String> {
             }
         }
         
-        Command::Withdraw { 
+        Command::Withdraw {
             wallet_address,
             asset_id,
             amount,
             subaccount_id,
             destination,
+            memo,
         } => {
-            match PASS_WALLET_MANAGER.withdraw(&wallet_address, &asset_id, amount, &subaccount_id, &destination) {
+            match PASS_WALLET_MANAGER.withdraw(&wallet_address, &asset_id, amount, &subaccount_id, &destination, memo) {
                 Ok(()) => {
                     let remaining_balance = PASS_WALLET_MANAGER.get_balance(&wallet_address, &subaccount_id, &asset_id)
-                        .unwrap_or(0);
-                    
+                        .unwrap_or(Amount::zero());
+
                     Ok(Response {
                         success: true,
                         data: Some(serde_json::json!({
                             "wallet_address": wallet_address,
                             "asset_id": asset_id,
-                            "amount": amount,
+                            "amount": amount.to_string(),
                             "subaccount_id": subaccount_id,
                             "destination": destination,
-                            "remaining_balance": remaining_balance
+                            "remaining_balance": remaining_balance.to_string()
                         })),
                         error: None,
                     })
@@ -488,39 +1105,404 @@ pub fn parse_command(command: &str) -> Result<Response, String> {
                 }),
             }
         }
-        
-        Command::ProcessOutbox { wallet_address } => {
-            match PASS_WALLET_MANAGER.process_outbox(&wallet_address) {
-                Ok(processed_items) => Ok(Response {
-                    success: true,
-                    data: Some(serde_json::json!({
-                        "wallet_address": wallet_address,
-                        "processed_items": processed_items,
-                        "count": processed_items.len()
-                    })),
-                    error: None,
-                }),
-                Err(e) => Ok(Response {
-                    success: false,
-                    data: None,
-                    error: Some(format!("Failed to process outbox: {}", e)),
-                }),
-            }
-        }
-        
-        Command::GetBalance { 
+
+        Command::WithdrawToExternal {
             wallet_address,
             subaccount_id,
             asset_id,
+            amount,
+            destination,
+            gas_price,
+            gas_limit,
+            chain_id,
+            memo,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            access_list,
         } => {
-            match PASS_WALLET_MANAGER.get_balance(&wallet_address, &subaccount_id, &asset_id) {
-                Ok(balance) => Ok(Response {
-                    success: true,
-                    data: Some(serde_json::json!({
+            match PASS_WALLET_MANAGER.withdraw_to_external(
+                &wallet_address,
+                &subaccount_id,
+                &asset_id,
+                amount,
+                &destination,
+                gas_price,
+                gas_limit,
+                chain_id,
+                memo,
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+                access_list,
+            ) {
+                Ok((raw_transaction, tx_nonce, actual_gas_price, actual_gas_limit, actual_max_fee_per_gas, actual_max_priority_fee_per_gas)) => {
+                    let tx_type = if actual_max_fee_per_gas.is_some() { "eip1559" } else { "legacy" };
+                    Ok(Response {
+                        success: true,
+                        data: Some(serde_json::json!({
+                            "wallet_address": wallet_address,
+                            "raw_transaction": raw_transaction,
+                            "nonce": tx_nonce,
+                            "gas_price": actual_gas_price,
+                            "gas_limit": actual_gas_limit,
+                            "max_fee_per_gas": actual_max_fee_per_gas,
+                            "max_priority_fee_per_gas": actual_max_priority_fee_per_gas,
+                            "tx_type": tx_type,
+                        })),
+                        error: None,
+                    })
+                }
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to withdraw to external address: {}", e)),
+                }),
+            }
+        }
+
+        Command::ConditionalTransfer {
+            wallet_address,
+            asset_id,
+            amount,
+            from_subaccount,
+            to_subaccount,
+            release_after,
+            witnesses,
+            required_signatures,
+            cancelable_by,
+        } => {
+            match PASS_WALLET_MANAGER.create_conditional_transfer(
+                &wallet_address,
+                &asset_id,
+                amount,
+                &from_subaccount,
+                &to_subaccount,
+                release_after,
+                witnesses,
+                required_signatures,
+                cancelable_by,
+            ) {
+                Ok(escrow_id) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "escrow_id": escrow_id,
+                        "asset_id": asset_id,
+                        "amount": amount.to_string(),
+                        "from_subaccount": from_subaccount,
+                        "to_subaccount": to_subaccount
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to create conditional transfer: {}", e)),
+                }),
+            }
+        }
+
+        Command::TimeElapsed { wallet_address, escrow_id } => {
+            match PASS_WALLET_MANAGER.release_escrow(&wallet_address, &escrow_id) {
+                Ok(()) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "escrow_id": escrow_id
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to release escrow: {}", e)),
+                }),
+            }
+        }
+
+        Command::WitnessApprove { wallet_address, escrow_id, witness, signature } => {
+            match PASS_WALLET_MANAGER.witness_approve(&wallet_address, &escrow_id, &witness, &signature) {
+                Ok(released) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "escrow_id": escrow_id,
+                        "witness": witness,
+                        "released": released
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to record witness approval: {}", e)),
+                }),
+            }
+        }
+
+        Command::CancelConditional { wallet_address, escrow_id, requester } => {
+            match PASS_WALLET_MANAGER.cancel_conditional_transfer(&wallet_address, &escrow_id, &requester) {
+                Ok(()) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "escrow_id": escrow_id
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to cancel conditional transfer: {}", e)),
+                }),
+            }
+        }
+
+        Command::BackupWallet { wallet_address, passphrase } => {
+            match PASS_WALLET_MANAGER.export_backup(&wallet_address, &passphrase) {
+                Ok(snapshot) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "snapshot": snapshot
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to back up wallet: {}", e)),
+                }),
+            }
+        }
+
+        Command::RestoreWallet { snapshot, passphrase } => {
+            match PASS_WALLET_MANAGER.import_backup(&snapshot, &passphrase) {
+                Ok(wallet_address) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to restore wallet: {}", e)),
+                }),
+            }
+        }
+
+        Command::ExportWalletMigration { wallet_address, passphrase } => {
+            match PASS_WALLET_MANAGER.export_wallet_for_migration(&wallet_address, &passphrase) {
+                Ok(blob) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "blob": blob
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to export wallet for migration: {}", e)),
+                }),
+            }
+        }
+
+        Command::ImportWalletMigration { blob, passphrase } => {
+            match PASS_WALLET_MANAGER.import_wallet_for_migration(&blob, &passphrase) {
+                Ok(wallet_address) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to import wallet for migration: {}", e)),
+                }),
+            }
+        }
+
+        Command::VerifyWalletMigration { blob, passphrase } => {
+            match PASS_WALLET_MANAGER.verify_migration_snapshot(&blob, &passphrase) {
+                Ok(wallet_address) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Migration snapshot verification failed: {}", e)),
+                }),
+            }
+        }
+
+        Command::VerifyWalletIntegrity { wallet_address } => {
+            match PASS_WALLET_MANAGER.verify_wallet_integrity(&wallet_address) {
+                Ok(matches) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "valid": matches
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to verify wallet integrity: {}", e)),
+                }),
+            }
+        }
+
+        Command::GetProvenanceHead { wallet_address } => {
+            match PASS_WALLET_MANAGER.head_hash(&wallet_address) {
+                Ok(head) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "head_hash": hex::encode(head)
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to get provenance head: {}", e)),
+                }),
+            }
+        }
+
+        Command::VerifyProvenanceHistory { wallet_address } => {
+            match PASS_WALLET_MANAGER.verify_history(&wallet_address) {
+                Ok(()) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({ "wallet_address": wallet_address, "valid": true })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Provenance history verification failed: {}", e)),
+                }),
+            }
+        }
+
+        Command::SignProvenanceHead { wallet_address } => {
+            match PASS_WALLET_MANAGER.sign_provenance_head(&wallet_address) {
+                Ok(signature) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "signature": signature
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to sign provenance head: {}", e)),
+                }),
+            }
+        }
+
+        Command::StartDepositSync { wallet_address, rpc_url, poll_interval_secs, watched_addresses } => {
+            match PASS_WALLET_MANAGER.start_deposit_sync(&wallet_address, rpc_url.clone(), poll_interval_secs, watched_addresses) {
+                Ok(()) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "rpc_url": rpc_url,
+                        "poll_interval_secs": poll_interval_secs
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to start deposit sync: {}", e)),
+                }),
+            }
+        }
+
+        Command::StopDepositSync { wallet_address } => {
+            PASS_WALLET_MANAGER.stop_deposit_sync(&wallet_address);
+            Ok(Response {
+                success: true,
+                data: Some(serde_json::json!({ "wallet_address": wallet_address })),
+                error: None,
+            })
+        }
+
+        Command::GetPaymentProof { wallet_address, nonce } => {
+            match PASS_WALLET_MANAGER.get_payment_proof(&wallet_address, nonce) {
+                Ok(proof) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({ "proof": proof })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to get payment proof: {}", e)),
+                }),
+            }
+        }
+
+        Command::VerifyPaymentProof { proof } => {
+            match PASS_WALLET_MANAGER.verify_payment_proof(&proof) {
+                Ok(valid) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({ "valid": valid })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to verify payment proof: {}", e)),
+                }),
+            }
+        }
+
+        Command::ProcessOutbox { wallet_address } => {
+            match PASS_WALLET_MANAGER.process_outbox(&wallet_address) {
+                Ok(processed_items) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "processed_items": processed_items,
+                        "count": processed_items.len()
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to process outbox: {}", e)),
+                }),
+            }
+        }
+        
+        Command::GetBalance { 
+            wallet_address,
+            subaccount_id,
+            asset_id,
+        } => {
+            match PASS_WALLET_MANAGER.get_balance(&wallet_address, &subaccount_id, &asset_id) {
+                Ok(balance) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
                         "wallet_address": wallet_address,
                         "subaccount_id": subaccount_id,
                         "asset_id": asset_id,
-                        "balance": balance
+                        "balance": balance.to_string()
                     })),
                     error: None,
                 }),
@@ -532,29 +1514,110 @@ pub fn parse_command(command: &str) -> Result<Response, String> {
             }
         }
         
-        Command::GetSubaccountBalances { 
+        Command::GetSubaccountBalances {
             wallet_address,
             subaccount_id,
+            reference_asset,
+        } => match reference_asset {
+            None => match PASS_WALLET_MANAGER.get_subaccount_balances(&wallet_address, &subaccount_id) {
+                Ok(balances) => {
+                    let balances: HashMap<String, String> = balances
+                        .into_iter()
+                        .map(|(asset_id, amount)| (asset_id, amount.to_string()))
+                        .collect();
+                    Ok(Response {
+                        success: true,
+                        data: Some(serde_json::json!({
+                            "wallet_address": wallet_address,
+                            "subaccount_id": subaccount_id,
+                            "balances": balances
+                        })),
+                        error: None,
+                    })
+                }
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to get subaccount balances: {}", e)),
+                }),
+            },
+            Some(reference_asset) => match PASS_WALLET_MANAGER.get_subaccount_balances_valued(&wallet_address, &subaccount_id, &reference_asset) {
+                Ok(valued) => {
+                    let balances: Vec<serde_json::Value> = valued
+                        .into_iter()
+                        .map(|(asset_id, amount, value)| serde_json::json!({
+                            "asset_id": asset_id,
+                            "balance": amount.to_string(),
+                            "value": value.to_string()
+                        }))
+                        .collect();
+                    Ok(Response {
+                        success: true,
+                        data: Some(serde_json::json!({
+                            "wallet_address": wallet_address,
+                            "subaccount_id": subaccount_id,
+                            "reference_asset": reference_asset,
+                            "balances": balances
+                        })),
+                        error: None,
+                    })
+                }
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to get valued subaccount balances: {}", e)),
+                }),
+            },
+        },
+
+        Command::SetAssetRate {
+            wallet_address,
+            asset_id,
+            reference_asset,
+            rate_numerator,
+            rate_denominator,
         } => {
-            match PASS_WALLET_MANAGER.get_subaccount_balances(&wallet_address, &subaccount_id) {
-                Ok(balances) => Ok(Response {
+            match PASS_WALLET_MANAGER.set_asset_rate(&wallet_address, &asset_id, &reference_asset, rate_numerator, rate_denominator) {
+                Ok(()) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "asset_id": asset_id,
+                        "reference_asset": reference_asset,
+                        "rate_numerator": rate_numerator.to_string(),
+                        "rate_denominator": rate_denominator.to_string()
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to set asset rate: {}", e)),
+                }),
+            }
+        }
+
+        Command::GetPortfolioValue { wallet_address, subaccount_id, reference_asset } => {
+            match PASS_WALLET_MANAGER.get_portfolio_value(&wallet_address, &subaccount_id, &reference_asset) {
+                Ok(value) => Ok(Response {
                     success: true,
                     data: Some(serde_json::json!({
                         "wallet_address": wallet_address,
                         "subaccount_id": subaccount_id,
-                        "balances": balances
+                        "reference_asset": reference_asset,
+                        "value": value.to_string()
                     })),
                     error: None,
                 }),
                 Err(e) => Ok(Response {
                     success: false,
                     data: None,
-                    error: Some(format!("Failed to get subaccount balances: {}", e)),
+                    error: Some(format!("Failed to get portfolio value: {}", e)),
                 }),
             }
         }
-        
-        Command::SignGSM { 
+
+        Command::SignGSM {
             wallet_address,
             domain,
             message,
@@ -595,5 +1658,238 @@ pub fn parse_command(command: &str) -> Result<Response, String> {
                 }),
             }
         }
+
+        Command::Batch { commands, atomic } => {
+            // Snapshot every wallet a sub-command targets before running anything, so an
+            // atomic batch can roll every one of them back to this point on the first failure.
+            let mut snapshots: HashMap<String, Option<PassWalletState>> = HashMap::new();
+            if atomic {
+                for sub_command in &commands {
+                    if let Some(wallet_address) = command_wallet_address(sub_command) {
+                        if !snapshots.contains_key(wallet_address) {
+                            snapshots.insert(wallet_address.to_string(), PASS_WALLET_MANAGER.snapshot_wallet(wallet_address));
+                        }
+                    }
+                }
+            }
+
+            let mut results = Vec::with_capacity(commands.len());
+            let mut failure: Option<(usize, String)> = None;
+
+            for (index, sub_command) in commands.into_iter().enumerate() {
+                let (success, data, error) = match dispatch_command(sub_command) {
+                    Ok(response) => (response.success, response.data, response.error),
+                    Err(e) => (false, None, Some(e)),
+                };
+
+                if !success && failure.is_none() {
+                    failure = Some((index, error.clone().unwrap_or_else(|| "Unknown error".to_string())));
+                }
+
+                results.push(serde_json::json!({
+                    "index": index,
+                    "success": success,
+                    "data": data,
+                    "error": error
+                }));
+
+                if atomic && failure.is_some() {
+                    break;
+                }
+            }
+
+            if atomic {
+                if let Some((index, error)) = failure {
+                    for (wallet_address, snapshot) in snapshots {
+                        if let Some(state) = snapshot {
+                            let _ = PASS_WALLET_MANAGER.restore_wallet(&wallet_address, state);
+                        }
+                    }
+
+                    return Ok(Response {
+                        success: false,
+                        data: Some(serde_json::json!({ "results": results })),
+                        error: Some(format!("Batch failed at index {}: {}", index, error)),
+                    });
+                }
+            }
+
+            Ok(Response {
+                success: failure.is_none(),
+                data: Some(serde_json::json!({ "results": results })),
+                error: None,
+            })
+        }
+
+        Command::ApplyWalletBatch { wallet_address, ops } => {
+            match PASS_WALLET_MANAGER.apply_batch(&wallet_address, ops) {
+                Ok(results) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "results": results
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to apply wallet batch: {}", e)),
+                }),
+            }
+        }
+
+        Command::AddRecoveryContact { wallet_address, contact, waiting_period_secs, required_approvals, signature } => {
+            match PASS_WALLET_MANAGER.add_recovery_contact(&wallet_address, &contact, waiting_period_secs, required_approvals, &signature) {
+                Ok(()) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "contact": contact
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to add recovery contact: {}", e)),
+                }),
+            }
+        }
+
+        Command::InitiateRecovery { wallet_address, contact, signature } => {
+            match PASS_WALLET_MANAGER.initiate_recovery(&wallet_address, &contact, &signature) {
+                Ok(()) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "initiated_by": contact
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to initiate recovery: {}", e)),
+                }),
+            }
+        }
+
+        Command::CancelRecovery { wallet_address, requester, signature } => {
+            match PASS_WALLET_MANAGER.cancel_recovery(&wallet_address, &requester, &signature) {
+                Ok(()) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to cancel recovery: {}", e)),
+                }),
+            }
+        }
+
+        Command::ApproveRecovery { wallet_address, contact, signature } => {
+            match PASS_WALLET_MANAGER.approve_recovery(&wallet_address, &contact, &signature) {
+                Ok(completed) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address,
+                        "completed": completed
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to approve recovery: {}", e)),
+                }),
+            }
+        }
+
+        Command::ProcessRecoveryTimeout { wallet_address } => {
+            match PASS_WALLET_MANAGER.process_recovery_timeout(&wallet_address) {
+                Ok(()) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "wallet_address": wallet_address
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to process recovery timeout: {}", e)),
+                }),
+            }
+        }
+
+        Command::InitSecureChannel { client_public_key } => {
+            let client_key_bytes = hex::decode(&client_public_key)
+                .map_err(|e| format!("Invalid client_public_key: {}", e))?;
+            let client_key: [u8; 32] = client_key_bytes
+                .try_into()
+                .map_err(|_| "client_public_key must be 32 bytes".to_string())?;
+
+            match secure_channel::open_session(&client_key) {
+                Ok((session_id, enclave_public_key)) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "session_id": session_id,
+                        "enclave_public_key": hex::encode(enclave_public_key)
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to open secure channel: {}", e)),
+                }),
+            }
+        }
+
+        Command::SecureCommand { session_id, nonce, body } => {
+            let nonce_bytes = match hex::decode(&nonce) {
+                Ok(b) => b,
+                Err(e) => return Ok(Response { success: false, data: None, error: Some(format!("Invalid nonce: {}", e)) }),
+            };
+            let body_bytes = match base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &body) {
+                Ok(b) => b,
+                Err(e) => return Ok(Response { success: false, data: None, error: Some(format!("Invalid body encoding: {}", e)) }),
+            };
+
+            let plaintext = match secure_channel::decrypt(&session_id, &nonce_bytes, &body_bytes) {
+                Ok(p) => p,
+                Err(e) => return Ok(Response { success: false, data: None, error: Some(format!("Failed to decrypt secure command: {}", e)) }),
+            };
+
+            let inner_command: Command = match serde_json::from_slice(&plaintext) {
+                Ok(c) => c,
+                Err(e) => return Ok(Response { success: false, data: None, error: Some(format!("Failed to parse secure command: {}", e)) }),
+            };
+
+            let inner_response = dispatch_command(inner_command)?;
+            let inner_response_bytes = serde_json::to_vec(&inner_response)
+                .map_err(|e| format!("Failed to serialize secure response: {}", e))?;
+
+            match secure_channel::encrypt(&session_id, &inner_response_bytes) {
+                Ok((reply_nonce, reply_body)) => Ok(Response {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "nonce": hex::encode(reply_nonce),
+                        "body": base64::Engine::encode(&base64::engine::general_purpose::STANDARD, reply_body)
+                    })),
+                    error: None,
+                }),
+                Err(e) => Ok(Response {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to encrypt secure response: {}", e)),
+                }),
+            }
+        }
     }
 }
\ No newline at end of file
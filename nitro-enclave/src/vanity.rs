@@ -0,0 +1,112 @@
+// Vanity Ethereum address generation: repeatedly generate a fresh secp256k1 keypair and check
+// whether its address - lowercased, without the `0x` prefix - starts with a caller-requested hex
+// prefix, ported from the prefix-search idea in the `ethkey` CLI. Feeds `Command::KeygenPrefix`
+// (see `server_logic`): this module never touches `EnclaveKMS` or its storage while searching -
+// key generation and address derivation are pure, so every discarded candidate costs nothing but
+// CPU, and only the winning keypair is ever handed back to the caller to persist.
+//
+// To keep the (conceptually single-threaded) enclave responsive, the search runs across a small
+// bounded pool of worker threads - the same fixed-worker-count shape `ConnectionPool` uses for
+// accepted connections - each generating candidates in batches and checking a shared `AtomicBool`
+// between batches so every thread stops as soon as any one of them finds a match.
+//
+// Declared via `pub mod vanity;` in `src/lib.rs`, next to `key_manager`.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{anyhow, Result};
+use k256::SecretKey;
+
+use crate::key_manager::public_key_to_address;
+
+/// Expected work to find a match grows as 16^n; beyond this a search is impractical to run
+/// synchronously inside one RPC call, so longer prefixes are rejected up front.
+const MAX_PREFIX_NIBBLES: usize = 6;
+
+/// Fixed worker-thread count for the search, matching `ConnectionPool`'s bounded-pool convention
+/// rather than spawning one thread per request.
+const WORKER_COUNT: usize = 4;
+
+/// Candidates a worker generates before re-checking the shared `found` flag, so threads aren't
+/// contending on the same atomics for every single candidate.
+const BATCH_SIZE: u64 = 64;
+
+/// The winning keypair plus how many candidates were tried in total (across every worker thread)
+/// to find it, so a caller can gauge how hard that prefix was to hit.
+pub struct VanityMatch {
+    pub address: String,
+    pub private_key: [u8; 32],
+    pub attempts: u64,
+}
+
+fn generate_candidate() -> ([u8; 32], String) {
+    let secret_key = SecretKey::random(&mut rand::thread_rng());
+    let public_key = secret_key.public_key();
+    let address = public_key_to_address(&public_key);
+
+    let mut private_key = [0u8; 32];
+    private_key.copy_from_slice(&secret_key.to_bytes());
+    (private_key, address)
+}
+
+/// Search for an Ethereum address starting with `prefix` (case-insensitive hex, `0x` optional),
+/// generating up to `max_attempts` candidates spread across `WORKER_COUNT` threads.
+pub fn search_prefix(prefix: &str, max_attempts: u64) -> Result<VanityMatch> {
+    let prefix = prefix.trim_start_matches("0x").to_lowercase();
+    if prefix.len() > MAX_PREFIX_NIBBLES {
+        return Err(anyhow!(
+            "Prefix too long: {} nibbles requested, {} is the maximum (expected work grows as 16^n)",
+            prefix.len(),
+            MAX_PREFIX_NIBBLES
+        ));
+    }
+    if !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!("Prefix must be hex digits: {}", prefix));
+    }
+
+    let found = Arc::new(AtomicBool::new(false));
+    let attempts = Arc::new(AtomicU64::new(0));
+    let winner: Arc<Mutex<Option<VanityMatch>>> = Arc::new(Mutex::new(None));
+
+    let workers: Vec<_> = (0..WORKER_COUNT)
+        .map(|_| {
+            let found = Arc::clone(&found);
+            let attempts = Arc::clone(&attempts);
+            let winner = Arc::clone(&winner);
+            let prefix = prefix.clone();
+            thread::spawn(move || loop {
+                if found.load(Ordering::SeqCst) {
+                    return;
+                }
+                for _ in 0..BATCH_SIZE {
+                    if found.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    let made = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+                    if made > max_attempts {
+                        return;
+                    }
+
+                    let (private_key, address) = generate_candidate();
+                    let normalized = address.trim_start_matches("0x").to_lowercase();
+                    if normalized.starts_with(&prefix) && !found.swap(true, Ordering::SeqCst) {
+                        *winner.lock().unwrap() = Some(VanityMatch { address, private_key, attempts: made });
+                        return;
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    winner
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| anyhow!("No address with prefix {} found in {} attempts", prefix, max_attempts))
+}
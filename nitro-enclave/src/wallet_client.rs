@@ -0,0 +1,182 @@
+// Reusable wallet-client core shared by `http_client`'s HTTP handlers and any foreign-function
+// bindings built on top of this crate.
+//
+// `http_client` exposes these operations over axum/HTTP. A `flutter_rust_bridge`-generated Dart
+// bridge or a `pyo3` Python module can instead call the async functions below directly, using
+// the same typed request structs, without spinning up the axum server at all. This module owns
+// command construction and the vsock round-trip to the enclave; `http_client`'s REST handlers
+// are thin HTTP-facing wrappers that could be rewritten in terms of it.
+//
+// The actual Dart and Python binding crates (a `flutter_rust_bridge` codegen target and a
+// `pyo3` extension module) aren't added alongside this file: both need their own crate
+// directories and manifests declaring those dependencies, and this tree has no Cargo.toml
+// anywhere to extend. Declared via `pub mod wallet_client;` in `src/lib.rs`, next to
+// `http_client`/`server_logic`/`pass_logic`.
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::os::unix::io::AsRawFd;
+
+use crate::protocol_helpers::{recv_loop, recv_u64, send_loop, send_u64};
+use crate::server_logic::Response;
+use crate::vsock_connect;
+
+/// Sanity ceiling on a single response frame, matching `enclave_transport::framed_round_trip`'s
+/// `MAX_RESPONSE_LEN`: `recv_u64` reads a length prefix straight off the wire, so an unbounded
+/// value must be rejected before we try to allocate a buffer for it.
+const MAX_RESPONSE_LEN: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CreatePassWalletRequest {
+    pub name: String,
+    pub owner: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SignRequest {
+    pub address: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InternalTransferRequest {
+    pub wallet_address: String,
+    pub asset_id: String,
+    pub amount: u64,
+    pub from_subaccount: String,
+    pub to_subaccount: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WithdrawToExternalRequest {
+    pub wallet_address: String,
+    pub subaccount_id: String,
+    pub asset_id: String,
+    pub amount: u64,
+    pub destination: String,
+    pub gas_price: Option<u64>,
+    pub gas_limit: Option<u64>,
+    pub chain_id: u64,
+    pub override_nonce: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GetProvenanceLogRequest {
+    pub wallet_address: String,
+}
+
+fn enclave_endpoint() -> Result<(u32, u32), String> {
+    let cid = std::env::var("ENCLAVE_CID")
+        .unwrap_or_else(|_| "19".to_string())
+        .parse::<u32>()
+        .map_err(|_| "Invalid ENCLAVE_CID".to_string())?;
+    Ok((cid, 7777u32))
+}
+
+async fn send_command_to_enclave(cid: u32, port: u32, command: &str) -> Result<Response, String> {
+    let vsocket = vsock_connect(cid, port)?;
+    let fd = vsocket.as_raw_fd();
+
+    let buf = command.as_bytes();
+    let len: u64 = buf.len().try_into().map_err(|err| format!("{:?}", err))?;
+    send_u64(fd, len)?;
+    send_loop(fd, buf, len)?;
+
+    let response_len = recv_u64(fd)?;
+    if response_len > MAX_RESPONSE_LEN {
+        return Err(format!(
+            "Response length {} exceeds the {} byte sanity ceiling",
+            response_len, MAX_RESPONSE_LEN
+        ));
+    }
+    let mut response_buf = vec![0u8; response_len as usize];
+    recv_loop(fd, &mut response_buf, response_len)?;
+
+    let response_str = String::from_utf8(response_buf)
+        .map_err(|err| format!("The received bytes are not UTF-8: {:?}", err))?;
+
+    serde_json::from_str(&response_str).map_err(|e| format!("Failed to parse response: {}", e))
+}
+
+async fn dispatch(command: serde_json::Value) -> Result<Response, String> {
+    let (cid, port) = enclave_endpoint()?;
+    send_command_to_enclave(cid, port, &command.to_string()).await
+}
+
+fn response_value(response: Response) -> Result<serde_json::Value, String> {
+    if response.success {
+        Ok(response.data.unwrap_or(serde_json::json!({})))
+    } else {
+        Err(response.error.unwrap_or_else(|| "Unknown error".to_string()))
+    }
+}
+
+/// Create a PASS wallet. Mirrors `create_pass_wallet_handler`'s command construction.
+pub async fn create_pass_wallet(request: CreatePassWalletRequest) -> Result<serde_json::Value, String> {
+    let command = serde_json::json!({
+        "CreatePassWallet": {
+            "name": request.name,
+            "owner": request.owner
+        }
+    });
+    response_value(dispatch(command).await?)
+}
+
+/// Sign an arbitrary message with a KMS-held key. Mirrors `sign_handler`.
+pub async fn sign(request: SignRequest) -> Result<String, String> {
+    let data = response_value(
+        dispatch(serde_json::json!({
+            "Sign": {
+                "address": request.address,
+                "message": request.message
+            }
+        }))
+        .await?,
+    )?;
+    data.get("signature")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| "Failed to sign message".to_string())
+}
+
+/// Move funds between two subaccounts of the same wallet. Mirrors `internal_transfer_handler`.
+pub async fn internal_transfer(request: InternalTransferRequest) -> Result<serde_json::Value, String> {
+    let command = serde_json::json!({
+        "InternalTransfer": {
+            "wallet_address": request.wallet_address,
+            "asset_id": request.asset_id,
+            "amount": request.amount,
+            "from_subaccount": request.from_subaccount,
+            "to_subaccount": request.to_subaccount
+        }
+    });
+    response_value(dispatch(command).await?)
+}
+
+/// Queue a withdrawal to an external address. Mirrors `withdraw_to_external_handler`.
+pub async fn withdraw_to_external(request: WithdrawToExternalRequest) -> Result<serde_json::Value, String> {
+    let command = serde_json::json!({
+        "WithdrawToExternal": {
+            "wallet_address": request.wallet_address,
+            "subaccount_id": request.subaccount_id,
+            "asset_id": request.asset_id,
+            "amount": request.amount,
+            "destination": request.destination,
+            "gas_price": request.gas_price,
+            "gas_limit": request.gas_limit,
+            "chain_id": request.chain_id,
+            "override_nonce": request.override_nonce
+        }
+    });
+    response_value(dispatch(command).await?)
+}
+
+/// Fetch a wallet's full provenance log. Mirrors `get_provenance_log_handler`.
+pub async fn get_provenance_log(request: GetProvenanceLogRequest) -> Result<serde_json::Value, String> {
+    let command = serde_json::json!({
+        "GetProvenanceLog": {
+            "wallet_address": request.wallet_address
+        }
+    });
+    response_value(dispatch(command).await?)
+}
@@ -4,7 +4,7 @@ use anyhow::Result;
 
 use nitro_enclave::key_manager::EnclaveKMS;
 use nitro_enclave::pass_logic::{
-    PassWalletManager, Asset, Subaccount, Deposit, TokenType
+    PassWalletManager, Asset, Subaccount, Deposit, TokenType, Amount
 };
 
 /// Integration test environment that simulates real-world scenarios
@@ -125,10 +125,10 @@ impl IntegrationTestEnvironment {
             .ok_or_else(|| anyhow::anyhow!("Asset not found: {} for wallet {}", asset_symbol, wallet_name))?;
         
         self.manager.withdraw_to_external(
-            wallet_address, 
-            subaccount_id, 
-            asset_id, 
-            amount, 
+            wallet_address,
+            subaccount_id,
+            asset_id,
+            amount,
             destination,
             None, // gas_price
             None, // gas_limit
@@ -136,6 +136,146 @@ impl IntegrationTestEnvironment {
             None // override_nonce
         )
     }
+
+    /// Run a declarative JSON scenario file (a top-level array of `ScenarioStep`s) against this
+    /// environment, driving the same underlying manager calls the hand-written scenario tests
+    /// above make directly. Each step is attempted in order; a step that errors doesn't abort the
+    /// run - its error is captured in the returned `ScenarioReport` and the next step still runs,
+    /// so one failing assertion or operation doesn't hide the outcome of the rest of the scenario.
+    fn run_scenario(&mut self, path: &str) -> Result<ScenarioReport> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read scenario file {}: {}", path, e))?;
+        let steps: Vec<ScenarioStep> = serde_json::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("Failed to parse scenario file {}: {}", path, e))?;
+
+        let mut report = ScenarioReport { outcomes: Vec::new() };
+        for (step_index, step) in steps.into_iter().enumerate() {
+            let description = step.describe();
+            let result = self.run_scenario_step(step);
+            report.outcomes.push(ScenarioStepOutcome {
+                step_index,
+                description,
+                error: result.err().map(|e| e.to_string()),
+            });
+        }
+        Ok(report)
+    }
+
+    fn run_scenario_step(&mut self, step: ScenarioStep) -> Result<()> {
+        match step {
+            ScenarioStep::CreateWallet { name, owner } => {
+                self.create_wallet(&name, &owner)?;
+                Ok(())
+            }
+            ScenarioStep::AddAsset { wallet, symbol, asset } => self.add_asset(&wallet, &symbol, asset),
+            ScenarioStep::AddSubaccount { wallet, subaccount_id, label } => {
+                self.add_subaccount(&wallet, &subaccount_id, &label)
+            }
+            ScenarioStep::Deposit { wallet, asset_symbol, amount, deposit_id, subaccount_id } => {
+                let wallet_address = self.wallets.get(&wallet)
+                    .ok_or_else(|| anyhow::anyhow!("Wallet not found: {}", wallet))?;
+                let asset_id = self.assets.get(&format!("{}_{}", asset_symbol, wallet))
+                    .ok_or_else(|| anyhow::anyhow!("Asset not found: {} for wallet {}", asset_symbol, wallet))?;
+
+                let deposit = Deposit {
+                    asset_id: asset_id.clone(),
+                    amount: Amount::from(amount),
+                    deposit_id: deposit_id.clone(),
+                    transaction_hash: format!("0x{}", hex::encode(format!("{}_{}", wallet, deposit_id))),
+                    block_number: "12345".to_string(),
+                    from_address: "0x1234567890abcdef1234567890abcdef12345678".to_string(),
+                    to_address: wallet_address.clone(),
+                };
+                self.manager.inbox_deposit(wallet_address, deposit)?;
+                self.manager.claim_inbox(wallet_address, &deposit_id, &subaccount_id)?;
+                Ok(())
+            }
+            ScenarioStep::Transfer { wallet, asset_symbol, amount, from_subaccount, to_subaccount } => {
+                let wallet_address = self.wallets.get(&wallet)
+                    .ok_or_else(|| anyhow::anyhow!("Wallet not found: {}", wallet))?;
+                let asset_id = self.assets.get(&format!("{}_{}", asset_symbol, wallet))
+                    .ok_or_else(|| anyhow::anyhow!("Asset not found: {} for wallet {}", asset_symbol, wallet))?;
+                self.manager.internal_transfer(
+                    wallet_address, asset_id, Amount::from(amount),
+                    &from_subaccount, &to_subaccount, None,
+                )
+            }
+            ScenarioStep::Withdraw { wallet, asset_symbol, amount, subaccount_id, destination } => {
+                let wallet_address = self.wallets.get(&wallet)
+                    .ok_or_else(|| anyhow::anyhow!("Wallet not found: {}", wallet))?;
+                let asset_id = self.assets.get(&format!("{}_{}", asset_symbol, wallet))
+                    .ok_or_else(|| anyhow::anyhow!("Asset not found: {} for wallet {}", asset_symbol, wallet))?;
+                self.manager.withdraw(
+                    wallet_address, asset_id, Amount::from(amount),
+                    &subaccount_id, &destination, None,
+                )
+            }
+            ScenarioStep::AssertBalance { wallet, subaccount_id, asset_symbol, expected } => {
+                let wallet_address = self.wallets.get(&wallet)
+                    .ok_or_else(|| anyhow::anyhow!("Wallet not found: {}", wallet))?;
+                let asset_id = self.assets.get(&format!("{}_{}", asset_symbol, wallet))
+                    .ok_or_else(|| anyhow::anyhow!("Asset not found: {} for wallet {}", asset_symbol, wallet))?;
+                let actual = self.manager.get_balance(wallet_address, &subaccount_id, asset_id)?;
+                let expected = Amount::from(expected);
+                if actual != expected {
+                    return Err(anyhow::anyhow!(
+                        "Balance mismatch for {}.{}.{}: expected {}, got {}",
+                        wallet, subaccount_id, asset_symbol, expected, actual
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// One step in a declarative JSON test scenario, as consumed by `IntegrationTestEnvironment::run_scenario`.
+/// Mirrors the primitive operations the environment's own helper methods already expose, so a
+/// scenario file reads like a transcript of the calls a hand-written test would otherwise make
+/// directly - letting the same scenario be replayed without recompiling a new `#[test]` for it.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum ScenarioStep {
+    CreateWallet { name: String, owner: String },
+    AddAsset { wallet: String, symbol: String, asset: Asset },
+    AddSubaccount { wallet: String, subaccount_id: String, label: String },
+    Deposit { wallet: String, asset_symbol: String, amount: u64, deposit_id: String, subaccount_id: String },
+    Transfer { wallet: String, asset_symbol: String, amount: u64, from_subaccount: String, to_subaccount: String },
+    Withdraw { wallet: String, asset_symbol: String, amount: u64, subaccount_id: String, destination: String },
+    AssertBalance { wallet: String, subaccount_id: String, asset_symbol: String, expected: u64 },
+}
+
+impl ScenarioStep {
+    /// Short human-readable label for this step, attached to its `ScenarioStepOutcome` so a
+    /// failing scenario's report is readable without cross-referencing the source JSON.
+    fn describe(&self) -> String {
+        match self {
+            ScenarioStep::CreateWallet { name, .. } => format!("create_wallet({})", name),
+            ScenarioStep::AddAsset { wallet, symbol, .. } => format!("add_asset({}, {})", wallet, symbol),
+            ScenarioStep::AddSubaccount { wallet, subaccount_id, .. } => format!("add_subaccount({}, {})", wallet, subaccount_id),
+            ScenarioStep::Deposit { wallet, asset_symbol, deposit_id, .. } => format!("deposit({}, {}, {})", wallet, asset_symbol, deposit_id),
+            ScenarioStep::Transfer { wallet, asset_symbol, from_subaccount, to_subaccount, .. } => {
+                format!("transfer({}, {}, {} -> {})", wallet, asset_symbol, from_subaccount, to_subaccount)
+            }
+            ScenarioStep::Withdraw { wallet, asset_symbol, subaccount_id, .. } => format!("withdraw({}, {}, {})", wallet, asset_symbol, subaccount_id),
+            ScenarioStep::AssertBalance { wallet, subaccount_id, asset_symbol, .. } => format!("assert_balance({}, {}, {})", wallet, subaccount_id, asset_symbol),
+        }
+    }
+}
+
+/// Per-step result from `IntegrationTestEnvironment::run_scenario`.
+#[derive(Debug, Clone, serde::Serialize)]
+struct ScenarioStepOutcome {
+    step_index: usize,
+    description: String,
+    /// `None` if the step succeeded.
+    error: Option<String>,
+}
+
+/// Outcome of replaying a whole scenario file via `IntegrationTestEnvironment::run_scenario`.
+#[derive(Debug, Clone, serde::Serialize)]
+struct ScenarioReport {
+    outcomes: Vec<ScenarioStepOutcome>,
 }
 
 #[cfg(test)]
@@ -769,4 +909,20 @@ mod integration_tests {
         println!("Gas calculation withdrawal tests completed successfully!");
         Ok(())
     }
+
+    #[test]
+    fn test_run_scenario_from_fixture() -> Result<()> {
+        let mut env = IntegrationTestEnvironment::new()?;
+        let report = env.run_scenario(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/basic_scenario.json"))?;
+
+        for outcome in &report.outcomes {
+            assert!(
+                outcome.error.is_none(),
+                "scenario step {} ({}) failed: {:?}",
+                outcome.step_index, outcome.description, outcome.error
+            );
+        }
+
+        Ok(())
+    }
 }
@@ -1,18 +1,46 @@
+use aes::Aes128;
 use aes_gcm::{aead::Aead, Aes256Gcm, Key, KeyInit, Nonce};
 use anyhow::{anyhow, Result};
+use bip39::Mnemonic;
+use ctr::cipher::{KeyIvInit, StreamCipher};
 use hex;
+use hmac::{Hmac, Mac};
 use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::elliptic_curve::{Field, PrimeField};
 use k256::{
     ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey},
-    PublicKey, SecretKey,
+    PublicKey, Scalar, SecretKey,
 };
 use rand::RngCore;
 use rlp::RlpStream;
+use scrypt::{scrypt, Params as ScryptParams};
 use serde::{Deserialize, Serialize};
+use sha2::Sha512;
 use sha3::{Digest, Keccak256};
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// BIP-32 index offset marking a hardened child (`2^31`).
+const BIP32_HARDENED_OFFSET: u32 = 0x8000_0000;
+
+/// AES-128-CTR, the symmetric cipher mandated by the keystore-v3 spec.
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+/// scrypt cost parameter (`n = 2^log_n`) used when writing new keystore-v3 files. Chosen to
+/// match the defaults most Ethereum tooling (geth, eth-keystore) uses for non-mobile keystores.
+const KEYSTORE_SCRYPT_LOG_N: u8 = 13;
+const KEYSTORE_SCRYPT_R: u32 = 8;
+const KEYSTORE_SCRYPT_P: u32 = 1;
+const KEYSTORE_SCRYPT_DKLEN: usize = 32;
+
+/// Domain-separation tag for deriving the passphrase `save_to_dir`/`load_from_dir` use to
+/// encrypt keystore-v3 files under the enclave's own master secret, so restarts can reload
+/// the directory without an operator re-entering a passphrase.
+const KEYSTORE_DIR_PASSPHRASE_DOMAIN: &[u8] = b"pass-wallet-keystore-v3-dir";
 
 #[derive(Serialize, Deserialize, Clone)]
 struct EncryptedKey {
@@ -20,30 +48,228 @@ struct EncryptedKey {
     nonce: String,
 }
 
+/// A decrypted 32-byte ECDSA private key. Zeroes its backing memory on drop, so a plaintext
+/// key never outlives the signing (or re-encryption) operation that needed it.
+#[derive(ZeroizeOnDrop)]
+struct Secret([u8; 32]);
+
+impl Secret {
+    fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// A decrypted BIP-39 seed (64 bytes). Zeroes its backing memory on drop, same rationale as
+/// [`Secret`].
+#[derive(ZeroizeOnDrop)]
+struct Seed([u8; 64]);
+
+impl Seed {
+    fn as_bytes(&self) -> &[u8; 64] {
+        &self.0
+    }
+}
+
+/// A BIP-32 extended private key: a secp256k1 scalar plus the chain code needed to derive its
+/// children. Zeroes its backing memory on drop.
+#[derive(ZeroizeOnDrop)]
+struct ExtendedPrivateKey {
+    private_key: [u8; 32],
+    chain_code: [u8; 32],
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct EthereumAccount {
     pub address: String,
     pub private_key: String,
 }
 
+/// Web3 Secret Storage (keystore-v3) file, as produced by `EnclaveKMS::export_keystore` and
+/// consumed by `EnclaveKMS::import_keystore`. Field names and nesting match the standard
+/// format so files interoperate with geth, ethers.js, and similar tooling.
+#[derive(Serialize, Deserialize)]
+struct KeystoreV3 {
+    crypto: KeystoreCrypto,
+    id: String,
+    address: String,
+    version: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreCrypto {
+    cipher: String,
+    ciphertext: String,
+    cipherparams: KeystoreCipherParams,
+    kdf: String,
+    kdfparams: KeystoreKdfParams,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreCipherParams {
+    iv: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreKdfParams {
+    n: u32,
+    r: u32,
+    p: u32,
+    dklen: u32,
+    salt: String,
+}
+
+/// Parameters used to derive the enclave's AES-256-GCM master key from its passphrase.
+/// `Keccak256Single` (version 1) is the original single-hash derivation, kept only so keystore
+/// directories written before this header existed can still be opened; `Scrypt` (version 2)
+/// adds a real, tunable work factor and is what [`EnclaveKMS::new`] uses going forward.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "kdf")]
+enum MasterKeyKdf {
+    #[serde(rename = "keccak256-single")]
+    Keccak256Single,
+    #[serde(rename = "scrypt")]
+    Scrypt {
+        salt: String,
+        n: u32,
+        r: u32,
+        p: u32,
+        dklen: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct MasterKeyHeader {
+    version: u8,
+    #[serde(flatten)]
+    kdf: MasterKeyKdf,
+}
+
+const MASTER_KEY_SCRYPT_LOG_N: u8 = 14;
+const MASTER_KEY_SCRYPT_R: u32 = 8;
+const MASTER_KEY_SCRYPT_P: u32 = 1;
+const MASTER_KEY_SCRYPT_DKLEN: usize = 32;
+
+const MASTER_KEY_HEADER_FILE: &str = "kdf.json";
+
 #[derive(Clone)]
 pub struct EnclaveKMS {
     secret: [u8; 32],
     keystore: Arc<Mutex<HashMap<String, EncryptedKey>>>,
+    /// The encrypted BIP-39 seed imported via `import_mnemonic`, if any. Stored once; every
+    /// `derive_account` call re-derives the requested child from it rather than persisting
+    /// derived keys separately.
+    master_seed: Arc<Mutex<Option<EncryptedKey>>>,
+    /// The KDF header this instance's `secret` was derived under. Round-tripped by
+    /// `save_master_key_header`/`open_from_dir` so a restarted enclave re-derives the same key.
+    master_key_header: MasterKeyHeader,
 }
 
 impl EnclaveKMS {
+    /// Derive the master key from `secret` using a freshly generated salt and a real work
+    /// factor (scrypt). This is the right constructor for anything new; use `new_legacy` only
+    /// to open a keystore directory written before the KDF header existed.
     pub fn new(secret: &str) -> Result<Self> {
-        let mut secret_bytes = [0u8; 32];
-        let secret_hash = Keccak256::digest(secret.as_bytes());
-        secret_bytes.copy_from_slice(&secret_hash);
+        let mut salt = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut salt);
+
+        let header = MasterKeyHeader {
+            version: 2,
+            kdf: MasterKeyKdf::Scrypt {
+                salt: hex::encode(salt),
+                n: 1u32 << MASTER_KEY_SCRYPT_LOG_N,
+                r: MASTER_KEY_SCRYPT_R,
+                p: MASTER_KEY_SCRYPT_P,
+                dklen: MASTER_KEY_SCRYPT_DKLEN as u32,
+            },
+        };
+        Self::new_with_header(secret, header)
+    }
+
+    /// Derive the master key under the original single-`Keccak256` scheme, with no work factor.
+    /// Kept only to reopen keystore directories that predate the KDF header; prefer `new`.
+    pub fn new_legacy(secret: &str) -> Result<Self> {
+        Self::new_with_header(
+            secret,
+            MasterKeyHeader {
+                version: 1,
+                kdf: MasterKeyKdf::Keccak256Single,
+            },
+        )
+    }
+
+    fn new_with_header(secret: &str, header: MasterKeyHeader) -> Result<Self> {
+        let secret_bytes = Self::derive_master_key(secret, &header.kdf)?;
 
         Ok(EnclaveKMS {
             secret: secret_bytes,
             keystore: Arc::new(Mutex::new(HashMap::new())),
+            master_seed: Arc::new(Mutex::new(None)),
+            master_key_header: header,
         })
     }
 
+    fn derive_master_key(secret: &str, kdf: &MasterKeyKdf) -> Result<[u8; 32]> {
+        match kdf {
+            MasterKeyKdf::Keccak256Single => {
+                let mut secret_bytes = [0u8; 32];
+                secret_bytes.copy_from_slice(&Keccak256::digest(secret.as_bytes()));
+                Ok(secret_bytes)
+            }
+            MasterKeyKdf::Scrypt {
+                salt,
+                n,
+                r,
+                p,
+                dklen,
+            } => {
+                let salt_bytes = hex::decode(salt)?;
+                let log_n = (31 - n.leading_zeros()) as u8;
+                let derived =
+                    Self::derive_keystore_key(secret, &salt_bytes, log_n, *r, *p, *dklen as usize)?;
+                let mut secret_bytes = [0u8; 32];
+                secret_bytes.copy_from_slice(&derived[..32]);
+                Ok(secret_bytes)
+            }
+        }
+    }
+
+    /// Persist this KMS's KDF header to `dir` (as `kdf.json`) alongside the keystore-v3 files
+    /// written by `save_to_dir`, so a later `open_from_dir` call re-derives the same master key.
+    pub fn save_master_key_header(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        let json = serde_json::to_string(&self.master_key_header)?;
+        fs::write(dir.join(MASTER_KEY_HEADER_FILE), json)?;
+        Ok(())
+    }
+
+    /// Open an enclave KMS backed by `dir`. If a KDF header is present, the master key is
+    /// derived exactly as it was when the header was written and every keystore-v3 file in
+    /// `dir` is loaded. Otherwise `dir` predates this header — it was written (if at all) under
+    /// the original single-Keccak256 derivation — so it's opened under that scheme and then
+    /// transparently re-wrapped: every key is re-encrypted under a freshly derived, scrypt-backed
+    /// master key and written back along with the new header.
+    pub fn open_from_dir(secret: &str, dir: &Path) -> Result<Self> {
+        let header_path = dir.join(MASTER_KEY_HEADER_FILE);
+        if header_path.exists() {
+            let json = fs::read_to_string(&header_path)?;
+            let header: MasterKeyHeader = serde_json::from_str(&json)?;
+            let mut kms = Self::new_with_header(secret, header)?;
+            kms.load_from_dir(dir)?;
+            return Ok(kms);
+        }
+
+        let mut legacy_kms = Self::new_legacy(secret)?;
+        legacy_kms.load_from_dir(dir)?;
+
+        let upgraded = Self::new(secret)?;
+        *upgraded.keystore.lock().unwrap() = legacy_kms.keystore.lock().unwrap().clone();
+        *upgraded.master_seed.lock().unwrap() = legacy_kms.master_seed.lock().unwrap().clone();
+        upgraded.save_to_dir(dir)?;
+        upgraded.save_master_key_header(dir)?;
+        Ok(upgraded)
+    }
+
     pub fn generate_ethereum_account(&self) -> Result<EthereumAccount> {
         let mut rng = rand::thread_rng();
         let mut private_key_bytes = [0u8; 32];
@@ -54,6 +280,7 @@ impl EnclaveKMS {
 
         let address = self.public_key_to_address(&public_key);
         let private_key = format!("0x{}", hex::encode(private_key_bytes));
+        private_key_bytes.zeroize();
 
         Ok(EthereumAccount {
             address,
@@ -79,11 +306,13 @@ impl EnclaveKMS {
         let nonce = Nonce::from_slice(&nonce_bytes);
 
         let private_key_clean = private_key.strip_prefix("0x").unwrap_or(private_key);
-        let private_key_bytes = hex::decode(private_key_clean)?;
+        let mut private_key_bytes = hex::decode(private_key_clean)?;
 
         let ciphertext = cipher
             .encrypt(nonce, private_key_bytes.as_ref())
-            .map_err(|e| anyhow!("Encryption failed: {}", e))?;
+            .map_err(|e| anyhow!("Encryption failed: {}", e));
+        private_key_bytes.zeroize();
+        let ciphertext = ciphertext?;
 
         Ok(EncryptedKey {
             ciphertext: hex::encode(ciphertext),
@@ -91,7 +320,9 @@ impl EnclaveKMS {
         })
     }
 
-    fn decrypt_key(&self, encrypted_key: &EncryptedKey) -> Result<String> {
+    /// Decrypt `encrypted_key` with the enclave's master secret, returning the raw plaintext.
+    /// Shared by `decrypt_key` (32-byte signing keys) and `decrypt_seed` (64-byte BIP-39 seeds).
+    fn aes_decrypt(&self, encrypted_key: &EncryptedKey) -> Result<Vec<u8>> {
         let key = Key::<Aes256Gcm>::from_slice(&self.secret);
         let cipher = Aes256Gcm::new(key);
 
@@ -100,11 +331,40 @@ impl EnclaveKMS {
 
         let ciphertext = hex::decode(&encrypted_key.ciphertext)?;
 
-        let plaintext = cipher
+        cipher
             .decrypt(nonce, ciphertext.as_ref())
-            .map_err(|e| anyhow!("Decryption failed: {}", e))?;
+            .map_err(|e| anyhow!("Decryption failed: {}", e))
+    }
+
+    /// Decrypt `encrypted_key` into a [`Secret`] that zeroes itself on drop, so the plaintext
+    /// key never lives in a long-lived `String`/`Vec<u8>` allocation.
+    fn decrypt_key(&self, encrypted_key: &EncryptedKey) -> Result<Secret> {
+        let mut plaintext = self.aes_decrypt(encrypted_key)?;
+
+        if plaintext.len() != 32 {
+            plaintext.zeroize();
+            return Err(anyhow!("Invalid private key length"));
+        }
+        let mut key_bytes = [0u8; 32];
+        key_bytes.copy_from_slice(&plaintext);
+        plaintext.zeroize();
+
+        Ok(Secret(key_bytes))
+    }
+
+    /// Decrypt the stored master seed into a [`Seed`] that zeroes itself on drop.
+    fn decrypt_seed(&self, encrypted_key: &EncryptedKey) -> Result<Seed> {
+        let mut plaintext = self.aes_decrypt(encrypted_key)?;
+
+        if plaintext.len() != 64 {
+            plaintext.zeroize();
+            return Err(anyhow!("Invalid seed length"));
+        }
+        let mut seed_bytes = [0u8; 64];
+        seed_bytes.copy_from_slice(&plaintext);
+        plaintext.zeroize();
 
-        Ok(format!("0x{}", hex::encode(plaintext)))
+        Ok(Seed(seed_bytes))
     }
 
     fn store_key(&mut self, address: &str, encrypted_key: &EncryptedKey) -> Result<()> {
@@ -123,34 +383,338 @@ impl EnclaveKMS {
         Ok(self.keystore.lock().unwrap().keys().cloned().collect())
     }
 
+    /// Domain-separated digest of this KMS's master secret and `wallet_address`, used by
+    /// `PassWalletManager::create_wallet` to seed a wallet's provenance hashchain genesis
+    /// (`PassWalletState::chain_head`). Never exposes `secret` itself - a party without the
+    /// enclave's secret can't derive the genesis a legitimate wallet should have started from,
+    /// even knowing its address.
+    pub fn provenance_genesis(&self, wallet_address: &str) -> String {
+        let mut hasher = Keccak256::new();
+        hasher.update(b"pass-wallet-provenance-genesis-v1");
+        hasher.update(self.secret);
+        hasher.update(wallet_address.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    /// Generate a fresh 12-word BIP-39 mnemonic from 128 bits of randomness.
+    pub fn generate_mnemonic(&self) -> Result<String> {
+        let mut entropy = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut entropy);
+        let mnemonic = Mnemonic::from_entropy(&entropy)
+            .map_err(|e| anyhow!("Failed to build mnemonic: {}", e))?;
+        Ok(mnemonic.to_string())
+    }
+
+    /// Import a BIP-39 mnemonic phrase, deriving its 64-byte seed via PBKDF2-HMAC-SHA512 (per
+    /// BIP-39, with `"mnemonic" || passphrase` as salt) and storing it encrypted as the HD
+    /// wallet's master seed. Subsequent `derive_account` calls walk a BIP-32 path from this
+    /// seed; the mnemonic phrase itself is never retained.
+    pub fn import_mnemonic(&mut self, phrase: &str, passphrase: &str) -> Result<()> {
+        let mnemonic = Mnemonic::parse_normalized(phrase)
+            .map_err(|e| anyhow!("Invalid mnemonic: {}", e))?;
+        let seed = mnemonic.to_seed(passphrase);
+        let encrypted_seed = self.encrypt_key(&format!("0x{}", hex::encode(seed)))?;
+        *self.master_seed.lock().unwrap() = Some(encrypted_seed);
+        Ok(())
+    }
+
+    /// Derive the secp256k1 master key (private key + chain code) from a BIP-39 seed via
+    /// `HMAC-SHA512(key = "Bitcoin seed", data = seed)`.
+    fn master_key_from_seed(seed: &[u8]) -> Result<ExtendedPrivateKey> {
+        let mut mac = Hmac::<Sha512>::new_from_slice(b"Bitcoin seed")
+            .map_err(|e| anyhow!("HMAC initialization failed: {}", e))?;
+        mac.update(seed);
+        let result = mac.finalize().into_bytes();
+
+        let mut private_key = [0u8; 32];
+        let mut chain_code = [0u8; 32];
+        private_key.copy_from_slice(&result[..32]);
+        chain_code.copy_from_slice(&result[32..]);
+
+        Ok(ExtendedPrivateKey {
+            private_key,
+            chain_code,
+        })
+    }
+
+    /// Derive one BIP-32 child key from `parent`. A hardened child (`index >= 2^31`) hashes
+    /// `0x00 || k_par || ser32(index)`; a normal child hashes the compressed parent public key
+    /// instead of `k_par`. Either way, the child key is `(I_L + k_par) mod n`, and the child
+    /// chain code is `I_R`.
+    fn derive_child(parent: &ExtendedPrivateKey, index: u32) -> Result<ExtendedPrivateKey> {
+        let mut mac = Hmac::<Sha512>::new_from_slice(&parent.chain_code)
+            .map_err(|e| anyhow!("HMAC initialization failed: {}", e))?;
+
+        if index >= BIP32_HARDENED_OFFSET {
+            mac.update(&[0x00]);
+            mac.update(&parent.private_key);
+        } else {
+            let parent_secret = SecretKey::from_bytes((&parent.private_key).into())?;
+            let compressed_pubkey = parent_secret.public_key().to_encoded_point(true);
+            mac.update(compressed_pubkey.as_bytes());
+        }
+        mac.update(&index.to_be_bytes());
+
+        let result = mac.finalize().into_bytes();
+        let il_bytes: [u8; 32] = result[..32]
+            .try_into()
+            .map_err(|_| anyhow!("Unexpected HMAC output length"))?;
+
+        let il_scalar = Option::<Scalar>::from(Scalar::from_repr(il_bytes.into())).ok_or_else(
+            || anyhow!("Invalid child key at index {} (I_L >= curve order); retry with the next index", index),
+        )?;
+        let parent_secret = SecretKey::from_bytes((&parent.private_key).into())?;
+        let child_scalar = il_scalar + *parent_secret.to_nonzero_scalar();
+        if bool::from(child_scalar.is_zero()) {
+            return Err(anyhow!(
+                "Invalid child key at index {} (derived to zero); retry with the next index",
+                index
+            ));
+        }
+
+        let mut private_key = [0u8; 32];
+        private_key.copy_from_slice(&child_scalar.to_repr());
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&result[32..]);
+
+        Ok(ExtendedPrivateKey {
+            private_key,
+            chain_code,
+        })
+    }
+
+    /// Derive the Ethereum account at `path` (e.g. `m/44'/60'/0'/0/0`) from the imported HD
+    /// master seed, walking one BIP-32 child derivation per path segment. The derived key is
+    /// stored alongside standalone keys, so it can be used with `sign_transaction` and friends
+    /// exactly like any other address in the keystore.
+    pub fn derive_account(&mut self, path: &str) -> Result<EthereumAccount> {
+        let encrypted_seed = self
+            .master_seed
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow!("No HD master seed imported; call import_mnemonic first"))?;
+        let seed = self.decrypt_seed(&encrypted_seed)?;
+
+        let mut extended = Self::master_key_from_seed(seed.as_bytes())?;
+        for index in parse_derivation_path(path)? {
+            extended = Self::derive_child(&extended, index)?;
+        }
+
+        let secret_key = SecretKey::from_bytes((&extended.private_key).into())?;
+        let public_key = secret_key.public_key();
+        let address = self.public_key_to_address(&public_key);
+        let private_key = format!("0x{}", hex::encode(extended.private_key));
+
+        let encrypted_key = self.encrypt_key(&private_key)?;
+        self.store_key(&address, &encrypted_key)?;
+
+        Ok(EthereumAccount {
+            address,
+            private_key,
+        })
+    }
+
+    /// Derive the scrypt cipher key (and, for decryption, the MAC) for a keystore-v3 file:
+    /// `scrypt(passphrase, salt, n, r, p, dklen)`.
+    fn derive_keystore_key(
+        passphrase: &str,
+        salt: &[u8],
+        log_n: u8,
+        r: u32,
+        p: u32,
+        dklen: usize,
+    ) -> Result<Vec<u8>> {
+        let params = ScryptParams::new(log_n, r, p, dklen)
+            .map_err(|e| anyhow!("Invalid scrypt parameters: {}", e))?;
+        let mut derived_key = vec![0u8; dklen];
+        scrypt(passphrase.as_bytes(), salt, &params, &mut derived_key)
+            .map_err(|e| anyhow!("scrypt key derivation failed: {}", e))?;
+        Ok(derived_key)
+    }
+
+    /// Export `address`'s private key as a passphrase-protected Web3 Secret Storage
+    /// (keystore-v3) JSON document: `{crypto: {cipher: "aes-128-ctr", ciphertext,
+    /// cipherparams: {iv}, kdf: "scrypt", kdfparams: {n, r, p, dklen, salt}, mac}, id,
+    /// address, version: 3}`, where `mac = keccak256(derived_key[16:32] || ciphertext)`.
+    pub fn export_keystore(&self, address: &str, passphrase: &str) -> Result<String> {
+        let encrypted_key = self
+            .get_key(address)?
+            .ok_or_else(|| anyhow!("Key not found for wallet"))?;
+        let secret = self.decrypt_key(&encrypted_key)?;
+
+        let mut salt = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let mut iv = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut iv);
+
+        let derived_key = Self::derive_keystore_key(
+            passphrase,
+            &salt,
+            KEYSTORE_SCRYPT_LOG_N,
+            KEYSTORE_SCRYPT_R,
+            KEYSTORE_SCRYPT_P,
+            KEYSTORE_SCRYPT_DKLEN,
+        )?;
+
+        let mut ciphertext = secret.as_bytes().to_vec();
+        let mut cipher = Aes128Ctr::new_from_slices(&derived_key[..16], &iv)
+            .map_err(|e| anyhow!("Invalid keystore cipher parameters: {}", e))?;
+        cipher.apply_keystream(&mut ciphertext);
+
+        let mut mac_input = Vec::with_capacity(16 + ciphertext.len());
+        mac_input.extend_from_slice(&derived_key[16..32]);
+        mac_input.extend_from_slice(&ciphertext);
+        let mac = Keccak256::digest(&mac_input);
+
+        let keystore = KeystoreV3 {
+            crypto: KeystoreCrypto {
+                cipher: "aes-128-ctr".to_string(),
+                ciphertext: hex::encode(&ciphertext),
+                cipherparams: KeystoreCipherParams {
+                    iv: hex::encode(iv),
+                },
+                kdf: "scrypt".to_string(),
+                kdfparams: KeystoreKdfParams {
+                    n: 1u32 << KEYSTORE_SCRYPT_LOG_N,
+                    r: KEYSTORE_SCRYPT_R,
+                    p: KEYSTORE_SCRYPT_P,
+                    dklen: KEYSTORE_SCRYPT_DKLEN as u32,
+                    salt: hex::encode(salt),
+                },
+                mac: hex::encode(mac),
+            },
+            id: random_uuid_v4(),
+            address: address.trim_start_matches("0x").to_lowercase(),
+            version: 3,
+        };
+
+        Ok(serde_json::to_string(&keystore)?)
+    }
+
+    /// Import a keystore-v3 JSON document, verifying its MAC before accepting the recovered
+    /// key. The recovered address is re-encrypted under the enclave's own master secret and
+    /// stored like any other key; the keystore's passphrase is never retained.
+    pub fn import_keystore(&mut self, json: &str, passphrase: &str) -> Result<String> {
+        let keystore: KeystoreV3 = serde_json::from_str(json)?;
+        if keystore.version != 3 {
+            return Err(anyhow!("Unsupported keystore version: {}", keystore.version));
+        }
+        if keystore.crypto.kdf != "scrypt" {
+            return Err(anyhow!("Unsupported keystore KDF: {}", keystore.crypto.kdf));
+        }
+        if keystore.crypto.cipher != "aes-128-ctr" {
+            return Err(anyhow!(
+                "Unsupported keystore cipher: {}",
+                keystore.crypto.cipher
+            ));
+        }
+
+        let salt = hex::decode(&keystore.crypto.kdfparams.salt)?;
+        let dklen = keystore.crypto.kdfparams.dklen as usize;
+        if dklen < 32 {
+            return Err(anyhow!("Keystore dklen too short to derive a MAC key"));
+        }
+        let log_n = (31 - keystore.crypto.kdfparams.n.leading_zeros()) as u8;
+        let derived_key = Self::derive_keystore_key(
+            passphrase,
+            &salt,
+            log_n,
+            keystore.crypto.kdfparams.r,
+            keystore.crypto.kdfparams.p,
+            dklen,
+        )?;
+
+        let ciphertext = hex::decode(&keystore.crypto.ciphertext)?;
+        let mut mac_input = Vec::with_capacity(16 + ciphertext.len());
+        mac_input.extend_from_slice(&derived_key[16..32]);
+        mac_input.extend_from_slice(&ciphertext);
+        let computed_mac = Keccak256::digest(&mac_input);
+        let expected_mac = hex::decode(&keystore.crypto.mac)?;
+        if computed_mac.as_slice() != expected_mac.as_slice() {
+            return Err(anyhow!(
+                "Keystore MAC verification failed: wrong passphrase or corrupted file"
+            ));
+        }
+
+        let iv = hex::decode(&keystore.crypto.cipherparams.iv)?;
+        if iv.len() != 16 {
+            return Err(anyhow!("Invalid keystore IV length"));
+        }
+        let mut private_key_bytes = ciphertext;
+        let mut cipher = Aes128Ctr::new_from_slices(&derived_key[..16], &iv)
+            .map_err(|e| anyhow!("Invalid keystore cipher parameters: {}", e))?;
+        cipher.apply_keystream(&mut private_key_bytes);
+
+        let private_key = format!("0x{}", hex::encode(&private_key_bytes));
+        private_key_bytes.zeroize();
+        let address = format!("0x{}", keystore.address.trim_start_matches("0x"));
+
+        let encrypted_key = self.encrypt_key(&private_key)?;
+        self.store_key(&address, &encrypted_key)?;
+
+        Ok(address)
+    }
+
+    /// Passphrase `save_to_dir`/`load_from_dir` use to protect on-disk keystore-v3 files,
+    /// derived from the enclave's own master secret so the directory can be reloaded across
+    /// restarts without operator involvement.
+    fn keystore_dir_passphrase(&self) -> String {
+        let mut mac_input = Vec::new();
+        mac_input.extend_from_slice(KEYSTORE_DIR_PASSPHRASE_DOMAIN);
+        mac_input.extend_from_slice(&self.secret);
+        hex::encode(Keccak256::digest(&mac_input))
+    }
+
+    /// Persist every key currently held in memory to `dir`, one keystore-v3 JSON file per
+    /// address, so they survive an enclave restart.
+    pub fn save_to_dir(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        let passphrase = self.keystore_dir_passphrase();
+        for address in self.list_addresses()? {
+            let json = self.export_keystore(&address, &passphrase)?;
+            let file_name = format!("{}.json", address.trim_start_matches("0x"));
+            fs::write(dir.join(file_name), json)?;
+        }
+        Ok(())
+    }
+
+    /// Reload every keystore-v3 file in `dir` (as written by `save_to_dir`) back into memory.
+    pub fn load_from_dir(&mut self, dir: &Path) -> Result<()> {
+        if !dir.exists() {
+            return Ok(());
+        }
+        let passphrase = self.keystore_dir_passphrase();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let json = fs::read_to_string(&path)?;
+            self.import_keystore(&json, &passphrase)?;
+        }
+        Ok(())
+    }
+
     pub fn sign_message(&self, message: &str, address: &str) -> Result<Option<String>> {
         let encrypted_key = match self.get_key(address)? {
             Some(key) => key,
             None => return Ok(None),
         };
 
-        let private_key_hex = match self.decrypt_key(&encrypted_key) {
+        let secret = match self.decrypt_key(&encrypted_key) {
             Ok(key) => key,
             Err(_) => return Ok(None),
         };
 
-        let private_key_clean = private_key_hex
-            .strip_prefix("0x")
-            .unwrap_or(&private_key_hex);
-        let private_key_bytes = hex::decode(private_key_clean)?;
-
-        // Convert Vec<u8> to [u8; 32] array
-        let private_key_array: [u8; 32] = private_key_bytes
-            .try_into()
-            .map_err(|_| anyhow!("Invalid private key length"))?;
-
-        let secret_key = SecretKey::from_bytes(&private_key_array.into())?;
+        let secret_key = SecretKey::from_bytes(secret.as_bytes().into())?;
         let signing_key = SigningKey::from(secret_key);
 
         // Create EIP-191 message hash
         let message_hash = self.hash_message(message);
 
         let (signature, recovery_id) = signing_key.sign_prehash_recoverable(&message_hash)?;
+        let (signature, recovery_id) = Self::normalize_signature(signature, recovery_id);
 
         // Convert to Ethereum signature format (r, s, v)
         let signature_bytes = signature.to_bytes();
@@ -169,6 +733,49 @@ impl EnclaveKMS {
         hasher.finalize().into()
     }
 
+    /// Normalize `signature` to the EIP-2 canonical low-s form (`s <= n/2`), flipping the
+    /// recovery id's y-parity bit to match: negating `s` corresponds to negating the signing
+    /// point `R`, which flips its y-coordinate's parity. Contracts that gate on `ecrecover`
+    /// commonly reject high-s signatures as a malleability defense, so every signer in this
+    /// module normalizes before serializing.
+    fn normalize_signature(signature: Signature, recovery_id: RecoveryId) -> (Signature, RecoveryId) {
+        match signature.normalize_s() {
+            Some(normalized) => (
+                normalized,
+                RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced()),
+            ),
+            None => (signature, recovery_id),
+        }
+    }
+
+    /// Recover the signer address from an arbitrary `message` + EIP-191-style `signature`
+    /// (`0x`-prefixed 65-byte `r || s || v`), without needing to already know which stored
+    /// address to check. Supports "sign-in with Ethereum"-style ownership claims, where the
+    /// caller learns the address *from* the signature rather than verifying a known one.
+    pub fn recover_address(&self, message: &str, signature: &str) -> Result<String> {
+        let signature_clean = signature.strip_prefix("0x").unwrap_or(signature);
+        let signature_bytes = hex::decode(signature_clean)?;
+
+        if signature_bytes.len() != 65 {
+            return Err(anyhow!("Invalid signature length"));
+        }
+
+        let recovery_id = RecoveryId::from_byte(signature_bytes[64] - 27)
+            .ok_or_else(|| anyhow!("Invalid recovery ID"))?;
+
+        let signature_array: [u8; 64] = signature_bytes[..64]
+            .try_into()
+            .map_err(|_| anyhow!("Invalid signature length"))?;
+        let signature = Signature::from_bytes(&signature_array.into())?;
+
+        let message_hash = self.hash_message(message);
+        let recovered_key =
+            VerifyingKey::recover_from_prehash(&message_hash, &signature, recovery_id)?;
+        let recovered_pubkey = PublicKey::from(&recovered_key);
+
+        Ok(self.public_key_to_address(&recovered_pubkey))
+    }
+
     pub fn verify_message(&self, message: &str, signature: &str, address: &str) -> Result<bool> {
         let signature_clean = signature.strip_prefix("0x").unwrap_or(signature);
         let signature_bytes = hex::decode(signature_clean)?;
@@ -218,24 +825,14 @@ impl EnclaveKMS {
         let encrypted_key = self
             .get_key(wallet_address)?
             .ok_or_else(|| anyhow!("Key not found for wallet"))?;
-        let private_key_hex = self.decrypt_key(&encrypted_key)?;
-
-        // Parse private key
-        let private_key_clean = private_key_hex
-            .strip_prefix("0x")
-            .unwrap_or(&private_key_hex);
-        let private_key_bytes = hex::decode(private_key_clean)?;
-        if private_key_bytes.len() != 32 {
-            return Err(anyhow!("Invalid private key length"));
-        }
-        let mut key_array = [0u8; 32];
-        key_array.copy_from_slice(&private_key_bytes);
-        let secret_key = SecretKey::from_bytes(&key_array.into())?;
+        let secret = self.decrypt_key(&encrypted_key)?;
+        let secret_key = SecretKey::from_bytes(secret.as_bytes().into())?;
         let signing_key = SigningKey::from(secret_key);
 
         // Sign the transaction
         let tx_hash = self.compute_transaction_hash(tx, chain_id)?;
         let (signature, recovery_id) = signing_key.sign_prehash_recoverable(&tx_hash)?;
+        let (signature, recovery_id) = Self::normalize_signature(signature, recovery_id);
 
         // Helper function to convert minimal big-endian bytes back to u64
         let bytes_to_u64 = |bytes: &[u8]| -> u64 {
@@ -279,6 +876,146 @@ impl EnclaveKMS {
         Ok(format!("0x{}", hex::encode(encoded)))
     }
 
+    /// Sign an EIP-1559 (type-2) dynamic-fee transaction, returning `0x02 || rlp(signed envelope)`.
+    /// Unlike the legacy path, `chain_id` is an explicit RLP field, so the recovery id is encoded
+    /// as a bare `y_parity` (0 or 1) rather than folded into `v = 35 + 2*chain_id + recid`.
+    pub fn sign_dynamic_fee_transaction(
+        &mut self,
+        wallet_address: &str,
+        tx: &DynamicFeeTransaction,
+        chain_id: u64,
+    ) -> Result<String> {
+        let encrypted_key = self
+            .get_key(wallet_address)?
+            .ok_or_else(|| anyhow!("Key not found for wallet"))?;
+        let secret = self.decrypt_key(&encrypted_key)?;
+        let secret_key = SecretKey::from_bytes(secret.as_bytes().into())?;
+        let signing_key = SigningKey::from(secret_key);
+
+        let tx_hash = self.compute_dynamic_fee_transaction_hash(tx, chain_id)?;
+        let (signature, recovery_id) = signing_key.sign_prehash_recoverable(&tx_hash)?;
+        let (signature, recovery_id) = Self::normalize_signature(signature, recovery_id);
+
+        let mut rlp_stream = RlpStream::new_list(12);
+        rlp_stream.append(&chain_id);
+        rlp_stream.append(&tx.nonce);
+        rlp_stream.append(&tx.max_priority_fee_per_gas);
+        rlp_stream.append(&tx.max_fee_per_gas);
+        rlp_stream.append(&tx.gas_limit);
+        if let Some(to_addr) = &tx.to {
+            rlp_stream.append(to_addr);
+        } else {
+            rlp_stream.append(&"");
+        }
+        rlp_stream.append(&tx.value);
+        rlp_stream.append(&tx.data);
+        rlp_append_access_list(&mut rlp_stream, &tx.access_list);
+        rlp_stream.append(&(recovery_id.to_byte() as u64));
+        rlp_stream.append(&signature.r().to_bytes().as_slice());
+        rlp_stream.append(&signature.s().to_bytes().as_slice());
+
+        let mut encoded = vec![0x02u8];
+        encoded.extend_from_slice(&rlp_stream.out());
+        Ok(format!("0x{}", hex::encode(encoded)))
+    }
+
+    /// Compute the EIP-1559 signing hash: `keccak256(0x02 || rlp([chain_id, nonce,
+    /// max_priority_fee_per_gas, max_fee_per_gas, gas_limit, to, value, data, access_list]))`
+    fn compute_dynamic_fee_transaction_hash(
+        &self,
+        tx: &DynamicFeeTransaction,
+        chain_id: u64,
+    ) -> Result<[u8; 32]> {
+        let mut rlp_stream = RlpStream::new_list(9);
+        rlp_stream.append(&chain_id);
+        rlp_stream.append(&tx.nonce);
+        rlp_stream.append(&tx.max_priority_fee_per_gas);
+        rlp_stream.append(&tx.max_fee_per_gas);
+        rlp_stream.append(&tx.gas_limit);
+        if let Some(to_addr) = &tx.to {
+            rlp_stream.append(to_addr);
+        } else {
+            rlp_stream.append(&"");
+        }
+        rlp_stream.append(&tx.value);
+        rlp_stream.append(&tx.data);
+        rlp_append_access_list(&mut rlp_stream, &tx.access_list);
+
+        let mut payload = vec![0x02u8];
+        payload.extend_from_slice(&rlp_stream.out());
+        let hash = Keccak256::digest(&payload);
+        Ok(hash.into())
+    }
+
+    /// Sign an EIP-2930 (type-1) access-list transaction, returning `0x01 || rlp(signed envelope)`.
+    /// Shares the same RLP/keccak machinery as the EIP-1559 path, differing only in which fields
+    /// are present (a single `gas_price` instead of the two EIP-1559 fee fields).
+    pub fn sign_access_list_transaction(
+        &mut self,
+        wallet_address: &str,
+        tx: &AccessListTransaction,
+        chain_id: u64,
+    ) -> Result<String> {
+        let encrypted_key = self
+            .get_key(wallet_address)?
+            .ok_or_else(|| anyhow!("Key not found for wallet"))?;
+        let secret = self.decrypt_key(&encrypted_key)?;
+        let secret_key = SecretKey::from_bytes(secret.as_bytes().into())?;
+        let signing_key = SigningKey::from(secret_key);
+
+        let tx_hash = self.compute_access_list_transaction_hash(tx, chain_id)?;
+        let (signature, recovery_id) = signing_key.sign_prehash_recoverable(&tx_hash)?;
+        let (signature, recovery_id) = Self::normalize_signature(signature, recovery_id);
+
+        let mut rlp_stream = RlpStream::new_list(11);
+        rlp_stream.append(&chain_id);
+        rlp_stream.append(&tx.nonce);
+        rlp_stream.append(&tx.gas_price);
+        rlp_stream.append(&tx.gas_limit);
+        if let Some(to_addr) = &tx.to {
+            rlp_stream.append(to_addr);
+        } else {
+            rlp_stream.append(&"");
+        }
+        rlp_stream.append(&tx.value);
+        rlp_stream.append(&tx.data);
+        rlp_append_access_list(&mut rlp_stream, &tx.access_list);
+        rlp_stream.append(&(recovery_id.to_byte() as u64));
+        rlp_stream.append(&signature.r().to_bytes().as_slice());
+        rlp_stream.append(&signature.s().to_bytes().as_slice());
+
+        let mut encoded = vec![0x01u8];
+        encoded.extend_from_slice(&rlp_stream.out());
+        Ok(format!("0x{}", hex::encode(encoded)))
+    }
+
+    /// Compute the EIP-2930 signing hash: `keccak256(0x01 || rlp([chain_id, nonce, gas_price,
+    /// gas_limit, to, value, data, access_list]))`
+    fn compute_access_list_transaction_hash(
+        &self,
+        tx: &AccessListTransaction,
+        chain_id: u64,
+    ) -> Result<[u8; 32]> {
+        let mut rlp_stream = RlpStream::new_list(8);
+        rlp_stream.append(&chain_id);
+        rlp_stream.append(&tx.nonce);
+        rlp_stream.append(&tx.gas_price);
+        rlp_stream.append(&tx.gas_limit);
+        if let Some(to_addr) = &tx.to {
+            rlp_stream.append(to_addr);
+        } else {
+            rlp_stream.append(&"");
+        }
+        rlp_stream.append(&tx.value);
+        rlp_stream.append(&tx.data);
+        rlp_append_access_list(&mut rlp_stream, &tx.access_list);
+
+        let mut payload = vec![0x01u8];
+        payload.extend_from_slice(&rlp_stream.out());
+        let hash = Keccak256::digest(&payload);
+        Ok(hash.into())
+    }
+
     /// Compute transaction hash for signing (EIP-155)
     fn compute_transaction_hash(&self, tx: &LegacyTransaction, chain_id: u64) -> Result<[u8; 32]> {
         // Helper function to convert minimal big-endian bytes back to u64
@@ -314,6 +1051,28 @@ impl EnclaveKMS {
         let hash = Keccak256::digest(&encoded);
         Ok(hash.into())
     }
+
+    /// Sign `tx` according to its EIP-2718 envelope type, dispatching to the legacy,
+    /// EIP-2930, or EIP-1559 signer. This is the preferred entry point for callers that
+    /// pick the envelope dynamically (e.g. based on the fee parameters a caller supplied);
+    /// `sign_transaction`/`sign_access_list_transaction`/`sign_dynamic_fee_transaction`
+    /// remain available directly for callers that already know their envelope.
+    pub fn sign_typed_transaction(
+        &mut self,
+        wallet_address: &str,
+        tx: &TypedTransaction,
+        chain_id: u64,
+    ) -> Result<String> {
+        match tx {
+            TypedTransaction::Legacy(tx) => self.sign_transaction(wallet_address, tx, chain_id),
+            TypedTransaction::Eip2930(tx) => {
+                self.sign_access_list_transaction(wallet_address, tx, chain_id)
+            }
+            TypedTransaction::Eip1559(tx) => {
+                self.sign_dynamic_fee_transaction(wallet_address, tx, chain_id)
+            }
+        }
+    }
 }
 
 /// Legacy Ethereum transaction structure
@@ -326,3 +1085,105 @@ pub struct LegacyTransaction {
     pub value: Vec<u8>,      // Big-endian bytes
     pub data: Vec<u8>,
 }
+
+/// EIP-2930 access list entry: a contract address plus the storage slots it touches
+pub type AccessListEntry = (Vec<u8>, Vec<Vec<u8>>);
+
+/// EIP-1559 (type-2) dynamic-fee Ethereum transaction structure
+#[derive(Debug, Clone)]
+pub struct DynamicFeeTransaction {
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: Vec<u8>, // Big-endian bytes
+    pub max_fee_per_gas: Vec<u8>,          // Big-endian bytes
+    pub gas_limit: Vec<u8>,                // Big-endian bytes
+    pub to: Option<Vec<u8>>,               // 20-byte address
+    pub value: Vec<u8>,                    // Big-endian bytes
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListEntry>,
+}
+
+/// EIP-2930 (type-1) access-list Ethereum transaction structure
+#[derive(Debug, Clone)]
+pub struct AccessListTransaction {
+    pub nonce: u64,
+    pub gas_price: Vec<u8>, // Big-endian bytes
+    pub gas_limit: Vec<u8>, // Big-endian bytes
+    pub to: Option<Vec<u8>>, // 20-byte address
+    pub value: Vec<u8>,      // Big-endian bytes
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListEntry>,
+}
+
+/// An Ethereum transaction in one of its EIP-2718 envelope forms, ready to be handed to
+/// [`EnclaveKMS::sign_typed_transaction`].
+#[derive(Debug, Clone)]
+pub enum TypedTransaction {
+    /// Pre-EIP-2718 legacy envelope, signed with EIP-155 replay protection.
+    Legacy(LegacyTransaction),
+    /// Type `0x01`: access-list envelope.
+    Eip2930(AccessListTransaction),
+    /// Type `0x02`: dynamic-fee envelope.
+    Eip1559(DynamicFeeTransaction),
+}
+
+/// Generate a random UUID v4 string for the keystore-v3 `id` field.
+/// Parse a BIP-32 derivation path like `m/44'/60'/0'/0/0` into per-segment indices, with the
+/// hardened offset (`2^31`) folded into any segment suffixed `'` or `h`.
+fn parse_derivation_path(path: &str) -> Result<Vec<u32>> {
+    let mut segments = path.split('/');
+    match segments.next() {
+        Some("m") => {}
+        _ => return Err(anyhow!("Derivation path must start with 'm': {}", path)),
+    }
+
+    segments
+        .map(|segment| {
+            let (index_str, hardened) = match segment
+                .strip_suffix('\'')
+                .or_else(|| segment.strip_suffix('h'))
+            {
+                Some(stripped) => (stripped, true),
+                None => (segment, false),
+            };
+            let index: u32 = index_str
+                .parse()
+                .map_err(|_| anyhow!("Invalid derivation path segment: {}", segment))?;
+            if index >= BIP32_HARDENED_OFFSET {
+                return Err(anyhow!("Derivation index out of range: {}", segment));
+            }
+            Ok(if hardened {
+                index + BIP32_HARDENED_OFFSET
+            } else {
+                index
+            })
+        })
+        .collect()
+}
+
+fn random_uuid_v4() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40; // version 4
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // variant 10
+
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        bytes[0], bytes[1], bytes[2], bytes[3],
+        bytes[4], bytes[5],
+        bytes[6], bytes[7],
+        bytes[8], bytes[9],
+        bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15]
+    )
+}
+
+fn rlp_append_access_list(rlp_stream: &mut RlpStream, access_list: &[AccessListEntry]) {
+    rlp_stream.begin_list(access_list.len());
+    for (address, storage_keys) in access_list {
+        rlp_stream.begin_list(2);
+        rlp_stream.append(address);
+        rlp_stream.begin_list(storage_keys.len());
+        for key in storage_keys {
+            rlp_stream.append(key);
+        }
+    }
+}